@@ -0,0 +1,4 @@
+ALTER TABLE crates
+    ADD COLUMN deprecated_at TIMESTAMP DEFAULT NULL,
+    ADD COLUMN deprecation_message VARCHAR DEFAULT NULL,
+    ADD COLUMN deprecation_successor VARCHAR DEFAULT NULL;
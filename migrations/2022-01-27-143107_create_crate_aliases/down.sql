@@ -0,0 +1,4 @@
+DROP TRIGGER trigger_ensure_crate_name_not_aliased ON crates;
+DROP FUNCTION ensure_crate_name_not_aliased();
+DROP TABLE crate_aliases;
+DROP FUNCTION ensure_crate_alias_not_in_use();
@@ -0,0 +1,37 @@
+CREATE TABLE crate_aliases (
+    name VARCHAR NOT NULL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE UNIQUE INDEX ON crate_aliases (canon_crate_name(name));
+CREATE INDEX ON crate_aliases (crate_id);
+
+CREATE FUNCTION ensure_crate_alias_not_in_use() RETURNS trigger AS $$
+BEGIN
+    IF canon_crate_name(NEW.name) IN (
+        SELECT canon_crate_name(name) FROM crates
+    ) THEN
+        RAISE EXCEPTION 'crate exists with name %', NEW.name;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_ensure_crate_alias_not_in_use
+BEFORE INSERT OR UPDATE ON crate_aliases
+FOR EACH ROW EXECUTE PROCEDURE ensure_crate_alias_not_in_use();
+
+CREATE FUNCTION ensure_crate_name_not_aliased() RETURNS trigger AS $$
+BEGIN
+    IF canon_crate_name(NEW.name) IN (
+        SELECT canon_crate_name(name) FROM crate_aliases
+    ) THEN
+        RAISE EXCEPTION 'crate name % is an alias of another crate', NEW.name;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_ensure_crate_name_not_aliased
+BEFORE INSERT OR UPDATE OF name ON crates
+FOR EACH ROW EXECUTE PROCEDURE ensure_crate_name_not_aliased();
@@ -0,0 +1,2 @@
+-- `NULL` means that the token was created before scopes existed and has full access
+ALTER TABLE api_tokens ADD COLUMN endpoint_scopes TEXT[];
@@ -0,0 +1,2 @@
+-- `NULL` means that the token can be used for all crates of the user
+ALTER TABLE api_tokens ADD COLUMN crate_scopes TEXT[];
@@ -0,0 +1,2 @@
+ALTER TABLE api_tokens DROP COLUMN expiry_notification_at;
+ALTER TABLE api_tokens DROP COLUMN expires_at;
@@ -0,0 +1,4 @@
+-- `NULL` means that the token never expires
+ALTER TABLE api_tokens ADD COLUMN expires_at TIMESTAMP;
+-- Set once the owner has been warned about the upcoming expiration
+ALTER TABLE api_tokens ADD COLUMN expiry_notification_at TIMESTAMP;
@@ -0,0 +1,13 @@
+CREATE TABLE api_token_activity (
+    id SERIAL PRIMARY KEY,
+    api_token_id INTEGER NOT NULL REFERENCES api_tokens (id) ON DELETE CASCADE,
+    endpoint VARCHAR NOT NULL,
+    crate_name VARCHAR,
+    version VARCHAR,
+    ip_address VARCHAR,
+    user_agent VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX api_token_activity_api_token_id_created_at ON api_token_activity (api_token_id, created_at);
+CREATE INDEX api_token_activity_created_at ON api_token_activity (created_at);
@@ -0,0 +1,3 @@
+ALTER TABLE api_tokens DROP COLUMN trusted_publisher_id;
+
+DROP TABLE trusted_publishers;
@@ -0,0 +1,20 @@
+CREATE TABLE trusted_publishers (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    created_by INTEGER NOT NULL REFERENCES users (id),
+    issuer VARCHAR NOT NULL,
+    repository VARCHAR NOT NULL,
+    workflow VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX trusted_publishers_unique_config
+    ON trusted_publishers (crate_id, issuer, lower(repository), workflow);
+
+COMMENT ON COLUMN trusted_publishers.repository IS 'The `owner/name` of the repository that is allowed to publish the crate';
+COMMENT ON COLUMN trusted_publishers.workflow IS 'The file name of the workflow in `.github/workflows` that is allowed to publish the crate';
+
+ALTER TABLE api_tokens
+    ADD COLUMN trusted_publisher_id INTEGER REFERENCES trusted_publishers (id) ON DELETE SET NULL;
+
+COMMENT ON COLUMN api_tokens.trusted_publisher_id IS 'Set for short-lived tokens obtained through trusted publishing';
@@ -0,0 +1,20 @@
+CREATE TABLE linked_identities (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    provider VARCHAR NOT NULL,
+    provider_user_id VARCHAR NOT NULL,
+    login VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    last_login_at TIMESTAMP
+);
+
+COMMENT ON COLUMN linked_identities.provider IS 'The name of the login provider, e.g. `github` or `gitlab`';
+COMMENT ON COLUMN linked_identities.provider_user_id IS 'The stable ID of the account at the login provider';
+
+CREATE UNIQUE INDEX linked_identities_provider_account
+    ON linked_identities (provider, provider_user_id);
+CREATE UNIQUE INDEX linked_identities_user_provider
+    ON linked_identities (user_id, provider);
+
+INSERT INTO linked_identities (user_id, provider, provider_user_id, login)
+    SELECT id, 'github', gh_id::text, gh_login FROM users WHERE gh_id > 0;
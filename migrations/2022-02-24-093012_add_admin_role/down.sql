@@ -0,0 +1,3 @@
+DROP TABLE admin_actions;
+
+ALTER TABLE users DROP COLUMN is_admin;
@@ -0,0 +1,10 @@
+CREATE TABLE user_sessions (
+    id BIGSERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    last_used_at TIMESTAMP NOT NULL DEFAULT now(),
+    ip_address VARCHAR,
+    user_agent VARCHAR
+);
+
+CREATE INDEX user_sessions_user_id_idx ON user_sessions (user_id);
@@ -0,0 +1,12 @@
+DELETE FROM emails WHERE NOT is_primary;
+
+DROP INDEX emails_user_id_for_publishing_idx;
+DROP INDEX emails_user_id_primary_idx;
+DROP INDEX emails_user_id_email_idx;
+DROP INDEX emails_user_id_idx;
+
+ALTER TABLE emails
+    DROP COLUMN is_primary,
+    DROP COLUMN for_publishing;
+
+ALTER TABLE emails ADD CONSTRAINT emails_user_id_key UNIQUE (user_id);
@@ -0,0 +1,14 @@
+ALTER TABLE emails DROP CONSTRAINT emails_user_id_key;
+
+ALTER TABLE emails
+    ADD COLUMN is_primary BOOLEAN NOT NULL DEFAULT FALSE,
+    -- The address recorded as `published_by_email` when the user publishes a crate
+    ADD COLUMN for_publishing BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Until now, every user had at most one address
+UPDATE emails SET is_primary = TRUE;
+
+CREATE INDEX emails_user_id_idx ON emails (user_id);
+CREATE UNIQUE INDEX emails_user_id_email_idx ON emails (user_id, lower(email));
+CREATE UNIQUE INDEX emails_user_id_primary_idx ON emails (user_id) WHERE is_primary;
+CREATE UNIQUE INDEX emails_user_id_for_publishing_idx ON emails (user_id) WHERE for_publishing;
@@ -0,0 +1,7 @@
+-- 0 grants full rights, 1 only allows publishing and yanking. Until now, users always had
+-- full rights and teams could only publish.
+ALTER TABLE crate_owners ADD COLUMN role INTEGER;
+UPDATE crate_owners SET role = CASE WHEN owner_kind = 0 THEN 0 ELSE 1 END;
+ALTER TABLE crate_owners ALTER COLUMN role SET NOT NULL;
+
+ALTER TABLE crate_owner_invitations ADD COLUMN role INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+-- Teams added as owners by someone who is not a maintainer of the team wait here until a
+-- maintainer confirms the addition.
+CREATE TABLE crate_owner_team_invitations (
+    team_id INTEGER NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    invited_by_user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (team_id, crate_id)
+);
+
+CREATE INDEX crate_owner_team_invitations_crate_id_idx ON crate_owner_team_invitations (crate_id);
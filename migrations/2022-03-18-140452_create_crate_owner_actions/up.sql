@@ -0,0 +1,13 @@
+CREATE TABLE crate_owner_actions (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id),
+    api_token_id INTEGER REFERENCES api_tokens (id),
+    action INTEGER NOT NULL,
+    -- The login of the user or team the action concerns
+    subject VARCHAR,
+    details VARCHAR,
+    time TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX crate_owner_actions_crate_id_idx ON crate_owner_actions (crate_id, id);
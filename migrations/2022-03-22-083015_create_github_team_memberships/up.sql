@@ -0,0 +1,11 @@
+-- The results of asking GitHub whether a user is a member of a team, so that publishing as a
+-- team member does not depend on GitHub answering every request.
+CREATE TABLE github_team_memberships (
+    team_id INTEGER NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    is_member BOOLEAN NOT NULL,
+    checked_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (team_id, user_id)
+);
+
+CREATE INDEX github_team_memberships_checked_at_idx ON github_team_memberships (checked_at);
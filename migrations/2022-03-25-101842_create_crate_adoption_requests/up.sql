@@ -0,0 +1,20 @@
+-- Requests of users to take over crates whose owners have become unreachable. Owners are
+-- notified and can object; requests nobody objected to are reviewed by an admin once the
+-- waiting period is over.
+CREATE TABLE crate_adoption_requests (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    requested_by_user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    reason VARCHAR NOT NULL,
+    status INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    resolved_by_user_id INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    resolved_at TIMESTAMP
+);
+
+-- Users can only have one pending request per crate
+CREATE UNIQUE INDEX crate_adoption_requests_pending_idx
+    ON crate_adoption_requests (crate_id, requested_by_user_id)
+    WHERE status = 0;
+CREATE INDEX crate_adoption_requests_status_created_at_idx
+    ON crate_adoption_requests (status, created_at);
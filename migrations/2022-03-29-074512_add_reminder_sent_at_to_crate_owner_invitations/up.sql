@@ -0,0 +1,2 @@
+-- When the invited user was reminded of the invitation, so that the reminder is only sent once.
+ALTER TABLE crate_owner_invitations ADD COLUMN reminder_sent_at TIMESTAMP;
@@ -0,0 +1,13 @@
+-- Invitations of GitHub users who do not have a crates.io account yet. They are turned into
+-- regular invitations once the user logs in for the first time.
+CREATE TABLE crate_owner_signup_invitations (
+    gh_id INTEGER NOT NULL,
+    gh_login VARCHAR NOT NULL,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    invited_by_user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (gh_id, crate_id)
+);
+
+CREATE INDEX crate_owner_signup_invitations_crate_id_idx ON crate_owner_signup_invitations (crate_id);
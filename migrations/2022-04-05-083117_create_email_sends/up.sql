@@ -0,0 +1,15 @@
+-- Every email sent by crates.io, so that failed deliveries can be inspected. The content of the
+-- emails is not stored, since it may contain tokens.
+CREATE TABLE email_sends (
+    id SERIAL PRIMARY KEY,
+    recipient VARCHAR NOT NULL,
+    template VARCHAR NOT NULL,
+    subject VARCHAR NOT NULL,
+    status INTEGER NOT NULL DEFAULT 0,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    sent_at TIMESTAMP
+);
+
+CREATE INDEX email_sends_created_at_idx ON email_sends (created_at);
@@ -0,0 +1,2 @@
+DROP TRIGGER trigger_trusted_publishers_revoke_tokens ON trusted_publishers;
+DROP FUNCTION revoke_trusted_publisher_tokens();
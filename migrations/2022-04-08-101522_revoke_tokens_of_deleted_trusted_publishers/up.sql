@@ -0,0 +1,15 @@
+-- Tokens of a trusted publisher are revoked however the publisher is removed, including when
+-- its crate is deleted. Deleting the tokens instead is not possible, since the versions they
+-- published keep referencing them.
+CREATE FUNCTION revoke_trusted_publisher_tokens() RETURNS trigger AS $$
+BEGIN
+    UPDATE api_tokens SET revoked = TRUE WHERE trusted_publisher_id = OLD.id;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_trusted_publishers_revoke_tokens
+    BEFORE DELETE
+    ON trusted_publishers
+    FOR EACH ROW
+EXECUTE PROCEDURE revoke_trusted_publisher_tokens();
@@ -0,0 +1,4 @@
+UPDATE users
+SET gh_login = substring(gh_login from position(':' in gh_login) + 1)
+WHERE gh_id = 0
+  AND gh_login LIKE '%:%';
@@ -0,0 +1,9 @@
+-- Users without a GitHub account get a login prefixed with their login provider, e.g.
+-- `gitlab:foo`, so that their login can never be mistaken for the GitHub account of the same
+-- name.
+UPDATE users
+SET gh_login = linked_identities.provider || ':' || linked_identities.login
+FROM linked_identities
+WHERE linked_identities.user_id = users.id
+  AND linked_identities.provider <> 'github'
+  AND users.gh_id = 0;
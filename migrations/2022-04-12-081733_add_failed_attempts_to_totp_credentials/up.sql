@@ -0,0 +1,6 @@
+ALTER TABLE totp_credentials
+    ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN locked_until TIMESTAMP;
+
+COMMENT ON COLUMN totp_credentials.failed_attempts IS 'Invalid codes entered since the last valid one, including recovery codes';
+COMMENT ON COLUMN totp_credentials.locked_until IS 'No codes are accepted until then, after too many invalid ones';
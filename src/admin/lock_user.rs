@@ -0,0 +1,104 @@
+use crate::{
+    admin::dialoguer,
+    db,
+    email::Emails,
+    models::{ApiToken, Owner},
+    schema::api_tokens,
+    util::errors::AppResult,
+};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+
+#[derive(clap::Parser, Debug)]
+#[clap(
+    name = "lock-user",
+    about = "Lock a user account, rejecting all of its authenticated requests.",
+    long_about = "Lock a user account, rejecting all of its authenticated requests until the \
+        lock expires. The user is notified by email if they have a verified email address."
+)]
+pub struct Opts {
+    /// Login of the user, e.g. `foo` for GitHub users or `gitlab:foo` for GitLab users
+    login: String,
+    /// Reason for locking the account, shown to the user
+    #[clap(long)]
+    reason: String,
+    /// Time at which the lock expires, either as an RFC 3339 timestamp or as `YYYY-MM-DD`.
+    /// Without it, the account is locked indefinitely.
+    #[clap(long, parse(try_from_str = parse_until))]
+    until: Option<NaiveDateTime>,
+    /// Also revoke all API tokens of the user
+    #[clap(long)]
+    revoke_tokens: bool,
+}
+
+pub fn run(opts: Opts) -> AppResult<()> {
+    let conn = db::connect_now()?;
+    let user = Owner::find_user_by_login(&conn, &opts.login)?;
+
+    let until = match opts.until {
+        Some(until) => format!("until {} UTC", until.format("%Y-%m-%d %H:%M")),
+        None => "indefinitely".into(),
+    };
+    let prompt = format!(
+        "Are you sure you want to lock {} ({}) {until}?",
+        user.gh_login, user.id
+    );
+    if !dialoguer::confirm(&prompt) {
+        return Ok(());
+    }
+
+    let user = conn.transaction::<_, diesel::result::Error, _>(|| {
+        let user = user.lock(&conn, &opts.reason, opts.until)?;
+
+        if opts.revoke_tokens {
+            let revoked = diesel::update(ApiToken::belonging_to(&user))
+                .filter(api_tokens::revoked.eq(false))
+                .set(api_tokens::revoked.eq(true))
+                .execute(&conn)?;
+            println!("revoked {revoked} API tokens");
+        }
+
+        Ok(user)
+    })?;
+    println!("locked {}", user.gh_login);
+
+    match user.verified_email(&conn)? {
+        Some(email) => {
+            let emails = Emails::from_environment();
+            let result =
+                emails.send_account_locked(&conn, &email, &user.gh_login, &opts.reason, opts.until);
+            if let Err(error) = result {
+                println!("failed to notify the user by email: {error}");
+            }
+        }
+        None => println!("the user has no verified email address to notify"),
+    }
+
+    Ok(())
+}
+
+fn parse_until(value: &str) -> Result<NaiveDateTime, String> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date_time.naive_utc());
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_hms(0, 0, 0))
+        .map_err(|_| format!("`{value}` is neither an RFC 3339 timestamp nor a date"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_until;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn until_accepts_timestamps_and_dates() {
+        let midnight = NaiveDate::from_ymd(2022, 3, 1).and_hms(0, 0, 0);
+        assert_eq!(parse_until("2022-03-01").unwrap(), midnight);
+        assert_eq!(parse_until("2022-03-01T00:00:00Z").unwrap(), midnight);
+        assert_eq!(parse_until("2022-03-01T02:00:00+02:00").unwrap(), midnight);
+        assert!(parse_until("next week").is_err());
+    }
+}
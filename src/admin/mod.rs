@@ -1,10 +1,14 @@
 pub mod delete_crate;
 pub mod delete_version;
 pub mod dialoguer;
+pub mod lock_user;
 pub mod migrate;
 pub mod on_call;
 pub mod populate;
+pub mod recent_emails;
+pub mod rename_crate;
 pub mod render_readmes;
 pub mod test_pagerduty;
 pub mod transfer_crates;
+pub mod unlock_user;
 pub mod verify_token;
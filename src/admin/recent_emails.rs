@@ -0,0 +1,45 @@
+use crate::{db, models::EmailSend, util::errors::AppResult};
+
+#[derive(clap::Parser, Debug)]
+#[clap(
+    name = "recent-emails",
+    about = "List the most recently sent emails.",
+    long_about = "List the most recently sent emails, newest first, along with their delivery \
+        status. Failed delivery attempts are retried by the background worker, and the last \
+        error is shown for each email that has failed at least once."
+)]
+pub struct Opts {
+    /// How many emails to list
+    #[clap(long, default_value = "50")]
+    limit: i64,
+    /// Only list emails with at least one failed delivery attempt
+    #[clap(long)]
+    failures: bool,
+}
+
+pub fn run(opts: Opts) -> AppResult<()> {
+    let conn = db::connect_now()?;
+    let emails = EmailSend::recent(&conn, opts.limit, opts.failures)?;
+    if emails.is_empty() {
+        println!("no emails found");
+        return Ok(());
+    }
+
+    for email in emails {
+        let status: &str = email.status.into();
+        println!(
+            "{} {} {status} after {} attempts: {:?} to {} ({})",
+            email.id,
+            email.created_at.format("%Y-%m-%d %H:%M:%S"),
+            email.attempts,
+            email.subject,
+            email.recipient,
+            email.template,
+        );
+        if let Some(error) = email.last_error {
+            println!("    last error: {error}");
+        }
+    }
+
+    Ok(())
+}
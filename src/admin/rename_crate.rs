@@ -0,0 +1,35 @@
+use crate::{admin::dialoguer, db, models::Crate, util::errors::AppResult};
+
+use diesel::prelude::*;
+
+#[derive(clap::Parser, Debug)]
+#[clap(
+    name = "rename-crate",
+    about = "Rename a crate, keeping the old name as an alias.",
+    long_about = "Rename a crate, keeping the old name as an alias. The old name keeps \
+        resolving to the crate on the API and the download endpoint, and can no longer be \
+        used to publish a new crate. The index file of the old name is not modified."
+)]
+pub struct Opts {
+    /// Current name of the crate
+    crate_name: String,
+    /// New name of the crate
+    new_name: String,
+}
+
+pub fn run(opts: Opts) -> AppResult<()> {
+    let conn = db::connect_now()?;
+    let krate: Crate = Crate::by_name(&opts.crate_name).first(&conn)?;
+
+    let prompt = format!(
+        "Are you sure you want to rename {} ({}) to {}?",
+        krate.name, krate.id, opts.new_name
+    );
+    if !dialoguer::confirm(&prompt) {
+        return Ok(());
+    }
+
+    let krate = krate.rename(&conn, &opts.new_name)?;
+    println!("renamed {} to {}", opts.crate_name, krate.name);
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use crate::{admin::dialoguer, db, models::Owner, util::errors::AppResult};
+
+use diesel::prelude::*;
+
+#[derive(clap::Parser, Debug)]
+#[clap(name = "unlock-user", about = "Unlock a locked user account.")]
+pub struct Opts {
+    /// Login of the user, e.g. `foo` for GitHub users or `gitlab:foo` for GitLab users
+    login: String,
+}
+
+pub fn run(opts: Opts) -> AppResult<()> {
+    let conn = db::connect_now()?;
+    let user = Owner::find_user_by_login(&conn, &opts.login)?;
+
+    let reason = match &user.account_lock_reason {
+        Some(reason) => reason,
+        None => {
+            println!("{} is not locked", user.gh_login);
+            return Ok(());
+        }
+    };
+
+    let prompt = format!(
+        "Are you sure you want to unlock {} ({}), locked because of: {reason}?",
+        user.gh_login, user.id
+    );
+    if !dialoguer::confirm(&prompt) {
+        return Ok(());
+    }
+
+    user.unlock(&conn)?;
+    println!("unlocked {}", user.gh_login);
+    Ok(())
+}
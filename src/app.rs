@@ -7,10 +7,11 @@ use std::{sync::Arc, time::Duration};
 use crate::downloads_counter::DownloadsCounter;
 use crate::email::Emails;
 use crate::github::GitHubClient;
+use crate::login::LoginProviders;
 use crate::metrics::{InstanceMetrics, ServiceMetrics};
+use crate::oidc::OidcKeyStore;
 use diesel::r2d2;
 use moka::sync::{Cache, CacheBuilder};
-use oauth2::basic::BasicClient;
 use reqwest::blocking::Client;
 use scheduled_thread_pool::ScheduledThreadPool;
 
@@ -26,8 +27,8 @@ pub struct App {
     /// GitHub API client
     pub github: GitHubClient,
 
-    /// The GitHub OAuth2 configuration
-    pub github_oauth: BasicClient,
+    /// The OAuth providers users can log in with
+    pub login_providers: LoginProviders,
 
     /// The server configuration
     pub config: config::Server,
@@ -42,7 +43,10 @@ pub struct App {
     pub downloads_counter: DownloadsCounter,
 
     /// Backend used to send emails
-    pub emails: Emails,
+    pub emails: Arc<Emails>,
+
+    /// Keys used to verify the OIDC tokens exchanged through trusted publishing
+    pub oidc_keys: OidcKeyStore,
 
     /// Metrics related to the service as a whole
     pub service_metrics: ServiceMetrics,
@@ -63,25 +67,16 @@ impl App {
     ///
     /// Configures and sets up:
     ///
-    /// - GitHub OAuth
+    /// - The OAuth login providers
     /// - Database connection pools
     /// - A `git2::Repository` instance from the index repo checkout (that server.rs ensures exists)
     pub fn new(config: config::Server, http_client: Option<Client>) -> App {
-        use oauth2::{AuthUrl, ClientId, ClientSecret, TokenUrl};
-
         let instance_metrics =
             InstanceMetrics::new().expect("could not initialize instance metrics");
 
         let github = GitHubClient::new(http_client.clone(), config.gh_base_url.clone());
 
-        let github_oauth = BasicClient::new(
-            ClientId::new(config.gh_client_id.clone()),
-            Some(ClientSecret::new(config.gh_client_secret.clone())),
-            AuthUrl::new(String::from("https://github.com/login/oauth/authorize")).unwrap(),
-            Some(
-                TokenUrl::new(String::from("https://github.com/login/oauth/access_token")).unwrap(),
-            ),
-        );
+        let login_providers = LoginProviders::from_config(&config, http_client.clone());
 
         let db_helper_threads = match (dotenv::var("DB_HELPER_THREADS"), config.env()) {
             (Ok(num), _) => num.parse().expect("couldn't parse DB_HELPER_THREADS"),
@@ -163,11 +158,12 @@ impl App {
             primary_database,
             read_only_replica_database: replica_database,
             github,
-            github_oauth,
+            login_providers,
             config,
             version_id_cacher,
             downloads_counter: DownloadsCounter::new(),
-            emails: Emails::from_environment(),
+            emails: Arc::new(Emails::from_environment()),
+            oidc_keys: OidcKeyStore::new(http_client.clone()),
             service_metrics: ServiceMetrics::new().expect("could not initialize service metrics"),
             instance_metrics,
             http_client,
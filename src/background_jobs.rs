@@ -5,7 +5,9 @@ use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use swirl::PerformError;
 
 use crate::db::{DieselPool, DieselPooledConn, PoolError};
+use crate::email::Emails;
 use crate::git::Repository;
+use crate::github::GitHubClient;
 use crate::uploaders::Uploader;
 
 impl<'a> swirl::db::BorrowedConnection<'a> for DieselPool {
@@ -24,6 +26,8 @@ pub struct Environment {
     index: Arc<Mutex<Repository>>,
     pub uploader: Uploader,
     http_client: AssertUnwindSafe<Client>,
+    github: AssertUnwindSafe<GitHubClient>,
+    emails: Arc<Emails>,
 }
 
 impl Clone for Environment {
@@ -32,24 +36,42 @@ impl Clone for Environment {
             index: self.index.clone(),
             uploader: self.uploader.clone(),
             http_client: AssertUnwindSafe(self.http_client.0.clone()),
+            github: AssertUnwindSafe(self.github.0.clone()),
+            emails: self.emails.clone(),
         }
     }
 }
 
 impl Environment {
-    pub fn new(index: Repository, uploader: Uploader, http_client: Client) -> Self {
-        Self::new_shared(Arc::new(Mutex::new(index)), uploader, http_client)
+    pub fn new(
+        index: Repository,
+        uploader: Uploader,
+        http_client: Client,
+        github: GitHubClient,
+        emails: Arc<Emails>,
+    ) -> Self {
+        Self::new_shared(
+            Arc::new(Mutex::new(index)),
+            uploader,
+            http_client,
+            github,
+            emails,
+        )
     }
 
     pub fn new_shared(
         index: Arc<Mutex<Repository>>,
         uploader: Uploader,
         http_client: Client,
+        github: GitHubClient,
+        emails: Arc<Emails>,
     ) -> Self {
         Self {
             index,
             uploader,
             http_client: AssertUnwindSafe(http_client),
+            github: AssertUnwindSafe(github),
+            emails,
         }
     }
 
@@ -63,4 +85,14 @@ impl Environment {
     pub(crate) fn http_client(&self) -> &Client {
         &self.http_client
     }
+
+    /// Returns the client for asking GitHub about teams.
+    pub(crate) fn github(&self) -> &GitHubClient {
+        &self.github
+    }
+
+    /// Returns the backend used to send emails.
+    pub(crate) fn emails(&self) -> &Emails {
+        &self.emails
+    }
 }
@@ -13,7 +13,9 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 use cargo_registry::config;
+use cargo_registry::email::Emails;
 use cargo_registry::git::{Repository, RepositoryConfig};
+use cargo_registry::github::{self, GitHubClient};
 use cargo_registry::{background_jobs::*, db};
 use diesel::r2d2;
 use reqwest::blocking::Client;
@@ -27,6 +29,7 @@ fn main() {
     let db_config = config::DatabasePools::full_from_environment();
     let base_config = config::Base::from_environment();
     let uploader = base_config.uploader();
+    let emails = Arc::new(Emails::from_environment());
 
     if db_config.are_all_read_only() {
         loop {
@@ -58,7 +61,14 @@ fn main() {
             .timeout(Duration::from_secs(45))
             .build()
             .expect("Couldn't build client");
-        let environment = Environment::new_shared(repository.clone(), uploader.clone(), client);
+        let github = GitHubClient::new(Some(client.clone()), github::BASE_URL.to_string());
+        let environment = Environment::new_shared(
+            repository.clone(),
+            uploader.clone(),
+            client,
+            github,
+            emails.clone(),
+        );
         let db_config = r2d2::Pool::builder().min_idle(Some(0));
         swirl::Runner::builder(environment)
             .connection_pool_builder(&db_url, db_config)
@@ -1,8 +1,8 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 use cargo_registry::admin::{
-    delete_crate, delete_version, migrate, populate, render_readmes, test_pagerduty,
-    transfer_crates, verify_token,
+    delete_crate, delete_version, lock_user, migrate, populate, recent_emails, rename_crate,
+    render_readmes, test_pagerduty, transfer_crates, unlock_user, verify_token,
 };
 
 #[derive(clap::Parser, Debug)]
@@ -16,10 +16,14 @@ struct Opts {
 enum SubCommand {
     DeleteCrate(delete_crate::Opts),
     DeleteVersion(delete_version::Opts),
+    LockUser(lock_user::Opts),
     Populate(populate::Opts),
+    RecentEmails(recent_emails::Opts),
+    RenameCrate(rename_crate::Opts),
     RenderReadmes(render_readmes::Opts),
     TestPagerduty(test_pagerduty::Opts),
     TransferCrates(transfer_crates::Opts),
+    UnlockUser(unlock_user::Opts),
     VerifyToken(verify_token::Opts),
     Migrate(migrate::Opts),
 }
@@ -32,10 +36,14 @@ fn main() -> anyhow::Result<()> {
     match opts.command {
         SubCommand::DeleteCrate(opts) => delete_crate::run(opts),
         SubCommand::DeleteVersion(opts) => delete_version::run(opts),
+        SubCommand::LockUser(opts) => lock_user::run(opts).unwrap(),
         SubCommand::Populate(opts) => populate::run(opts),
+        SubCommand::RecentEmails(opts) => recent_emails::run(opts).unwrap(),
+        SubCommand::RenameCrate(opts) => rename_crate::run(opts).unwrap(),
         SubCommand::RenderReadmes(opts) => render_readmes::run(opts)?,
         SubCommand::TestPagerduty(opts) => test_pagerduty::run(opts)?,
         SubCommand::TransferCrates(opts) => transfer_crates::run(opts),
+        SubCommand::UnlockUser(opts) => unlock_user::run(opts).unwrap(),
         SubCommand::VerifyToken(opts) => verify_token::run(opts).unwrap(),
         SubCommand::Migrate(opts) => migrate::run(opts)?,
     }
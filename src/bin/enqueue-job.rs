@@ -1,7 +1,7 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 use anyhow::{anyhow, Result};
-use cargo_registry::{db, env, worker};
+use cargo_registry::{config, db, env, env_optional, worker};
 use diesel::prelude::*;
 use swirl::schema::background_jobs::dsl::*;
 use swirl::Job;
@@ -37,6 +37,22 @@ fn main() -> Result<()> {
         }
         "daily_db_maintenance" => Ok(worker::daily_db_maintenance().enqueue(&conn)?),
         "squash_index" => Ok(worker::squash_index().enqueue(&conn)?),
+        "notify_expiring_tokens" => Ok(worker::notify_expiring_tokens().enqueue(&conn)?),
+        "process_owner_invitations" => {
+            let expiration_days = env_optional("OWNERSHIP_INVITATIONS_EXPIRATION_DAYS")
+                .unwrap_or(config::DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS);
+            Ok(worker::process_owner_invitations(expiration_days).enqueue(&conn)?)
+        }
+        "prune_token_activity" => {
+            let retention_days = env_optional("API_TOKEN_ACTIVITY_RETENTION_DAYS")
+                .unwrap_or(worker::prune_token_activity::DEFAULT_RETENTION_DAYS);
+            Ok(worker::prune_token_activity(retention_days).enqueue(&conn)?)
+        }
+        "refresh_team_memberships" => {
+            let refresh_after = env_optional("TEAM_MEMBERSHIP_REFRESH_AFTER")
+                .unwrap_or(worker::team_memberships::DEFAULT_REFRESH_AFTER_SECONDS);
+            Ok(worker::refresh_team_memberships(refresh_after).enqueue(&conn)?)
+        }
         other => Err(anyhow!("Unrecognized job type `{}`", other)),
     }
 }
@@ -1,16 +1,24 @@
+use crate::github::{self, GitHubPublicKey, GitHubPublicKeyList};
+use crate::oidc::GITHUB_ACTIONS_ISSUER;
 use crate::publish_rate_limit::PublishRateLimit;
 use crate::{env, env_optional, uploaders::Uploader, Env};
 
 mod base;
 mod database_pools;
+mod login_providers;
 
 pub use self::base::Base;
 pub use self::database_pools::DatabasePools;
+pub use self::login_providers::{GitLabLoginConfig, OpenIdLoginConfig};
 use std::collections::HashSet;
 use std::time::Duration;
 
 const DEFAULT_VERSION_ID_CACHE_SIZE: u64 = 10_000;
 const DEFAULT_VERSION_ID_CACHE_TTL: u64 = 5 * 60; // 5 minutes
+pub const DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS: u64 = 30;
+const DEFAULT_CRATE_ADOPTION_WAITING_PERIOD_DAYS: u64 = 30;
+const DEFAULT_TEAM_MEMBERSHIP_CACHE_TTL: u64 = 10 * 60; // 10 minutes
+const DEFAULT_TEAM_MEMBERSHIP_FALLBACK_TTL: u64 = 24 * 60 * 60; // 1 day
 
 pub struct Server {
     pub base: Base,
@@ -29,6 +37,8 @@ pub struct Server {
     pub allowed_origins: Vec<String>,
     pub downloads_persist_interval_ms: usize,
     pub ownership_invitations_expiration_days: u64,
+    /// How long owners can object to a request to adopt their crate before admins review it
+    pub crate_adoption_waiting_period_days: u64,
     pub metrics_authorization_token: Option<String>,
     pub use_test_database_pool: bool,
     pub instance_metrics_log_every_seconds: Option<u64>,
@@ -36,6 +46,15 @@ pub struct Server {
     pub blocked_routes: HashSet<String>,
     pub version_id_cache_size: u64,
     pub version_id_cache_ttl: Duration,
+    /// How long GitHub's answer to whether a user is a member of a team is used for
+    pub team_membership_cache_ttl: Duration,
+    /// How long a confirmed team membership is still trusted while GitHub is unavailable
+    pub team_membership_fallback_ttl: Duration,
+    pub max_token_lifetime_days: Option<u64>,
+    pub secret_scanning_public_keys: Vec<GitHubPublicKey>,
+    pub trusted_publishing_issuers: Vec<String>,
+    pub gitlab_login: Option<GitLabLoginConfig>,
+    pub openid_login: Option<OpenIdLoginConfig>,
 }
 
 impl Default for Server {
@@ -44,7 +63,6 @@ impl Default for Server {
     /// Sets the following default values:
     ///
     /// - `Config::max_upload_size`: 10MiB
-    /// - `Config::ownership_invitations_expiration_days`: 30
     ///
     /// Pulls values from the following environment variables:
     ///
@@ -68,6 +86,23 @@ impl Default for Server {
     ///   endpoint even with a healthy database pool.
     /// - `BLOCKED_ROUTES`: A comma separated list of HTTP route patterns that are manually blocked
     ///   by an operator (e.g. `/crates/:crate_id/:version/download`).
+    /// - `MAX_TOKEN_LIFETIME_DAYS`: The maximum number of days a new API token may be valid for.
+    ///   If set, tokens created without an expiration date expire after this many days.
+    /// - `GITHUB_SECRET_SCANNING_KEYS`: The public keys used to verify leaked token reports, in
+    ///   the JSON format of `https://api.github.com/meta/public_keys/secret_scanning`. If not
+    ///   set, all reports are rejected.
+    /// - `OWNERSHIP_INVITATIONS_EXPIRATION_DAYS`: How many days an invitation to become an owner
+    ///   of a crate can be accepted for. Defaults to 30.
+    /// - `CRATE_ADOPTION_WAITING_PERIOD_DAYS`: How many days owners are given to object to a
+    ///   request to adopt their crate before it is reviewed by the admins. Defaults to 30.
+    /// - `TEAM_MEMBERSHIP_CACHE_TTL`: How many seconds GitHub's answer to whether a user is a
+    ///   member of a team is cached for. Defaults to 10 minutes.
+    /// - `TEAM_MEMBERSHIP_FALLBACK_TTL`: How many seconds after it was last confirmed a team
+    ///   membership is still trusted while GitHub is unavailable. Defaults to 1 day.
+    /// - `TRUSTED_PUBLISHING_ISSUERS`: A comma separated list of the OIDC issuers whose tokens can
+    ///   be exchanged for API tokens through trusted publishing. Defaults to GitHub Actions.
+    /// - The variables documented in the `login_providers` module, which enable logging in with
+    ///   GitLab or a generic OpenID Connect provider.
     ///
     /// # Panics
     ///
@@ -83,13 +118,19 @@ impl Default for Server {
             Some(s) if s.is_empty() => vec![],
             Some(s) => s.split(',').map(String::from).collect(),
         };
+        let trusted_publishing_issuers = match env_optional::<String>("TRUSTED_PUBLISHING_ISSUERS")
+        {
+            None => vec![GITHUB_ACTIONS_ISSUER.to_string()],
+            Some(s) if s.is_empty() => vec![],
+            Some(s) => s.split(',').map(String::from).collect(),
+        };
         Server {
             db: DatabasePools::full_from_environment(),
             base: Base::from_environment(),
             session_key: env("SESSION_KEY"),
             gh_client_id: env("GH_CLIENT_ID"),
             gh_client_secret: env("GH_CLIENT_SECRET"),
-            gh_base_url: "https://api.github.com".to_string(),
+            gh_base_url: github::BASE_URL.to_string(),
             max_upload_size: 10 * 1024 * 1024, // 10 MB default file upload size limit
             max_unpack_size: 512 * 1024 * 1024, // 512 MB max when decompressed
             publish_rate_limit: Default::default(),
@@ -105,7 +146,12 @@ impl Default for Server {
                         .expect("invalid DOWNLOADS_PERSIST_INTERVAL_MS")
                 })
                 .unwrap_or(60_000), // 1 minute
-            ownership_invitations_expiration_days: 30,
+            ownership_invitations_expiration_days: env_optional(
+                "OWNERSHIP_INVITATIONS_EXPIRATION_DAYS",
+            )
+            .unwrap_or(DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS),
+            crate_adoption_waiting_period_days: env_optional("CRATE_ADOPTION_WAITING_PERIOD_DAYS")
+                .unwrap_or(DEFAULT_CRATE_ADOPTION_WAITING_PERIOD_DAYS),
             metrics_authorization_token: dotenv::var("METRICS_AUTHORIZATION_TOKEN").ok(),
             use_test_database_pool: false,
             instance_metrics_log_every_seconds: env_optional("INSTANCE_METRICS_LOG_EVERY_SECONDS"),
@@ -118,6 +164,19 @@ impl Default for Server {
             version_id_cache_ttl: Duration::from_secs(
                 env_optional("VERSION_ID_CACHE_TTL").unwrap_or(DEFAULT_VERSION_ID_CACHE_TTL),
             ),
+            team_membership_cache_ttl: Duration::from_secs(
+                env_optional("TEAM_MEMBERSHIP_CACHE_TTL")
+                    .unwrap_or(DEFAULT_TEAM_MEMBERSHIP_CACHE_TTL),
+            ),
+            team_membership_fallback_ttl: Duration::from_secs(
+                env_optional("TEAM_MEMBERSHIP_FALLBACK_TTL")
+                    .unwrap_or(DEFAULT_TEAM_MEMBERSHIP_FALLBACK_TTL),
+            ),
+            max_token_lifetime_days: env_optional("MAX_TOKEN_LIFETIME_DAYS"),
+            secret_scanning_public_keys: secret_scanning_public_keys(),
+            trusted_publishing_issuers,
+            gitlab_login: GitLabLoginConfig::from_environment(),
+            openid_login: OpenIdLoginConfig::from_environment(),
         }
     }
 }
@@ -136,6 +195,17 @@ pub(crate) fn domain_name() -> String {
     dotenv::var("DOMAIN_NAME").unwrap_or_else(|_| "crates.io".into())
 }
 
+fn secret_scanning_public_keys() -> Vec<GitHubPublicKey> {
+    match dotenv::var("GITHUB_SECRET_SCANNING_KEYS") {
+        Ok(keys) => {
+            let keys: GitHubPublicKeyList =
+                serde_json::from_str(&keys).expect("invalid GITHUB_SECRET_SCANNING_KEYS");
+            keys.public_keys
+        }
+        Err(_) => vec![],
+    }
+}
+
 fn blocked_traffic() -> Vec<(String, Vec<String>)> {
     let pattern_list = dotenv::var("BLOCKED_TRAFFIC").unwrap_or_default();
     parse_traffic_patterns(&pattern_list)
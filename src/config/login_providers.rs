@@ -0,0 +1,60 @@
+//! Configuration of the login providers offered in addition to GitHub
+//!
+//! Each provider is only enabled if its client ID and secret are set.
+//!
+//! - `GITLAB_CLIENT_ID`, `GITLAB_CLIENT_SECRET`: The credentials of the GitLab OAuth application.
+//! - `GITLAB_BASE_URL`: The URL of the GitLab instance. Defaults to `https://gitlab.com`.
+//! - `OIDC_LOGIN_CLIENT_ID`, `OIDC_LOGIN_CLIENT_SECRET`: The credentials of the OpenID Connect
+//!   client.
+//! - `OIDC_LOGIN_AUTHORIZE_URL`, `OIDC_LOGIN_TOKEN_URL`, `OIDC_LOGIN_USERINFO_URL`: The
+//!   endpoints of the OpenID Connect provider. Required if the client ID is set.
+//! - `OIDC_LOGIN_NAME`: The name used for the provider in URLs. Defaults to `oidc`.
+//! - `OIDC_LOGIN_DISPLAY_NAME`: The name shown to users. Defaults to `Single Sign-On`.
+
+use crate::{env, env_optional};
+
+pub struct GitLabLoginConfig {
+    pub base_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl GitLabLoginConfig {
+    pub fn from_environment() -> Option<Self> {
+        let client_id = env_optional("GITLAB_CLIENT_ID")?;
+
+        Some(Self {
+            base_url: env_optional("GITLAB_BASE_URL")
+                .unwrap_or_else(|| "https://gitlab.com".to_string()),
+            client_id,
+            client_secret: env("GITLAB_CLIENT_SECRET"),
+        })
+    }
+}
+
+pub struct OpenIdLoginConfig {
+    pub name: String,
+    pub display_name: String,
+    pub authorize_url: String,
+    pub token_url: String,
+    pub userinfo_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl OpenIdLoginConfig {
+    pub fn from_environment() -> Option<Self> {
+        let client_id = env_optional("OIDC_LOGIN_CLIENT_ID")?;
+
+        Some(Self {
+            name: env_optional("OIDC_LOGIN_NAME").unwrap_or_else(|| "oidc".to_string()),
+            display_name: env_optional("OIDC_LOGIN_DISPLAY_NAME")
+                .unwrap_or_else(|| "Single Sign-On".to_string()),
+            authorize_url: env("OIDC_LOGIN_AUTHORIZE_URL"),
+            token_url: env("OIDC_LOGIN_TOKEN_URL"),
+            userinfo_url: env("OIDC_LOGIN_USERINFO_URL"),
+            client_id,
+            client_secret: env("OIDC_LOGIN_CLIENT_SECRET"),
+        })
+    }
+}
@@ -84,8 +84,10 @@ mod prelude {
 pub mod helpers;
 mod util;
 
+pub mod admin;
 pub mod category;
 pub mod crate_owner_invitation;
+pub mod github;
 pub mod keyword;
 pub mod krate;
 pub mod metrics;
@@ -0,0 +1,442 @@
+//! Endpoints for site administrators
+//!
+//! All routes require a cookie session of a user with the `is_admin` flag, and every action is
+//! recorded in the `admin_actions` audit log.
+
+use crate::controllers::frontend_prelude::*;
+
+use chrono::{DateTime, Duration, Utc};
+use swirl::Job;
+
+use crate::models::{
+    insert_version_owner_action, AdminAction, AdoptionStatus, Crate, CrateAdoptionRequest,
+    NewAdminAction, User, Version, VersionAction,
+};
+use crate::schema::{
+    admin_actions, crate_adoption_requests, crates, publish_rate_overrides, users, versions,
+};
+use crate::util::errors::not_found;
+use crate::views::EncodableCrateAdoptionRequest;
+use crate::worker;
+
+/// The number of entries returned by the audit log endpoint.
+const AUDIT_LOG_LIMIT: i64 = 100;
+
+/// Handles the `GET /api/private/admin/actions` route.
+pub fn actions(req: &mut dyn RequestExt) -> EndpointResult {
+    req.authenticate()?.require_admin()?;
+    let conn = req.db_conn()?;
+
+    let actions: Vec<(AdminAction, String)> = admin_actions::table
+        .inner_join(users::table)
+        .select((admin_actions::all_columns, users::gh_login))
+        .order(admin_actions::id.desc())
+        .limit(AUDIT_LOG_LIMIT)
+        .load(&*conn)?;
+
+    let actions = actions
+        .into_iter()
+        .map(|(action, admin)| json!({ "admin": admin, "action": action }))
+        .collect::<Vec<_>>();
+
+    Ok(req.json(&json!({ "actions": actions })))
+}
+
+/// Handles the `PUT /api/private/admin/users/:user_id/lock` route.
+///
+/// The format of the request body is:
+///
+/// ```json
+/// {"reason": "spam", "until": "2022-03-01T00:00:00Z"}
+/// ```
+///
+/// Without `until`, the account is locked indefinitely.
+pub fn lock_user(req: &mut dyn RequestExt) -> EndpointResult {
+    #[derive(Deserialize)]
+    struct Request {
+        reason: String,
+        #[serde(default)]
+        until: Option<DateTime<Utc>>,
+    }
+
+    let request: Request = parse_body(req)?;
+    if request.reason.trim().is_empty() {
+        return Err(bad_request("a reason for locking the account is required"));
+    }
+
+    let admin = req.authenticate()?.require_admin()?.user();
+    let conn = req.db_conn()?;
+    let user = find_user(&conn, req)?;
+    let until = request.until.map(|until| until.naive_utc());
+
+    conn.transaction(|| {
+        user.lock(&conn, &request.reason, until)?;
+        record_action(
+            &conn,
+            &admin,
+            "lock_user",
+            &user.gh_login,
+            json!({ "reason": request.reason, "until": request.until }),
+        )?;
+        ok_true()
+    })
+}
+
+/// Handles the `DELETE /api/private/admin/users/:user_id/lock` route.
+pub fn unlock_user(req: &mut dyn RequestExt) -> EndpointResult {
+    let admin = req.authenticate()?.require_admin()?.user();
+    let conn = req.db_conn()?;
+    let user = find_user(&conn, req)?;
+
+    conn.transaction(|| {
+        user.unlock(&conn)?;
+        record_action(&conn, &admin, "unlock_user", &user.gh_login, json!({}))?;
+        ok_true()
+    })
+}
+
+/// Handles the `PUT /api/private/admin/users/:user_id/transfer` route.
+///
+/// Transfers all crates owned by the user to another user, given by their id. The format of the
+/// request body is:
+///
+/// ```json
+/// {"to": 42}
+/// ```
+pub fn transfer_crates(req: &mut dyn RequestExt) -> EndpointResult {
+    #[derive(Deserialize)]
+    struct Request {
+        to: i32,
+    }
+
+    let request: Request = parse_body(req)?;
+
+    let admin = req.authenticate()?.require_admin()?.user();
+    let conn = req.db_conn()?;
+    let from = find_user(&conn, req)?;
+    let to = User::find(&conn, request.to)?;
+    if from.id == to.id {
+        return Err(bad_request("cannot transfer crates to the same user"));
+    }
+
+    conn.transaction(|| {
+        let names = from.transfer_crates(&conn, &to, admin.id)?;
+        record_action(
+            &conn,
+            &admin,
+            "transfer_crates",
+            &from.gh_login,
+            json!({ "to": to.gh_login, "crates": names }),
+        )?;
+
+        Ok(req.json(&json!({ "crates": names })))
+    })
+}
+
+/// Handles the `PUT /api/private/admin/users/:user_id/publish_rate_override` route.
+///
+/// The format of the request body is:
+///
+/// ```json
+/// {"burst": 100, "expires_at": "2022-03-01T00:00:00Z"}
+/// ```
+///
+/// Without `expires_at`, the override applies until it is removed.
+pub fn set_publish_rate_override(req: &mut dyn RequestExt) -> EndpointResult {
+    #[derive(Deserialize)]
+    struct Request {
+        burst: i32,
+        #[serde(default)]
+        expires_at: Option<DateTime<Utc>>,
+    }
+
+    let request: Request = parse_body(req)?;
+    if request.burst < 1 {
+        return Err(bad_request("burst must be a positive number"));
+    }
+
+    let admin = req.authenticate()?.require_admin()?.user();
+    let conn = req.db_conn()?;
+    let user = find_user(&conn, req)?;
+    let expires_at = request.expires_at.map(|expires_at| expires_at.naive_utc());
+
+    conn.transaction(|| {
+        diesel::insert_into(publish_rate_overrides::table)
+            .values((
+                publish_rate_overrides::user_id.eq(user.id),
+                publish_rate_overrides::burst.eq(request.burst),
+                publish_rate_overrides::expires_at.eq(expires_at),
+            ))
+            .on_conflict(publish_rate_overrides::user_id)
+            .do_update()
+            .set((
+                publish_rate_overrides::burst.eq(request.burst),
+                publish_rate_overrides::expires_at.eq(expires_at),
+            ))
+            .execute(&*conn)?;
+
+        record_action(
+            &conn,
+            &admin,
+            "set_publish_rate_override",
+            &user.gh_login,
+            json!({ "burst": request.burst, "expires_at": request.expires_at }),
+        )?;
+        ok_true()
+    })
+}
+
+/// Handles the `DELETE /api/private/admin/users/:user_id/publish_rate_override` route.
+pub fn remove_publish_rate_override(req: &mut dyn RequestExt) -> EndpointResult {
+    let admin = req.authenticate()?.require_admin()?.user();
+    let conn = req.db_conn()?;
+    let user = find_user(&conn, req)?;
+
+    conn.transaction(|| {
+        diesel::delete(publish_rate_overrides::table.find(user.id)).execute(&*conn)?;
+        record_action(
+            &conn,
+            &admin,
+            "remove_publish_rate_override",
+            &user.gh_login,
+            json!({}),
+        )?;
+        ok_true()
+    })
+}
+
+/// Handles the `DELETE /api/private/admin/crates/:crate_id/:version` route.
+///
+/// This purges the version from the database. The index file of the crate is not modified, so
+/// the version should be yanked first.
+pub fn delete_version(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req.authenticate()?.require_admin()?;
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+    let admin = authenticated_user.user();
+    let (krate, version) = find_version(&conn, req)?;
+
+    conn.transaction(|| {
+        diesel::delete(versions::table.find(version.id)).execute(&*conn)?;
+        record_action(
+            &conn,
+            &admin,
+            "delete_version",
+            &format!("{}@{}", krate.name, version.num),
+            json!({}),
+        )?;
+        ok_true()
+    })
+}
+
+/// Handles the `DELETE /api/private/admin/crates/:crate_id/:version/yank` route.
+pub fn yank(req: &mut dyn RequestExt) -> EndpointResult {
+    modify_yank(req, true)
+}
+
+/// Handles the `PUT /api/private/admin/crates/:crate_id/:version/unyank` route.
+pub fn unyank(req: &mut dyn RequestExt) -> EndpointResult {
+    modify_yank(req, false)
+}
+
+/// Yanks or unyanks a version on behalf of its owners. The version owner action is attributed
+/// to the admin.
+fn modify_yank(req: &mut dyn RequestExt, yanked: bool) -> EndpointResult {
+    let admin = req.authenticate()?.require_admin()?.user();
+    let conn = req.db_conn()?;
+    let (krate, version) = find_version(&conn, req)?;
+
+    if version.yanked == yanked {
+        return ok_true();
+    }
+
+    let action = if yanked {
+        VersionAction::Yank
+    } else {
+        VersionAction::Unyank
+    };
+
+    conn.transaction(|| {
+        diesel::update(&version)
+            .set(versions::yanked.eq(yanked))
+            .execute(&*conn)?;
+
+        let owner_action = insert_version_owner_action(&conn, version.id, admin.id, None, action)?;
+        record_action(
+            &conn,
+            &admin,
+            action.into(),
+            &format!("{}@{}", krate.name, version.num),
+            json!({}),
+        )?;
+
+        // Versions published before a rename live in the index file of the old name
+        let index_name = Crate::name_at(&conn, krate.id, version.created_at)?
+            .unwrap_or_else(|| krate.name.clone());
+        worker::sync_yanked(index_name, version.num.clone()).enqueue(&conn)?;
+        worker::notify_owners_of_version_action(owner_action.id).enqueue(&conn)?;
+
+        ok_true()
+    })
+}
+
+/// Handles the `GET /api/private/admin/adoption_requests` route.
+///
+/// Lists the pending adoption requests whose waiting period is over, oldest first.
+pub fn adoption_requests(req: &mut dyn RequestExt) -> EndpointResult {
+    req.authenticate()?.require_admin()?;
+    let conn = req.db_conn()?;
+    let config = &req.app().config;
+
+    let waiting_period = Duration::days(config.crate_adoption_waiting_period_days as i64);
+    let requests: Vec<(CrateAdoptionRequest, String, String)> = crate_adoption_requests::table
+        .inner_join(crates::table)
+        .inner_join(users::table)
+        .filter(crate_adoption_requests::status.eq(AdoptionStatus::Pending))
+        .filter(crate_adoption_requests::created_at.le(Utc::now().naive_utc() - waiting_period))
+        .select((
+            crate_adoption_requests::all_columns,
+            crates::name,
+            users::gh_login,
+        ))
+        .order(crate_adoption_requests::created_at)
+        .load(&*conn)?;
+
+    let requests = requests
+        .into_iter()
+        .map(|(request, crate_name, requester)| {
+            let review_after = request.review_after(config);
+            EncodableCrateAdoptionRequest::from(request, crate_name, requester, review_after)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(req.json(&json!({ "adoption_requests": requests })))
+}
+
+/// Handles the `PUT /api/private/admin/adoption_requests/:id/approve` route.
+///
+/// Makes the requester the only owner of the crate. The previous owners are recorded in the
+/// audit logs of the crate and of the admins.
+pub fn approve_adoption(req: &mut dyn RequestExt) -> EndpointResult {
+    let admin = req.authenticate()?.require_admin()?.user();
+    let conn = req.db_conn()?;
+    let (adoption_request, krate, requester) = find_adoption_request(&conn, req)?;
+
+    if !adoption_request.is_ready_for_review(&req.app().config) {
+        return Err(bad_request(
+            "the adoption request is still waiting for objections of the owners",
+        ));
+    }
+
+    conn.transaction(|| {
+        let previous_owners = adoption_request.approve(&conn, &krate, &admin, &requester)?;
+        record_action(
+            &conn,
+            &admin,
+            "approve_adoption",
+            &krate.name,
+            json!({
+                "request": adoption_request.id,
+                "to": requester.gh_login,
+                "previous_owners": previous_owners,
+            }),
+        )
+    })?;
+
+    notify_adoption_requester(req, &conn, &requester, &krate, "approved");
+    ok_true()
+}
+
+/// Handles the `PUT /api/private/admin/adoption_requests/:id/reject` route.
+pub fn reject_adoption(req: &mut dyn RequestExt) -> EndpointResult {
+    let admin = req.authenticate()?.require_admin()?.user();
+    let conn = req.db_conn()?;
+    let (adoption_request, krate, requester) = find_adoption_request(&conn, req)?;
+
+    conn.transaction(|| {
+        if !adoption_request.resolve(&conn, AdoptionStatus::Rejected, admin.id)? {
+            return Err(bad_request("the adoption request is not pending anymore"));
+        }
+        record_action(
+            &conn,
+            &admin,
+            "reject_adoption",
+            &krate.name,
+            json!({ "request": adoption_request.id, "to": requester.gh_login }),
+        )
+    })?;
+
+    notify_adoption_requester(req, &conn, &requester, &krate, "rejected");
+    ok_true()
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(req: &mut dyn RequestExt) -> AppResult<T> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))
+}
+
+/// Users are identified by their id, since logins can change and are only unique per provider.
+fn find_user(conn: &PgConnection, req: &dyn RequestExt) -> AppResult<User> {
+    let id = req.params()["user_id"]
+        .parse::<i32>()
+        .map_err(|_| not_found())?;
+    Ok(User::find(conn, id)?)
+}
+
+fn find_version(conn: &PgConnection, req: &dyn RequestExt) -> AppResult<(Crate, Version)> {
+    let krate: Crate = Crate::by_name(&req.params()["crate_id"]).first(conn)?;
+    let version = krate.find_version(conn, &req.params()["version"])?;
+    Ok((krate, version))
+}
+
+fn find_adoption_request(
+    conn: &PgConnection,
+    req: &dyn RequestExt,
+) -> AppResult<(CrateAdoptionRequest, Crate, User)> {
+    let id = req.params()["id"].parse::<i32>().map_err(|_| not_found())?;
+    let adoption_request: CrateAdoptionRequest =
+        crate_adoption_requests::table.find(id).first(conn)?;
+    let krate: Crate = crates::table.find(adoption_request.crate_id).first(conn)?;
+    let requester: User = users::table
+        .find(adoption_request.requested_by_user_id)
+        .first(conn)?;
+    Ok((adoption_request, krate, requester))
+}
+
+/// Tells the requester what became of their adoption request. Errors are swallowed, since the
+/// request has already been resolved.
+fn notify_adoption_requester(
+    req: &dyn RequestExt,
+    conn: &PgConnection,
+    requester: &User,
+    krate: &Crate,
+    outcome: &str,
+) {
+    if let Ok(Some(email)) = requester.verified_email(conn) {
+        let _ = req.app().emails.send_adoption_request_resolved(
+            conn,
+            &email,
+            &requester.gh_login,
+            &krate.name,
+            outcome,
+        );
+    }
+}
+
+fn record_action(
+    conn: &PgConnection,
+    admin: &User,
+    action: &str,
+    target: &str,
+    details: serde_json::Value,
+) -> AppResult<()> {
+    NewAdminAction {
+        admin_id: admin.id,
+        action,
+        target,
+        details,
+    }
+    .insert(conn)?;
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use super::frontend_prelude::*;
 
 use crate::controllers::helpers::pagination::{Page, PaginationOptions};
-use crate::controllers::util::AuthenticatedUser;
-use crate::models::{Crate, CrateOwnerInvitation, Rights, User};
+use crate::controllers::util::{record_token_activity, AuthenticatedUser};
+use crate::models::{Crate, CrateOwnerInvitation, EndpointScope, Rights, User};
 use crate::schema::{crate_owner_invitations, crates, users};
 use crate::util::errors::{forbidden, internal};
 use crate::views::{
@@ -94,8 +94,8 @@ fn prepare_list(
             ListFilter::CrateName(crate_name) => {
                 // Only allow crate owners to query pending invitations for their crate.
                 let krate: Crate = Crate::by_name(&crate_name).first(&*conn)?;
-                let owners = krate.owners(&*conn)?;
-                if user.rights(req.app(), &owners)? != Rights::Full {
+                let owners = krate.owners_with_roles(&*conn)?;
+                if user.rights(req.app(), &conn, &owners)? != Rights::Full {
                     return Err(forbidden());
                 }
 
@@ -255,17 +255,27 @@ pub fn handle_invite(req: &mut dyn RequestExt) -> EndpointResult {
         serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))?;
 
     let crate_invite = crate_invite.crate_owner_invite;
-    let user_id = req.authenticate()?.user_id();
+    let authenticated_user = req
+        .authenticate()?
+        .require_scope(EndpointScope::ChangeOwners)?;
+    let user_id = authenticated_user.user_id();
+    let api_token_id = authenticated_user.api_token_id();
     let conn = &*req.db_conn()?;
     let config = &req.app().config;
 
     let invitation = CrateOwnerInvitation::find_by_id(user_id, crate_invite.crate_id, conn)?;
     if crate_invite.accepted {
-        invitation.accept(conn, config)?;
+        invitation.accept(conn, config, api_token_id)?;
     } else {
-        invitation.decline(conn)?;
+        invitation.decline(conn, api_token_id)?;
     }
 
+    let crate_name: String = crates::table
+        .find(crate_invite.crate_id)
+        .select(crates::name)
+        .first(conn)?;
+    record_token_activity(conn, req, api_token_id, Some(&crate_name), None)?;
+
     Ok(req.json(&json!({ "crate_owner_invitation": crate_invite })))
 }
 
@@ -277,7 +287,7 @@ pub fn handle_invite_with_token(req: &mut dyn RequestExt) -> EndpointResult {
 
     let invitation = CrateOwnerInvitation::find_by_token(req_token, &conn)?;
     let crate_id = invitation.crate_id;
-    invitation.accept(&conn, config)?;
+    invitation.accept(&conn, config, None)?;
 
     Ok(req.json(&json!({
         "crate_owner_invitation": {
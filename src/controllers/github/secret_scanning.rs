@@ -0,0 +1,171 @@
+//! Endpoint for GitHub's secret scanning partner program
+//!
+//! GitHub scans public repositories for strings that look like crates.io API tokens and reports
+//! them to us. See <https://docs.github.com/en/developers/overview/secret-scanning-partner-program>
+//! for the request format.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::email::Emails;
+use crate::models::{ApiToken, User};
+use crate::schema::api_tokens;
+use crate::util::errors::internal;
+use crate::util::read_fill;
+use crate::util::token::{SecureToken, SecureTokenKind};
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Verifier;
+
+const GITHUB_PUBLIC_KEY_IDENTIFIER: &str = "GITHUB-PUBLIC-KEY-IDENTIFIER";
+const GITHUB_PUBLIC_KEY_SIGNATURE: &str = "GITHUB-PUBLIC-KEY-SIGNATURE";
+
+#[derive(Deserialize)]
+struct GitHubSecretAlert {
+    token: String,
+    #[serde(rename = "type")]
+    token_type: String,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct GitHubSecretAlertFeedback {
+    token_raw: String,
+    token_type: String,
+    label: GitHubSecretAlertFeedbackLabel,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum GitHubSecretAlertFeedbackLabel {
+    TruePositive,
+    FalsePositive,
+}
+
+/// Handles the `POST /api/github/secret-scanning/verify` route.
+pub fn verify(req: &mut dyn RequestExt) -> EndpointResult {
+    let max_size = 1024 * 1024;
+    let length = req
+        .content_length()
+        .ok_or_else(|| bad_request("missing header: Content-Length"))?;
+
+    if length > max_size {
+        return Err(bad_request(&format!("max content length is: {max_size}")));
+    }
+
+    let mut body = vec![0; length as usize];
+    read_fill(req.body(), &mut body)?;
+
+    verify_signature(req, &body)?;
+
+    let alerts: Vec<GitHubSecretAlert> = serde_json::from_slice(&body)
+        .map_err(|e| bad_request(&format!("invalid secret alert request: {e:?}")))?;
+
+    let conn = req.db_conn()?;
+    let emails = &req.app().emails;
+
+    let mut feedback = Vec::with_capacity(alerts.len());
+    for alert in alerts {
+        let label = if revoke_token(&*conn, emails, &alert)? {
+            GitHubSecretAlertFeedbackLabel::TruePositive
+        } else {
+            GitHubSecretAlertFeedbackLabel::FalsePositive
+        };
+
+        feedback.push(GitHubSecretAlertFeedback {
+            token_raw: alert.token,
+            token_type: alert.token_type,
+            label,
+        });
+    }
+
+    Ok(req.json(&feedback))
+}
+
+/// Checks that the request body was signed with one of the configured GitHub public keys.
+fn verify_signature(req: &dyn RequestExt, body: &[u8]) -> AppResult<()> {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| bad_request(&format!("missing header: {name}")))
+    };
+
+    let key_identifier = header(GITHUB_PUBLIC_KEY_IDENTIFIER)?;
+    let signature = header(GITHUB_PUBLIC_KEY_SIGNATURE)?;
+    let signature = base64::decode(signature).map_err(|_| bad_request("invalid signature"))?;
+
+    let public_key = req
+        .app()
+        .config
+        .secret_scanning_public_keys
+        .iter()
+        .find(|key| key.key_identifier == key_identifier)
+        .ok_or_else(|| bad_request("unknown public key identifier"))?;
+
+    let public_key = PKey::public_key_from_pem(public_key.key.as_bytes()).map_err(|e| {
+        internal(&format_args!(
+            "invalid secret scanning public key `{key_identifier}`: {e}"
+        ))
+    })?;
+
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &public_key)?;
+    verifier.update(body)?;
+
+    // Malformed signatures are reported as errors by OpenSSL, treat them as invalid ones.
+    if !verifier.verify(&signature).unwrap_or(false) {
+        return Err(bad_request("invalid signature"));
+    }
+
+    Ok(())
+}
+
+/// Revokes the API token reported in the alert and notifies its owner.
+///
+/// Returns `false` if the reported string is not an active crates.io API token. Both regular and
+/// trusted publishing tokens are accepted.
+fn revoke_token(
+    conn: &PgConnection,
+    emails: &Emails,
+    alert: &GitHubSecretAlert,
+) -> AppResult<bool> {
+    let hashed_token = SecureToken::parse(SecureTokenKind::Api, &alert.token)
+        .or_else(|| SecureToken::parse(SecureTokenKind::TrustedPublishing, &alert.token));
+    let hashed_token = match hashed_token {
+        Some(hashed_token) => hashed_token,
+        None => return Ok(false),
+    };
+
+    let tokens = api_tokens::table
+        .filter(api_tokens::token.eq(&hashed_token))
+        .filter(api_tokens::revoked.eq(false));
+
+    let token: ApiToken = match diesel::update(tokens)
+        .set(api_tokens::revoked.eq(true))
+        .get_result(conn)
+        .optional()?
+    {
+        Some(token) => token,
+        None => return Ok(false),
+    };
+
+    info!(
+        "Revoked API token {} of user {} reported by GitHub secret scanning",
+        token.id, token.user_id
+    );
+
+    let user = User::find(conn, token.user_id)?;
+    if let Some(email) = user.verified_email(conn)? {
+        // Swallow any error. The token is revoked either way and the report should not be
+        // retried because of an email delivery problem.
+        let _ = emails.send_token_exposed_notification(
+            conn,
+            &email,
+            &user.gh_login,
+            &token.name,
+            &alert.url,
+        );
+    }
+
+    Ok(true)
+}
@@ -1,6 +1,9 @@
+pub mod adoption;
+pub mod deprecation;
 pub mod downloads;
 pub mod follow;
 pub mod metadata;
 pub mod owners;
 pub mod publish;
 pub mod search;
+pub mod trusted_publishing;
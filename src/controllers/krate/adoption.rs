@@ -0,0 +1,175 @@
+//! Endpoints for asking to adopt a crate whose owners have become unreachable
+//!
+//! The owners are notified of a new request and can object to it. Requests that nobody objected
+//! to within the waiting period are reviewed by the crates.io team through the admin endpoints.
+
+use crate::controllers::frontend_prelude::*;
+use crate::models::{
+    AdoptionStatus, Crate, CrateAdoptionRequest, Owner, Rights, User, MAX_ADOPTION_REQUESTS_PER_DAY,
+};
+use crate::schema::{crate_adoption_requests, users};
+use crate::util::errors::{forbidden, not_found, too_many_adoption_requests};
+use crate::views::EncodableCrateAdoptionRequest;
+
+/// Handles the `PUT /crates/:crate_id/adoption_requests` route.
+///
+/// The format of the request body is:
+///
+/// ```json
+/// {"reason": "the owners have not replied to issues in two years"}
+/// ```
+pub fn request(req: &mut dyn RequestExt) -> EndpointResult {
+    #[derive(Deserialize)]
+    struct Request {
+        reason: String,
+    }
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let request: Request =
+        serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))?;
+    let reason = request.reason.trim();
+    if reason.is_empty() {
+        return Err(bad_request("a reason for adopting the crate is required"));
+    }
+
+    let user = req.authenticate()?.forbid_api_token_auth()?.user();
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let config = &req.app().config;
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    let owners = krate.owners(&conn)?;
+    let is_owner = owners
+        .iter()
+        .any(|owner| matches!(owner, Owner::User(owner) if owner.id == user.id));
+    if is_owner {
+        return Err(bad_request("you are already an owner of this crate"));
+    }
+    // Only users can be notified of the request and object to it
+    if !owners.iter().any(|owner| matches!(owner, Owner::User(_))) {
+        return Err(bad_request(
+            "this crate is only owned by teams, please contact the members of the teams instead",
+        ));
+    }
+    if CrateAdoptionRequest::find_pending(&conn, krate.id, user.id)?.is_some() {
+        return Err(bad_request(
+            "you already asked to adopt this crate, please wait for the review",
+        ));
+    }
+    if CrateAdoptionRequest::count_recent(&conn, user.id)? >= MAX_ADOPTION_REQUESTS_PER_DAY {
+        return Err(too_many_adoption_requests(MAX_ADOPTION_REQUESTS_PER_DAY));
+    }
+
+    let adoption_request = CrateAdoptionRequest::create(&conn, krate.id, user.id, reason)?;
+    let review_after = adoption_request.review_after(config);
+
+    for owner in &owners {
+        if let Owner::User(owner) = owner {
+            if let Ok(Some(email)) = owner.verified_email(&conn) {
+                // Swallow any error. The owners can still find the request through the
+                // adoption requests endpoint of the crate.
+                let _ = req.app().emails.send_adoption_request(
+                    &conn,
+                    &email,
+                    &owner.gh_login,
+                    &krate.name,
+                    &user.gh_login,
+                    reason,
+                    review_after,
+                );
+            }
+        }
+    }
+
+    let adoption_request = EncodableCrateAdoptionRequest::from(
+        adoption_request,
+        krate.name,
+        user.gh_login,
+        review_after,
+    );
+    Ok(req.json(&json!({ "adoption_request": adoption_request })))
+}
+
+/// Handles the `GET /crates/:crate_id/adoption_requests` route.
+///
+/// Lists the pending requests to adopt the crate. Only owners of the crate can see them.
+pub fn list(req: &mut dyn RequestExt) -> EndpointResult {
+    let user = req.authenticate()?.user();
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_read_only()?;
+    let config = &req.app().config;
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    let owners = krate.owners_with_roles(&conn)?;
+    if user.rights(req.app(), &conn, &owners)? < Rights::Publish {
+        return Err(forbidden());
+    }
+
+    let requests: Vec<(CrateAdoptionRequest, String)> = CrateAdoptionRequest::belonging_to(&krate)
+        .inner_join(users::table)
+        .filter(crate_adoption_requests::status.eq(AdoptionStatus::Pending))
+        .select((crate_adoption_requests::all_columns, users::gh_login))
+        .order(crate_adoption_requests::id)
+        .load(&*conn)?;
+    let requests = requests
+        .into_iter()
+        .map(|(request, requester)| {
+            let review_after = request.review_after(config);
+            EncodableCrateAdoptionRequest::from(
+                request,
+                krate.name.clone(),
+                requester,
+                review_after,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Ok(req.json(&json!({ "adoption_requests": requests })))
+}
+
+/// Handles the `DELETE /crates/:crate_id/adoption_requests/:id` route.
+///
+/// Owners of the crate use this to object to a request, and the requester to withdraw it.
+pub fn resolve(req: &mut dyn RequestExt) -> EndpointResult {
+    let user = req.authenticate()?.forbid_api_token_auth()?.user();
+    let crate_name = &req.params()["crate_id"];
+    let id = req.params()["id"].parse::<i32>().map_err(|_| not_found())?;
+    let conn = req.db_conn()?;
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    let adoption_request: CrateAdoptionRequest = CrateAdoptionRequest::belonging_to(&krate)
+        .filter(crate_adoption_requests::id.eq(id))
+        .first(&*conn)?;
+
+    let status = if adoption_request.requested_by_user_id == user.id {
+        AdoptionStatus::Withdrawn
+    } else {
+        let owners = krate.owners_with_roles(&conn)?;
+        if user.rights(req.app(), &conn, &owners)? < Rights::Publish {
+            return Err(forbidden());
+        }
+        AdoptionStatus::Objected
+    };
+
+    if !adoption_request.resolve(&conn, status, user.id)? {
+        return Err(bad_request("the adoption request is not pending anymore"));
+    }
+
+    if status == AdoptionStatus::Objected {
+        let requester: User = users::table
+            .find(adoption_request.requested_by_user_id)
+            .first(&*conn)?;
+        if let Ok(Some(email)) = requester.verified_email(&conn) {
+            let _ = req.app().emails.send_adoption_request_resolved(
+                &conn,
+                &email,
+                &requester.gh_login,
+                &krate.name,
+                "objected to by an owner of the crate",
+            );
+        }
+    }
+
+    ok_true()
+}
@@ -0,0 +1,109 @@
+//! Endpoints for deprecating a crate and pointing users to its successor
+
+use crate::controllers::cargo_prelude::*;
+use crate::controllers::util::record_token_activity;
+use crate::models::{Crate, CrateAction, NewCrateOwnerAction, Rights};
+use crate::views::EncodableCrateDeprecation;
+
+/// Handles the `PUT /crates/:crate_id/deprecation` route.
+///
+/// The format of the request body is:
+///
+/// ```json
+/// {"deprecation": {"message": "use bar instead", "successor": "bar"}}
+/// ```
+pub fn deprecate(req: &mut dyn RequestExt) -> EndpointResult {
+    #[derive(Deserialize)]
+    struct NewDeprecation {
+        message: String,
+        successor: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct Request {
+        deprecation: NewDeprecation,
+    }
+
+    // Publish-only tokens, like those of trusted publishers, cannot deprecate crates
+    let authenticated_user = req.authenticate()?.forbid_scoped_api_token_auth()?;
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let request: Request =
+        serde_json::from_str(&body).map_err(|_| cargo_err("invalid json request"))?;
+
+    let message = request.deprecation.message.trim();
+    if message.is_empty() {
+        return Err(cargo_err("a deprecation message is required"));
+    }
+
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let api_token_id = authenticated_user.api_token_id();
+    let user = authenticated_user.user();
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    let owners = krate.owners_with_roles(&conn)?;
+    if user.rights(req.app(), &conn, &owners)? < Rights::Full {
+        return Err(cargo_err(
+            "only owners have permission to deprecate a crate",
+        ));
+    }
+
+    let successor = request.deprecation.successor.as_deref();
+    let krate = conn.transaction::<_, Box<dyn AppError>, _>(|| {
+        let krate = krate.deprecate(&conn, message, successor)?;
+        // The successor is logged under its canonical name
+        NewCrateOwnerAction {
+            crate_id: krate.id,
+            user_id: user.id,
+            api_token_id,
+            action: CrateAction::Deprecate,
+            subject: krate.deprecation_successor.as_deref(),
+            details: Some(message),
+        }
+        .insert(&conn)?;
+        Ok(krate)
+    })?;
+    record_token_activity(&conn, req, api_token_id, Some(&krate.name), None)?;
+
+    Ok(req.json(&json!({
+        "ok": true,
+        "deprecation": EncodableCrateDeprecation::from_crate(&krate),
+    })))
+}
+
+/// Handles the `DELETE /crates/:crate_id/deprecation` route.
+pub fn undeprecate(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req.authenticate()?.forbid_scoped_api_token_auth()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let api_token_id = authenticated_user.api_token_id();
+    let user = authenticated_user.user();
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    let owners = krate.owners_with_roles(&conn)?;
+    if user.rights(req.app(), &conn, &owners)? < Rights::Full {
+        return Err(cargo_err(
+            "only owners have permission to undeprecate a crate",
+        ));
+    }
+
+    if krate.is_deprecated() {
+        conn.transaction(|| {
+            krate.undeprecate(&conn)?;
+            NewCrateOwnerAction {
+                crate_id: krate.id,
+                user_id: user.id,
+                api_token_id,
+                action: CrateAction::Undeprecate,
+                subject: None,
+                details: None,
+            }
+            .insert(&conn)
+        })?;
+        record_token_activity(&conn, req, api_token_id, Some(&krate.name), None)?;
+    }
+
+    ok_true()
+}
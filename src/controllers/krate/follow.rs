@@ -3,6 +3,7 @@
 use diesel::associations::Identifiable;
 
 use crate::controllers::frontend_prelude::*;
+use crate::controllers::util::record_token_activity;
 use crate::db::DieselPooledConn;
 use crate::models::{Crate, Follow};
 use crate::schema::*;
@@ -21,24 +22,42 @@ fn follow_target(
 
 /// Handles the `PUT /crates/:crate_id/follow` route.
 pub fn follow(req: &mut dyn RequestExt) -> EndpointResult {
-    let user_id = req.authenticate()?.user_id();
+    let authenticated_user = req.authenticate()?.forbid_scoped_api_token_auth()?;
     let conn = req.db_conn()?;
-    let follow = follow_target(req, &conn, user_id)?;
+    let follow = follow_target(req, &conn, authenticated_user.user_id())?;
     diesel::insert_into(follows::table)
         .values(&follow)
         .on_conflict_do_nothing()
         .execute(&*conn)?;
 
+    let crate_name = &req.params()["crate_id"];
+    record_token_activity(
+        &conn,
+        req,
+        authenticated_user.api_token_id(),
+        Some(crate_name),
+        None,
+    )?;
+
     ok_true()
 }
 
 /// Handles the `DELETE /crates/:crate_id/follow` route.
 pub fn unfollow(req: &mut dyn RequestExt) -> EndpointResult {
-    let user_id = req.authenticate()?.user_id();
+    let authenticated_user = req.authenticate()?.forbid_scoped_api_token_auth()?;
     let conn = req.db_conn()?;
-    let follow = follow_target(req, &conn, user_id)?;
+    let follow = follow_target(req, &conn, authenticated_user.user_id())?;
     diesel::delete(&follow).execute(&*conn)?;
 
+    let crate_name = &req.params()["crate_id"];
+    record_token_activity(
+        &conn,
+        req,
+        authenticated_user.api_token_id(),
+        Some(crate_name),
+        None,
+    )?;
+
     ok_true()
 }
 
@@ -15,7 +15,8 @@ use crate::models::{
 };
 use crate::schema::*;
 use crate::views::{
-    EncodableCategory, EncodableCrate, EncodableDependency, EncodableKeyword, EncodableVersion,
+    EncodableCategory, EncodableCrate, EncodableCrateDeprecation, EncodableDependency,
+    EncodableKeyword, EncodableVersion,
 };
 
 use crate::models::krate::ALL_COLUMNS;
@@ -173,6 +174,8 @@ pub fn show(req: &mut dyn RequestExt) -> EndpointResult {
             .collect::<Vec<_>>(),
         "keywords": kws.into_iter().map(Keyword::into).collect::<Vec<EncodableKeyword>>(),
         "categories": cats.into_iter().map(Category::into).collect::<Vec<EncodableCategory>>(),
+        // Hints clients that still use a previous name of the crate to switch to the new one
+        "renamed_from": (!krate.is_canonical_name(name)).then(|| name),
     })))
 }
 
@@ -263,9 +266,12 @@ pub fn reverse_dependencies(req: &mut dyn RequestExt) -> EndpointResult {
         })
         .collect::<Vec<_>>();
 
+    // Dependents are listed together with the deprecation state of the crate they depend on, so
+    // they can be pointed to its successor.
     Ok(req.json(&json!({
         "dependencies": rev_deps,
         "versions": versions,
+        "deprecation": EncodableCrateDeprecation::from_crate(&krate),
         "meta": { "total": total },
     })))
 }
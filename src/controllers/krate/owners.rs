@@ -1,8 +1,21 @@
 //! All routes related to managing owners of a crate
 
+use std::collections::HashMap;
+
+use crate::controllers::helpers::pagination::{Paginated, PaginationOptions};
 use crate::controllers::prelude::*;
-use crate::models::{Crate, Owner, Rights, Team, User};
-use crate::views::EncodableOwner;
+use crate::controllers::util::record_token_activity;
+use crate::models::{
+    Crate, CrateOwnerAction, CrateOwnerInvitation, CrateOwnerSignupInvitation,
+    CrateOwnerTeamInvitation, EndpointScope, Owner, OwnerRole, Rights, Team, User,
+};
+use crate::schema::{
+    crate_owner_actions, crate_owner_invitations, crate_owner_signup_invitations,
+    crate_owner_team_invitations, teams, users,
+};
+use crate::sql::lower;
+use crate::util::errors::{forbidden, not_found};
+use crate::views::{EncodableCrateOwnerAction, EncodableOwner, EncodablePendingOwnerInvitation};
 
 /// Handles the `GET /crates/:crate_id/owners` route.
 pub fn owners(req: &mut dyn RequestExt) -> EndpointResult {
@@ -10,10 +23,10 @@ pub fn owners(req: &mut dyn RequestExt) -> EndpointResult {
     let conn = req.db_read_only()?;
     let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
     let owners = krate
-        .owners(&conn)?
+        .owners_with_roles(&conn)?
         .into_iter()
-        .map(Owner::into)
-        .collect::<Vec<EncodableOwner>>();
+        .map(EncodableOwner::from)
+        .collect::<Vec<_>>();
 
     Ok(req.json(&json!({ "users": owners })))
 }
@@ -25,8 +38,8 @@ pub fn owner_team(req: &mut dyn RequestExt) -> EndpointResult {
     let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
     let owners = Team::owning(&krate, &conn)?
         .into_iter()
-        .map(Owner::into)
-        .collect::<Vec<EncodableOwner>>();
+        .map(EncodableOwner::from)
+        .collect::<Vec<_>>();
 
     Ok(req.json(&json!({ "teams": owners })))
 }
@@ -38,8 +51,8 @@ pub fn owner_user(req: &mut dyn RequestExt) -> EndpointResult {
     let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
     let owners = User::owning(&krate, &conn)?
         .into_iter()
-        .map(Owner::into)
-        .collect::<Vec<EncodableOwner>>();
+        .map(EncodableOwner::from)
+        .collect::<Vec<_>>();
 
     Ok(req.json(&json!({ "users": owners })))
 }
@@ -59,9 +72,11 @@ pub fn remove_owners(req: &mut dyn RequestExt) -> EndpointResult {
 /// The format is:
 ///
 /// ```json
-/// {"owners": ["username", "github:org:team", ...]}
+/// {"owners": ["username", "github:org:team", ...], "role": "full"}
 /// ```
-fn parse_owners_request(req: &mut dyn RequestExt) -> AppResult<Vec<String>> {
+///
+/// The optional `role` is either `full` or `publish` and only applies when adding owners.
+fn parse_owners_request(req: &mut dyn RequestExt) -> AppResult<(Vec<String>, Option<OwnerRole>)> {
     let mut body = String::new();
     req.body().read_to_string(&mut body)?;
     #[derive(Deserialize)]
@@ -69,32 +84,55 @@ fn parse_owners_request(req: &mut dyn RequestExt) -> AppResult<Vec<String>> {
         // identical, for back-compat (owners preferred)
         users: Option<Vec<String>>,
         owners: Option<Vec<String>>,
+        role: Option<String>,
     }
     let request: Request =
         serde_json::from_str(&body).map_err(|_| cargo_err("invalid json request"))?;
-    request
+    let role = match request.role {
+        Some(role) => Some(OwnerRole::from_name(&role).ok_or_else(|| {
+            cargo_err(&format_args!(
+                "invalid role `{}`, expected `full` or `publish`",
+                role
+            ))
+        })?),
+        None => None,
+    };
+    let logins = request
         .owners
         .or(request.users)
-        .ok_or_else(|| cargo_err("invalid json request"))
+        .ok_or_else(|| cargo_err("invalid json request"))?;
+    Ok((logins, role))
 }
 
 fn modify_owners(req: &mut dyn RequestExt, add: bool) -> EndpointResult {
-    let authenticated_user = req.authenticate()?;
-    let logins = parse_owners_request(req)?;
+    let authenticated_user = req
+        .authenticate()?
+        .require_scope(EndpointScope::ChangeOwners)?;
+    let (logins, role) = parse_owners_request(req)?;
     let app = req.app();
     let crate_name = &req.params()["crate_id"];
-
     let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+    let api_token_id = authenticated_user.api_token_id();
     let user = authenticated_user.user();
 
     conn.transaction(|| {
         let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
-        let owners = krate.owners(&conn)?;
+        authenticated_user.require_crate_scope(&krate.name)?;
+        let owners = krate.owners_with_roles(&conn)?;
 
-        match user.rights(app, &owners)? {
+        match user.rights(app, &conn, &owners)? {
             Rights::Full => {}
             // Yes!
             Rights::Publish => {
+                let is_user_owner = owners
+                    .iter()
+                    .any(|(owner, _)| matches!(owner, Owner::User(other) if other.id == user.id));
+                if is_user_owner {
+                    return Err(cargo_err(
+                        "publish-only owners don't have permission to modify owners",
+                    ));
+                }
                 return Err(cargo_err(
                     "team members don't have permission to modify owners",
                 ));
@@ -107,18 +145,33 @@ fn modify_owners(req: &mut dyn RequestExt, add: bool) -> EndpointResult {
         let comma_sep_msg = if add {
             let mut msgs = Vec::with_capacity(logins.len());
             for login in &logins {
-                let login_test =
-                    |owner: &Owner| owner.login().to_lowercase() == *login.to_lowercase();
-                if owners.iter().any(login_test) {
-                    return Err(cargo_err(&format_args!("`{}` is already an owner", login)));
+                let login_test = |(owner, _): &&(Owner, OwnerRole)| {
+                    owner.login().to_lowercase() == *login.to_lowercase()
+                };
+                if let Some((owner, current_role)) = owners.iter().find(login_test) {
+                    // Adding an existing owner with another role changes their role
+                    match role {
+                        Some(role) if role != *current_role => {
+                            krate.owner_set_role(&conn, &user, api_token_id, owner, role)?;
+                            msgs.push(format!(
+                                "role of {} has been changed to {}",
+                                owner.login(),
+                                <&'static str>::from(role)
+                            ));
+                            continue;
+                        }
+                        _ => {
+                            return Err(cargo_err(&format_args!("`{}` is already an owner", login)))
+                        }
+                    }
                 }
-                let msg = krate.owner_add(app, &conn, &user, login)?;
+                let msg = krate.owner_add(app, &conn, &user, api_token_id, login, role)?;
                 msgs.push(msg);
             }
             msgs.join(",")
         } else {
             for login in &logins {
-                krate.owner_remove(app, &conn, &user, login)?;
+                krate.owner_remove(app, &conn, &user, api_token_id, login)?;
             }
             if User::owning(&krate, &conn)?.is_empty() {
                 return Err(cargo_err(
@@ -130,6 +183,245 @@ fn modify_owners(req: &mut dyn RequestExt, add: bool) -> EndpointResult {
             "owners successfully removed".to_owned()
         };
 
+        let has_full_owner = krate
+            .owners_with_roles(&conn)?
+            .iter()
+            .any(|(_, role)| *role == OwnerRole::Full);
+        if !has_full_owner {
+            return Err(cargo_err(
+                "at least one owner of a crate must have full rights to manage its owners",
+            ));
+        }
+
+        record_token_activity(&conn, req, api_token_id, Some(&krate.name), None)?;
+
         Ok(req.json(&json!({ "ok": true, "msg": comma_sep_msg })))
     })
 }
+
+/// Handles the `GET /crates/:crate_id/owner_invitations` route.
+///
+/// Lists the users and teams that were invited to become owners of the crate and did not respond
+/// yet. Only owners with full rights can see them.
+pub fn invitations(req: &mut dyn RequestExt) -> EndpointResult {
+    let user = req.authenticate()?.user();
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_read_only()?;
+    let config = &req.app().config;
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    let owners = krate.owners_with_roles(&conn)?;
+    if user.rights(req.app(), &conn, &owners)? != Rights::Full {
+        return Err(forbidden());
+    }
+
+    let user_invitations: Vec<CrateOwnerInvitation> = crate_owner_invitations::table
+        .filter(crate_owner_invitations::crate_id.eq(krate.id))
+        .order(crate_owner_invitations::created_at)
+        .load(&*conn)?;
+    let team_invitations: Vec<(CrateOwnerTeamInvitation, String)> =
+        crate_owner_team_invitations::table
+            .inner_join(teams::table)
+            .filter(crate_owner_team_invitations::crate_id.eq(krate.id))
+            .select((crate_owner_team_invitations::all_columns, teams::login))
+            .order(crate_owner_team_invitations::created_at)
+            .load(&*conn)?;
+    let signup_invitations: Vec<CrateOwnerSignupInvitation> = crate_owner_signup_invitations::table
+        .filter(crate_owner_signup_invitations::crate_id.eq(krate.id))
+        .order(crate_owner_signup_invitations::created_at)
+        .load(&*conn)?;
+
+    let user_ids = user_invitations
+        .iter()
+        .flat_map(|i| vec![i.invited_user_id, i.invited_by_user_id])
+        .chain(team_invitations.iter().map(|(i, _)| i.invited_by_user_id))
+        .chain(signup_invitations.iter().map(|i| i.invited_by_user_id))
+        .collect::<Vec<_>>();
+    let logins: HashMap<i32, String> = users::table
+        .filter(users::id.eq_any(user_ids))
+        .select((users::id, users::gh_login))
+        .load(&*conn)?
+        .into_iter()
+        .collect();
+
+    let user_invitations = user_invitations
+        .into_iter()
+        .filter(|invitation| !invitation.is_expired(config))
+        .map(|invitation| EncodablePendingOwnerInvitation {
+            login: logins
+                .get(&invitation.invited_user_id)
+                .cloned()
+                .unwrap_or_default(),
+            kind: "user".into(),
+            role: <&'static str>::from(invitation.role).into(),
+            invited_by: logins.get(&invitation.invited_by_user_id).cloned(),
+            created_at: invitation.created_at,
+            expires_at: invitation.expires_at(config),
+        });
+    let team_invitations = team_invitations
+        .into_iter()
+        .filter(|(invitation, _)| !invitation.is_expired(config))
+        .map(|(invitation, login)| EncodablePendingOwnerInvitation {
+            login,
+            kind: "team".into(),
+            role: <&'static str>::from(invitation.role).into(),
+            invited_by: logins.get(&invitation.invited_by_user_id).cloned(),
+            created_at: invitation.created_at,
+            expires_at: invitation.expires_at(config),
+        });
+    // Users who have not signed up yet are listed like any other invited user
+    let signup_invitations = signup_invitations
+        .into_iter()
+        .filter(|invitation| !invitation.is_expired(config))
+        .map(|invitation| EncodablePendingOwnerInvitation {
+            login: invitation.gh_login.clone(),
+            kind: "user".into(),
+            role: <&'static str>::from(invitation.role).into(),
+            invited_by: logins.get(&invitation.invited_by_user_id).cloned(),
+            created_at: invitation.created_at,
+            expires_at: invitation.expires_at(config),
+        });
+    let invitations = user_invitations
+        .chain(signup_invitations)
+        .chain(team_invitations)
+        .collect::<Vec<_>>();
+
+    Ok(req.json(&json!({ "invitations": invitations })))
+}
+
+/// Handles the `PUT /crates/:crate_id/owner_invitations/:user` route.
+///
+/// Accepts the invitation of a team, given as `github:org:team`. Only maintainers of the team
+/// can accept it.
+pub fn accept_team_invitation(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req
+        .authenticate()?
+        .require_scope(EndpointScope::ChangeOwners)?;
+    let crate_name = &req.params()["crate_id"];
+    let login = &req.params()["user"];
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+    let api_token_id = authenticated_user.api_token_id();
+    let user = authenticated_user.user();
+
+    conn.transaction(|| {
+        let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+        authenticated_user.require_crate_scope(&krate.name)?;
+        let team = find_team(&conn, login)?;
+        let invitation =
+            CrateOwnerTeamInvitation::find(team.id, krate.id, &conn)?.ok_or_else(not_found)?;
+
+        if !team.is_maintained_by(req.app(), &user)? {
+            return Err(cargo_err(
+                "only maintainers of a team can accept invitations for it",
+            ));
+        }
+        invitation.accept(&conn, &req.app().config, user.id, api_token_id)?;
+
+        record_token_activity(&conn, req, api_token_id, Some(&krate.name), None)?;
+
+        Ok(req.json(&json!({
+            "ok": true,
+            "msg": format!("team {} has been added as an owner of crate {}", team.login, krate.name),
+        })))
+    })
+}
+
+/// Handles the `DELETE /crates/:crate_id/owner_invitations/:user` route.
+///
+/// Owners with full rights can cancel the invitation of a user or team. The invitation of a team
+/// can also be declined by a maintainer of the team.
+pub fn cancel_invitation(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req
+        .authenticate()?
+        .require_scope(EndpointScope::ChangeOwners)?;
+    let crate_name = &req.params()["crate_id"];
+    let login = &req.params()["user"];
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+    let api_token_id = authenticated_user.api_token_id();
+    let user = authenticated_user.user();
+    let app = req.app();
+
+    conn.transaction(|| {
+        let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+        authenticated_user.require_crate_scope(&krate.name)?;
+        let owners = krate.owners_with_roles(&conn)?;
+        let is_full_owner = user.rights(app, &conn, &owners)? == Rights::Full;
+
+        if Owner::is_team_login(login) {
+            let team = find_team(&conn, login)?;
+            let invitation =
+                CrateOwnerTeamInvitation::find(team.id, krate.id, &conn)?.ok_or_else(not_found)?;
+            if !is_full_owner && !team.is_maintained_by(app, &user)? {
+                return Err(cargo_err(
+                    "only owners with full rights or maintainers of the team can remove this invitation",
+                ));
+            }
+            if is_full_owner {
+                invitation.cancel(&conn, user.id, api_token_id)?;
+            } else {
+                invitation.decline(&conn, user.id, api_token_id)?;
+            }
+        } else {
+            if !is_full_owner {
+                return Err(cargo_err(
+                    "only owners with full rights have permission to remove invitations",
+                ));
+            }
+            match Owner::find_user_by_login(&conn, login) {
+                Ok(invitee) => CrateOwnerInvitation::find_by_id(invitee.id, krate.id, &conn)?
+                    .cancel(&conn, user.id, api_token_id)?,
+                Err(_) => CrateOwnerSignupInvitation::find_by_login(login, krate.id, &conn)?
+                    .ok_or_else(not_found)?
+                    .cancel(&conn, user.id, api_token_id)?,
+            }
+        }
+
+        record_token_activity(&conn, req, api_token_id, Some(&krate.name), None)?;
+
+        ok_true()
+    })
+}
+
+fn find_team(conn: &PgConnection, login: &str) -> AppResult<Team> {
+    teams::table
+        .filter(lower(teams::login).eq(login.to_lowercase()))
+        .first(conn)
+        .optional()?
+        .ok_or_else(not_found)
+}
+
+/// Handles the `GET /crates/:crate_id/audit_log` route.
+///
+/// Lists changes to the owners and the deprecation state of the crate, newest first. Only
+/// owners of the crate can see it.
+pub fn audit_log(req: &mut dyn RequestExt) -> EndpointResult {
+    let user = req.authenticate()?.user();
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_read_only()?;
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    let owners = krate.owners_with_roles(&conn)?;
+    if user.rights(req.app(), &conn, &owners)? < Rights::Publish {
+        return Err(forbidden());
+    }
+
+    let data: Paginated<(CrateOwnerAction, User)> = crate_owner_actions::table
+        .inner_join(users::table)
+        .filter(crate_owner_actions::crate_id.eq(krate.id))
+        .order(crate_owner_actions::id.desc())
+        .pages_pagination(PaginationOptions::builder().gather(req)?)
+        .load(&*conn)?;
+    let total = data.total();
+    let next_page = data.next_page_params().map(|p| req.query_with_params(p));
+    let actions = data
+        .into_iter()
+        .map(EncodableCrateOwnerAction::from)
+        .collect::<Vec<_>>();
+
+    Ok(req.json(&json!({
+        "actions": actions,
+        "meta": { "total": total, "next_page": next_page },
+    })))
+}
@@ -10,10 +10,11 @@ use std::sync::Arc;
 use swirl::Job;
 
 use crate::controllers::cargo_prelude::*;
+use crate::controllers::util::record_token_activity;
 use crate::git;
 use crate::models::{
-    insert_version_owner_action, Badge, Category, Crate, DependencyKind, Keyword, NewCrate,
-    NewVersion, Rights, VersionAction,
+    insert_version_owner_action, Badge, Category, Crate, DependencyKind, EndpointScope, Keyword,
+    NewCrate, NewVersion, Rights, VersionAction,
 };
 use crate::worker;
 
@@ -64,11 +65,34 @@ pub fn publish(req: &mut dyn RequestExt) -> EndpointResult {
     req.log_metadata("crate_version", new_crate.vers.to_string());
 
     let conn = app.primary_database.get()?;
-    let ids = req.authenticate()?;
+    // Aliases resolve to the renamed crate everywhere else, but new versions must be
+    // published under the current name
+    if let Some(renamed_to) = Crate::renamed_to(&conn, &new_crate.name)? {
+        return Err(cargo_err(&format_args!(
+            "crate `{}` has been renamed to `{}`, please publish under the new name",
+            new_crate.name, renamed_to
+        )));
+    }
+
+    // Publishing a new crate and publishing a new version of an existing crate are
+    // separate token scopes
+    let crate_exists = Crate::by_name(&new_crate.name)
+        .select(crates::id)
+        .first::<i32>(&*conn)
+        .optional()?
+        .is_some();
+    let endpoint_scope = if crate_exists {
+        EndpointScope::PublishUpdate
+    } else {
+        EndpointScope::PublishNew
+    };
+
+    let ids = req.authenticate()?.require_scope(endpoint_scope)?;
+    ids.require_crate_scope(&new_crate.name)?;
     let api_token_id = ids.api_token_id();
     let user = ids.user();
 
-    let verified_email_address = user.verified_email(&conn)?;
+    let verified_email_address = user.publish_email(&conn)?;
     let verified_email_address = verified_email_address.ok_or_else(|| {
         cargo_err(&format!(
             "A verified email address is required to publish crates to crates.io. \
@@ -116,8 +140,8 @@ pub fn publish(req: &mut dyn RequestExt) -> EndpointResult {
         let krate =
             persist.create_or_update(&conn, user.id, Some(&app.config.publish_rate_limit))?;
 
-        let owners = krate.owners(&conn)?;
-        if user.rights(req.app(), &owners)? < Rights::Publish {
+        let owners = krate.owners_with_roles(&conn)?;
+        if user.rights(req.app(), &conn, &owners)? < Rights::Publish {
             return Err(cargo_err(MISSING_RIGHTS_ERROR_MESSAGE));
         }
 
@@ -168,13 +192,21 @@ pub fn publish(req: &mut dyn RequestExt) -> EndpointResult {
         )?
         .save(&conn, &verified_email_address)?;
 
-        insert_version_owner_action(
+        let action = insert_version_owner_action(
             &conn,
             version.id,
             user.id,
             api_token_id,
             VersionAction::Publish,
         )?;
+        worker::notify_owners_of_version_action(action.id).enqueue(&conn)?;
+        record_token_activity(
+            &conn,
+            req,
+            api_token_id,
+            Some(&krate.name),
+            Some(&version.num),
+        )?;
 
         // Link this new version to all dependencies
         let git_deps = add_dependencies(&conn, &new_crate.deps, version.id)?;
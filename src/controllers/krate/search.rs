@@ -8,7 +8,7 @@ use indexmap::IndexMap;
 use crate::controllers::cargo_prelude::*;
 use crate::controllers::helpers::Paginate;
 use crate::models::{
-    Crate, CrateBadge, CrateOwner, CrateVersions, OwnerKind, TopVersions, Version,
+    Crate, CrateBadge, CrateOwner, CrateVersions, EndpointScope, OwnerKind, TopVersions, Version,
 };
 use crate::schema::*;
 use crate::util::errors::bad_request;
@@ -48,6 +48,10 @@ pub fn search(req: &mut dyn RequestExt) -> EndpointResult {
         .get("include_yanked")
         .map(|s| s == "yes")
         .unwrap_or(true);
+    let include_deprecated = params
+        .get("include_deprecated")
+        .map(|s| s == "yes")
+        .unwrap_or(true);
 
     let selection = (
         ALL_COLUMNS,
@@ -183,7 +187,10 @@ pub fn search(req: &mut dyn RequestExt) -> EndpointResult {
         // Calculating the total number of results with filters is not supported yet.
         supports_seek = false;
 
-        let user_id = req.authenticate()?.user_id();
+        let user_id = req
+            .authenticate()?
+            .require_scope(EndpointScope::ReadOnly)?
+            .user_id();
         query = query.filter(
             crates::id.eq_any(
                 follows::table
@@ -215,6 +222,13 @@ pub fn search(req: &mut dyn RequestExt) -> EndpointResult {
         ));
     }
 
+    if !include_deprecated {
+        // Calculating the total number of results with filters is not supported yet.
+        supports_seek = false;
+
+        query = query.filter(crates::deprecated_at.is_null());
+    }
+
     if sort == Some("downloads") {
         // Custom sorting is not supported yet with seek.
         supports_seek = false;
@@ -0,0 +1,247 @@
+//! Endpoints for publishing from CI without long-lived API tokens
+//!
+//! Owners register the repository and workflow that are allowed to publish a crate. That
+//! workflow can then exchange the OIDC token issued by its CI provider for a short-lived API
+//! token that is only able to publish new versions of the crate.
+
+use crate::controllers::frontend_prelude::*;
+use crate::models::{
+    ApiToken, Crate, CrateAction, NewCrateOwnerAction, NewTrustedPublisher, Rights,
+    TrustedPublisher, User,
+};
+use crate::oidc::GITHUB_ACTIONS_ISSUER;
+use crate::schema::trusted_publishers;
+use crate::sql::lower;
+use crate::views::EncodableApiTokenWithToken;
+
+use chrono::{Duration, Utc};
+
+/// How long the API tokens obtained through trusted publishing can be used for.
+const TOKEN_LIFETIME_MINUTES: i64 = 30;
+
+/// Handles the `GET /crates/:crate_id/trusted_publishers` route.
+pub fn list(req: &mut dyn RequestExt) -> EndpointResult {
+    let user = req.authenticate()?.forbid_api_token_auth()?.user();
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    require_full_rights(req, &conn, &krate, &user)?;
+
+    let publishers: Vec<TrustedPublisher> = TrustedPublisher::belonging_to(&krate)
+        .order(trusted_publishers::id)
+        .load(&*conn)?;
+
+    Ok(req.json(&json!({ "trusted_publishers": publishers })))
+}
+
+/// Handles the `PUT /crates/:crate_id/trusted_publishers` route.
+///
+/// The format of the request body is:
+///
+/// ```json
+/// {"trusted_publisher": {"repository": "rust-lang/foo", "workflow": "release.yml"}}
+/// ```
+///
+/// The `issuer` defaults to GitHub Actions.
+pub fn create(req: &mut dyn RequestExt) -> EndpointResult {
+    #[derive(Deserialize)]
+    struct NewPublisher {
+        #[serde(default = "default_issuer")]
+        issuer: String,
+        repository: String,
+        workflow: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Request {
+        trusted_publisher: NewPublisher,
+    }
+
+    fn default_issuer() -> String {
+        GITHUB_ACTIONS_ISSUER.into()
+    }
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let request: Request =
+        serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))?;
+    let new = request.trusted_publisher;
+
+    if !req
+        .app()
+        .config
+        .trusted_publishing_issuers
+        .contains(&new.issuer)
+    {
+        return Err(bad_request(&format_args!(
+            "tokens issued by `{}` are not accepted",
+            new.issuer
+        )));
+    }
+
+    let is_valid_repository = matches!(
+        new.repository.split_once('/'),
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/')
+    );
+    if !is_valid_repository {
+        return Err(bad_request("repository must be given as `owner/name`"));
+    }
+
+    let is_valid_workflow = (new.workflow.ends_with(".yml") || new.workflow.ends_with(".yaml"))
+        && !new.workflow.contains('/');
+    if !is_valid_workflow {
+        return Err(bad_request(
+            "workflow must be the file name of a workflow in `.github/workflows`",
+        ));
+    }
+
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    // The publisher can publish the crate, just like an API token
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+    let user = authenticated_user.user();
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    require_full_rights(req, &conn, &krate, &user)?;
+
+    conn.transaction(|| {
+        let existing = TrustedPublisher::belonging_to(&krate)
+            .filter(trusted_publishers::issuer.eq(&new.issuer))
+            .filter(lower(trusted_publishers::repository).eq(new.repository.to_lowercase()))
+            .filter(trusted_publishers::workflow.eq(&new.workflow))
+            .count()
+            .get_result::<i64>(&*conn)?;
+        if existing > 0 {
+            return Err(bad_request("this trusted publisher is already registered"));
+        }
+
+        let publisher = NewTrustedPublisher {
+            crate_id: krate.id,
+            created_by: user.id,
+            issuer: &new.issuer,
+            repository: &new.repository,
+            workflow: &new.workflow,
+        }
+        .insert(&conn)?;
+
+        NewCrateOwnerAction {
+            crate_id: krate.id,
+            user_id: user.id,
+            api_token_id: None,
+            action: CrateAction::AddTrustedPublisher,
+            subject: Some(&publisher.repository),
+            details: Some(&publisher.workflow),
+        }
+        .insert(&conn)?;
+
+        Ok(req.json(&json!({ "trusted_publisher": publisher })))
+    })
+}
+
+/// Handles the `DELETE /crates/:crate_id/trusted_publishers/:id` route.
+///
+/// This also revokes all tokens that were obtained through the publisher.
+pub fn delete(req: &mut dyn RequestExt) -> EndpointResult {
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid trusted publisher id: {e:?}")))?;
+
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+    let user = authenticated_user.user();
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    require_full_rights(req, &conn, &krate, &user)?;
+
+    let publisher: TrustedPublisher = TrustedPublisher::belonging_to(&krate)
+        .find(id)
+        .first(&*conn)?;
+
+    conn.transaction(|| {
+        // A database trigger revokes the tokens obtained through the publisher
+        diesel::delete(&publisher).execute(&*conn)?;
+
+        NewCrateOwnerAction {
+            crate_id: krate.id,
+            user_id: user.id,
+            api_token_id: None,
+            action: CrateAction::RemoveTrustedPublisher,
+            subject: Some(&publisher.repository),
+            details: Some(&publisher.workflow),
+        }
+        .insert(&conn)?;
+
+        Ok(req.json(&json!({ "ok": true })))
+    })
+}
+
+/// Handles the `PUT /crates/:crate_id/trusted_publishing_token` route.
+///
+/// The format of the request body is:
+///
+/// ```json
+/// {"jwt": "<OIDC token issued to the workflow>"}
+/// ```
+///
+/// The token is audience-restricted to the domain name of this crates.io instance.
+pub fn exchange_token(req: &mut dyn RequestExt) -> EndpointResult {
+    #[derive(Deserialize)]
+    struct Request {
+        jwt: String,
+    }
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let request: Request =
+        serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))?;
+
+    let app = req.app();
+    let claims = app.oidc_keys.verify(
+        &request.jwt,
+        &app.config.domain_name,
+        &app.config.trusted_publishing_issuers,
+    )?;
+
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+
+    let publishers: Vec<TrustedPublisher> = TrustedPublisher::belonging_to(&krate)
+        .filter(trusted_publishers::issuer.eq(&claims.iss))
+        .load(&*conn)?;
+    let publisher = publishers
+        .iter()
+        .find(|publisher| publisher.matches(&claims))
+        .ok_or_else(|| {
+            bad_request(&format_args!(
+                "no trusted publisher of `{}` matches the workflow `{}`",
+                krate.name, claims.workflow_ref
+            ))
+        })?;
+
+    let expires_at = Utc::now().naive_utc() + Duration::minutes(TOKEN_LIFETIME_MINUTES);
+    let api_token =
+        ApiToken::insert_for_trusted_publisher(&conn, publisher, &krate.name, expires_at)?;
+    let api_token = EncodableApiTokenWithToken::from(api_token);
+
+    Ok(req.json(&json!({ "api_token": api_token })))
+}
+
+fn require_full_rights(
+    req: &dyn RequestExt,
+    conn: &PgConnection,
+    krate: &Crate,
+    user: &User,
+) -> AppResult<()> {
+    let owners = krate.owners_with_roles(conn)?;
+    if user.rights(req.app(), conn, &owners)? < Rights::Full {
+        return Err(bad_request(
+            "only owners have permission to manage trusted publishers",
+        ));
+    }
+    Ok(())
+}
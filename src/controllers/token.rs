@@ -1,10 +1,13 @@
 use super::frontend_prelude::*;
 
-use crate::models::ApiToken;
-use crate::schema::api_tokens;
+use crate::controllers::helpers::pagination::{Paginate, Paginated, PaginationOptions};
+use crate::controllers::util::record_token_activity;
+use crate::models::{ApiToken, ApiTokenActivity, CrateScope, EndpointScope};
+use crate::schema::{api_token_activity, api_tokens};
 use crate::util::read_fill;
 use crate::views::EncodableApiTokenWithToken;
 
+use chrono::{DateTime, Duration, Utc};
 use conduit::{Body, Response};
 use serde_json as json;
 
@@ -16,6 +19,7 @@ pub fn list(req: &mut dyn RequestExt) -> EndpointResult {
 
     let tokens: Vec<ApiToken> = ApiToken::belonging_to(&user)
         .filter(api_tokens::revoked.eq(false))
+        .filter(api_tokens::trusted_publisher_id.is_null())
         .order(api_tokens::created_at.desc())
         .load(&*conn)?;
 
@@ -28,6 +32,15 @@ pub fn new(req: &mut dyn RequestExt) -> EndpointResult {
     #[derive(Deserialize, Serialize)]
     struct NewApiToken {
         name: String,
+        /// Restricts the token to these actions. Tokens without scopes have full access.
+        #[serde(default)]
+        endpoint_scopes: Option<Vec<EndpointScope>>,
+        /// Restricts the token to these crate names or `prefix-*` patterns.
+        #[serde(default)]
+        crate_scopes: Option<Vec<CrateScope>>,
+        /// The time after which the token can no longer be used.
+        #[serde(default)]
+        expires_at: Option<DateTime<Utc>>,
     }
 
     /// The incoming serialization format for the `ApiToken` model.
@@ -59,6 +72,35 @@ pub fn new(req: &mut dyn RequestExt) -> EndpointResult {
         return Err(bad_request("name must have a value"));
     }
 
+    let endpoint_scopes = new.api_token.endpoint_scopes;
+    if matches!(&endpoint_scopes, Some(scopes) if scopes.is_empty()) {
+        return Err(bad_request(
+            "endpoint_scopes must not be empty, omit it to create a token with full access",
+        ));
+    }
+
+    let crate_scopes = new.api_token.crate_scopes;
+    if matches!(&crate_scopes, Some(scopes) if scopes.is_empty()) {
+        return Err(bad_request(
+            "crate_scopes must not be empty, omit it to create a token for all crates",
+        ));
+    }
+
+    let now = Utc::now();
+    let mut expires_at = new.api_token.expires_at;
+    if matches!(expires_at, Some(expires_at) if expires_at <= now) {
+        return Err(bad_request("expires_at must be in the future"));
+    }
+
+    if let Some(max_days) = req.app().config.max_token_lifetime_days {
+        let max_expires_at = now + Duration::days(max_days as i64);
+        if *expires_at.get_or_insert(max_expires_at) > max_expires_at {
+            return Err(bad_request(&format!(
+                "API tokens cannot be valid for more than {max_days} days"
+            )));
+        }
+    }
+
     let authenticated_user = req.authenticate()?;
     if authenticated_user.api_token_id().is_some() {
         return Err(bad_request(
@@ -67,10 +109,14 @@ pub fn new(req: &mut dyn RequestExt) -> EndpointResult {
     }
 
     let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
     let user = authenticated_user.user();
 
     let max_token_per_user = 500;
-    let count: i64 = ApiToken::belonging_to(&user).count().get_result(&*conn)?;
+    let count: i64 = ApiToken::belonging_to(&user)
+        .filter(api_tokens::trusted_publisher_id.is_null())
+        .count()
+        .get_result(&*conn)?;
     if count >= max_token_per_user {
         return Err(bad_request(&format!(
             "maximum tokens per user is: {}",
@@ -78,7 +124,14 @@ pub fn new(req: &mut dyn RequestExt) -> EndpointResult {
         )));
     }
 
-    let api_token = ApiToken::insert(&*conn, user.id, name)?;
+    let api_token = ApiToken::insert_with_scopes(
+        &*conn,
+        user.id,
+        name,
+        endpoint_scopes,
+        crate_scopes,
+        expires_at.map(|expires_at| expires_at.naive_utc()),
+    )?;
     let api_token = EncodableApiTokenWithToken::from(api_token);
 
     Ok(req.json(&json!({ "api_token": api_token })))
@@ -90,16 +143,48 @@ pub fn revoke(req: &mut dyn RequestExt) -> EndpointResult {
         .parse::<i32>()
         .map_err(|e| bad_request(&format!("invalid token id: {e:?}")))?;
 
-    let authenticated_user = req.authenticate()?;
+    let authenticated_user = req.authenticate()?.forbid_scoped_api_token_auth()?;
     let conn = req.db_conn()?;
+    let api_token_id = authenticated_user.api_token_id();
     let user = authenticated_user.user();
     diesel::update(ApiToken::belonging_to(&user).find(id))
         .set(api_tokens::revoked.eq(true))
         .execute(&*conn)?;
 
+    record_token_activity(&conn, req, api_token_id, None, None)?;
+
     Ok(req.json(&json!({})))
 }
 
+/// Handles the `GET /me/tokens/:id/activity` route.
+pub fn activity(req: &mut dyn RequestExt) -> EndpointResult {
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid token id: {e:?}")))?;
+
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let user = authenticated_user.user();
+    let pagination = PaginationOptions::builder().gather(req)?;
+
+    let conn = req.db_conn()?;
+    let token: ApiToken = ApiToken::belonging_to(&user).find(id).first(&*conn)?;
+
+    let data: Paginated<ApiTokenActivity> = ApiTokenActivity::belonging_to(&token)
+        .order((
+            api_token_activity::created_at.desc(),
+            api_token_activity::id.desc(),
+        ))
+        .pages_pagination(pagination)
+        .load(&*conn)?;
+    let more = data.next_page_params().is_some();
+    let activity = data.into_iter().collect::<Vec<_>>();
+
+    Ok(req.json(&json!({
+        "activity": activity,
+        "meta": { "more": more },
+    })))
+}
+
 /// Handles the `DELETE /tokens/current` route.
 pub fn revoke_current(req: &mut dyn RequestExt) -> EndpointResult {
     let authenticated_user = req.authenticate()?;
@@ -112,5 +197,7 @@ pub fn revoke_current(req: &mut dyn RequestExt) -> EndpointResult {
         .set(api_tokens::revoked.eq(true))
         .execute(&*conn)?;
 
+    record_token_activity(&conn, req, Some(api_token_id), None, None)?;
+
     Ok(Response::builder().status(204).body(Body::empty()).unwrap())
 }
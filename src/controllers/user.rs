@@ -1,3 +1,5 @@
+pub mod account;
 pub mod me;
 pub mod other;
 pub mod session;
+pub mod two_factor;
@@ -0,0 +1,238 @@
+//! Endpoints for exporting all data of an account and for deleting it
+
+use crate::controllers::frontend_prelude::*;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::collections::HashMap;
+
+use crate::controllers::util::end_session;
+use crate::models::{
+    ApiToken, Email, LinkedIdentity, Owner, OwnerKind, TotpCredential, User, UserSession,
+    VersionAction,
+};
+use crate::schema::{
+    api_tokens, crate_owner_invitations, crate_owners, crates, emails, follows, linked_identities,
+    user_sessions, users, version_owner_actions, versions, versions_published_by,
+};
+use crate::views::EncodableEmail;
+
+/// Handles the `GET /me/export` route.
+///
+/// Returns everything stored about the user as a single JSON document.
+pub fn export(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+    let user = authenticated_user.user();
+
+    let emails: Vec<Email> = Email::belonging_to(&user).order(emails::id).load(&*conn)?;
+    let emails = emails
+        .into_iter()
+        .map(EncodableEmail::from)
+        .collect::<Vec<_>>();
+
+    let identities: Vec<LinkedIdentity> = LinkedIdentity::belonging_to(&user)
+        .order(linked_identities::id)
+        .load(&*conn)?;
+
+    let api_tokens: Vec<ApiToken> = ApiToken::belonging_to(&user)
+        .order(api_tokens::id)
+        .load(&*conn)?;
+    let api_tokens = api_tokens
+        .into_iter()
+        .map(|token| json!({ "revoked": token.revoked, "token": token }))
+        .collect::<Vec<_>>();
+
+    let sessions: Vec<UserSession> = UserSession::belonging_to(&user)
+        .order(user_sessions::id)
+        .load(&*conn)?;
+
+    let owned_crates: Vec<(String, bool)> = crate_owners::table
+        .inner_join(crates::table)
+        .filter(crate_owners::owner_id.eq(user.id))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .filter(crate_owners::deleted.eq(false))
+        .select((crates::name, crate_owners::email_notifications))
+        .order(crates::name)
+        .load(&*conn)?;
+    let owned_crates = owned_crates
+        .into_iter()
+        .map(|(name, email_notifications)| {
+            json!({ "name": name, "email_notifications": email_notifications })
+        })
+        .collect::<Vec<_>>();
+
+    let follows: Vec<String> = follows::table
+        .inner_join(crates::table)
+        .filter(follows::user_id.eq(user.id))
+        .select(crates::name)
+        .order(crates::name)
+        .load(&*conn)?;
+
+    let invitations: Vec<(String, i32, i32, NaiveDateTime)> = crate_owner_invitations::table
+        .inner_join(crates::table)
+        .filter(
+            crate_owner_invitations::invited_user_id
+                .eq(user.id)
+                .or(crate_owner_invitations::invited_by_user_id.eq(user.id)),
+        )
+        .select((
+            crates::name,
+            crate_owner_invitations::invited_user_id,
+            crate_owner_invitations::invited_by_user_id,
+            crate_owner_invitations::created_at,
+        ))
+        .order(crate_owner_invitations::created_at)
+        .load(&*conn)?;
+    let user_ids = invitations
+        .iter()
+        .flat_map(|(_, invitee, inviter, _)| vec![*invitee, *inviter])
+        .collect::<Vec<_>>();
+    let logins: HashMap<i32, String> = users::table
+        .filter(users::id.eq_any(user_ids))
+        .select((users::id, users::gh_login))
+        .load(&*conn)?
+        .into_iter()
+        .collect();
+    let invitations = invitations
+        .into_iter()
+        .map(|(crate_name, invitee, inviter, created_at)| {
+            json!({
+                "crate": crate_name,
+                "invited_user": logins.get(&invitee),
+                "invited_by": logins.get(&inviter),
+                "created_at": to_rfc3339(created_at),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let version_actions: Vec<(String, String, VersionAction, NaiveDateTime)> =
+        version_owner_actions::table
+            .inner_join(versions::table.inner_join(crates::table))
+            .filter(version_owner_actions::user_id.eq(user.id))
+            .select((
+                crates::name,
+                versions::num,
+                version_owner_actions::action,
+                version_owner_actions::time,
+            ))
+            .order(version_owner_actions::id)
+            .load(&*conn)?;
+    let version_actions = version_actions
+        .into_iter()
+        .map(|(crate_name, version, action, time)| {
+            json!({
+                "crate": crate_name,
+                "version": version,
+                "action": String::from(action),
+                "time": to_rfc3339(time),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let published_versions: Vec<(String, String, Option<String>)> = versions::table
+        .inner_join(crates::table)
+        .left_join(versions_published_by::table)
+        .filter(versions::published_by.eq(user.id))
+        .select((
+            crates::name,
+            versions::num,
+            versions_published_by::email.nullable(),
+        ))
+        .order(versions::id)
+        .load(&*conn)?;
+    let published_versions = published_versions
+        .into_iter()
+        .map(|(crate_name, version, email)| {
+            json!({ "crate": crate_name, "version": version, "email": email })
+        })
+        .collect::<Vec<_>>();
+
+    let two_factor_enabled = TotpCredential::find_enabled(&conn, user.id)?.is_some();
+
+    Ok(req.json(&json!({
+        "user": {
+            "id": user.id,
+            "login": user.gh_login,
+            "name": user.name,
+            "avatar": user.gh_avatar,
+            "github_id": user.gh_id,
+            "is_admin": user.is_admin,
+            "two_factor_enabled": two_factor_enabled,
+        },
+        "emails": emails,
+        "identities": identities,
+        "api_tokens": api_tokens,
+        "sessions": sessions,
+        "owned_crates": owned_crates,
+        "follows": follows,
+        "crate_owner_invitations": invitations,
+        "version_actions": version_actions,
+        "published_versions": published_versions,
+    })))
+}
+
+/// Handles the `DELETE /me` route.
+///
+/// The request body decides what happens to the crates the user owns. They are either
+/// transferred to another user, or the user is removed as an owner, which leaves crates without
+/// other owners orphaned. Crates cannot be transferred to locked or deleted accounts:
+///
+/// ```json
+/// {"crates": "transfer", "to": "new-owner"}
+/// {"crates": "orphan"}
+/// ```
+///
+/// The account is anonymized rather than removed, since published versions refer to it.
+pub fn delete(req: &mut dyn RequestExt) -> EndpointResult {
+    #[derive(Deserialize)]
+    #[serde(tag = "crates", rename_all = "snake_case")]
+    enum Request {
+        Transfer { to: String },
+        Orphan,
+    }
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let request: Request = serde_json::from_str(&body).map_err(|_| {
+        bad_request(r#"invalid json request, expected `{"crates": "transfer", "to": "login"}` or `{"crates": "orphan"}`"#)
+    })?;
+
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    {
+        let conn = req.db_conn()?;
+        authenticated_user.require_recent_second_factor(req, &conn)?;
+        let user = authenticated_user.user();
+
+        conn.transaction(|| {
+            match &request {
+                Request::Transfer { to } => {
+                    let to = Owner::find_user_by_login(&conn, to)?;
+                    if to.id == user.id {
+                        return Err(bad_request("cannot transfer crates to the same user"));
+                    }
+                    if to.is_locked() || to.is_anonymized() {
+                        return Err(bad_request(&format_args!(
+                            "cannot transfer crates to `{}`, their account is locked or deleted",
+                            to.gh_login
+                        )));
+                    }
+                    user.transfer_crates(&conn, &to, user.id)?;
+                }
+                Request::Orphan => {
+                    user.orphan_crates(&conn)?;
+                }
+            }
+
+            user.anonymize(&conn)?;
+            Ok(())
+        })?;
+    }
+
+    end_session(req)?;
+    ok_true()
+}
+
+fn to_rfc3339(time: NaiveDateTime) -> String {
+    DateTime::<Utc>::from_utc(time, Utc).to_rfc3339()
+}
@@ -3,13 +3,20 @@ use std::collections::HashMap;
 use crate::controllers::frontend_prelude::*;
 
 use crate::controllers::helpers::*;
+use crate::controllers::util::record_token_activity;
 
 use crate::controllers::helpers::pagination::{Paginated, PaginationOptions};
 use crate::models::{
-    CrateOwner, Email, Follow, NewEmail, OwnerKind, User, Version, VersionOwnerAction,
+    CrateOwner, Email, Follow, LinkedIdentity, NewEmail, OwnerKind, User, UserSession, Version,
+    VersionOwnerAction,
+};
+use crate::schema::{
+    crate_owners, crates, emails, follows, linked_identities, user_sessions, users, versions,
+};
+use crate::util::errors::not_found;
+use crate::views::{
+    EncodableEmail, EncodableMe, EncodablePrivateUser, EncodableVersion, OwnedCrate,
 };
-use crate::schema::{crate_owners, crates, emails, follows, users, versions};
-use crate::views::{EncodableMe, EncodablePrivateUser, EncodableVersion, OwnedCrate};
 
 /// Handles the `GET /me` route.
 pub fn me(req: &mut dyn RequestExt) -> EndpointResult {
@@ -19,7 +26,11 @@ pub fn me(req: &mut dyn RequestExt) -> EndpointResult {
     let (user, verified, email, verification_sent): (User, Option<bool>, Option<String>, bool) =
         users::table
             .find(user_id)
-            .left_join(emails::table)
+            .left_join(
+                emails::table.on(emails::user_id
+                    .eq(users::id)
+                    .and(emails::is_primary.eq(true))),
+            )
             .select((
                 users::all_columns,
                 emails::verified.nullable(),
@@ -96,13 +107,15 @@ pub fn update_user(req: &mut dyn RequestExt) -> EndpointResult {
     use self::emails::user_id;
     use diesel::insert_into;
 
-    let authenticated_user = req.authenticate()?;
+    let authenticated_user = req.authenticate()?.forbid_scoped_api_token_auth()?;
 
     let mut body = String::new();
     req.body().read_to_string(&mut body)?;
 
     let param_user_id = &req.params()["user_id"];
     let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+    let api_token_id = authenticated_user.api_token_id();
     let user = authenticated_user.user();
 
     // need to check if current user matches user to be updated
@@ -133,20 +146,32 @@ pub fn update_user(req: &mut dyn RequestExt) -> EndpointResult {
     }
 
     conn.transaction::<_, Box<dyn AppError>, _>(|| {
-        let new_email = NewEmail {
-            user_id: user.id,
-            email: user_email,
+        // This replaces the primary address. Changing the address of an existing row resets its
+        // verification through the `trigger_emails_reconfirm` trigger.
+        let email_id: Option<i32> = diesel::update(
+            emails::table
+                .filter(user_id.eq(user.id))
+                .filter(emails::is_primary.eq(true)),
+        )
+        .set(emails::email.eq(user_email))
+        .returning(emails::id)
+        .get_result(&*conn)
+        .optional()
+        .map_err(|_| server_error("Error in creating token"))?;
+
+        let email_id = match email_id {
+            Some(email_id) => email_id,
+            None => insert_into(emails::table)
+                .values(&NewEmail {
+                    user_id: user.id,
+                    email: user_email,
+                    is_primary: true,
+                })
+                .returning(emails::id)
+                .get_result(&*conn)
+                .map_err(|_| server_error("Error in creating token"))?,
         };
 
-        let token: String = insert_into(emails::table)
-            .values(&new_email)
-            .on_conflict(user_id)
-            .do_update()
-            .set(&new_email)
-            .returning(emails::token)
-            .get_result(&*conn)
-            .map_err(|_| server_error("Error in creating token"))?;
-
         // This swallows any errors that occur while attempting to send the email. Some users have
         // an invalid email set in their GitHub profile, and we should let them sign in even though
         // we're trying to silently use their invalid address during signup and can't send them an
@@ -154,11 +179,13 @@ pub fn update_user(req: &mut dyn RequestExt) -> EndpointResult {
         let _ = req
             .app()
             .emails
-            .send_user_confirm(user_email, &user.gh_login, &token);
+            .send_user_confirm(&conn, user_email, &user.gh_login, email_id);
 
         Ok(())
     })?;
 
+    record_token_activity(&conn, req, api_token_id, None, None)?;
+
     ok_true()
 }
 
@@ -188,8 +215,9 @@ pub fn regenerate_token_and_send(req: &mut dyn RequestExt) -> EndpointResult {
     let param_user_id = req.params()["user_id"]
         .parse::<i32>()
         .map_err(|err| err.chain(bad_request("invalid user_id")))?;
-    let authenticated_user = req.authenticate()?;
+    let authenticated_user = req.authenticate()?.forbid_scoped_api_token_auth()?;
     let conn = req.db_conn()?;
+    let api_token_id = authenticated_user.api_token_id();
     let user = authenticated_user.user();
 
     // need to check if current user matches user to be updated
@@ -198,16 +226,18 @@ pub fn regenerate_token_and_send(req: &mut dyn RequestExt) -> EndpointResult {
     }
 
     conn.transaction(|| {
-        let email: Email = update(Email::belonging_to(&user))
+        let email: Email = update(Email::belonging_to(&user).filter(emails::is_primary.eq(true)))
             .set(emails::token.eq(sql("DEFAULT")))
             .get_result(&*conn)
             .map_err(|_| bad_request("Email could not be found"))?;
 
         req.app()
             .emails
-            .send_user_confirm(&email.email, &user.gh_login, &email.token)
+            .send_user_confirm(&conn, &email.email, &user.gh_login, email.id)
     })?;
 
+    record_token_activity(&conn, req, api_token_id, None, None)?;
+
     ok_true()
 }
 
@@ -230,7 +260,8 @@ pub fn update_email_notifications(req: &mut dyn RequestExt) -> EndpointResult {
         .map(|c| (c.id, c.email_notifications))
         .collect();
 
-    let user_id = req.authenticate()?.user_id();
+    let authenticated_user = req.authenticate()?.forbid_scoped_api_token_auth()?;
+    let user_id = authenticated_user.user_id();
     let conn = req.db_conn()?;
 
     // Build inserts from existing crates belonging to the current user
@@ -261,5 +292,257 @@ pub fn update_email_notifications(req: &mut dyn RequestExt) -> EndpointResult {
         .set(email_notifications.eq(excluded(email_notifications)))
         .execute(&*conn)?;
 
+    record_token_activity(&conn, req, authenticated_user.api_token_id(), None, None)?;
+
+    ok_true()
+}
+
+/// Handles the `GET /me/identities` route.
+pub fn identities(req: &mut dyn RequestExt) -> EndpointResult {
+    let user = req.authenticate()?.forbid_api_token_auth()?.user();
+    let conn = req.db_conn()?;
+
+    let identities: Vec<LinkedIdentity> = LinkedIdentity::belonging_to(&user)
+        .order(linked_identities::id)
+        .load(&*conn)?;
+
+    Ok(req.json(&json!({ "identities": identities })))
+}
+
+/// Handles the `DELETE /me/identities/:provider` route.
+///
+/// The last identity of a user cannot be removed, since they would not be able to log in anymore.
+pub fn unlink_identity(req: &mut dyn RequestExt) -> EndpointResult {
+    let user = req.authenticate()?.forbid_api_token_auth()?.user();
+    let provider = &req.params()["provider"];
+    let conn = req.db_conn()?;
+
+    conn.transaction(|| {
+        let identities: Vec<LinkedIdentity> = LinkedIdentity::belonging_to(&user)
+            .for_update()
+            .load(&*conn)?;
+
+        let identity = identities
+            .iter()
+            .find(|identity| &identity.provider == provider)
+            .ok_or_else(|| {
+                bad_request(&format_args!("your account is not linked to `{provider}`"))
+            })?;
+        if identities.len() == 1 {
+            return Err(bad_request(
+                "the only login method of an account cannot be removed",
+            ));
+        }
+
+        diesel::delete(identity).execute(&*conn)?;
+
+        // Without a linked GitHub account, the user can no longer prove team memberships, and
+        // their GitHub login may be taken by somebody else
+        if provider == "github" {
+            let remaining = identities
+                .iter()
+                .find(|other| other.id != identity.id)
+                .expect("the last identity cannot be removed");
+            let login = LinkedIdentity::namespaced_login(&remaining.provider, &remaining.login);
+            diesel::update(&user)
+                .set((
+                    users::gh_id.eq(0),
+                    users::gh_login.eq(login),
+                    users::gh_access_token.eq(""),
+                ))
+                .execute(&*conn)?;
+        }
+
+        ok_true()
+    })
+}
+
+/// Handles the `GET /me/sessions` route.
+pub fn sessions(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let current_session_id = authenticated_user.session_id();
+    let user = authenticated_user.user();
+    let conn = req.db_conn()?;
+
+    let sessions: Vec<UserSession> = UserSession::belonging_to(&user)
+        .order(user_sessions::last_used_at.desc())
+        .load(&*conn)?;
+
+    Ok(req.json(&json!({ "sessions": sessions, "current_session_id": current_session_id })))
+}
+
+/// Handles the `DELETE /me/sessions/:id` route.
+///
+/// Revoking the current session logs the user out.
+pub fn revoke_session(req: &mut dyn RequestExt) -> EndpointResult {
+    let user_id = req.authenticate()?.forbid_api_token_auth()?.user_id();
+    let session_id = req.params()["id"]
+        .parse::<i64>()
+        .map_err(|_| bad_request("invalid session id"))?;
+    let conn = req.db_conn()?;
+
+    let session = UserSession::find(&conn, session_id, user_id)?.ok_or_else(not_found)?;
+    session.revoke(&conn)?;
+
+    ok_true()
+}
+
+/// The number of email addresses a user can register
+const MAX_EMAILS_PER_USER: i64 = 10;
+
+/// Handles the `GET /me/emails` route.
+pub fn emails(req: &mut dyn RequestExt) -> EndpointResult {
+    let user = req.authenticate()?.forbid_api_token_auth()?.user();
+    let conn = req.db_conn()?;
+
+    let emails: Vec<Email> = Email::belonging_to(&user).order(emails::id).load(&*conn)?;
+    let emails = emails
+        .into_iter()
+        .map(EncodableEmail::from)
+        .collect::<Vec<_>>();
+
+    Ok(req.json(&json!({ "emails": emails })))
+}
+
+/// Handles the `PUT /me/emails` route.
+///
+/// Adds an address in the format `{"email": "user@example.com"}` and sends a confirmation
+/// email to it. The first address of a user becomes their primary address.
+pub fn add_email(req: &mut dyn RequestExt) -> EndpointResult {
+    #[derive(Deserialize)]
+    struct Request {
+        email: String,
+    }
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let request: Request =
+        serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))?;
+    let address = request.email.trim();
+    if address.is_empty() {
+        return Err(bad_request("empty email rejected"));
+    }
+
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+    let user = authenticated_user.user();
+
+    let email = conn.transaction(|| {
+        let existing: Vec<Email> = Email::belonging_to(&user).for_update().load(&*conn)?;
+        if existing
+            .iter()
+            .any(|email| email.email.to_lowercase() == address.to_lowercase())
+        {
+            return Err(bad_request("this email address is already registered"));
+        }
+        if existing.len() as i64 >= MAX_EMAILS_PER_USER {
+            return Err(bad_request(&format!(
+                "a user cannot register more than {MAX_EMAILS_PER_USER} email addresses"
+            )));
+        }
+
+        let email: Email = diesel::insert_into(emails::table)
+            .values(&NewEmail {
+                user_id: user.id,
+                email: address,
+                is_primary: existing.is_empty(),
+            })
+            .get_result(&*conn)?;
+
+        // Like in `update_user`, an invalid address should not fail the request
+        let _ = req
+            .app()
+            .emails
+            .send_user_confirm(&conn, &email.email, &user.gh_login, email.id);
+
+        Ok(email)
+    })?;
+
+    Ok(req.json(&json!({ "email": EncodableEmail::from(email) })))
+}
+
+/// Handles the `DELETE /me/emails/:email_id` route.
+///
+/// The primary address cannot be removed, another address has to be made primary first.
+pub fn remove_email(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let email_id = parse_email_id(req)?;
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+
+    let email = Email::find(&conn, email_id, authenticated_user.user_id())?;
+    if email.is_primary {
+        return Err(bad_request("the primary email address cannot be removed"));
+    }
+
+    diesel::delete(&email).execute(&*conn)?;
     ok_true()
 }
+
+/// Handles the `PUT /me/emails/:email_id/primary` route.
+pub fn make_email_primary(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let email_id = parse_email_id(req)?;
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+
+    let email = Email::find(&conn, email_id, authenticated_user.user_id())?;
+    if !email.verified {
+        return Err(bad_request(
+            "only verified email addresses can be made primary",
+        ));
+    }
+
+    email.make_primary(&conn)?;
+    ok_true()
+}
+
+/// Handles the `PUT /me/emails/:email_id/publish` route.
+///
+/// Chooses the address that is recorded as the publisher's email of new versions.
+pub fn use_email_for_publishing(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let email_id = parse_email_id(req)?;
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+
+    let email = Email::find(&conn, email_id, authenticated_user.user_id())?;
+    if !email.verified {
+        return Err(bad_request(
+            "only verified email addresses can be used for publishing",
+        ));
+    }
+
+    email.use_for_publishing(&conn)?;
+    ok_true()
+}
+
+/// Handles the `PUT /me/emails/:email_id/resend` route.
+pub fn resend_email_confirmation(req: &mut dyn RequestExt) -> EndpointResult {
+    use diesel::dsl::sql;
+
+    let user = req.authenticate()?.forbid_api_token_auth()?.user();
+    let email_id = parse_email_id(req)?;
+    let conn = req.db_conn()?;
+
+    let email = Email::find(&conn, email_id, user.id)?;
+    if email.verified {
+        return Err(bad_request("this email address is already verified"));
+    }
+
+    let email: Email = diesel::update(&email)
+        .set(emails::token.eq(sql("DEFAULT")))
+        .get_result(&*conn)?;
+
+    req.app()
+        .emails
+        .send_user_confirm(&conn, &email.email, &user.gh_login, email.id)?;
+    ok_true()
+}
+
+fn parse_email_id(req: &dyn RequestExt) -> AppResult<i32> {
+    req.params()["email_id"]
+        .parse::<i32>()
+        .map_err(|_| bad_request("invalid email id"))
+}
@@ -1,22 +1,50 @@
 use crate::controllers::frontend_prelude::*;
 
 use conduit_cookie::RequestSession;
-use oauth2::reqwest::http_client;
-use oauth2::{AuthorizationCode, Scope, TokenResponse};
+use oauth2::AuthorizationCode;
 
+use crate::controllers::util::{current_session, end_session, start_session};
 use crate::email::Emails;
-use crate::github::GithubUser;
-use crate::models::{NewUser, User};
-use crate::schema::users;
+use crate::login::{LoginProvider, ProviderUser};
+use crate::models::{CrateOwnerSignupInvitation, LinkedIdentity, NewLinkedIdentity, NewUser, User};
+use crate::schema::{linked_identities, users};
+use crate::sql::lower;
 use crate::util::errors::ReadOnlyMode;
 
+/// The provider used if the `provider` query parameter is missing.
+const DEFAULT_PROVIDER: &str = "github";
+
+/// Handles the `GET /api/private/session/providers` route.
+///
+/// ## Response Body Example
+///
+/// ```json
+/// {
+///     "providers": [{ "name": "github", "display_name": "GitHub" }]
+/// }
+/// ```
+pub fn providers(req: &mut dyn RequestExt) -> EndpointResult {
+    let providers = req
+        .app()
+        .login_providers
+        .iter()
+        .map(|provider| json!({ "name": provider.name(), "display_name": provider.display_name() }))
+        .collect::<Vec<_>>();
+
+    Ok(req.json(&json!({ "providers": providers })))
+}
+
 /// Handles the `GET /api/private/session/begin` route.
 ///
-/// This route will return an authorization URL for the GitHub OAuth flow including the crates.io
-/// `client_id` and a randomly generated `state` secret.
+/// This route will return an authorization URL for the OAuth flow of the login provider,
+/// including the crates.io `client_id` and a randomly generated `state` secret.
 ///
 /// see <https://developer.github.com/v3/oauth/#redirect-users-to-request-github-access>
 ///
+/// ## Query Parameters
+///
+/// - `provider` – name of the login provider, defaults to `github`
+///
 /// ## Response Body Example
 ///
 /// ```json
@@ -26,32 +54,36 @@ use crate::util::errors::ReadOnlyMode;
 /// }
 /// ```
 pub fn begin(req: &mut dyn RequestExt) -> EndpointResult {
-    let (url, state) = req
-        .app()
-        .github_oauth
-        .authorize_url(oauth2::CsrfToken::new_random)
-        .add_scope(Scope::new("read:org".to_string()))
-        .url();
+    let provider_name = req
+        .query()
+        .remove("provider")
+        .unwrap_or_else(|| DEFAULT_PROVIDER.to_string());
+
+    let (url, state) = find_provider(req, &provider_name)?.authorize_url();
     let state = state.secret().to_string();
-    req.session_mut()
-        .insert("github_oauth_state".to_string(), state.clone());
+
+    let session = req.session_mut();
+    session.insert("oauth_state".to_string(), state.clone());
+    session.insert("oauth_provider".to_string(), provider_name);
 
     Ok(req.json(&json!({ "url": url.to_string(), "state": state })))
 }
 
 /// Handles the `GET /api/private/session/authorize` route.
 ///
-/// This route is called from the GitHub API OAuth flow after the user accepted or rejected
-/// the data access permissions. It will check the `state` parameter and then call the GitHub API
-/// to exchange the temporary `code` for an API token. The API token is returned together with
-/// the corresponding user information.
+/// This route is called from the OAuth flow of the login provider after the user accepted or
+/// rejected the data access permissions. It will check the `state` parameter and then call the
+/// provider to exchange the temporary `code` for the account information of the user.
+///
+/// If the account is not linked to any crates.io user yet, it is linked to the user that is
+/// currently logged in, or a new user is created for it. The user information is returned.
 ///
 /// see <https://developer.github.com/v3/oauth/#github-redirects-back-to-your-site>
 ///
 /// ## Query Parameters
 ///
-/// - `code` – temporary code received from the GitHub API  **(Required)**
-/// - `state` – state parameter received from the GitHub API  **(Required)**
+/// - `code` – temporary code received from the login provider  **(Required)**
+/// - `state` – state parameter received from the login provider  **(Required)**
 ///
 /// ## Response Body Example
 ///
@@ -75,58 +107,126 @@ pub fn authorize(req: &mut dyn RequestExt) -> EndpointResult {
 
     // Make sure that the state we just got matches the session state that we
     // should have issued earlier.
-    {
-        let session_state = req.session_mut().remove(&"github_oauth_state".to_string());
-        let session_state = session_state.as_deref();
-        if Some(&state[..]) != session_state {
+    let provider_name = {
+        let session = req.session_mut();
+        let session_state = session.remove(&"oauth_state".to_string());
+        let provider_name = session.remove(&"oauth_provider".to_string());
+        if Some(&state[..]) != session_state.as_deref() {
             return Err(bad_request("invalid state parameter"));
         }
+        provider_name.unwrap_or_else(|| DEFAULT_PROVIDER.to_string())
+    };
+
+    // Fetch the account information from the provider using the code we just got
+    let provider = find_provider(req, &provider_name)?;
+    let provider_user = provider.exchange_code(AuthorizationCode::new(code))?;
+
+    // Linking another account allows logging in with it, so it needs a recent second factor
+    let linking = {
+        let conn = req.db_conn()?;
+        current_session(req, &conn)?.is_some()
+            && LinkedIdentity::find(&conn, provider.name(), &provider_user.id)?.is_none()
+    };
+    if linking {
+        let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+        let conn = req.db_conn()?;
+        authenticated_user.require_recent_second_factor(req, &conn)?;
     }
+    // Authenticating borrowed the request mutably, so the provider has to be looked up again
+    let provider = find_provider(req, &provider_name)?;
 
-    // Fetch the access token from GitHub using the code we just got
-    let code = AuthorizationCode::new(code);
-    let token = req
-        .app()
-        .github_oauth
-        .exchange_code(code)
-        .request(http_client)
-        .map_err(|err| err.chain(server_error("Error obtaining token")))?;
-    let token = token.access_token();
+    let (user, current_user_id) = {
+        let conn = req.db_conn()?;
+        let current_user_id = current_session(req, &conn)?.map(|session| session.user_id);
+        let user = save_user_to_database(
+            provider,
+            &provider_user,
+            current_user_id,
+            &req.app().emails,
+            &conn,
+        )?;
+
+        // Owners may have invited the GitHub account before it signed up
+        if user.gh_id > 0 {
+            let config = &req.app().config;
+            if let Err(error) = CrateOwnerSignupInvitation::convert_for_user(&user, &conn, config) {
+                warn!(
+                    "Could not convert the invitations of {} after login: {error}",
+                    user.gh_login
+                );
+            }
+        }
 
-    // Fetch the user info from GitHub using the access token we just got and create a user record
-    let ghuser = req.app().github.current_user(token)?;
-    let user = save_user_to_database(&ghuser, token.secret(), &req.app().emails, &*req.db_conn()?)?;
+        (user, current_user_id)
+    };
 
-    // Log in by setting a cookie and the middleware authentication
-    req.session_mut()
-        .insert("user_id".to_string(), user.id.to_string());
+    // Linking an account keeps the current session, otherwise a new one replaces it
+    if current_user_id != Some(user.id) {
+        end_session(req)?;
+        start_session(req, user.id)?;
+    }
 
     super::me::me(req)
 }
 
+fn find_provider<'a>(req: &'a dyn RequestExt, name: &str) -> AppResult<&'a dyn LoginProvider> {
+    req.app()
+        .login_providers
+        .get(name)
+        .ok_or_else(|| bad_request(&format_args!("unknown login provider `{name}`")))
+}
+
+/// Logs in the user the account is linked to, or links the account to the user that is
+/// currently logged in, or creates a new user for the account.
 fn save_user_to_database(
-    user: &GithubUser,
-    access_token: &str,
+    provider: &dyn LoginProvider,
+    provider_user: &ProviderUser,
+    current_user_id: Option<i32>,
     emails: &Emails,
     conn: &PgConnection,
 ) -> AppResult<User> {
-    NewUser::new(
-        user.id,
-        &user.login,
-        user.name.as_deref(),
-        user.avatar_url.as_deref(),
-        access_token,
-    )
-    .create_or_update(user.email.as_deref(), emails, conn)
-    .map_err(Into::into)
+    conn.transaction(|| {
+        let identity = LinkedIdentity::find(conn, provider.name(), &provider_user.id)?;
+
+        if let Some(identity) = identity {
+            if current_user_id.map_or(false, |id| id != identity.user_id) {
+                return Err(bad_request(&format_args!(
+                    "this {} account is already linked to another crates.io account",
+                    provider.display_name()
+                )));
+            }
+
+            identity.touch(conn, &provider_user.login)?;
+            return match new_github_user(provider_user) {
+                Some(new_user) => {
+                    Ok(new_user.create_or_update(provider_user.email.as_deref(), emails, conn)?)
+                }
+                None => Ok(User::find(conn, identity.user_id)?),
+            };
+        }
+
+        let user = match current_user_id {
+            Some(user_id) => link_to_user(provider, provider_user, user_id, conn)?,
+            None => create_user(provider, provider_user, emails, conn)?,
+        };
+
+        NewLinkedIdentity {
+            user_id: user.id,
+            provider: provider.name(),
+            provider_user_id: &provider_user.id,
+            login: &provider_user.login,
+        }
+        .insert(conn)?;
+
+        Ok(user)
+    })
     .or_else(|e: Box<dyn AppError>| {
         // If we're in read only mode, we can't update their details
         // just look for an existing user
         if e.is::<ReadOnlyMode>() {
-            users::table
-                .filter(users::gh_id.eq(user.id))
-                .first(conn)
-                .optional()?
+            LinkedIdentity::find(conn, provider.name(), &provider_user.id)?
+                .map(|identity| User::find(conn, identity.user_id))
+                .transpose()?
                 .ok_or(e)
         } else {
             Err(e)
@@ -134,15 +234,122 @@ fn save_user_to_database(
     })
 }
 
+fn link_to_user(
+    provider: &dyn LoginProvider,
+    provider_user: &ProviderUser,
+    user_id: i32,
+    conn: &PgConnection,
+) -> AppResult<User> {
+    let user = User::find(conn, user_id)?;
+
+    let already_linked = LinkedIdentity::belonging_to(&user)
+        .filter(linked_identities::provider.eq(provider.name()))
+        .count()
+        .get_result::<i64>(conn)?;
+    if already_linked > 0 {
+        return Err(bad_request(&format_args!(
+            "your crates.io account is already linked to a different {} account",
+            provider.display_name()
+        )));
+    }
+
+    // GitHub logins are used to check team memberships, so they take precedence over the
+    // logins of other providers.
+    match &provider_user.github {
+        Some(github) => Ok(diesel::update(&user)
+            .set((
+                users::gh_id.eq(github.id),
+                users::gh_login.eq(&provider_user.login),
+                users::gh_avatar.eq(&provider_user.avatar),
+                users::gh_access_token.eq(&github.access_token),
+            ))
+            .get_result(conn)?),
+        None => Ok(user),
+    }
+}
+
+fn create_user(
+    provider: &dyn LoginProvider,
+    provider_user: &ProviderUser,
+    emails: &Emails,
+    conn: &PgConnection,
+) -> AppResult<User> {
+    let email = provider_user.email.as_deref();
+    if let Some(new_user) = new_github_user(provider_user) {
+        return Ok(new_user.create_or_update(email, emails, conn)?);
+    }
+
+    // Users without a GitHub account are stored with a `gh_id` of 0, which is excluded from the
+    // uniqueness constraint on `gh_id`, and a login namespaced by their provider.
+    let login = LinkedIdentity::namespaced_login(provider.name(), &provider_user.login);
+    let login_taken = users::table
+        .filter(lower(users::gh_login).eq(login.to_lowercase()))
+        .count()
+        .get_result::<i64>(conn)?;
+    if login_taken > 0 {
+        return Err(bad_request(&format_args!(
+            "the username `{}` is already taken. If this is you, log in with your \
+             existing account and link your {} account in your account settings.",
+            login,
+            provider.display_name()
+        )));
+    }
+
+    let new_user = NewUser::new(
+        0,
+        &login,
+        provider_user.name.as_deref(),
+        provider_user.avatar.as_deref(),
+        "",
+    );
+    Ok(new_user.create_or_update(email, emails, conn)?)
+}
+
+fn new_github_user(provider_user: &ProviderUser) -> Option<NewUser<'_>> {
+    provider_user.github.as_ref().map(|github| {
+        NewUser::new(
+            github.id,
+            &provider_user.login,
+            provider_user.name.as_deref(),
+            provider_user.avatar.as_deref(),
+            &github.access_token,
+        )
+    })
+}
+
 /// Handles the `DELETE /api/private/session` route.
 pub fn logout(req: &mut dyn RequestExt) -> EndpointResult {
-    req.session_mut().remove(&"user_id".to_string());
+    end_session(req)?;
     Ok(req.json(&true))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::login::GitHubIdentity;
+    use oauth2::CsrfToken;
+    use url::Url;
+
+    struct GitHub;
+
+    impl LoginProvider for GitHub {
+        fn name(&self) -> &str {
+            "github"
+        }
+
+        fn display_name(&self) -> &str {
+            "GitHub"
+        }
+
+        fn authorize_url(&self) -> (Url, CsrfToken) {
+            let url = Url::parse("https://github.com/login/oauth/authorize").unwrap();
+            (url, CsrfToken::new("state".into()))
+        }
+
+        fn exchange_code(&self, _code: AuthorizationCode) -> AppResult<ProviderUser> {
+            panic!("not used in this test")
+        }
+    }
 
     fn pg_connection() -> PgConnection {
         let database_url =
@@ -154,14 +361,18 @@ mod tests {
     fn gh_user_with_invalid_email_doesnt_fail() {
         let emails = Emails::new_in_memory();
         let conn = pg_connection();
-        let gh_user = GithubUser {
+        let gh_user = ProviderUser {
+            id: "-1".into(),
             email: Some("String.Format(\"{0}.{1}@live.com\", FirstName, LastName)".into()),
             name: Some("My Name".into()),
             login: "github_user".into(),
-            id: -1,
-            avatar_url: None,
+            avatar: None,
+            github: Some(GitHubIdentity {
+                id: -1,
+                access_token: "arbitrary_token".into(),
+            }),
         };
-        let result = save_user_to_database(&gh_user, "arbitrary_token", &emails, &conn);
+        let result = save_user_to_database(&GitHub, &gh_user, None, &emails, &conn);
 
         assert!(
             result.is_ok(),
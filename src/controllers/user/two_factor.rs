@@ -0,0 +1,152 @@
+//! Endpoints for managing two-factor authentication with an authenticator app
+//!
+//! Once enabled, sensitive actions taken from a cookie session require a code that was
+//! confirmed recently through `PUT /me/two_factor/confirm`.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::controllers::util::record_second_factor;
+use crate::models::TotpCredential;
+use crate::util::errors::second_factor_locked;
+use crate::util::totp;
+
+#[derive(Deserialize)]
+struct CodeRequest {
+    code: String,
+}
+
+/// Handles the `GET /me/two_factor` route.
+pub fn status(req: &mut dyn RequestExt) -> EndpointResult {
+    let user_id = req.authenticate()?.forbid_api_token_auth()?.user_id();
+    let conn = req.db_conn()?;
+
+    let enabled = TotpCredential::find_enabled(&conn, user_id)?.is_some();
+    let recovery_codes = if enabled {
+        TotpCredential::remaining_recovery_codes(&conn, user_id)?
+    } else {
+        0
+    };
+
+    Ok(req.json(&json!({ "enabled": enabled, "recovery_codes_remaining": recovery_codes })))
+}
+
+/// Handles the `PUT /me/two_factor` route.
+///
+/// Starts the enrollment by generating a new secret. Two-factor authentication is only enabled
+/// once a code of the authenticator app is confirmed through `PUT /me/two_factor/enable`.
+pub fn begin_enrollment(req: &mut dyn RequestExt) -> EndpointResult {
+    let user = req.authenticate()?.forbid_api_token_auth()?.user();
+    let conn = req.db_conn()?;
+
+    let credential = conn.transaction(|| {
+        if TotpCredential::find_enabled(&conn, user.id)?.is_some() {
+            return Err(bad_request("two-factor authentication is already enabled"));
+        }
+        Ok(TotpCredential::start_enrollment(&conn, user.id)?)
+    })?;
+
+    let issuer = &req.app().config.domain_name;
+    Ok(req.json(&json!({
+        "secret": totp::encode_secret(&credential.secret),
+        "otpauth_url": totp::provisioning_url(&credential.secret, issuer, &user.gh_login),
+    })))
+}
+
+/// Handles the `PUT /me/two_factor/enable` route.
+///
+/// The format of the request body is `{"code": "123456"}`. Returns the recovery codes, which
+/// are not shown again.
+pub fn enable(req: &mut dyn RequestExt) -> EndpointResult {
+    let request: CodeRequest = parse_body(req)?;
+    let user_id = req.authenticate()?.forbid_api_token_auth()?.user_id();
+    let conn = req.db_conn()?;
+
+    let credential = match TotpCredential::find(&conn, user_id)? {
+        Some(credential) if credential.enabled_at.is_none() => credential,
+        Some(_) => return Err(bad_request("two-factor authentication is already enabled")),
+        None => {
+            return Err(bad_request(
+                "two-factor authentication enrollment not started",
+            ))
+        }
+    };
+    check_code(&conn, &credential, &request.code, false)?;
+    let recovery_codes = credential.enable(&conn)?;
+    drop(conn);
+
+    record_second_factor(req, user_id);
+    Ok(req.json(&json!({ "recovery_codes": recovery_codes })))
+}
+
+/// Handles the `PUT /me/two_factor/confirm` route.
+///
+/// Accepts a code of the authenticator app or an unused recovery code in the format
+/// `{"code": "123456"}`, allowing sensitive actions for the next few minutes.
+pub fn confirm(req: &mut dyn RequestExt) -> EndpointResult {
+    let request: CodeRequest = parse_body(req)?;
+    let user_id = req.authenticate()?.forbid_api_token_auth()?.user_id();
+    let conn = req.db_conn()?;
+
+    let credential = TotpCredential::find_enabled(&conn, user_id)?
+        .ok_or_else(|| bad_request("two-factor authentication is not enabled"))?;
+    check_code(&conn, &credential, &request.code, true)?;
+    drop(conn);
+
+    record_second_factor(req, user_id);
+    ok_true()
+}
+
+/// Handles the `PUT /me/two_factor/recovery_codes` route.
+pub fn regenerate_recovery_codes(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+    let user_id = authenticated_user.user_id();
+
+    if TotpCredential::find_enabled(&conn, user_id)?.is_none() {
+        return Err(bad_request("two-factor authentication is not enabled"));
+    }
+    let recovery_codes = TotpCredential::regenerate_recovery_codes(&conn, user_id)?;
+
+    Ok(req.json(&json!({ "recovery_codes": recovery_codes })))
+}
+
+/// Handles the `DELETE /me/two_factor` route.
+pub fn disable(req: &mut dyn RequestExt) -> EndpointResult {
+    let authenticated_user = req.authenticate()?.forbid_api_token_auth()?;
+    let conn = req.db_conn()?;
+    authenticated_user.require_recent_second_factor(req, &conn)?;
+
+    TotpCredential::disable(&conn, authenticated_user.user_id())?;
+    ok_true()
+}
+
+/// Checks a code of the authenticator app, or optionally a recovery code. Invalid codes are
+/// counted so that codes cannot be guessed.
+fn check_code(
+    conn: &PgConnection,
+    credential: &TotpCredential,
+    code: &str,
+    allow_recovery_code: bool,
+) -> AppResult<()> {
+    if let Some(until) = credential.locked_until() {
+        return Err(second_factor_locked(until));
+    }
+
+    if credential.verify(conn, code)? {
+        return Ok(());
+    }
+    if allow_recovery_code && TotpCredential::use_recovery_code(conn, credential.user_id, code)? {
+        credential.clear_failed_attempts(conn)?;
+        return Ok(());
+    }
+
+    credential.record_failed_attempt(conn)?;
+    Err(bad_request("invalid two-factor authentication code"))
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(req: &mut dyn RequestExt) -> AppResult<T> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))
+}
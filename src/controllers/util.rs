@@ -1,18 +1,32 @@
 use chrono::Utc;
 use conduit_cookie::RequestSession;
+use conduit_router::RoutePattern;
 
 use super::prelude::*;
 
 use crate::middleware::log_request;
-use crate::models::{ApiToken, User};
+use crate::models::{
+    ApiToken, CrateScope, EndpointScope, NewApiTokenActivity, NewUserSession, TotpCredential, User,
+    UserSession,
+};
 use crate::util::errors::{
-    account_locked, forbidden, internal, AppError, AppResult, InsecurelyGeneratedTokenRevoked,
+    account_locked, forbidden, internal, missing_crate_scope, missing_token_scope,
+    second_factor_required, AppError, AppResult, ExpiredToken, InsecurelyGeneratedTokenRevoked,
 };
+use crate::util::request_header;
+
+/// The session key recording when the user last confirmed a two-factor authentication code
+const SECOND_FACTOR_SESSION_KEY: &str = "second_factor_at";
+/// How long a confirmed two-factor authentication code allows sensitive actions
+const SECOND_FACTOR_VALIDITY_MINUTES: i64 = 10;
 
 #[derive(Debug)]
 pub struct AuthenticatedUser {
     user: User,
     token_id: Option<i32>,
+    session_id: Option<i64>,
+    endpoint_scopes: Option<Vec<EndpointScope>>,
+    crate_scopes: Option<Vec<CrateScope>>,
 }
 
 impl AuthenticatedUser {
@@ -24,6 +38,11 @@ impl AuthenticatedUser {
         self.token_id
     }
 
+    /// The `user_sessions` row of a cookie authenticated request
+    pub fn session_id(&self) -> Option<i64> {
+        self.session_id
+    }
+
     pub fn user(self) -> User {
         self.user
     }
@@ -39,6 +58,184 @@ impl AuthenticatedUser {
             )
         }
     }
+
+    /// Disallows users that are not site administrators. Admin actions cannot be taken with
+    /// API tokens.
+    pub fn require_admin(self) -> AppResult<Self> {
+        let authenticated_user = self.forbid_api_token_auth()?;
+        if authenticated_user.user.is_admin {
+            Ok(authenticated_user)
+        } else {
+            Err(internal("this action requires admin privileges").chain(forbidden()))
+        }
+    }
+
+    /// Ensures that a scoped API token was granted `scope`. Cookie sessions and tokens
+    /// created without scopes may do anything the user can.
+    pub fn require_scope(self, scope: EndpointScope) -> AppResult<Self> {
+        match &self.endpoint_scopes {
+            Some(scopes) if !scopes.contains(&scope) => {
+                Err(missing_token_scope(Some((&scope).into())))
+            }
+            _ => Ok(self),
+        }
+    }
+
+    /// Ensures that an API token restricted to certain crates may act on `crate_name`
+    pub fn require_crate_scope(&self, crate_name: &str) -> AppResult<()> {
+        match &self.crate_scopes {
+            Some(scopes) if !scopes.iter().any(|scope| scope.matches(crate_name)) => {
+                Err(missing_crate_scope(crate_name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Disallows scoped API tokens for actions that none of the scopes cover
+    pub fn forbid_scoped_api_token_auth(self) -> AppResult<Self> {
+        if self.endpoint_scopes.is_some() || self.crate_scopes.is_some() {
+            Err(missing_token_scope(None))
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// Requires users with two-factor authentication enabled to have confirmed a code in their
+    /// current session within the last few minutes.
+    ///
+    /// API tokens are not affected, since they are created in a confirmed session.
+    pub fn require_recent_second_factor(
+        &self,
+        req: &dyn RequestExt,
+        conn: &PgConnection,
+    ) -> AppResult<()> {
+        if self.token_id.is_some() || TotpCredential::find_enabled(conn, self.user.id)?.is_none() {
+            return Ok(());
+        }
+
+        let confirmed_at = req
+            .session()
+            .get(SECOND_FACTOR_SESSION_KEY)
+            .and_then(|value| value.split_once(':'))
+            .filter(|(user_id, _)| *user_id == self.user.id.to_string())
+            .and_then(|(_, timestamp)| timestamp.parse::<i64>().ok());
+
+        match confirmed_at {
+            Some(at) if Utc::now().timestamp() - at <= SECOND_FACTOR_VALIDITY_MINUTES * 60 => {
+                Ok(())
+            }
+            _ => Err(second_factor_required()),
+        }
+    }
+}
+
+/// Records in the session that the user just confirmed a two-factor authentication code.
+pub fn record_second_factor(req: &mut dyn RequestExt, user_id: i32) {
+    let value = format!("{}:{}", user_id, Utc::now().timestamp());
+    req.session_mut()
+        .insert(SECOND_FACTOR_SESSION_KEY.to_string(), value);
+}
+
+/// Logs the user in by recording a new session in the database and in the session cookie.
+pub fn start_session(req: &mut dyn RequestExt, user_id: i32) -> AppResult<()> {
+    let session = {
+        let conn = req.db_conn()?;
+        let ip_address = client_ip_address(req);
+        NewUserSession {
+            user_id,
+            ip_address: Some(&ip_address),
+            user_agent: client_user_agent(req),
+        }
+        .insert(&conn)?
+    };
+
+    let cookie = req.session_mut();
+    cookie.insert("user_id".to_string(), user_id.to_string());
+    cookie.insert("session_id".to_string(), session.id.to_string());
+    cookie.remove(SECOND_FACTOR_SESSION_KEY);
+    Ok(())
+}
+
+/// Revokes the current session, if any, and removes it from the session cookie.
+pub fn end_session(req: &mut dyn RequestExt) -> AppResult<()> {
+    {
+        let conn = req.db_conn()?;
+        if let Some(session) = current_session(req, &conn)? {
+            session.revoke(&conn)?;
+        }
+    }
+
+    let cookie = req.session_mut();
+    cookie.remove("user_id");
+    cookie.remove("session_id");
+    cookie.remove(SECOND_FACTOR_SESSION_KEY);
+    Ok(())
+}
+
+/// Returns the session referenced by the session cookie, unless it has been revoked.
+pub fn current_session(
+    req: &dyn RequestExt,
+    conn: &PgConnection,
+) -> QueryResult<Option<UserSession>> {
+    let cookie = req.session();
+    let user_id = cookie.get("user_id").and_then(|s| s.parse::<i32>().ok());
+    let session_id = cookie.get("session_id").and_then(|s| s.parse::<i64>().ok());
+
+    match (user_id, session_id) {
+        (Some(user_id), Some(session_id)) => UserSession::find(conn, session_id, user_id),
+        _ => Ok(None),
+    }
+}
+
+fn client_ip_address(req: &dyn RequestExt) -> String {
+    match request_header(req, "x-real-ip") {
+        "" => req.remote_addr().ip().to_string(),
+        forwarded_ip => forwarded_ip.to_string(),
+    }
+}
+
+fn client_user_agent(req: &dyn RequestExt) -> Option<&str> {
+    match request_header(req, header::USER_AGENT) {
+        "" => None,
+        user_agent => Some(user_agent),
+    }
+}
+
+/// Records a mutating request in the activity log of the API token it was made with.
+///
+/// This does nothing for requests that were authenticated with a cookie session.
+pub fn record_token_activity(
+    conn: &PgConnection,
+    req: &dyn RequestExt,
+    api_token_id: Option<i32>,
+    crate_name: Option<&str>,
+    version: Option<&str>,
+) -> AppResult<()> {
+    let api_token_id = match api_token_id {
+        Some(api_token_id) => api_token_id,
+        None => return Ok(()),
+    };
+
+    let pattern = req
+        .extensions()
+        .get::<RoutePattern>()
+        .map(|pattern| pattern.pattern())
+        .unwrap_or_else(|| req.path());
+    let endpoint = format!("{} {}", req.method(), pattern);
+
+    let ip_address = client_ip_address(req);
+
+    NewApiTokenActivity {
+        api_token_id,
+        endpoint: &endpoint,
+        crate_name,
+        version,
+        ip_address: Some(&ip_address),
+        user_agent: client_user_agent(req),
+    }
+    .insert(conn)?;
+
+    Ok(())
 }
 
 /// The Origin header (https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Origin)
@@ -74,9 +271,19 @@ fn authenticate_user(req: &dyn RequestExt) -> AppResult<AuthenticatedUser> {
         let user = User::find(&conn, id)
             .map_err(|err| err.chain(internal("user_id from cookie not found in database")))?;
 
+        let session = current_session(req, &conn)?.ok_or_else(|| {
+            internal("session from cookie not found in database, it may have been revoked")
+                .chain(forbidden())
+        })?;
+        // This may fail in read-only mode, which must not prevent the user from browsing
+        let _ = session.touch(&conn);
+
         return Ok(AuthenticatedUser {
             user,
             token_id: None,
+            session_id: Some(session.id),
+            endpoint_scopes: None,
+            crate_scopes: None,
         });
     }
 
@@ -88,7 +295,7 @@ fn authenticate_user(req: &dyn RequestExt) -> AppResult<AuthenticatedUser> {
 
     if let Some(header_value) = maybe_authorization {
         let token = ApiToken::find_by_api_token(&conn, header_value).map_err(|e| {
-            if e.is::<InsecurelyGeneratedTokenRevoked>() {
+            if e.is::<InsecurelyGeneratedTokenRevoked>() || e.is::<ExpiredToken>() {
                 e
             } else {
                 e.chain(internal("invalid token")).chain(forbidden())
@@ -101,6 +308,9 @@ fn authenticate_user(req: &dyn RequestExt) -> AppResult<AuthenticatedUser> {
         return Ok(AuthenticatedUser {
             user,
             token_id: Some(token.id),
+            session_id: None,
+            endpoint_scopes: token.endpoint_scopes,
+            crate_scopes: token.crate_scopes,
         });
     }
 
@@ -115,18 +325,9 @@ impl<'a> UserAuthenticationExt for dyn RequestExt + 'a {
 
         let authenticated_user = authenticate_user(self)?;
 
-        if let Some(reason) = &authenticated_user.user.account_lock_reason {
-            let still_locked = if let Some(until) = authenticated_user.user.account_lock_until {
-                until > Utc::now().naive_utc()
-            } else {
-                true
-            };
-            if still_locked {
-                return Err(account_locked(
-                    reason,
-                    authenticated_user.user.account_lock_until,
-                ));
-            }
+        let user = &authenticated_user.user;
+        if let (true, Some(reason)) = (user.is_locked(), &user.account_lock_reason) {
+            return Err(account_locked(reason, user.account_lock_until));
         }
 
         log_request::add_custom_metadata(self, "uid", authenticated_user.user_id());
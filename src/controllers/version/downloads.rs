@@ -10,6 +10,10 @@ use crate::schema::*;
 use crate::views::EncodableVersionDownload;
 use chrono::{Duration, NaiveDate, Utc};
 
+/// The response header telling clients that download a crate through a previous name what the
+/// crate is called now.
+const RENAMED_TO_HEADER: &str = "x-crate-renamed-to";
+
 /// Handles the `GET /crates/:crate_id/:version/download` route.
 /// This returns a URL to the location where the crate is stored.
 pub fn download(req: &mut dyn RequestExt) -> EndpointResult {
@@ -19,6 +23,7 @@ pub fn download(req: &mut dyn RequestExt) -> EndpointResult {
     let version = req.params()["version"].as_str();
 
     let mut log_metadata = None;
+    let mut renamed_to = None;
 
     let cache_key = (crate_name.to_string(), version.to_string());
     if let Some(version_id) = app.version_id_cacher.get(&cache_key) {
@@ -48,29 +53,46 @@ pub fn download(req: &mut dyn RequestExt) -> EndpointResult {
             use self::versions::dsl::*;
 
             // Returns the crate name as stored in the database, or an error if we could
-            // not load the version ID from the database.
-            let (version_id, canonical_crate_name) = app
+            // not load the version ID from the database. Versions published before the crate
+            // was renamed are still stored under the name the crate had back then, which is
+            // the first alias created after the version was published.
+            let (version_id, canonical_crate_name, alias) = app
                 .instance_metrics
                 .downloads_select_query_execution_time
                 .observe_closure_duration(|| {
                     versions
                         .inner_join(crates::table)
-                        .select((id, crates::name))
-                        .filter(Crate::with_name(&crate_name))
+                        .left_join(
+                            crate_aliases::table.on(crate_aliases::crate_id
+                                .eq(crates::id)
+                                .and(crate_aliases::created_at.gt(created_at))),
+                        )
+                        .select((id, crates::name, crate_aliases::name.nullable()))
+                        .filter(Crate::with_name_or_alias(&crate_name))
                         .filter(num.eq(version))
-                        .first::<(i32, String)>(&**conn)
+                        .order(crate_aliases::created_at.asc())
+                        .first::<(i32, String, Option<String>)>(&**conn)
                 })?;
 
-            if canonical_crate_name != crate_name {
-                app.instance_metrics
-                    .downloads_non_canonical_crate_name_total
-                    .inc();
-                log_metadata = Some(("bot", "dl"));
-                crate_name = canonical_crate_name;
-            } else {
-                // The version_id is only cached if the provided crate name was canonical.
-                // Non-canonical requests fallback to the "slow" path with a DB query, but
-                // we typically only get a few hundred non-canonical requests in a day anyway.
+            let stored_crate_name = alias.unwrap_or_else(|| canonical_crate_name.clone());
+            if !Crate::names_match(&canonical_crate_name, &crate_name) {
+                renamed_to = Some(canonical_crate_name);
+            }
+
+            if stored_crate_name != crate_name {
+                if Crate::names_match(&stored_crate_name, &crate_name) {
+                    app.instance_metrics
+                        .downloads_non_canonical_crate_name_total
+                        .inc();
+                    log_metadata = Some(("bot", "dl"));
+                } else {
+                    log_metadata = Some(("crate_renamed", "true"));
+                }
+                crate_name = stored_crate_name;
+            } else if renamed_to.is_none() {
+                // The version_id is only cached if the provided crate name was canonical and is
+                // the current name of the crate. Other requests fallback to the "slow" path with a
+                // DB query, but we typically only get a few hundred of them in a day anyway.
                 app.version_id_cacher.insert(cache_key, version_id);
             }
 
@@ -111,11 +133,23 @@ pub fn download(req: &mut dyn RequestExt) -> EndpointResult {
         req.log_metadata(key, value);
     }
 
-    if req.wants_json() {
-        Ok(req.json(&json!({ "url": redirect_url })))
+    let mut response = if req.wants_json() {
+        req.json(&json!({ "url": redirect_url, "renamed_to": renamed_to }))
     } else {
-        Ok(req.redirect(redirect_url))
+        req.redirect(redirect_url)
+    };
+
+    // Hints clients that still use a previous name of the crate to switch to the new one
+    if let Some(renamed_to) = renamed_to {
+        response.headers_mut().insert(
+            RENAMED_TO_HEADER,
+            renamed_to
+                .try_into()
+                .expect("crate names only contain ASCII characters"),
+        );
     }
+
+    Ok(response)
 }
 
 /// Handles the `GET /crates/:crate_id/:version/downloads` route.
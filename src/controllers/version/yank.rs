@@ -4,8 +4,9 @@ use swirl::Job;
 
 use super::{extract_crate_name_and_semver, version_and_crate};
 use crate::controllers::cargo_prelude::*;
+use crate::controllers::util::record_token_activity;
 use crate::models::Rights;
-use crate::models::{insert_version_owner_action, VersionAction};
+use crate::models::{insert_version_owner_action, Crate, EndpointScope, VersionAction};
 use crate::schema::versions;
 use crate::worker;
 
@@ -31,16 +32,18 @@ pub fn unyank(req: &mut dyn RequestExt) -> EndpointResult {
 fn modify_yank(req: &mut dyn RequestExt, yanked: bool) -> EndpointResult {
     // FIXME: Should reject bad requests before authentication, but can't due to
     // lifetime issues with `req`.
-    let authenticated_user = req.authenticate()?;
+    let authenticated_user = req.authenticate()?.require_scope(EndpointScope::Yank)?;
     let (crate_name, semver) = extract_crate_name_and_semver(req)?;
-
     let conn = req.db_conn()?;
     let (version, krate) = version_and_crate(&conn, crate_name, semver)?;
+    // Scopes are matched against the canonical name, so that an alias left behind by a rename
+    // cannot widen the reach of a scoped token
+    authenticated_user.require_crate_scope(&krate.name)?;
     let api_token_id = authenticated_user.api_token_id();
     let user = authenticated_user.user();
-    let owners = krate.owners(&conn)?;
+    let owners = krate.owners_with_roles(&conn)?;
 
-    if user.rights(req.app(), &owners)? < Rights::Publish {
+    if user.rights(req.app(), &conn, &owners)? < Rights::Publish {
         return Err(cargo_err("must already be an owner to yank or unyank"));
     }
 
@@ -59,9 +62,13 @@ fn modify_yank(req: &mut dyn RequestExt, yanked: bool) -> EndpointResult {
         VersionAction::Unyank
     };
 
-    insert_version_owner_action(&conn, version.id, user.id, api_token_id, action)?;
+    let action = insert_version_owner_action(&conn, version.id, user.id, api_token_id, action)?;
+    record_token_activity(&conn, req, api_token_id, Some(crate_name), Some(semver))?;
 
-    worker::sync_yanked(krate.name, version.num).enqueue(&conn)?;
+    // Versions published before a rename live in the index file of the old name
+    let index_name = Crate::name_at(&conn, krate.id, version.created_at)?.unwrap_or(krate.name);
+    worker::sync_yanked(index_name, version.num).enqueue(&conn)?;
+    worker::notify_owners_of_version_action(action.id).enqueue(&conn)?;
 
     ok_true()
 }
@@ -1,21 +1,66 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Mutex;
 
+use crate::models::EmailSend;
+use crate::schema::{crate_owner_invitations, emails};
 use crate::util::errors::{server_error, AppResult};
+use crate::worker;
 
+use lettre::message::{Mailbox, MultiPart, SinglePart};
 use lettre::transport::file::FileTransport;
 use lettre::transport::smtp::authentication::{Credentials, Mechanism};
 use lettre::transport::smtp::SmtpTransport;
 use lettre::{Message, Transport};
+use swirl::Job;
+
+pub use self::templates::RenderedEmail;
+use self::templates::Templates;
+
+mod templates;
+
+/// A secret link that is part of an email, like the one confirming an email address. Only the
+/// ids needed to look the token up are kept in the job queue.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum EmailToken {
+    /// The confirmation token of the `emails` row with this id
+    Confirmation { email_id: i32 },
+    /// The token of the ownership invitation of `invited_user_id` to `crate_id`
+    OwnerInvitation { invited_user_id: i32, crate_id: i32 },
+}
+
+impl EmailToken {
+    pub(crate) fn load(self, conn: &PgConnection) -> QueryResult<String> {
+        match self {
+            EmailToken::Confirmation { email_id } => emails::table
+                .find(email_id)
+                .select(emails::token)
+                .first(conn),
+            EmailToken::OwnerInvitation {
+                invited_user_id,
+                crate_id,
+            } => crate_owner_invitations::table
+                .find((invited_user_id, crate_id))
+                .select(crate_owner_invitations::token)
+                .first(conn),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Emails {
     backend: EmailBackend,
+    templates: Templates,
 }
 
 impl Emails {
     /// Create a new instance detecting the backend from the environment. This will either connect
     /// to a SMTP server or store the emails on the local filesystem.
+    ///
+    /// The templates in the directories listed in `EMAIL_TEMPLATE_DIRS` (separated like `PATH`)
+    /// take precedence over the builtin ones.
     pub fn from_environment() -> Self {
         let backend = match (
             dotenv::var("MAILGUN_SMTP_LOGIN"),
@@ -32,7 +77,13 @@ impl Emails {
             },
         };
 
-        Self { backend }
+        let template_dirs = dotenv::var("EMAIL_TEMPLATE_DIRS")
+            .map(|dirs| std::env::split_paths(&dirs).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let templates = Templates::load(&template_dirs)
+            .unwrap_or_else(|err| panic!("Could not load the email templates: {err:#}"));
+
+        Self { backend, templates }
     }
 
     /// Create a new test backend that stores all the outgoing emails in memory, allowing for tests
@@ -42,45 +93,201 @@ impl Emails {
             backend: EmailBackend::Memory {
                 mails: Mutex::new(Vec::new()),
             },
+            templates: Templates::builtin(),
         }
     }
 
-    /// Attempts to send a confirmation email.
-    pub fn send_user_confirm(&self, email: &str, user_name: &str, token: &str) -> AppResult<()> {
+    /// Attempts to send a confirmation email for the `emails` row with the id `email_id`.
+    pub fn send_user_confirm(
+        &self,
+        conn: &PgConnection,
+        email: &str,
+        user_name: &str,
+        email_id: i32,
+    ) -> AppResult<()> {
         // Create a URL with token string as path to send to user
         // If user clicks on path, look email/user up in database,
         // make sure tokens match
+        let context = json!({ "user_name": user_name });
+        let token = EmailToken::Confirmation { email_id };
+        self.send_template_with_token(conn, email, "user_confirm", context, Some(token))
+    }
 
-        let subject = "Please confirm your email address";
-        let body = format!(
-            "Hello {}! Welcome to Crates.io. Please click the
-link below to verify your email address. Thank you!\n
-https://{}/confirm/{}",
-            user_name,
-            crate::config::domain_name(),
-            token
-        );
+    /// Attempts to send the ownership invitation of `invited_user_id` to `crate_id`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_owner_invite(
+        &self,
+        conn: &PgConnection,
+        email: &str,
+        user_name: &str,
+        crate_name: &str,
+        invited_user_id: i32,
+        crate_id: i32,
+    ) -> AppResult<()> {
+        let context = json!({ "inviter": user_name, "crate_name": crate_name });
+        let token = EmailToken::OwnerInvitation {
+            invited_user_id,
+            crate_id,
+        };
+        self.send_template_with_token(conn, email, "owner_invite", context, Some(token))
+    }
 
-        self.send(email, subject, &body)
+    /// Attempts to tell an owner of a crate that a version of it was published, yanked or
+    /// unyanked. `token_name` is the API token that was used, if any.
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_version_action_notification(
+        &self,
+        conn: &PgConnection,
+        email: &str,
+        user_name: &str,
+        crate_name: &str,
+        version: &str,
+        action: &str,
+        actor: &str,
+        token_name: Option<&str>,
+        time: NaiveDateTime,
+    ) -> AppResult<()> {
+        let context = json!({
+            "user_name": user_name,
+            "crate_name": crate_name,
+            "version": version,
+            "action": action,
+            "actor": actor,
+            "token_name": token_name,
+            "time": format_time(time),
+        });
+        self.send_template(conn, email, "version_action", context)
     }
 
-    /// Attempts to send an ownership invitation.
-    pub fn send_owner_invite(
+    /// Attempts to remind a user of an ownership invitation that is about to expire.
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_owner_invite_reminder(
+        &self,
+        conn: &PgConnection,
+        email: &str,
+        user_name: &str,
+        inviter: &str,
+        crate_name: &str,
+        invited_user_id: i32,
+        crate_id: i32,
+        expires_at: NaiveDateTime,
+    ) -> AppResult<()> {
+        let context = json!({
+            "user_name": user_name,
+            "inviter": inviter,
+            "crate_name": crate_name,
+            "expires_at": format_time(expires_at),
+        });
+        let token = EmailToken::OwnerInvitation {
+            invited_user_id,
+            crate_id,
+        };
+        self.send_template_with_token(conn, email, "owner_invite_reminder", context, Some(token))
+    }
+
+    /// Attempts to tell a user that an ownership invitation they sent has expired without being
+    /// accepted.
+    pub fn send_owner_invite_expired(
+        &self,
+        conn: &PgConnection,
+        email: &str,
+        user_name: &str,
+        invitee: &str,
+        crate_name: &str,
+    ) -> AppResult<()> {
+        let context = json!({
+            "user_name": user_name,
+            "invitee": invitee,
+            "crate_name": crate_name,
+        });
+        self.send_template(conn, email, "owner_invite_expired", context)
+    }
+
+    /// Attempts to warn a user that one of their API tokens is about to expire.
+    pub fn send_token_expiry_notification(
+        &self,
+        conn: &PgConnection,
+        email: &str,
+        user_name: &str,
+        token_name: &str,
+        expires_at: NaiveDateTime,
+    ) -> AppResult<()> {
+        let context = json!({
+            "user_name": user_name,
+            "token_name": token_name,
+            "expires_at": format_time(expires_at),
+        });
+        self.send_template(conn, email, "token_expiry", context)
+    }
+
+    /// Attempts to notify a user that one of their API tokens was found in a public place and
+    /// has been revoked.
+    pub fn send_token_exposed_notification(
+        &self,
+        conn: &PgConnection,
+        email: &str,
+        user_name: &str,
+        token_name: &str,
+        url: &str,
+    ) -> AppResult<()> {
+        let context = json!({ "user_name": user_name, "token_name": token_name, "url": url });
+        self.send_template(conn, email, "token_exposed", context)
+    }
+
+    /// Attempts to notify a user that their account has been locked by the crates.io team.
+    pub fn send_account_locked(
         &self,
+        conn: &PgConnection,
+        email: &str,
+        user_name: &str,
+        reason: &str,
+        until: Option<NaiveDateTime>,
+    ) -> AppResult<()> {
+        let context = json!({
+            "user_name": user_name,
+            "reason": reason,
+            "until": until.map(format_time),
+        });
+        self.send_template(conn, email, "account_locked", context)
+    }
+
+    /// Attempts to notify an owner of a crate that another user asked to adopt it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_adoption_request(
+        &self,
+        conn: &PgConnection,
         email: &str,
         user_name: &str,
         crate_name: &str,
-        token: &str,
+        requester: &str,
+        reason: &str,
+        review_after: NaiveDateTime,
     ) -> AppResult<()> {
-        let subject = "Crate ownership invitation";
-        let body = format!(
-            "{user_name} has invited you to become an owner of the crate {crate_name}!\n
-Visit https://{domain}/accept-invite/{token} to accept this invitation,
-or go to https://{domain}/me/pending-invites to manage all of your crate ownership invitations.",
-            domain = crate::config::domain_name()
-        );
+        let context = json!({
+            "user_name": user_name,
+            "crate_name": crate_name,
+            "requester": requester,
+            "reason": reason,
+            "review_after": format_time(review_after),
+        });
+        self.send_template(conn, email, "adoption_request", context)
+    }
 
-        self.send(email, subject, &body)
+    /// Attempts to tell a user who asked to adopt a crate what became of their request.
+    pub fn send_adoption_request_resolved(
+        &self,
+        conn: &PgConnection,
+        email: &str,
+        user_name: &str,
+        crate_name: &str,
+        outcome: &str,
+    ) -> AppResult<()> {
+        let context = json!({
+            "user_name": user_name,
+            "crate_name": crate_name,
+            "outcome": outcome,
+        });
+        self.send_template(conn, email, "adoption_request_resolved", context)
     }
 
     /// This is supposed to be used only during tests, to retrieve the messages stored in the
@@ -93,12 +300,78 @@ or go to https://{domain}/me/pending-invites to manage all of your crate ownersh
         }
     }
 
-    fn send(&self, recipient: &str, subject: &str, body: &str) -> AppResult<()> {
+    fn send_template<C: Serialize>(
+        &self,
+        conn: &PgConnection,
+        recipient: &str,
+        name: &str,
+        context: C,
+    ) -> AppResult<()> {
+        self.send_template_with_token(conn, recipient, name, context, None)
+    }
+
+    /// Enqueues a background job delivering the email. Every email is recorded in the
+    /// `email_sends` table, where failed deliveries can be inspected.
+    ///
+    /// The job stores the name of the templates and the context in the `background_jobs` table
+    /// until the email is delivered, and renders the email then. The context must therefore not
+    /// contain any secrets. The `token` of the email is only referenced by the ids needed to look
+    /// it up, and added to the context as `token` when rendering. The email is still rendered
+    /// here once, to report broken templates right away.
+    ///
+    /// The in-memory backend delivers the email right away instead, so that tests can check the
+    /// sent emails without running the background jobs.
+    fn send_template_with_token<C: Serialize>(
+        &self,
+        conn: &PgConnection,
+        recipient: &str,
+        name: &str,
+        context: C,
+        token: Option<EmailToken>,
+    ) -> AppResult<()> {
+        let context = serde_json::to_value(context)?;
+        let mut full_context = context.clone();
+        if let Some(token) = token {
+            full_context["token"] = token.load(conn)?.into();
+        }
+        let rendered = self.render(name, &full_context)?;
+
+        // Retrying would not help with an invalid address
+        let _: Mailbox = recipient.parse()?;
+
+        let email_send = EmailSend::create(conn, recipient, name, &rendered.subject)?;
+        if matches!(self.backend, EmailBackend::Memory { .. }) {
+            self.deliver(recipient, rendered)?;
+            EmailSend::mark_sent(conn, email_send.id)?;
+            return Ok(());
+        }
+
+        worker::send_email(email_send.id, recipient.into(), name.into(), context, token)
+            .enqueue(conn)?;
+        Ok(())
+    }
+
+    /// Renders the email called `name` from its templates.
+    pub(crate) fn render<C: Serialize>(
+        &self,
+        name: &str,
+        context: C,
+    ) -> Result<RenderedEmail, minijinja::Error> {
+        self.templates.render(name, context)
+    }
+
+    /// Delivers the email through the backend. Use one of the `send_*` methods to send emails
+    /// instead, which take care of retrying failed deliveries.
+    pub(crate) fn deliver(&self, recipient: &str, rendered: RenderedEmail) -> AppResult<()> {
         let email = Message::builder()
             .to(recipient.parse()?)
             .from(self.sender_address().parse()?)
-            .subject(subject)
-            .body(body.to_string())?;
+            .subject(&rendered.subject)
+            .multipart(
+                MultiPart::alternative()
+                    .singlepart(SinglePart::plain(rendered.text.clone()))
+                    .singlepart(SinglePart::html(rendered.html.clone())),
+            )?;
 
         match &self.backend {
             EmailBackend::Smtp {
@@ -120,8 +393,9 @@ or go to https://{domain}/me/pending-invites to manage all of your crate ownersh
             }
             EmailBackend::Memory { mails } => mails.lock().unwrap().push(StoredEmail {
                 to: recipient.into(),
-                subject: subject.into(),
-                body: body.into(),
+                subject: rendered.subject,
+                body: rendered.text,
+                html_body: rendered.html,
             }),
         }
 
@@ -174,21 +448,34 @@ impl std::fmt::Debug for EmailBackend {
 pub struct StoredEmail {
     pub to: String,
     pub subject: String,
+    /// The plain text part of the email
     pub body: String,
+    pub html_body: String,
+}
+
+fn format_time(time: NaiveDateTime) -> String {
+    time.format("%Y-%m-%d %H:%M").to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn test_email() -> RenderedEmail {
+        RenderedEmail {
+            subject: "test".into(),
+            text: "test".into(),
+            html: "<p>test</p>".into(),
+        }
+    }
+
     #[test]
     fn sending_to_invalid_email_fails() {
         let emails = Emails::new_in_memory();
 
-        assert_err!(emails.send(
+        assert_err!(emails.deliver(
             "String.Format(\"{0}.{1}@live.com\", FirstName, LastName)",
-            "test",
-            "test",
+            test_email(),
         ));
     }
 
@@ -196,6 +483,6 @@ mod tests {
     fn sending_to_valid_email_succeeds() {
         let emails = Emails::new_in_memory();
 
-        assert_ok!(emails.send("someone@example.com", "test", "test"));
+        assert_ok!(emails.deliver("someone@example.com", test_email()));
     }
 }
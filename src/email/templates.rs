@@ -0,0 +1,318 @@
+//! Templates for the subjects and bodies of the emails sent by crates.io
+//!
+//! Every email has a directory containing a `subject.txt`, a `body.txt` and a `body.html`
+//! template. The bodies extend `layout.txt` and `layout.html`, which hold everything shared by
+//! all emails. Operators of other instances can replace any of these files, for example to
+//! change the branding, by putting a file at the same path in one of their own template
+//! directories.
+
+use anyhow::Context;
+use minijinja::Environment;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+macro_rules! builtin_templates {
+    ($($name:literal),* $(,)?) => {
+        &[$(($name, include_str!(concat!("templates/", $name)))),*]
+    };
+}
+
+const LAYOUTS: &[&str] = &["layout.txt", "layout.html"];
+
+/// The path of every template, along with its default content.
+const BUILTIN_TEMPLATES: &[(&str, &str)] = builtin_templates![
+    "layout.txt",
+    "layout.html",
+    "account_locked/subject.txt",
+    "account_locked/body.txt",
+    "account_locked/body.html",
+    "adoption_request/subject.txt",
+    "adoption_request/body.txt",
+    "adoption_request/body.html",
+    "adoption_request_resolved/subject.txt",
+    "adoption_request_resolved/body.txt",
+    "adoption_request_resolved/body.html",
+    "owner_invite/subject.txt",
+    "owner_invite/body.txt",
+    "owner_invite/body.html",
+    "owner_invite_expired/subject.txt",
+    "owner_invite_expired/body.txt",
+    "owner_invite_expired/body.html",
+    "owner_invite_reminder/subject.txt",
+    "owner_invite_reminder/body.txt",
+    "owner_invite_reminder/body.html",
+    "token_expiry/subject.txt",
+    "token_expiry/body.txt",
+    "token_expiry/body.html",
+    "token_exposed/subject.txt",
+    "token_exposed/body.txt",
+    "token_exposed/body.html",
+    "user_confirm/subject.txt",
+    "user_confirm/body.txt",
+    "user_confirm/body.html",
+    "version_action/subject.txt",
+    "version_action/body.txt",
+    "version_action/body.html",
+];
+
+/// The parts of an email, rendered from its templates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Templates {
+    sources: HashMap<&'static str, String>,
+}
+
+impl Templates {
+    /// The templates shipped with crates.io.
+    pub fn builtin() -> Self {
+        let sources = BUILTIN_TEMPLATES
+            .iter()
+            .map(|(name, source)| (*name, source.to_string()))
+            .collect();
+
+        Self { sources }
+    }
+
+    /// Loads the templates, preferring the files found in `dirs` over the builtin ones. If a
+    /// template exists in several of the directories, the first one wins.
+    ///
+    /// Every template is parsed up front, so that a broken override is reported at startup
+    /// instead of when the email is sent.
+    pub fn load(dirs: &[PathBuf]) -> anyhow::Result<Self> {
+        let mut templates = Self::builtin();
+        for (name, source) in &mut templates.sources {
+            if let Some(path) = dirs.iter().map(|dir| dir.join(name)).find(|p| p.is_file()) {
+                *source = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Could not read {}", path.display()))?;
+            }
+        }
+
+        let mut env = Environment::new();
+        for (name, source) in &templates.sources {
+            env.add_template(name, source)
+                .with_context(|| format!("Invalid email template {name}"))?;
+        }
+
+        Ok(templates)
+    }
+
+    /// Renders the subject and both bodies of the email called `email`. The domain name of
+    /// crates.io is always available to the templates as `domain`.
+    pub fn render<C: Serialize>(
+        &self,
+        email: &str,
+        context: C,
+    ) -> Result<RenderedEmail, minijinja::Error> {
+        #[derive(Serialize)]
+        struct Context<C> {
+            domain: String,
+            #[serde(flatten)]
+            inner: C,
+        }
+
+        let subject = format!("{email}/subject.txt");
+        let text = format!("{email}/body.txt");
+        let html = format!("{email}/body.html");
+
+        let mut env = Environment::new();
+        for name in LAYOUTS.iter().copied().chain([&*subject, &*text, &*html]) {
+            // Unknown emails are reported by `get_template()` below
+            if let Some(source) = self.sources.get(name) {
+                env.add_template(name, source)?;
+            }
+        }
+
+        let context = Context {
+            domain: crate::config::domain_name(),
+            inner: context,
+        };
+        let render = |name: &str| -> Result<String, minijinja::Error> {
+            let rendered = env.get_template(name)?.render(&context)?;
+            Ok(rendered.trim().to_string())
+        };
+
+        Ok(RenderedEmail {
+            subject: render(&subject)?,
+            text: render(&text)?,
+            html: render(&html)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// A context for every email, containing everything its templates use.
+    fn fixtures() -> Vec<(&'static str, Value)> {
+        vec![
+            (
+                "account_locked",
+                json!({ "user_name": "foo", "reason": "spam", "until": "2022-04-12 10:00" }),
+            ),
+            (
+                "adoption_request",
+                json!({
+                    "user_name": "foo",
+                    "crate_name": "abandoned",
+                    "requester": "adopter",
+                    "reason": "the owner has not been seen in years",
+                    "review_after": "2022-05-01 10:00",
+                }),
+            ),
+            (
+                "adoption_request_resolved",
+                json!({ "user_name": "adopter", "crate_name": "abandoned", "outcome": "approved" }),
+            ),
+            (
+                "owner_invite",
+                json!({ "inviter": "foo", "crate_name": "demo", "token": "s3cr3t" }),
+            ),
+            (
+                "owner_invite_expired",
+                json!({ "user_name": "foo", "invitee": "bar", "crate_name": "demo" }),
+            ),
+            (
+                "owner_invite_reminder",
+                json!({
+                    "user_name": "bar",
+                    "inviter": "foo",
+                    "crate_name": "demo",
+                    "token": "s3cr3t",
+                    "expires_at": "2022-05-01 10:00",
+                }),
+            ),
+            (
+                "token_expiry",
+                json!({ "user_name": "foo", "token_name": "ci", "expires_at": "2022-05-01 10:00" }),
+            ),
+            (
+                "token_exposed",
+                json!({ "user_name": "foo", "token_name": "ci", "url": "https://example.com/leak" }),
+            ),
+            (
+                "user_confirm",
+                json!({ "user_name": "foo", "token": "s3cr3t" }),
+            ),
+            (
+                "version_action",
+                json!({
+                    "user_name": "foo",
+                    "crate_name": "demo",
+                    "version": "1.0.0",
+                    "action": "published",
+                    "actor": "bar",
+                    "token_name": "ci",
+                    "time": "2022-04-12 10:00",
+                }),
+            ),
+        ]
+    }
+
+    #[test]
+    fn every_template_renders_with_its_fixture() {
+        let templates = Templates::builtin();
+        let fixtures = fixtures();
+
+        for (name, _) in BUILTIN_TEMPLATES {
+            if LAYOUTS.contains(name) {
+                continue;
+            }
+            let email = name.split('/').next().unwrap();
+            assert!(
+                fixtures.iter().any(|(fixture, _)| *fixture == email),
+                "missing fixture for the {email} email"
+            );
+        }
+
+        let footer = format!("https://{}", crate::config::domain_name());
+        for (email, context) in fixtures {
+            let rendered = templates.render(email, &context).unwrap();
+            assert!(!rendered.subject.is_empty(), "{email} has no subject");
+            assert!(
+                !rendered.subject.contains('\n'),
+                "{email} subject spans lines"
+            );
+            assert!(rendered.text.ends_with(&footer), "{email} lacks the layout");
+            assert!(rendered.html.starts_with("<!DOCTYPE html>"), "{email}");
+            assert!(rendered.html.ends_with("</html>"), "{email}");
+
+            // Nothing in the fixture is left out of the text body
+            for value in context.as_object().unwrap().values() {
+                let value = value.as_str().unwrap();
+                assert!(rendered.text.contains(value), "{email} lacks {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn optional_values_can_be_omitted() {
+        let templates = Templates::builtin();
+
+        let context = json!({ "user_name": "foo", "reason": "spam", "until": null });
+        let rendered = templates.render("account_locked", context).unwrap();
+        assert!(rendered.text.contains("locked indefinitely"));
+
+        let context = json!({ "user_name": "foo", "token_name": "ci", "url": "" });
+        let rendered = templates.render("token_exposed", context).unwrap();
+        assert!(!rendered.text.contains("The token was found at"));
+    }
+
+    #[test]
+    fn html_bodies_are_escaped() {
+        let context = json!({ "user_name": "foo", "reason": "<script>", "until": null });
+        let rendered = Templates::builtin()
+            .render("account_locked", context)
+            .unwrap();
+        assert!(rendered.text.contains("<script>"));
+        assert!(!rendered.html.contains("<script>"));
+        assert!(rendered.html.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn unknown_emails_fail_to_render() {
+        assert_err!(Templates::builtin().render("unknown", json!({})));
+    }
+
+    #[test]
+    fn templates_can_be_overridden() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        std::fs::write(
+            first.path().join("layout.txt"),
+            "{% block body %}{% endblock %}\n-- Example Registry",
+        )
+        .unwrap();
+        std::fs::write(second.path().join("layout.txt"), "ignored").unwrap();
+        std::fs::create_dir(second.path().join("user_confirm")).unwrap();
+        std::fs::write(
+            second.path().join("user_confirm/subject.txt"),
+            "Welcome to Example Registry",
+        )
+        .unwrap();
+
+        let dirs = [first.path().to_path_buf(), second.path().to_path_buf()];
+        let templates = Templates::load(&dirs).unwrap();
+        let context = json!({ "user_name": "foo", "token": "s3cr3t" });
+        let rendered = templates.render("user_confirm", context).unwrap();
+        assert_eq!(rendered.subject, "Welcome to Example Registry");
+        assert!(rendered.text.ends_with("-- Example Registry"));
+        assert!(rendered.html.contains("crates.io"));
+    }
+
+    #[test]
+    fn invalid_overrides_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("layout.html"), "{% block body %}").unwrap();
+
+        assert_err!(Templates::load(&[dir.path().to_path_buf()]));
+    }
+}
@@ -7,10 +7,13 @@ use serde::de::DeserializeOwned;
 
 use std::str;
 
-use crate::util::errors::{cargo_err, internal, not_found, AppError, AppResult};
+use crate::util::errors::{cargo_err, not_found, AppError, AppResult};
 use reqwest::blocking::Client;
 
-#[derive(Debug)]
+/// The URL of the GitHub API.
+pub const BASE_URL: &str = "https://api.github.com";
+
+#[derive(Debug, Clone)]
 pub struct GitHubClient {
     base_url: String,
     client: Option<Client>,
@@ -25,6 +28,11 @@ impl GitHubClient {
         self.request("/user", auth)
     }
 
+    pub fn user_by_login(&self, login: &str, auth: &AccessToken) -> AppResult<GithubUser> {
+        let url = format!("/users/{login}");
+        self.request(&url, auth)
+    }
+
     pub fn org_by_name(&self, org_name: &str, auth: &AccessToken) -> AppResult<GitHubOrganization> {
         let url = format!("/orgs/{org_name}");
         self.request(&url, auth)
@@ -66,7 +74,8 @@ impl GitHubClient {
             .header(header::ACCEPT, "application/vnd.github.v3+json")
             .header(header::AUTHORIZATION, format!("token {}", auth.secret()))
             .header(header::USER_AGENT, "crates.io (https://crates.io)")
-            .send()?
+            .send()
+            .map_err(|e| GitHubUnavailable(e.to_string()))?
             .error_for_status()
             .map_err(|e| handle_error_response(&e))?
             .json()
@@ -102,13 +111,18 @@ fn handle_error_response(error: &reqwest::Error) -> Box<dyn AppError> {
              GitHub org memberships.",
         ),
         Some(Status::NOT_FOUND) => not_found(),
-        _ => internal(&format_args!(
-            "didn't get a 200 result from github: {}",
-            error
-        )),
+        _ => Box::new(GitHubUnavailable(format!(
+            "didn't get a 200 result from github: {error}"
+        ))),
     }
 }
 
+/// GitHub could not be reached or failed to answer a request, as opposed to answering it with
+/// an error.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct GitHubUnavailable(String);
+
 #[derive(Debug, Deserialize)]
 pub struct GithubUser {
     pub avatar_url: Option<String>,
@@ -134,6 +148,34 @@ pub struct GitHubTeam {
 #[derive(Debug, Deserialize)]
 pub struct GitHubTeamMembership {
     pub state: String,
+    /// Either `member` or `maintainer`
+    pub role: String,
+}
+
+impl GitHubTeamMembership {
+    pub fn is_active(&self) -> bool {
+        self.state == "active"
+    }
+
+    pub fn is_maintainer(&self) -> bool {
+        self.is_active() && self.role == "maintainer"
+    }
+}
+
+/// A key GitHub signs its secret scanning alerts with.
+///
+/// The list of currently valid keys is published at
+/// `https://api.github.com/meta/public_keys/secret_scanning`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitHubPublicKey {
+    pub key_identifier: String,
+    /// The PEM encoded ECDSA public key
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitHubPublicKeyList {
+    pub public_keys: Vec<GitHubPublicKey>,
 }
 
 pub fn team_url(login: &str) -> String {
@@ -42,8 +42,10 @@ mod downloads_counter;
 pub mod email;
 pub mod git;
 pub mod github;
+pub mod login;
 pub mod metrics;
 pub mod middleware;
+pub mod oidc;
 mod publish_rate_limit;
 pub mod schema;
 pub mod sql;
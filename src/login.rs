@@ -0,0 +1,114 @@
+//! This module implements the OAuth providers that users can log in with.
+//!
+//! GitHub is always available. GitLab and a generic OpenID Connect provider can be enabled
+//! through the configuration, see `config::login_providers`.
+
+use oauth2::{AuthorizationCode, CsrfToken};
+use reqwest::blocking::Client;
+use url::Url;
+
+use crate::config;
+use crate::util::errors::AppResult;
+
+pub use self::github::GitHubLoginProvider;
+pub use self::gitlab::GitLabLoginProvider;
+pub use self::openid::OpenIdLoginProvider;
+
+mod github;
+mod gitlab;
+mod openid;
+
+/// The account information a provider returns after a successful login.
+#[derive(Debug)]
+pub struct ProviderUser {
+    /// The stable identifier of the account at the provider
+    pub id: String,
+    pub login: String,
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+    /// An email address the provider has verified
+    pub email: Option<String>,
+    /// Only set by the GitHub provider
+    pub github: Option<GitHubIdentity>,
+}
+
+/// GitHub accounts also fill the `gh_*` columns of the `users` table, which are needed to check
+/// the membership of team owners.
+#[derive(Debug)]
+pub struct GitHubIdentity {
+    pub id: i32,
+    pub access_token: String,
+}
+
+pub trait LoginProvider: Send + Sync {
+    /// The identifier of the provider in URLs and the `linked_identities` table, e.g. `github`
+    fn name(&self) -> &str;
+
+    /// The name of the provider shown to users, e.g. `GitHub`
+    fn display_name(&self) -> &str;
+
+    /// Returns the URL the user has to visit to log in, and the state the provider will send
+    /// back to us when redirecting the user.
+    fn authorize_url(&self) -> (Url, CsrfToken);
+
+    /// Exchanges the temporary code the provider redirected the user with for their account
+    /// information.
+    fn exchange_code(&self, code: AuthorizationCode) -> AppResult<ProviderUser>;
+}
+
+pub struct LoginProviders {
+    providers: Vec<Box<dyn LoginProvider>>,
+}
+
+impl LoginProviders {
+    /// Creates the providers enabled in the configuration.
+    pub fn from_config(config: &config::Server, client: Option<Client>) -> Self {
+        let mut providers = Self { providers: vec![] };
+
+        providers.add(Box::new(GitHubLoginProvider::new(config, client.clone())));
+        if let Some(gitlab) = &config.gitlab_login {
+            providers.add(Box::new(GitLabLoginProvider::new(
+                gitlab,
+                &config.domain_name,
+                client.clone(),
+            )));
+        }
+        if let Some(openid) = &config.openid_login {
+            providers.add(Box::new(OpenIdLoginProvider::new(
+                openid,
+                &config.domain_name,
+                client,
+            )));
+        }
+
+        providers
+    }
+
+    /// Adds a provider, replacing any existing provider with the same name.
+    pub fn add(&mut self, provider: Box<dyn LoginProvider>) {
+        self.providers.retain(|p| p.name() != provider.name());
+        self.providers.push(provider);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn LoginProvider> {
+        self.providers
+            .iter()
+            .find(|provider| provider.name() == name)
+            .map(Box::as_ref)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn LoginProvider> {
+        self.providers.iter().map(Box::as_ref)
+    }
+}
+
+/// The URL of the frontend route that providers redirect users back to after logging in.
+fn redirect_url(domain_name: &str, provider: &str) -> String {
+    format!("https://{domain_name}/authorize/{provider}")
+}
+
+fn http_client(client: &Option<Client>) -> &Client {
+    client
+        .as_ref()
+        .expect("No HTTP client is configured.  In tests, use `TestApp::with_proxy()`.")
+}
@@ -0,0 +1,75 @@
+use oauth2::basic::BasicClient;
+use oauth2::reqwest::http_client;
+use oauth2::{
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, Scope, TokenResponse, TokenUrl,
+};
+use reqwest::blocking::Client;
+use url::Url;
+
+use super::{GitHubIdentity, LoginProvider, ProviderUser};
+use crate::config;
+use crate::github::GitHubClient;
+use crate::util::errors::{server_error, AppError, AppResult};
+
+pub struct GitHubLoginProvider {
+    oauth: BasicClient,
+    github: GitHubClient,
+}
+
+impl GitHubLoginProvider {
+    pub fn new(config: &config::Server, client: Option<Client>) -> Self {
+        let oauth = BasicClient::new(
+            ClientId::new(config.gh_client_id.clone()),
+            Some(ClientSecret::new(config.gh_client_secret.clone())),
+            AuthUrl::new(String::from("https://github.com/login/oauth/authorize")).unwrap(),
+            Some(
+                TokenUrl::new(String::from("https://github.com/login/oauth/access_token")).unwrap(),
+            ),
+        );
+
+        Self {
+            oauth,
+            github: GitHubClient::new(client, config.gh_base_url.clone()),
+        }
+    }
+}
+
+impl LoginProvider for GitHubLoginProvider {
+    fn name(&self) -> &str {
+        "github"
+    }
+
+    fn display_name(&self) -> &str {
+        "GitHub"
+    }
+
+    fn authorize_url(&self) -> (Url, CsrfToken) {
+        self.oauth
+            .authorize_url(CsrfToken::new_random)
+            .add_scope(Scope::new("read:org".to_string()))
+            .url()
+    }
+
+    fn exchange_code(&self, code: AuthorizationCode) -> AppResult<ProviderUser> {
+        let token = self
+            .oauth
+            .exchange_code(code)
+            .request(http_client)
+            .map_err(|err| err.chain(server_error("Error obtaining token")))?;
+        let token = token.access_token();
+
+        let user = self.github.current_user(token)?;
+
+        Ok(ProviderUser {
+            id: user.id.to_string(),
+            login: user.login,
+            name: user.name,
+            avatar: user.avatar_url,
+            email: user.email,
+            github: Some(GitHubIdentity {
+                id: user.id,
+                access_token: token.secret().clone(),
+            }),
+        })
+    }
+}
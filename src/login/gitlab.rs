@@ -0,0 +1,96 @@
+use oauth2::basic::BasicClient;
+use oauth2::reqwest::http_client;
+use oauth2::{
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope,
+    TokenResponse, TokenUrl,
+};
+use reqwest::blocking::Client;
+use reqwest::header;
+use url::Url;
+
+use super::{LoginProvider, ProviderUser};
+use crate::config::GitLabLoginConfig;
+use crate::util::errors::{server_error, AppError, AppResult};
+
+pub struct GitLabLoginProvider {
+    base_url: String,
+    oauth: BasicClient,
+    client: Option<Client>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabUser {
+    id: i64,
+    username: String,
+    name: Option<String>,
+    avatar_url: Option<String>,
+    /// Only the primary email address is returned, which GitLab requires to be confirmed
+    email: Option<String>,
+}
+
+impl GitLabLoginProvider {
+    pub fn new(config: &GitLabLoginConfig, domain_name: &str, client: Option<Client>) -> Self {
+        let base_url = config.base_url.trim_end_matches('/').to_string();
+
+        let oauth = BasicClient::new(
+            ClientId::new(config.client_id.clone()),
+            Some(ClientSecret::new(config.client_secret.clone())),
+            AuthUrl::new(format!("{base_url}/oauth/authorize")).expect("invalid GITLAB_BASE_URL"),
+            Some(
+                TokenUrl::new(format!("{base_url}/oauth/token")).expect("invalid GITLAB_BASE_URL"),
+            ),
+        )
+        .set_redirect_uri(RedirectUrl::new(super::redirect_url(domain_name, "gitlab")).unwrap());
+
+        Self {
+            base_url,
+            oauth,
+            client,
+        }
+    }
+}
+
+impl LoginProvider for GitLabLoginProvider {
+    fn name(&self) -> &str {
+        "gitlab"
+    }
+
+    fn display_name(&self) -> &str {
+        "GitLab"
+    }
+
+    fn authorize_url(&self) -> (Url, CsrfToken) {
+        self.oauth
+            .authorize_url(CsrfToken::new_random)
+            .add_scope(Scope::new("read_user".to_string()))
+            .url()
+    }
+
+    fn exchange_code(&self, code: AuthorizationCode) -> AppResult<ProviderUser> {
+        let token = self
+            .oauth
+            .exchange_code(code)
+            .request(http_client)
+            .map_err(|err| err.chain(server_error("Error obtaining token")))?;
+
+        let url = format!("{}/api/v4/user", self.base_url);
+        info!("GITLAB HTTP: {url}");
+
+        let user: GitLabUser = super::http_client(&self.client)
+            .get(&url)
+            .bearer_auth(token.access_token().secret())
+            .header(header::USER_AGENT, "crates.io (https://crates.io)")
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        Ok(ProviderUser {
+            id: user.id.to_string(),
+            login: user.username,
+            name: user.name,
+            avatar: user.avatar_url,
+            email: user.email,
+            github: None,
+        })
+    }
+}
@@ -0,0 +1,108 @@
+use oauth2::basic::BasicClient;
+use oauth2::reqwest::http_client;
+use oauth2::{
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope,
+    TokenResponse, TokenUrl,
+};
+use reqwest::blocking::Client;
+use reqwest::header;
+use url::Url;
+
+use super::{LoginProvider, ProviderUser};
+use crate::config::OpenIdLoginConfig;
+use crate::util::errors::{bad_request, server_error, AppError, AppResult};
+
+/// Logs users in with any OpenID Connect provider, using the claims of its userinfo endpoint.
+pub struct OpenIdLoginProvider {
+    name: String,
+    display_name: String,
+    userinfo_url: String,
+    oauth: BasicClient,
+    client: Option<Client>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserInfo {
+    sub: String,
+    preferred_username: Option<String>,
+    name: Option<String>,
+    picture: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+impl OpenIdLoginProvider {
+    pub fn new(config: &OpenIdLoginConfig, domain_name: &str, client: Option<Client>) -> Self {
+        let redirect_url = super::redirect_url(domain_name, &config.name);
+
+        let oauth = BasicClient::new(
+            ClientId::new(config.client_id.clone()),
+            Some(ClientSecret::new(config.client_secret.clone())),
+            AuthUrl::new(config.authorize_url.clone()).expect("invalid OIDC_LOGIN_AUTHORIZE_URL"),
+            Some(TokenUrl::new(config.token_url.clone()).expect("invalid OIDC_LOGIN_TOKEN_URL")),
+        )
+        .set_redirect_uri(RedirectUrl::new(redirect_url).expect("invalid OIDC_LOGIN_NAME"));
+
+        Self {
+            name: config.name.clone(),
+            display_name: config.display_name.clone(),
+            userinfo_url: config.userinfo_url.clone(),
+            oauth,
+            client,
+        }
+    }
+}
+
+impl LoginProvider for OpenIdLoginProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    fn authorize_url(&self) -> (Url, CsrfToken) {
+        self.oauth
+            .authorize_url(CsrfToken::new_random)
+            .add_scope(Scope::new("openid".to_string()))
+            .add_scope(Scope::new("profile".to_string()))
+            .add_scope(Scope::new("email".to_string()))
+            .url()
+    }
+
+    fn exchange_code(&self, code: AuthorizationCode) -> AppResult<ProviderUser> {
+        let token = self
+            .oauth
+            .exchange_code(code)
+            .request(http_client)
+            .map_err(|err| err.chain(server_error("Error obtaining token")))?;
+
+        info!("OIDC HTTP: {}", self.userinfo_url);
+
+        let user: UserInfo = super::http_client(&self.client)
+            .get(&self.userinfo_url)
+            .bearer_auth(token.access_token().secret())
+            .header(header::USER_AGENT, "crates.io (https://crates.io)")
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        let login = user.preferred_username.ok_or_else(|| {
+            bad_request(&format_args!(
+                "{} did not provide a username for your account",
+                self.display_name
+            ))
+        })?;
+
+        Ok(ProviderUser {
+            id: user.sub,
+            login,
+            name: user.name,
+            avatar: user.picture,
+            email: user.email.filter(|_| user.email_verified),
+            github: None,
+        })
+    }
+}
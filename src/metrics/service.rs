@@ -10,9 +10,11 @@
 //! As a rule of thumb, if the metric is not straight up fetched from the database it's probably an
 //! instance-level metric, and you should add it to `src/metrics/instance.rs`.
 
-use crate::schema::{background_jobs, crates, versions};
+use crate::models::EmailStatus;
+use crate::schema::{background_jobs, crates, email_sends, versions};
 use crate::util::errors::AppResult;
-use diesel::{dsl::count_star, prelude::*, PgConnection};
+use diesel::dsl::{count_star, now, IntervalDsl};
+use diesel::{prelude::*, PgConnection};
 use prometheus::{proto::MetricFamily, IntGauge};
 
 metrics! {
@@ -23,6 +25,10 @@ metrics! {
         versions_total: IntGauge,
         /// Number of queued up background jobs
         background_jobs: IntGauge,
+        /// Number of emails waiting for a retry after a failed delivery attempt
+        emails_retrying: IntGauge,
+        /// Number of emails that could not be delivered in the last 24 hours
+        emails_failed: IntGauge,
     }
 
     // All service metrics will be prefixed with this namespace.
@@ -37,6 +43,20 @@ impl ServiceMetrics {
             .set(versions::table.select(count_star()).first(conn)?);
         self.background_jobs
             .set(background_jobs::table.select(count_star()).first(conn)?);
+        self.emails_retrying.set(
+            email_sends::table
+                .filter(email_sends::status.eq(EmailStatus::Pending))
+                .filter(email_sends::attempts.gt(0))
+                .select(count_star())
+                .first(conn)?,
+        );
+        self.emails_failed.set(
+            email_sends::table
+                .filter(email_sends::status.eq(EmailStatus::Failed))
+                .filter(email_sends::created_at.gt(now - 1.day()))
+                .select(count_star())
+                .first(conn)?,
+        );
 
         Ok(self.registry.gather())
     }
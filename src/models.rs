@@ -1,35 +1,58 @@
 pub use self::action::{insert_version_owner_action, VersionAction, VersionOwnerAction};
+pub use self::admin_action::{AdminAction, NewAdminAction};
 pub use self::badge::{Badge, CrateBadge, MaintenanceStatus};
 pub use self::category::{Category, CrateCategory, NewCategory};
-pub use self::crate_owner_invitation::{CrateOwnerInvitation, NewCrateOwnerInvitationOutcome};
+pub use self::crate_adoption_request::{
+    AdoptionStatus, CrateAdoptionRequest, MAX_ADOPTION_REQUESTS_PER_DAY,
+};
+pub use self::crate_owner_action::{CrateAction, CrateOwnerAction, NewCrateOwnerAction};
+pub use self::crate_owner_invitation::{
+    CrateOwnerInvitation, CrateOwnerSignupInvitation, CrateOwnerTeamInvitation,
+    NewCrateOwnerInvitationOutcome,
+};
 pub use self::dependency::{Dependency, DependencyKind, ReverseDependency};
 pub use self::download::VersionDownload;
 pub use self::email::{Email, NewEmail};
+pub use self::email_send::{EmailSend, EmailStatus, MAX_EMAIL_ATTEMPTS};
 pub use self::follow::Follow;
 pub use self::keyword::{CrateKeyword, Keyword};
 pub use self::krate::{Crate, CrateVersions, NewCrate, RecentCrateDownloads};
-pub use self::owner::{CrateOwner, Owner, OwnerKind};
+pub use self::linked_identity::{LinkedIdentity, NewLinkedIdentity};
+pub use self::owner::{CrateOwner, Owner, OwnerKind, OwnerRole};
 pub use self::rights::Rights;
-pub use self::team::{NewTeam, Team};
-pub use self::token::{ApiToken, CreatedApiToken};
+pub use self::team::{NewTeam, Team, TeamMembership};
+pub use self::token::{
+    ApiToken, ApiTokenActivity, CrateScope, CreatedApiToken, EndpointScope, NewApiTokenActivity,
+};
+pub use self::totp::{TotpCredential, MAX_FAILED_ATTEMPTS};
+pub use self::trusted_publisher::{NewTrustedPublisher, TrustedPublisher};
 pub use self::user::{NewUser, User};
+pub use self::user_session::{NewUserSession, UserSession};
 pub use self::version::{NewVersion, TopVersions, Version};
 
 pub mod helpers;
 
 mod action;
+mod admin_action;
 mod badge;
 pub mod category;
+mod crate_adoption_request;
+mod crate_owner_action;
 mod crate_owner_invitation;
 pub mod dependency;
 mod download;
 mod email;
+mod email_send;
 mod follow;
 mod keyword;
 pub mod krate;
+mod linked_identity;
 mod owner;
 mod rights;
 mod team;
 mod token;
+mod totp;
+mod trusted_publisher;
 pub mod user;
+mod user_session;
 mod version;
@@ -0,0 +1,37 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::User;
+use crate::schema::admin_actions;
+use crate::util::rfc3339;
+
+/// An entry of the audit log of actions taken through the admin API.
+#[derive(Clone, Debug, PartialEq, Identifiable, Queryable, Associations, Serialize)]
+#[belongs_to(User, foreign_key = "admin_id")]
+pub struct AdminAction {
+    pub id: i32,
+    pub admin_id: i32,
+    pub action: String,
+    /// The user, crate or version the action was taken on
+    pub target: String,
+    pub details: serde_json::Value,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "admin_actions"]
+pub struct NewAdminAction<'a> {
+    pub admin_id: i32,
+    pub action: &'a str,
+    pub target: &'a str,
+    pub details: serde_json::Value,
+}
+
+impl NewAdminAction<'_> {
+    pub fn insert(&self, conn: &PgConnection) -> QueryResult<AdminAction> {
+        diesel::insert_into(admin_actions::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
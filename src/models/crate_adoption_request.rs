@@ -0,0 +1,212 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Integer,
+};
+use std::io::Write;
+
+use crate::config;
+use crate::models::{
+    Crate, CrateAction, CrateOwner, NewCrateOwnerAction, Owner, OwnerKind, OwnerRole, User,
+};
+use crate::schema::{crate_adoption_requests, crate_owners};
+use crate::util::errors::{bad_request, AppResult};
+
+/// How many crates a user can ask to adopt per day.
+pub const MAX_ADOPTION_REQUESTS_PER_DAY: i64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[repr(i32)]
+#[sql_type = "Integer"]
+pub enum AdoptionStatus {
+    /// Waiting for objections of the owners, or for an admin once the waiting period is over
+    Pending = 0,
+    /// An owner of the crate objected to the request
+    Objected = 1,
+    /// The requester withdrew the request
+    Withdrawn = 2,
+    /// An admin approved the request and transferred the crate
+    Approved = 3,
+    /// An admin rejected the request
+    Rejected = 4,
+}
+
+impl From<AdoptionStatus> for &'static str {
+    fn from(status: AdoptionStatus) -> Self {
+        match status {
+            AdoptionStatus::Pending => "pending",
+            AdoptionStatus::Objected => "objected",
+            AdoptionStatus::Withdrawn => "withdrawn",
+            AdoptionStatus::Approved => "approved",
+            AdoptionStatus::Rejected => "rejected",
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for AdoptionStatus {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(AdoptionStatus::Pending),
+            1 => Ok(AdoptionStatus::Objected),
+            2 => Ok(AdoptionStatus::Withdrawn),
+            3 => Ok(AdoptionStatus::Approved),
+            4 => Ok(AdoptionStatus::Rejected),
+            n => Err(format!("unknown adoption status: {n}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for AdoptionStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
+/// A request of a user to become the owner of a crate whose owners have become unreachable.
+#[derive(Debug, Clone, Queryable, Identifiable, Associations)]
+#[belongs_to(Crate)]
+#[belongs_to(User, foreign_key = "requested_by_user_id")]
+#[table_name = "crate_adoption_requests"]
+pub struct CrateAdoptionRequest {
+    pub id: i32,
+    pub crate_id: i32,
+    pub requested_by_user_id: i32,
+    pub reason: String,
+    pub status: AdoptionStatus,
+    pub created_at: NaiveDateTime,
+    /// The owner, requester or admin who resolved the request
+    pub resolved_by_user_id: Option<i32>,
+    pub resolved_at: Option<NaiveDateTime>,
+}
+
+impl CrateAdoptionRequest {
+    pub fn create(
+        conn: &PgConnection,
+        crate_id: i32,
+        requested_by_user_id: i32,
+        reason: &str,
+    ) -> QueryResult<Self> {
+        diesel::insert_into(crate_adoption_requests::table)
+            .values((
+                crate_adoption_requests::crate_id.eq(crate_id),
+                crate_adoption_requests::requested_by_user_id.eq(requested_by_user_id),
+                crate_adoption_requests::reason.eq(reason),
+            ))
+            .get_result(conn)
+    }
+
+    /// Returns the pending request of the user for the crate, if any.
+    pub fn find_pending(
+        conn: &PgConnection,
+        crate_id: i32,
+        requested_by_user_id: i32,
+    ) -> QueryResult<Option<Self>> {
+        crate_adoption_requests::table
+            .filter(crate_adoption_requests::crate_id.eq(crate_id))
+            .filter(crate_adoption_requests::requested_by_user_id.eq(requested_by_user_id))
+            .filter(crate_adoption_requests::status.eq(AdoptionStatus::Pending))
+            .first(conn)
+            .optional()
+    }
+
+    /// Counts the requests the user made in the last 24 hours, whatever became of them.
+    pub fn count_recent(conn: &PgConnection, requested_by_user_id: i32) -> QueryResult<i64> {
+        let since = Utc::now().naive_utc() - Duration::days(1);
+        crate_adoption_requests::table
+            .filter(crate_adoption_requests::requested_by_user_id.eq(requested_by_user_id))
+            .filter(crate_adoption_requests::created_at.gt(since))
+            .count()
+            .get_result(conn)
+    }
+
+    /// The time after which the request is handed to the admins, unless an owner objects.
+    pub fn review_after(&self, config: &config::Server) -> NaiveDateTime {
+        self.created_at + Duration::days(config.crate_adoption_waiting_period_days as i64)
+    }
+
+    pub fn is_ready_for_review(&self, config: &config::Server) -> bool {
+        self.status == AdoptionStatus::Pending
+            && self.review_after(config) <= Utc::now().naive_utc()
+    }
+
+    /// Closes the pending request on behalf of `user_id`. Returns whether the request was still
+    /// pending.
+    pub fn resolve(
+        &self,
+        conn: &PgConnection,
+        status: AdoptionStatus,
+        user_id: i32,
+    ) -> QueryResult<bool> {
+        let updated = diesel::update(
+            crate_adoption_requests::table
+                .find(self.id)
+                .filter(crate_adoption_requests::status.eq(AdoptionStatus::Pending)),
+        )
+        .set((
+            crate_adoption_requests::status.eq(status),
+            crate_adoption_requests::resolved_by_user_id.eq(user_id),
+            crate_adoption_requests::resolved_at.eq(diesel::dsl::now.nullable()),
+        ))
+        .execute(conn)?;
+
+        Ok(updated > 0)
+    }
+
+    /// Makes the requester the only owner of the crate, removing all previous owners. Returns
+    /// the logins of the previous owners.
+    pub fn approve(
+        &self,
+        conn: &PgConnection,
+        krate: &Crate,
+        admin: &User,
+        requester: &User,
+    ) -> AppResult<Vec<String>> {
+        conn.transaction(|| {
+            if !self.resolve(conn, AdoptionStatus::Approved, admin.id)? {
+                return Err(bad_request("the adoption request is not pending anymore"));
+            }
+
+            let previous_owners = krate
+                .owners(conn)?
+                .iter()
+                .map(Owner::login)
+                .map(String::from)
+                .collect::<Vec<_>>();
+
+            diesel::update(crate_owners::table.filter(crate_owners::crate_id.eq(krate.id)))
+                .set(crate_owners::deleted.eq(true))
+                .execute(conn)?;
+            diesel::insert_into(crate_owners::table)
+                .values(&CrateOwner {
+                    crate_id: krate.id,
+                    owner_id: requester.id,
+                    created_by: admin.id,
+                    owner_kind: OwnerKind::User as i32,
+                    email_notifications: true,
+                    role: OwnerRole::Full,
+                })
+                .on_conflict(crate_owners::table.primary_key())
+                .do_update()
+                .set((
+                    crate_owners::deleted.eq(false),
+                    crate_owners::role.eq(OwnerRole::Full),
+                ))
+                .execute(conn)?;
+
+            NewCrateOwnerAction {
+                crate_id: krate.id,
+                user_id: admin.id,
+                api_token_id: None,
+                action: CrateAction::AdoptCrate,
+                subject: Some(&requester.gh_login),
+                details: Some(&previous_owners.join(", ")),
+            }
+            .insert(conn)?;
+
+            Ok(previous_owners)
+        })
+    }
+}
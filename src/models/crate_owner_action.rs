@@ -0,0 +1,134 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Integer,
+};
+use std::io::Write;
+
+use crate::models::{ApiToken, Crate, User};
+use crate::schema::crate_owner_actions;
+
+/// Changes to the ownership and settings of a crate that are recorded in its audit log.
+#[derive(Debug, Clone, Copy, PartialEq, FromSqlRow, AsExpression)]
+#[repr(i32)]
+#[sql_type = "Integer"]
+pub enum CrateAction {
+    /// A user or team was invited, `details` is the role they were offered
+    InviteOwner = 0,
+    /// A team was added without an invitation, `details` is its role
+    AddOwner = 1,
+    RemoveOwner = 2,
+    /// `details` is the new role
+    ChangeOwnerRole = 3,
+    AcceptInvitation = 4,
+    DeclineInvitation = 5,
+    CancelInvitation = 6,
+    /// `subject` is the new owner and `details` the previous one
+    TransferOwnership = 7,
+    /// `details` is the deprecation message and `subject` the successor, if any
+    Deprecate = 8,
+    Undeprecate = 9,
+    /// An admin approved an adoption request, `subject` is the new owner and `details` lists the
+    /// previous ones
+    AdoptCrate = 10,
+    /// `subject` is the repository of the trusted publisher and `details` its workflow
+    AddTrustedPublisher = 11,
+    /// `subject` is the repository of the trusted publisher and `details` its workflow
+    RemoveTrustedPublisher = 12,
+}
+
+impl From<CrateAction> for &'static str {
+    fn from(action: CrateAction) -> Self {
+        match action {
+            CrateAction::InviteOwner => "invite_owner",
+            CrateAction::AddOwner => "add_owner",
+            CrateAction::RemoveOwner => "remove_owner",
+            CrateAction::ChangeOwnerRole => "change_owner_role",
+            CrateAction::AcceptInvitation => "accept_invitation",
+            CrateAction::DeclineInvitation => "decline_invitation",
+            CrateAction::CancelInvitation => "cancel_invitation",
+            CrateAction::TransferOwnership => "transfer_ownership",
+            CrateAction::Deprecate => "deprecate",
+            CrateAction::Undeprecate => "undeprecate",
+            CrateAction::AdoptCrate => "adopt_crate",
+            CrateAction::AddTrustedPublisher => "add_trusted_publisher",
+            CrateAction::RemoveTrustedPublisher => "remove_trusted_publisher",
+        }
+    }
+}
+
+impl From<CrateAction> for String {
+    fn from(action: CrateAction) -> Self {
+        let string: &'static str = action.into();
+
+        string.into()
+    }
+}
+
+impl FromSql<Integer, Pg> for CrateAction {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(CrateAction::InviteOwner),
+            1 => Ok(CrateAction::AddOwner),
+            2 => Ok(CrateAction::RemoveOwner),
+            3 => Ok(CrateAction::ChangeOwnerRole),
+            4 => Ok(CrateAction::AcceptInvitation),
+            5 => Ok(CrateAction::DeclineInvitation),
+            6 => Ok(CrateAction::CancelInvitation),
+            7 => Ok(CrateAction::TransferOwnership),
+            8 => Ok(CrateAction::Deprecate),
+            9 => Ok(CrateAction::Undeprecate),
+            10 => Ok(CrateAction::AdoptCrate),
+            11 => Ok(CrateAction::AddTrustedPublisher),
+            12 => Ok(CrateAction::RemoveTrustedPublisher),
+            n => Err(format!("unknown crate action: {n}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for CrateAction {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable, Associations)]
+#[belongs_to(Crate)]
+#[belongs_to(User, foreign_key = "user_id")]
+#[belongs_to(ApiToken, foreign_key = "api_token_id")]
+#[table_name = "crate_owner_actions"]
+pub struct CrateOwnerAction {
+    pub id: i32,
+    pub crate_id: i32,
+    pub user_id: i32,
+    pub api_token_id: Option<i32>,
+    pub action: CrateAction,
+    /// The login of the user or team, or the repository of the trusted publisher, the action
+    /// concerns
+    pub subject: Option<String>,
+    pub details: Option<String>,
+    pub time: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "crate_owner_actions"]
+pub struct NewCrateOwnerAction<'a> {
+    pub crate_id: i32,
+    pub user_id: i32,
+    pub api_token_id: Option<i32>,
+    pub action: CrateAction,
+    pub subject: Option<&'a str>,
+    pub details: Option<&'a str>,
+}
+
+impl NewCrateOwnerAction<'_> {
+    pub fn insert(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::insert_into(crate_owner_actions::table)
+            .values(self)
+            .execute(conn)?;
+        Ok(())
+    }
+}
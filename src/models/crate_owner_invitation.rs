@@ -2,8 +2,13 @@ use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 
 use crate::config;
-use crate::models::{CrateOwner, OwnerKind};
-use crate::schema::{crate_owner_invitations, crate_owners, crates};
+use crate::github::GithubUser;
+use crate::models::{CrateAction, CrateOwner, NewCrateOwnerAction, OwnerKind, OwnerRole, User};
+use crate::schema::{
+    crate_owner_invitations, crate_owner_signup_invitations, crate_owner_team_invitations,
+    crate_owners, crates, teams, users,
+};
+use crate::sql::lower;
 use crate::util::errors::{AppResult, OwnershipInvitationExpired};
 
 #[derive(Debug)]
@@ -22,6 +27,10 @@ pub struct CrateOwnerInvitation {
     pub created_at: NaiveDateTime,
     pub token: String,
     pub token_created_at: Option<NaiveDateTime>,
+    /// The role the user gets once they accept
+    pub role: OwnerRole,
+    /// When the invited user was reminded that the invitation is about to expire
+    pub reminder_sent_at: Option<NaiveDateTime>,
 }
 
 impl CrateOwnerInvitation {
@@ -29,6 +38,7 @@ impl CrateOwnerInvitation {
         invited_user_id: i32,
         invited_by_user_id: i32,
         crate_id: i32,
+        role: OwnerRole,
         conn: &PgConnection,
         config: &config::Server,
     ) -> AppResult<NewCrateOwnerInvitationOutcome> {
@@ -38,6 +48,7 @@ impl CrateOwnerInvitation {
             invited_user_id: i32,
             invited_by_user_id: i32,
             crate_id: i32,
+            role: OwnerRole,
         }
 
         // Before actually creating the invite, check if an expired invitation already exists
@@ -65,6 +76,7 @@ impl CrateOwnerInvitation {
                 invited_user_id,
                 invited_by_user_id,
                 crate_id,
+                role,
             })
             // The ON CONFLICT DO NOTHING clause results in not creating the invite if another one
             // already exists. This does not cause problems with expired invitation as those are
@@ -93,7 +105,13 @@ impl CrateOwnerInvitation {
             .first::<Self>(&*conn)?)
     }
 
-    pub fn accept(self, conn: &PgConnection, config: &config::Server) -> AppResult<()> {
+    /// Makes the invited user an owner. `api_token_id` is the token they accepted with, if any.
+    pub fn accept(
+        self,
+        conn: &PgConnection,
+        config: &config::Server,
+        api_token_id: Option<i32>,
+    ) -> AppResult<()> {
         if self.is_expired(config) {
             let crate_name = crates::table
                 .find(self.crate_id)
@@ -110,24 +128,233 @@ impl CrateOwnerInvitation {
                     created_by: self.invited_by_user_id,
                     owner_kind: OwnerKind::User as i32,
                     email_notifications: true,
+                    role: self.role,
                 })
                 .on_conflict(crate_owners::table.primary_key())
                 .do_update()
-                .set(crate_owners::deleted.eq(false))
+                .set((
+                    crate_owners::deleted.eq(false),
+                    crate_owners::role.eq(self.role),
+                ))
                 .execute(conn)?;
 
             diesel::delete(&self).execute(conn)?;
 
+            NewCrateOwnerAction {
+                crate_id: self.crate_id,
+                user_id: self.invited_user_id,
+                api_token_id,
+                action: CrateAction::AcceptInvitation,
+                subject: None,
+                details: Some(self.role.into()),
+            }
+            .insert(conn)?;
+
             Ok(())
         })
     }
 
-    pub fn decline(self, conn: &PgConnection) -> AppResult<()> {
+    pub fn decline(self, conn: &PgConnection, api_token_id: Option<i32>) -> AppResult<()> {
         // The check to prevent declining expired invitations is *explicitly* missing. We do not
         // care if an expired invitation is declined, as that just removes the invitation from the
         // database.
 
-        diesel::delete(&self).execute(conn)?;
+        conn.transaction(|| {
+            diesel::delete(&self).execute(conn)?;
+
+            NewCrateOwnerAction {
+                crate_id: self.crate_id,
+                user_id: self.invited_user_id,
+                api_token_id,
+                action: CrateAction::DeclineInvitation,
+                subject: None,
+                details: None,
+            }
+            .insert(conn)?;
+
+            Ok(())
+        })
+    }
+
+    /// Withdraws the invitation on behalf of an owner of the crate.
+    pub fn cancel(
+        self,
+        conn: &PgConnection,
+        user_id: i32,
+        api_token_id: Option<i32>,
+    ) -> AppResult<()> {
+        conn.transaction(|| {
+            diesel::delete(&self).execute(conn)?;
+
+            let invitee: String = users::table
+                .find(self.invited_user_id)
+                .select(users::gh_login)
+                .first(conn)?;
+            NewCrateOwnerAction {
+                crate_id: self.crate_id,
+                user_id,
+                api_token_id,
+                action: CrateAction::CancelInvitation,
+                subject: Some(&invitee),
+                details: None,
+            }
+            .insert(conn)?;
+
+            Ok(())
+        })
+    }
+
+    pub fn is_expired(&self, config: &config::Server) -> bool {
+        self.expires_at(config) <= Utc::now().naive_utc()
+    }
+
+    pub fn expires_at(&self, config: &config::Server) -> NaiveDateTime {
+        let days = chrono::Duration::days(config.ownership_invitations_expiration_days as i64);
+        self.created_at + days
+    }
+}
+
+/// A team that was added as an owner by someone who is not a maintainer of the team. The team
+/// only becomes an owner once one of its maintainers accepts.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable)]
+#[primary_key(team_id, crate_id)]
+pub struct CrateOwnerTeamInvitation {
+    pub team_id: i32,
+    pub crate_id: i32,
+    pub invited_by_user_id: i32,
+    pub role: OwnerRole,
+    pub created_at: NaiveDateTime,
+}
+
+impl CrateOwnerTeamInvitation {
+    /// Invites the team, returning `false` if a pending invitation already exists.
+    pub fn create(
+        team_id: i32,
+        invited_by_user_id: i32,
+        crate_id: i32,
+        role: OwnerRole,
+        conn: &PgConnection,
+        config: &config::Server,
+    ) -> AppResult<bool> {
+        conn.transaction(|| {
+            let existing: Option<Self> = crate_owner_team_invitations::table
+                .find((team_id, crate_id))
+                .for_update()
+                .first(conn)
+                .optional()?;
+
+            match existing {
+                Some(existing) if existing.is_expired(config) => {
+                    diesel::delete(&existing).execute(conn)?;
+                }
+                Some(_) => return Ok(false),
+                None => {}
+            }
+
+            diesel::insert_into(crate_owner_team_invitations::table)
+                .values((
+                    crate_owner_team_invitations::team_id.eq(team_id),
+                    crate_owner_team_invitations::invited_by_user_id.eq(invited_by_user_id),
+                    crate_owner_team_invitations::crate_id.eq(crate_id),
+                    crate_owner_team_invitations::role.eq(role),
+                ))
+                .execute(conn)?;
+            Ok(true)
+        })
+    }
+
+    pub fn find(team_id: i32, crate_id: i32, conn: &PgConnection) -> QueryResult<Option<Self>> {
+        crate_owner_team_invitations::table
+            .find((team_id, crate_id))
+            .first(conn)
+            .optional()
+    }
+
+    /// Makes the team an owner of the crate. Must only be called once a maintainer of the team,
+    /// `user_id`, confirmed the invitation.
+    pub fn accept(
+        self,
+        conn: &PgConnection,
+        config: &config::Server,
+        user_id: i32,
+        api_token_id: Option<i32>,
+    ) -> AppResult<()> {
+        if self.is_expired(config) {
+            let crate_name = crates::table
+                .find(self.crate_id)
+                .select(crates::name)
+                .first(conn)?;
+            return Err(Box::new(OwnershipInvitationExpired { crate_name }));
+        }
+
+        conn.transaction(|| {
+            diesel::insert_into(crate_owners::table)
+                .values(&CrateOwner {
+                    crate_id: self.crate_id,
+                    owner_id: self.team_id,
+                    created_by: self.invited_by_user_id,
+                    owner_kind: OwnerKind::Team as i32,
+                    email_notifications: true,
+                    role: self.role,
+                })
+                .on_conflict(crate_owners::table.primary_key())
+                .do_update()
+                .set((
+                    crate_owners::deleted.eq(false),
+                    crate_owners::role.eq(self.role),
+                ))
+                .execute(conn)?;
+
+            self.remove(conn, user_id, api_token_id, CrateAction::AcceptInvitation)
+        })
+    }
+
+    /// Rejects the invitation on behalf of a maintainer of the team.
+    pub fn decline(
+        self,
+        conn: &PgConnection,
+        user_id: i32,
+        api_token_id: Option<i32>,
+    ) -> AppResult<()> {
+        conn.transaction(|| {
+            self.remove(conn, user_id, api_token_id, CrateAction::DeclineInvitation)
+        })
+    }
+
+    /// Withdraws the invitation on behalf of an owner of the crate.
+    pub fn cancel(
+        self,
+        conn: &PgConnection,
+        user_id: i32,
+        api_token_id: Option<i32>,
+    ) -> AppResult<()> {
+        conn.transaction(|| self.remove(conn, user_id, api_token_id, CrateAction::CancelInvitation))
+    }
+
+    /// Deletes the invitation, recording why in the audit log of the crate.
+    fn remove(
+        &self,
+        conn: &PgConnection,
+        user_id: i32,
+        api_token_id: Option<i32>,
+        action: CrateAction,
+    ) -> AppResult<()> {
+        diesel::delete(self).execute(conn)?;
+
+        let team: String = teams::table
+            .find(self.team_id)
+            .select(teams::login)
+            .first(conn)?;
+        NewCrateOwnerAction {
+            crate_id: self.crate_id,
+            user_id,
+            api_token_id,
+            action,
+            subject: Some(&team),
+            details: (action == CrateAction::AcceptInvitation).then(|| self.role.into()),
+        }
+        .insert(conn)?;
+
         Ok(())
     }
 
@@ -140,3 +367,136 @@ impl CrateOwnerInvitation {
         self.created_at + days
     }
 }
+
+/// An invitation of a GitHub user who has no crates.io account yet. It is turned into a
+/// `CrateOwnerInvitation` the first time the user logs in.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable)]
+#[primary_key(gh_id, crate_id)]
+pub struct CrateOwnerSignupInvitation {
+    pub gh_id: i32,
+    /// The login of the user at the time they were invited
+    pub gh_login: String,
+    pub crate_id: i32,
+    pub invited_by_user_id: i32,
+    pub role: OwnerRole,
+    pub created_at: NaiveDateTime,
+}
+
+impl CrateOwnerSignupInvitation {
+    /// Invites the GitHub user, returning `false` if a pending invitation already exists.
+    pub fn create(
+        github_user: &GithubUser,
+        invited_by_user_id: i32,
+        crate_id: i32,
+        role: OwnerRole,
+        conn: &PgConnection,
+        config: &config::Server,
+    ) -> AppResult<bool> {
+        conn.transaction(|| {
+            let existing: Option<Self> = crate_owner_signup_invitations::table
+                .find((github_user.id, crate_id))
+                .for_update()
+                .first(conn)
+                .optional()?;
+
+            match existing {
+                Some(existing) if existing.is_expired(config) => {
+                    diesel::delete(&existing).execute(conn)?;
+                }
+                Some(_) => return Ok(false),
+                None => {}
+            }
+
+            diesel::insert_into(crate_owner_signup_invitations::table)
+                .values((
+                    crate_owner_signup_invitations::gh_id.eq(github_user.id),
+                    crate_owner_signup_invitations::gh_login.eq(&github_user.login),
+                    crate_owner_signup_invitations::crate_id.eq(crate_id),
+                    crate_owner_signup_invitations::invited_by_user_id.eq(invited_by_user_id),
+                    crate_owner_signup_invitations::role.eq(role),
+                ))
+                .execute(conn)?;
+            Ok(true)
+        })
+    }
+
+    /// Finds the invitation of the GitHub user with the given login, ignoring case.
+    pub fn find_by_login(
+        gh_login: &str,
+        crate_id: i32,
+        conn: &PgConnection,
+    ) -> QueryResult<Option<Self>> {
+        crate_owner_signup_invitations::table
+            .filter(lower(crate_owner_signup_invitations::gh_login).eq(gh_login.to_lowercase()))
+            .filter(crate_owner_signup_invitations::crate_id.eq(crate_id))
+            .first(conn)
+            .optional()
+    }
+
+    /// Turns the invitations of the GitHub account of `user` into regular invitations, which the
+    /// user can then accept or decline. Returns the number of invitations that were created.
+    pub fn convert_for_user(
+        user: &User,
+        conn: &PgConnection,
+        config: &config::Server,
+    ) -> QueryResult<usize> {
+        conn.transaction(|| {
+            let invitations: Vec<Self> = diesel::delete(crate_owner_signup_invitations::table)
+                .filter(crate_owner_signup_invitations::gh_id.eq(user.gh_id))
+                .get_results(conn)?;
+
+            let values = invitations
+                .iter()
+                .filter(|invitation| !invitation.is_expired(config))
+                .map(|invitation| {
+                    (
+                        crate_owner_invitations::invited_user_id.eq(user.id),
+                        crate_owner_invitations::invited_by_user_id
+                            .eq(invitation.invited_by_user_id),
+                        crate_owner_invitations::crate_id.eq(invitation.crate_id),
+                        crate_owner_invitations::role.eq(invitation.role),
+                    )
+                })
+                .collect::<Vec<_>>();
+            if values.is_empty() {
+                return Ok(0);
+            }
+
+            diesel::insert_into(crate_owner_invitations::table)
+                .values(&values)
+                .on_conflict_do_nothing()
+                .execute(conn)
+        })
+    }
+
+    /// Withdraws the invitation on behalf of an owner of the crate.
+    pub fn cancel(
+        self,
+        conn: &PgConnection,
+        user_id: i32,
+        api_token_id: Option<i32>,
+    ) -> AppResult<()> {
+        conn.transaction(|| {
+            diesel::delete(&self).execute(conn)?;
+            NewCrateOwnerAction {
+                crate_id: self.crate_id,
+                user_id,
+                api_token_id,
+                action: CrateAction::CancelInvitation,
+                subject: Some(&self.gh_login),
+                details: None,
+            }
+            .insert(conn)?;
+            Ok(())
+        })
+    }
+
+    pub fn is_expired(&self, config: &config::Server) -> bool {
+        self.expires_at(config) <= Utc::now().naive_utc()
+    }
+
+    pub fn expires_at(&self, config: &config::Server) -> NaiveDateTime {
+        let days = chrono::Duration::days(config.ownership_invitations_expiration_days as i64);
+        self.created_at + days
+    }
+}
@@ -1,4 +1,5 @@
 use chrono::NaiveDateTime;
+use diesel::prelude::*;
 
 use crate::models::User;
 use crate::schema::emails;
@@ -12,6 +13,44 @@ pub struct Email {
     pub verified: bool,
     pub token: String,
     pub token_generated_at: Option<NaiveDateTime>,
+    /// The address notifications are sent to
+    pub is_primary: bool,
+    /// The address recorded as `published_by_email` when the user publishes a crate
+    pub for_publishing: bool,
+}
+
+impl Email {
+    /// Finds an address of the given user.
+    pub fn find(conn: &PgConnection, id: i32, user_id: i32) -> QueryResult<Email> {
+        emails::table
+            .find(id)
+            .filter(emails::user_id.eq(user_id))
+            .first(conn)
+    }
+
+    pub fn make_primary(&self, conn: &PgConnection) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::update(emails::table.filter(emails::user_id.eq(self.user_id)))
+                .set(emails::is_primary.eq(false))
+                .execute(conn)?;
+            diesel::update(self)
+                .set(emails::is_primary.eq(true))
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    pub fn use_for_publishing(&self, conn: &PgConnection) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::update(emails::table.filter(emails::user_id.eq(self.user_id)))
+                .set(emails::for_publishing.eq(false))
+                .execute(conn)?;
+            diesel::update(self)
+                .set(emails::for_publishing.eq(true))
+                .execute(conn)?;
+            Ok(())
+        })
+    }
 }
 
 #[derive(Debug, Insertable, AsChangeset)]
@@ -19,4 +58,5 @@ pub struct Email {
 pub struct NewEmail<'a> {
     pub user_id: i32,
     pub email: &'a str,
+    pub is_primary: bool,
 }
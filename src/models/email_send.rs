@@ -0,0 +1,145 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Integer,
+};
+use std::io::Write;
+
+use crate::schema::email_sends;
+
+/// How often the delivery of an email is attempted before giving up on it.
+pub const MAX_EMAIL_ATTEMPTS: i32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[repr(i32)]
+#[sql_type = "Integer"]
+pub enum EmailStatus {
+    /// Waiting for the first delivery attempt, or for a retry after a failed one
+    Pending = 0,
+    Sent = 1,
+    /// Every delivery attempt failed
+    Failed = 2,
+}
+
+impl From<EmailStatus> for &'static str {
+    fn from(status: EmailStatus) -> Self {
+        match status {
+            EmailStatus::Pending => "pending",
+            EmailStatus::Sent => "sent",
+            EmailStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for EmailStatus {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(EmailStatus::Pending),
+            1 => Ok(EmailStatus::Sent),
+            2 => Ok(EmailStatus::Failed),
+            n => Err(format!("unknown email status: {n}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for EmailStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
+/// The record of an email sent by crates.io. The content of the email is only kept in the
+/// background job delivering it.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+pub struct EmailSend {
+    pub id: i32,
+    pub recipient: String,
+    /// The name of the templates the email was rendered from
+    pub template: String,
+    pub subject: String,
+    pub status: EmailStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub sent_at: Option<NaiveDateTime>,
+}
+
+impl EmailSend {
+    pub fn create(
+        conn: &PgConnection,
+        recipient: &str,
+        template: &str,
+        subject: &str,
+    ) -> QueryResult<Self> {
+        diesel::insert_into(email_sends::table)
+            .values((
+                email_sends::recipient.eq(recipient),
+                email_sends::template.eq(template),
+                email_sends::subject.eq(subject),
+            ))
+            .get_result(conn)
+    }
+
+    /// The most recent emails, newest first, optionally limited to the ones that have not been
+    /// delivered on the first attempt.
+    pub fn recent(conn: &PgConnection, limit: i64, only_failures: bool) -> QueryResult<Vec<Self>> {
+        let mut query = email_sends::table
+            .order(email_sends::id.desc())
+            .limit(limit)
+            .into_boxed();
+        if only_failures {
+            query = query.filter(email_sends::last_error.is_not_null());
+        }
+
+        query.load(conn)
+    }
+
+    pub fn mark_sent(conn: &PgConnection, id: i32) -> QueryResult<()> {
+        diesel::update(email_sends::table.find(id))
+            .set((
+                email_sends::status.eq(EmailStatus::Sent),
+                email_sends::attempts.eq(email_sends::attempts + 1),
+                email_sends::sent_at.eq(diesel::dsl::now.nullable()),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Records a failed delivery attempt. Once `MAX_EMAIL_ATTEMPTS` is reached the email is
+    /// marked as failed. Returns whether the delivery should be retried.
+    pub fn record_failure(conn: &PgConnection, id: i32, error: &str) -> QueryResult<bool> {
+        let email: EmailSend = diesel::update(email_sends::table.find(id))
+            .set((
+                email_sends::attempts.eq(email_sends::attempts + 1),
+                email_sends::last_error.eq(error),
+            ))
+            .get_result(conn)?;
+
+        if email.attempts < MAX_EMAIL_ATTEMPTS {
+            return Ok(true);
+        }
+
+        diesel::update(&email)
+            .set(email_sends::status.eq(EmailStatus::Failed))
+            .execute(conn)?;
+
+        Ok(false)
+    }
+
+    /// Marks the email as failed without retrying it, for errors that retrying cannot fix.
+    pub fn give_up(conn: &PgConnection, id: i32, error: &str) -> QueryResult<()> {
+        diesel::update(email_sends::table.find(id))
+            .set((
+                email_sends::status.eq(EmailStatus::Failed),
+                email_sends::attempts.eq(email_sends::attempts + 1),
+                email_sends::last_error.eq(error),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
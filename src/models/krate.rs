@@ -3,16 +3,18 @@ use diesel::associations::Identifiable;
 use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::sql_types::{Bool, Text};
+use oauth2::AccessToken;
 use url::Url;
 
 use crate::app::App;
 use crate::controllers::helpers::pagination::*;
 use crate::models::version::TopVersions;
 use crate::models::{
-    Badge, CrateOwner, CrateOwnerInvitation, NewCrateOwnerInvitationOutcome, Owner, OwnerKind,
-    ReverseDependency, User, Version,
+    Badge, CrateAction, CrateOwner, CrateOwnerInvitation, CrateOwnerSignupInvitation,
+    CrateOwnerTeamInvitation, NewCrateOwnerAction, NewCrateOwnerInvitationOutcome, Owner,
+    OwnerKind, OwnerRole, ReverseDependency, Team, User, Version,
 };
-use crate::util::errors::{cargo_err, AppResult};
+use crate::util::errors::{cargo_err, AppError, AppResult, NotFound};
 
 use crate::models::helpers::with_count::*;
 use crate::publish_rate_limit::PublishRateLimit;
@@ -41,6 +43,9 @@ pub struct Crate {
     pub documentation: Option<String>,
     pub repository: Option<String>,
     pub max_upload_size: Option<i32>,
+    pub deprecated_at: Option<NaiveDateTime>,
+    pub deprecation_message: Option<String>,
+    pub deprecation_successor: Option<String>,
 }
 
 /// We literally never want to select `textsearchable_index_col`
@@ -56,6 +61,9 @@ type AllColumns = (
     crates::documentation,
     crates::repository,
     crates::max_upload_size,
+    crates::deprecated_at,
+    crates::deprecation_message,
+    crates::deprecation_successor,
 );
 
 pub const ALL_COLUMNS: AllColumns = (
@@ -69,6 +77,9 @@ pub const ALL_COLUMNS: AllColumns = (
     crates::documentation,
     crates::repository,
     crates::max_upload_size,
+    crates::deprecated_at,
+    crates::deprecation_message,
+    crates::deprecation_successor,
 );
 
 pub const MAX_NAME_LENGTH: usize = 64;
@@ -76,7 +87,13 @@ pub const MAX_NAME_LENGTH: usize = 64;
 type CanonCrateName<T> = canon_crate_name::HelperType<T>;
 type All = diesel::dsl::Select<crates::table, AllColumns>;
 type WithName<'a> = diesel::dsl::Eq<CanonCrateName<crates::name>, CanonCrateName<&'a str>>;
-type ByName<'a> = diesel::dsl::Filter<All, WithName<'a>>;
+type AliasedCrateId<'a> = diesel::dsl::Filter<
+    diesel::dsl::Select<crate_aliases::table, crate_aliases::crate_id>,
+    diesel::dsl::Eq<CanonCrateName<crate_aliases::name>, CanonCrateName<&'a str>>,
+>;
+type WithNameOrAlias<'a> =
+    diesel::dsl::Or<WithName<'a>, diesel::dsl::EqAny<crates::id, AliasedCrateId<'a>>>;
+type ByName<'a> = diesel::dsl::Filter<All, WithNameOrAlias<'a>>;
 type ByExactName<'a> = diesel::dsl::Filter<All, diesel::dsl::Eq<crates::name, &'a str>>;
 
 #[derive(Insertable, AsChangeset, Default, Debug)]
@@ -154,15 +171,7 @@ impl<'a> NewCrate<'a> {
     }
 
     fn ensure_name_not_reserved(&self, conn: &PgConnection) -> AppResult<()> {
-        use crate::schema::reserved_crate_names::dsl::*;
-        use diesel::dsl::exists;
-        use diesel::select;
-
-        let reserved_name: bool = select(exists(
-            reserved_crate_names.filter(canon_crate_name(name).eq(canon_crate_name(self.name))),
-        ))
-        .get_result(conn)?;
-        if reserved_name {
+        if is_reserved_name(conn, self.name)? {
             Err(cargo_err("cannot upload a crate with a reserved name"))
         } else {
             Ok(())
@@ -187,6 +196,7 @@ impl<'a> NewCrate<'a> {
                     created_by: user_id,
                     owner_kind: OwnerKind::User as i32,
                     email_notifications: true,
+                    role: OwnerRole::Full,
                 };
                 diesel::insert_into(crate_owners::table)
                     .values(&owner)
@@ -198,6 +208,17 @@ impl<'a> NewCrate<'a> {
     }
 }
 
+fn is_reserved_name(conn: &PgConnection, crate_name: &str) -> QueryResult<bool> {
+    use crate::schema::reserved_crate_names::dsl::*;
+    use diesel::dsl::exists;
+    use diesel::select;
+
+    select(exists(reserved_crate_names.filter(
+        canon_crate_name(name).eq(canon_crate_name(crate_name)),
+    )))
+    .get_result(conn)
+}
+
 impl Crate {
     /// SQL filter based on whether the crate's name loosely matches the given
     /// string.
@@ -226,8 +247,22 @@ impl Crate {
         canon_crate_name(crates::name).eq(canon_crate_name(name))
     }
 
+    /// SQL filter matching the crate's current name, or a name it was
+    /// previously known by before being renamed
+    pub fn with_name_or_alias(name: &str) -> WithNameOrAlias<'_> {
+        let aliased_crate_id = crate_aliases::table
+            .select(crate_aliases::crate_id)
+            .filter(canon_crate_name(crate_aliases::name).eq(canon_crate_name(name)));
+
+        Self::with_name(name).or(crates::id.eq_any(aliased_crate_id))
+    }
+
+    /// Looks up a crate by name, following aliases left behind by renames.
+    ///
+    /// Callers that need to know whether an alias was followed should compare
+    /// the requested name against `Crate::name` with `Crate::is_canonical_name`.
     pub fn by_name(name: &str) -> ByName<'_> {
-        Crate::all().filter(Self::with_name(name))
+        Crate::all().filter(Self::with_name_or_alias(name))
     }
 
     pub fn by_exact_name(name: &str) -> ByExactName<'_> {
@@ -317,86 +352,217 @@ impl Crate {
     }
 
     pub fn owners(&self, conn: &PgConnection) -> QueryResult<Vec<Owner>> {
+        Ok(self
+            .owners_with_roles(conn)?
+            .into_iter()
+            .map(|(owner, _)| owner)
+            .collect())
+    }
+
+    /// Returns the owners of the crate together with the role each of them was granted.
+    pub fn owners_with_roles(&self, conn: &PgConnection) -> QueryResult<Vec<(Owner, OwnerRole)>> {
         let users = CrateOwner::by_owner_kind(OwnerKind::User)
             .filter(crate_owners::crate_id.eq(self.id))
             .inner_join(users::table)
-            .select(users::all_columns)
+            .select((users::all_columns, crate_owners::role))
             .load(conn)?
             .into_iter()
-            .map(Owner::User);
+            .map(|(user, role)| (Owner::User(user), role));
         let teams = CrateOwner::by_owner_kind(OwnerKind::Team)
             .filter(crate_owners::crate_id.eq(self.id))
             .inner_join(teams::table)
-            .select(teams::all_columns)
+            .select((teams::all_columns, crate_owners::role))
             .load(conn)?
             .into_iter()
-            .map(Owner::Team);
+            .map(|(team, role)| (Owner::Team(team), role));
 
         Ok(users.chain(teams).collect())
     }
 
+    /// Adds a user or team as an owner of the crate. Without an explicit role, users get full
+    /// rights and teams may only publish.
     pub fn owner_add(
         &self,
         app: &App,
         conn: &PgConnection,
         req_user: &User,
+        api_token_id: Option<i32>,
         login: &str,
+        role: Option<OwnerRole>,
     ) -> AppResult<String> {
         use diesel::insert_into;
 
-        let owner = Owner::find_or_create_by_login(app, conn, req_user, login)?;
+        let config = &app.config;
+
+        if Owner::is_team_login(login) {
+            let (team, membership) =
+                Team::create_or_update_with_membership(app, conn, login, req_user)?;
+            let role = role.unwrap_or_else(|| OwnerRole::default_for(OwnerKind::Team));
+            let mut action = NewCrateOwnerAction {
+                crate_id: self.id,
+                user_id: req_user.id,
+                api_token_id,
+                action: CrateAction::AddOwner,
+                subject: Some(&team.login),
+                details: Some(role.into()),
+            };
 
-        match owner {
-            // Users are invited and must accept before being added
-            Owner::User(user) => {
-                let config = &app.config;
-                match CrateOwnerInvitation::create(user.id, req_user.id, self.id, conn, config)? {
-                    NewCrateOwnerInvitationOutcome::InviteCreated { plaintext_token } => {
-                        if let Ok(Some(email)) = user.verified_email(conn) {
-                            // Swallow any error. Whether or not the email is sent, the invitation
-                            // entry will be created in the database and the user will see the
-                            // invitation when they visit https://crates.io/me/pending-invites/.
-                            let _ = app.emails.send_owner_invite(
-                                &email,
-                                &req_user.gh_login,
-                                &self.name,
-                                &plaintext_token,
-                            );
-                        }
-
-                        Ok(format!(
-                            "user {} has been invited to be an owner of crate {}",
-                            user.gh_login, self.name
-                        ))
-                    }
-                    NewCrateOwnerInvitationOutcome::AlreadyExists => Ok(format!(
-                        "user {} already has a pending invitation to be an owner of crate {}",
-                        user.gh_login, self.name
-                    )),
-                }
+            // Teams are invited unless a maintainer of the team adds them, in which case they
+            // are added as owners immediately
+            if !membership.is_maintainer() {
+                let created = CrateOwnerTeamInvitation::create(
+                    team.id,
+                    req_user.id,
+                    self.id,
+                    role,
+                    conn,
+                    config,
+                )?;
+                return Ok(if created {
+                    action.action = CrateAction::InviteOwner;
+                    action.insert(conn)?;
+                    format!(
+                        "team {} has been invited to be an owner of crate {}, \
+                         a maintainer of the team needs to accept the invitation",
+                        team.login, self.name
+                    )
+                } else {
+                    format!(
+                        "team {} already has a pending invitation to be an owner of crate {}",
+                        team.login, self.name
+                    )
+                });
             }
-            // Teams are added as owners immediately
-            owner @ Owner::Team(_) => {
-                insert_into(crate_owners::table)
-                    .values(&CrateOwner {
-                        crate_id: self.id,
-                        owner_id: owner.id(),
-                        created_by: req_user.id,
-                        owner_kind: OwnerKind::Team as i32,
-                        email_notifications: true,
-                    })
-                    .on_conflict(crate_owners::table.primary_key())
-                    .do_update()
-                    .set(crate_owners::deleted.eq(false))
-                    .execute(conn)?;
+
+            insert_into(crate_owners::table)
+                .values(&CrateOwner {
+                    crate_id: self.id,
+                    owner_id: team.id,
+                    created_by: req_user.id,
+                    owner_kind: OwnerKind::Team as i32,
+                    email_notifications: true,
+                    role,
+                })
+                .on_conflict(crate_owners::table.primary_key())
+                .do_update()
+                .set((crate_owners::deleted.eq(false), crate_owners::role.eq(role)))
+                .execute(conn)?;
+            diesel::delete(crate_owner_team_invitations::table.find((team.id, self.id)))
+                .execute(conn)?;
+            action.insert(conn)?;
+
+            return Ok(format!(
+                "team {} has been added as an owner of crate {}",
+                team.login, self.name
+            ));
+        }
+
+        // Users are invited and must accept before being added
+        let role = role.unwrap_or_else(|| OwnerRole::default_for(OwnerKind::User));
+        let user = match Owner::find_user_by_login(conn, login) {
+            Ok(user) => user,
+            Err(error) => {
+                return self.invite_github_user(
+                    app,
+                    conn,
+                    req_user,
+                    api_token_id,
+                    login,
+                    role,
+                    error,
+                )
+            }
+        };
+        match CrateOwnerInvitation::create(user.id, req_user.id, self.id, role, conn, config)? {
+            NewCrateOwnerInvitationOutcome::InviteCreated { .. } => {
+                NewCrateOwnerAction {
+                    crate_id: self.id,
+                    user_id: req_user.id,
+                    api_token_id,
+                    action: CrateAction::InviteOwner,
+                    subject: Some(&user.gh_login),
+                    details: Some(role.into()),
+                }
+                .insert(conn)?;
+
+                if let Ok(Some(email)) = user.verified_email(conn) {
+                    // Failed deliveries are retried by the background job. Swallow the
+                    // remaining errors, like an invalid address: the invitation entry will be
+                    // created in the database either way and the user will see the invitation
+                    // when they visit https://crates.io/me/pending-invites/.
+                    let _ = app.emails.send_owner_invite(
+                        conn,
+                        &email,
+                        &req_user.gh_login,
+                        &self.name,
+                        user.id,
+                        self.id,
+                    );
+                }
 
                 Ok(format!(
-                    "team {} has been added as an owner of crate {}",
-                    owner.login(),
-                    self.name
+                    "user {} has been invited to be an owner of crate {}",
+                    user.gh_login, self.name
                 ))
             }
+            NewCrateOwnerInvitationOutcome::AlreadyExists => Ok(format!(
+                "user {} already has a pending invitation to be an owner of crate {}",
+                user.gh_login, self.name
+            )),
+        }
+    }
+
+    /// Invites a GitHub user who has not signed up on crates.io yet. The invitation is shown to
+    /// them once they log in for the first time. `not_found` is returned if GitHub does not know
+    /// the user either.
+    #[allow(clippy::too_many_arguments)]
+    fn invite_github_user(
+        &self,
+        app: &App,
+        conn: &PgConnection,
+        req_user: &User,
+        api_token_id: Option<i32>,
+        login: &str,
+        role: OwnerRole,
+        not_found: Box<dyn AppError>,
+    ) -> AppResult<String> {
+        let token = AccessToken::new(req_user.gh_access_token.clone());
+        let github_user = match app.github.user_by_login(login, &token) {
+            Ok(github_user) => github_user,
+            Err(error) if error.is::<NotFound>() => return Err(not_found),
+            Err(error) => return Err(error),
+        };
+
+        let created = CrateOwnerSignupInvitation::create(
+            &github_user,
+            req_user.id,
+            self.id,
+            role,
+            conn,
+            &app.config,
+        )?;
+        if !created {
+            return Ok(format!(
+                "user {} already has a pending invitation to be an owner of crate {}",
+                github_user.login, self.name
+            ));
+        }
+
+        NewCrateOwnerAction {
+            crate_id: self.id,
+            user_id: req_user.id,
+            api_token_id,
+            action: CrateAction::InviteOwner,
+            subject: Some(&github_user.login),
+            details: Some(role.into()),
         }
+        .insert(conn)?;
+
+        Ok(format!(
+            "user {} has been invited to be an owner of crate {}, \
+             they will see the invitation once they log in to crates.io",
+            github_user.login, self.name
+        ))
     }
 
     pub fn owner_remove(
@@ -404,17 +570,223 @@ impl Crate {
         app: &App,
         conn: &PgConnection,
         req_user: &User,
+        api_token_id: Option<i32>,
         login: &str,
     ) -> AppResult<()> {
         let owner = Owner::find_or_create_by_login(app, conn, req_user, login)?;
 
-        let target = crate_owners::table.find((self.id(), owner.id(), owner.kind() as i32));
-        diesel::update(target)
+        let target = crate_owners::table
+            .find((self.id(), owner.id(), owner.kind() as i32))
+            .filter(crate_owners::deleted.eq(false));
+        let removed = diesel::update(target)
             .set(crate_owners::deleted.eq(true))
             .execute(conn)?;
+        if removed > 0 {
+            NewCrateOwnerAction {
+                crate_id: self.id,
+                user_id: req_user.id,
+                api_token_id,
+                action: CrateAction::RemoveOwner,
+                subject: Some(owner.login()),
+                details: None,
+            }
+            .insert(conn)?;
+        }
         Ok(())
     }
 
+    /// Changes the role of an existing owner of the crate.
+    pub fn owner_set_role(
+        &self,
+        conn: &PgConnection,
+        req_user: &User,
+        api_token_id: Option<i32>,
+        owner: &Owner,
+        role: OwnerRole,
+    ) -> QueryResult<()> {
+        let target = crate_owners::table.find((self.id(), owner.id(), owner.kind()));
+        diesel::update(target)
+            .set(crate_owners::role.eq(role))
+            .execute(conn)?;
+
+        NewCrateOwnerAction {
+            crate_id: self.id,
+            user_id: req_user.id,
+            api_token_id,
+            action: CrateAction::ChangeOwnerRole,
+            subject: Some(owner.login()),
+            details: Some(role.into()),
+        }
+        .insert(conn)
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.deprecated_at.is_some()
+    }
+
+    /// Marks the crate as deprecated, optionally pointing users to the crate that replaces it.
+    ///
+    /// The successor must be an existing crate other than this one, and is stored under its
+    /// canonical name.
+    pub fn deprecate(
+        &self,
+        conn: &PgConnection,
+        message: &str,
+        successor: Option<&str>,
+    ) -> AppResult<Crate> {
+        use diesel::dsl::now;
+
+        let successor = match successor {
+            Some(successor) => {
+                let successor: Crate = Crate::by_name(successor).first(conn).map_err(|_| {
+                    cargo_err(&format_args!(
+                        "successor crate `{}` does not exist",
+                        successor
+                    ))
+                })?;
+                if successor.id == self.id {
+                    return Err(cargo_err("a crate cannot be its own successor"));
+                }
+                Some(successor.name)
+            }
+            None => None,
+        };
+
+        diesel::update(self)
+            .set((
+                crates::deprecated_at.eq(now.nullable()),
+                crates::deprecation_message.eq(message),
+                crates::deprecation_successor.eq(successor),
+            ))
+            .returning(ALL_COLUMNS)
+            .get_result(conn)
+            .map_err(Into::into)
+    }
+
+    /// Removes the deprecation state of the crate.
+    pub fn undeprecate(&self, conn: &PgConnection) -> QueryResult<Crate> {
+        diesel::update(self)
+            .set((
+                crates::deprecated_at.eq(None::<NaiveDateTime>),
+                crates::deprecation_message.eq(None::<String>),
+                crates::deprecation_successor.eq(None::<String>),
+            ))
+            .returning(ALL_COLUMNS)
+            .get_result(conn)
+    }
+
+    /// Returns `false` if `name` only refers to this crate through an alias.
+    pub fn is_canonical_name(&self, name: &str) -> bool {
+        Self::names_match(&self.name, name)
+    }
+
+    /// Whether both names refer to the same crate, ignoring case and treating `-` and `_` as
+    /// equal.
+    pub fn names_match(a: &str, b: &str) -> bool {
+        Self::canonicalize_name(a) == Self::canonicalize_name(b)
+    }
+
+    /// Rust equivalent of the `canon_crate_name` SQL function.
+    pub fn canonicalize_name(name: &str) -> String {
+        name.to_lowercase().replace('-', "_")
+    }
+
+    /// Returns the current name of the crate that `alias` was renamed to, if any.
+    pub fn renamed_to(conn: &PgConnection, alias: &str) -> QueryResult<Option<String>> {
+        crate_aliases::table
+            .inner_join(crates::table)
+            .filter(canon_crate_name(crate_aliases::name).eq(canon_crate_name(alias)))
+            .select(crates::name)
+            .first(conn)
+            .optional()
+    }
+
+    /// Returns the name the crate had at the given point in time, if it has been renamed
+    /// since then.
+    pub fn name_at(
+        conn: &PgConnection,
+        crate_id: i32,
+        at: NaiveDateTime,
+    ) -> QueryResult<Option<String>> {
+        crate_aliases::table
+            .filter(crate_aliases::crate_id.eq(crate_id))
+            .filter(crate_aliases::created_at.gt(at))
+            .order(crate_aliases::created_at)
+            .select(crate_aliases::name)
+            .first(conn)
+            .optional()
+    }
+
+    /// Names this crate was previously known by, oldest first.
+    pub fn aliases(&self, conn: &PgConnection) -> QueryResult<Vec<String>> {
+        crate_aliases::table
+            .filter(crate_aliases::crate_id.eq(self.id))
+            .select(crate_aliases::name)
+            .order(crate_aliases::created_at)
+            .load(conn)
+    }
+
+    /// Renames the crate, keeping the current name as an alias.
+    ///
+    /// The alias keeps resolving through `Crate::by_name` and cannot be claimed by a new
+    /// crate. Nothing is written to the index, so the index file of the old name stays
+    /// exactly as it was and existing lockfiles keep working.
+    pub fn rename(&self, conn: &PgConnection, new_name: &str) -> AppResult<Crate> {
+        if !Crate::valid_name(new_name) {
+            return Err(cargo_err(&format_args!(
+                "`{}` is not a valid crate name",
+                new_name
+            )));
+        }
+        if self.is_canonical_name(new_name) {
+            return Err(cargo_err(&format_args!(
+                "crate `{}` is already named `{}`",
+                self.name, new_name
+            )));
+        }
+
+        conn.transaction(|| {
+            // Renaming a crate back to one of its previous names drops that alias
+            diesel::delete(
+                crate_aliases::table
+                    .filter(crate_aliases::crate_id.eq(self.id))
+                    .filter(canon_crate_name(crate_aliases::name).eq(canon_crate_name(new_name))),
+            )
+            .execute(conn)?;
+
+            let taken = Crate::by_name(new_name)
+                .select(crates::id)
+                .first::<i32>(conn)
+                .optional()?;
+            if taken.is_some() {
+                return Err(cargo_err(&format_args!(
+                    "crate name `{}` is already taken",
+                    new_name
+                )));
+            }
+            if is_reserved_name(conn, new_name)? {
+                return Err(cargo_err(&format_args!(
+                    "crate name `{}` is reserved",
+                    new_name
+                )));
+            }
+
+            let renamed = diesel::update(self)
+                .set(crates::name.eq(new_name))
+                .returning(ALL_COLUMNS)
+                .get_result(conn)?;
+
+            diesel::insert_into(crate_aliases::table)
+                .values((
+                    crate_aliases::name.eq(&self.name),
+                    crate_aliases::crate_id.eq(self.id),
+                ))
+                .execute(conn)?;
+
+            Ok(renamed)
+        })
+    }
+
     pub fn badges(&self, conn: &PgConnection) -> QueryResult<Vec<Badge>> {
         badges::table
             .filter(badges::crate_id.eq(self.id))
@@ -474,6 +846,13 @@ mod tests {
         assert!(!Crate::valid_name("-foo"));
     }
 
+    #[test]
+    fn names_match() {
+        assert!(Crate::names_match("foo-bar", "foo_bar"));
+        assert!(Crate::names_match("Foo_Bar", "foo-bar"));
+        assert!(!Crate::names_match("foo", "foobar"));
+    }
+
     #[test]
     fn valid_dependency_name() {
         assert!(Crate::valid_dependency_name("foo"));
@@ -0,0 +1,72 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::User;
+use crate::schema::linked_identities;
+use crate::util::rfc3339;
+
+/// An account at a login provider that a user can log in with.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations, Serialize)]
+#[belongs_to(User)]
+#[table_name = "linked_identities"]
+pub struct LinkedIdentity {
+    #[serde(skip)]
+    pub id: i32,
+    #[serde(skip)]
+    pub user_id: i32,
+    pub provider: String,
+    #[serde(skip)]
+    pub provider_user_id: String,
+    pub login: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339::option")]
+    pub last_login_at: Option<NaiveDateTime>,
+}
+
+impl LinkedIdentity {
+    /// The crates.io login of users without a GitHub account, which is prefixed with the
+    /// provider so that it cannot clash with a GitHub login, e.g. `gitlab:foo`.
+    pub fn namespaced_login(provider: &str, login: &str) -> String {
+        format!("{provider}:{login}")
+    }
+
+    pub fn find(
+        conn: &PgConnection,
+        provider: &str,
+        provider_user_id: &str,
+    ) -> QueryResult<Option<LinkedIdentity>> {
+        linked_identities::table
+            .filter(linked_identities::provider.eq(provider))
+            .filter(linked_identities::provider_user_id.eq(provider_user_id))
+            .first(conn)
+            .optional()
+    }
+
+    /// Records a login, keeping the login name up to date with the provider.
+    pub fn touch(&self, conn: &PgConnection, login: &str) -> QueryResult<LinkedIdentity> {
+        diesel::update(self)
+            .set((
+                linked_identities::login.eq(login),
+                linked_identities::last_login_at.eq(diesel::dsl::now.nullable()),
+            ))
+            .get_result(conn)
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "linked_identities"]
+pub struct NewLinkedIdentity<'a> {
+    pub user_id: i32,
+    pub provider: &'a str,
+    pub provider_user_id: &'a str,
+    pub login: &'a str,
+}
+
+impl NewLinkedIdentity<'_> {
+    pub fn insert(&self, conn: &PgConnection) -> QueryResult<LinkedIdentity> {
+        diesel::insert_into(linked_identities::table)
+            .values((self, linked_identities::last_login_at.eq(diesel::dsl::now)))
+            .get_result(conn)
+    }
+}
@@ -1,10 +1,14 @@
+use diesel::deserialize::{self, FromSql};
 use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Integer;
+use std::io::Write;
 
 use crate::app::App;
 use crate::util::errors::{cargo_err, AppResult};
 
-use crate::models::{Crate, Team, User};
+use crate::models::{Crate, Rights, Team, User};
 use crate::schema::{crate_owners, users};
 use crate::sql::lower;
 
@@ -20,6 +24,7 @@ pub struct CrateOwner {
     pub created_by: i32,
     pub owner_kind: i32,
     pub email_notifications: bool,
+    pub role: OwnerRole,
 }
 
 type BoxedQuery<'a> = crate_owners::BoxedQuery<'a, Pg, crate_owners::SqlType>;
@@ -44,6 +49,68 @@ pub enum OwnerKind {
     Team = 1,
 }
 
+/// What an owner is allowed to do with a crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[repr(i32)]
+#[sql_type = "Integer"]
+pub enum OwnerRole {
+    /// Publishing, yanking and managing the owners of the crate
+    Full = 0,
+    /// Only publishing and yanking
+    Publish = 1,
+}
+
+impl OwnerRole {
+    /// The role owners get if none is requested: full rights for users and publish rights for
+    /// teams.
+    pub fn default_for(kind: OwnerKind) -> Self {
+        match kind {
+            OwnerKind::User => OwnerRole::Full,
+            OwnerKind::Team => OwnerRole::Publish,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(OwnerRole::Full),
+            "publish" => Some(OwnerRole::Publish),
+            _ => None,
+        }
+    }
+
+    pub fn rights(self) -> Rights {
+        match self {
+            OwnerRole::Full => Rights::Full,
+            OwnerRole::Publish => Rights::Publish,
+        }
+    }
+}
+
+impl From<OwnerRole> for &'static str {
+    fn from(role: OwnerRole) -> Self {
+        match role {
+            OwnerRole::Full => "full",
+            OwnerRole::Publish => "publish",
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for OwnerRole {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(OwnerRole::Full),
+            1 => Ok(OwnerRole::Publish),
+            n => Err(format!("unknown owner role: {n}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for OwnerRole {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
 /// Unifies the notion of a User or a Team.
 #[derive(Debug)]
 pub enum Owner {
@@ -64,21 +131,41 @@ impl Owner {
         req_user: &User,
         name: &str,
     ) -> AppResult<Owner> {
-        if name.contains(':') {
+        if Self::is_team_login(name) {
             Ok(Owner::Team(Team::create_or_update(
                 app, conn, name, req_user,
             )?))
         } else {
-            users::table
-                .filter(lower(users::gh_login).eq(name.to_lowercase()))
-                .filter(users::gh_id.ne(-1))
-                .order(users::gh_id.desc())
-                .first(conn)
-                .map(Owner::User)
-                .map_err(|_| cargo_err(&format_args!("could not find user with login `{}`", name)))
+            Self::find_user_by_login(conn, name).map(Owner::User)
         }
     }
 
+    /// Team logins look like `github:org:team`.
+    pub fn is_team_login(login: &str) -> bool {
+        login.starts_with("github:")
+    }
+
+    /// Finds a user by their login, ignoring case.
+    ///
+    /// Plain logins only match users with a GitHub account, while users who signed up with
+    /// another provider have a login like `gitlab:foo`. A GitHub login can therefore never
+    /// resolve to the account of somebody else who picked the same name elsewhere.
+    pub fn find_user_by_login(conn: &PgConnection, name: &str) -> AppResult<User> {
+        let query = users::table
+            .filter(lower(users::gh_login).eq(name.to_lowercase()))
+            .into_boxed();
+        let query = if name.contains(':') {
+            query.filter(users::gh_id.eq(0))
+        } else {
+            query.filter(users::gh_id.gt(0))
+        };
+
+        query
+            .order(users::gh_id.desc())
+            .first(conn)
+            .map_err(|_| cargo_err(&format_args!("could not find user with login `{}`", name)))
+    }
+
     pub fn kind(&self) -> i32 {
         match *self {
             Owner::User(_) => OwnerKind::User as i32,
@@ -1,12 +1,15 @@
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
+use std::time::Duration;
 
 use crate::app::App;
+use crate::github::{GitHubClient, GitHubTeamMembership, GitHubUnavailable};
 use crate::util::errors::{cargo_err, AppResult, NotFound};
 
 use oauth2::AccessToken;
 
-use crate::models::{Crate, CrateOwner, Owner, OwnerKind, User};
-use crate::schema::{crate_owners, teams};
+use crate::models::{Crate, CrateOwner, Owner, OwnerKind, OwnerRole, User};
+use crate::schema::{crate_owners, github_team_memberships, teams};
 
 /// For now, just a Github Team. Can be upgraded to other teams
 /// later if desirable.
@@ -29,6 +32,62 @@ pub struct Team {
     pub org_id: Option<i32>,
 }
 
+/// GitHub's last answer to whether a user is a member of a team.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Team)]
+#[belongs_to(User)]
+#[primary_key(team_id, user_id)]
+#[table_name = "github_team_memberships"]
+pub struct TeamMembership {
+    pub team_id: i32,
+    pub user_id: i32,
+    pub is_member: bool,
+    pub checked_at: NaiveDateTime,
+}
+
+impl TeamMembership {
+    pub fn find(conn: &PgConnection, team_id: i32, user_id: i32) -> QueryResult<Option<Self>> {
+        github_team_memberships::table
+            .find((team_id, user_id))
+            .first(conn)
+            .optional()
+    }
+
+    /// Stores an answer of GitHub, replacing the previous one.
+    pub fn record(
+        conn: &PgConnection,
+        team_id: i32,
+        user_id: i32,
+        is_member: bool,
+    ) -> QueryResult<()> {
+        diesel::insert_into(github_team_memberships::table)
+            .values((
+                github_team_memberships::team_id.eq(team_id),
+                github_team_memberships::user_id.eq(user_id),
+                github_team_memberships::is_member.eq(is_member),
+            ))
+            .on_conflict((
+                github_team_memberships::team_id,
+                github_team_memberships::user_id,
+            ))
+            .do_update()
+            .set((
+                github_team_memberships::is_member.eq(is_member),
+                github_team_memberships::checked_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Whether GitHub answered less than `max_age` ago.
+    pub fn checked_within(&self, max_age: Duration) -> bool {
+        match chrono::Duration::from_std(max_age) {
+            Ok(max_age) => Utc::now().naive_utc() - self.checked_at < max_age,
+            Err(_) => true,
+        }
+    }
+}
+
 #[derive(Insertable, AsChangeset, Debug)]
 #[table_name = "teams"]
 pub struct NewTeam<'a> {
@@ -81,6 +140,17 @@ impl Team {
         login: &str,
         req_user: &User,
     ) -> AppResult<Self> {
+        Self::create_or_update_with_membership(app, conn, login, req_user).map(|(team, _)| team)
+    }
+
+    /// Like `create_or_update`, but also returns the membership of `req_user` in the team, which
+    /// tells whether they are a maintainer of it.
+    pub fn create_or_update_with_membership(
+        app: &App,
+        conn: &PgConnection,
+        login: &str,
+        req_user: &User,
+    ) -> AppResult<(Self, GitHubTeamMembership)> {
         // must look like system:xxxxxxx
         let mut chunks = login.split(':');
         // unwrap is okay, split on an empty string still has 1 chunk
@@ -121,7 +191,7 @@ impl Team {
         org_name: &str,
         team_name: &str,
         req_user: &User,
-    ) -> AppResult<Self> {
+    ) -> AppResult<(Self, GitHubTeamMembership)> {
         // GET orgs/:org/teams
         // check that `team` is the `slug` in results, and grab its data
 
@@ -138,6 +208,12 @@ impl Team {
             )));
         }
 
+        if req_user.gh_access_token.is_empty() {
+            return Err(cargo_err(
+                "link a GitHub account to your crates.io account to add GitHub teams as owners",
+            ));
+        }
+
         let token = AccessToken::new(req_user.gh_access_token.clone());
         let team = app
             .github
@@ -151,73 +227,142 @@ impl Team {
 
         let org_id = team.organization.id;
 
-        if !team_with_gh_id_contains_user(app, org_id, team.id, req_user)? {
-            return Err(cargo_err("only members of a team can add it as an owner"));
-        }
+        let membership = active_team_membership(&app.github, org_id, team.id, req_user)?
+            .ok_or_else(|| cargo_err("only members of a team can add it as an owner"))?;
 
         let org = app.github.org_by_name(org_name, &token)?;
 
-        NewTeam::new(
+        let team = NewTeam::new(
             &login.to_lowercase(),
             org_id,
             team.id,
             team.name,
             org.avatar_url,
         )
-        .create_or_update(conn)
-        .map_err(Into::into)
+        .create_or_update(conn)?;
+        Ok((team, membership))
     }
 
     /// Phones home to Github to ask if this User is a member of the given team.
     /// Note that we're assuming that the given user is the one interested in
     /// the answer. If this is not the case, then we could accidentally leak
     /// private membership information here.
-    pub fn contains_user(&self, app: &App, user: &User) -> AppResult<bool> {
+    ///
+    /// Answers are cached for `team_membership_cache_ttl`. If GitHub is unavailable, a
+    /// membership confirmed within `team_membership_fallback_ttl` is trusted instead.
+    pub fn contains_user(&self, app: &App, conn: &PgConnection, user: &User) -> AppResult<bool> {
+        // This means we don't have an org_id on file for the `self` team. It much
+        // probably was deleted from github by the time we backfilled the database.
+        // Short-circuiting to false since a non-existent team cannot contain any
+        // user
+        if self.org_id.is_none() {
+            return Ok(false);
+        }
+
+        let config = &app.config;
+        let cached = TeamMembership::find(conn, self.id, user.id)?;
+        if let Some(cached) = &cached {
+            if cached.checked_within(config.team_membership_cache_ttl) {
+                return Ok(cached.is_member);
+            }
+        }
+
+        match self.github_contains_user(&app.github, user) {
+            Ok(is_member) => {
+                // The cache is only an optimization, so requests made with a read-only
+                // connection are answered anyway
+                if let Err(error) = TeamMembership::record(conn, self.id, user.id, is_member) {
+                    warn!(
+                        "Could not cache the membership of {} in {}: {error}",
+                        user.gh_login, self.login
+                    );
+                }
+                Ok(is_member)
+            }
+            Err(error) if error.is::<GitHubUnavailable>() => match cached {
+                Some(cached)
+                    if cached.is_member
+                        && cached.checked_within(config.team_membership_fallback_ttl) =>
+                {
+                    warn!(
+                        "Using the cached membership of {} in {}: {error}",
+                        user.gh_login, self.login
+                    );
+                    Ok(true)
+                }
+                _ => Err(error),
+            },
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Asks GitHub whether this User is a member of the team, ignoring the cached answer.
+    pub fn github_contains_user(&self, github: &GitHubClient, user: &User) -> AppResult<bool> {
+        match self.org_id {
+            Some(org_id) => team_with_gh_id_contains_user(github, org_id, self.github_id, user),
+            None => Ok(false),
+        }
+    }
+
+    /// Asks GitHub whether this User is a maintainer of the team. The same caveat about leaking
+    /// private membership information as for `contains_user` applies.
+    pub fn is_maintained_by(&self, app: &App, user: &User) -> AppResult<bool> {
         match self.org_id {
-            Some(org_id) => team_with_gh_id_contains_user(app, org_id, self.github_id, user),
-            // This means we don't have an org_id on file for the `self` team. It much
-            // probably was deleted from github by the time we backfilled the database.
-            // Short-circuiting to false since a non-existent team cannot contain any
-            // user
+            Some(org_id) => Ok(
+                active_team_membership(&app.github, org_id, self.github_id, user)?
+                    .map_or(false, |membership| membership.is_maintainer()),
+            ),
             None => Ok(false),
         }
     }
 
-    pub fn owning(krate: &Crate, conn: &PgConnection) -> QueryResult<Vec<Owner>> {
+    pub fn owning(krate: &Crate, conn: &PgConnection) -> QueryResult<Vec<(Owner, OwnerRole)>> {
         let base_query = CrateOwner::belonging_to(krate).filter(crate_owners::deleted.eq(false));
         let teams = base_query
             .inner_join(teams::table)
-            .select(teams::all_columns)
+            .select((teams::all_columns, crate_owners::role))
             .filter(crate_owners::owner_kind.eq(OwnerKind::Team as i32))
             .load(conn)?
             .into_iter()
-            .map(Owner::Team);
+            .map(|(team, role)| (Owner::Team(team), role));
 
         Ok(teams.collect())
     }
 }
 
 fn team_with_gh_id_contains_user(
-    app: &App,
+    github: &GitHubClient,
     github_org_id: i32,
     github_team_id: i32,
     user: &User,
 ) -> AppResult<bool> {
+    Ok(active_team_membership(github, github_org_id, github_team_id, user)?.is_some())
+}
+
+/// Returns the membership of the user in the team if they are an active member of it.
+fn active_team_membership(
+    github: &GitHubClient,
+    github_org_id: i32,
+    github_team_id: i32,
+    user: &User,
+) -> AppResult<Option<GitHubTeamMembership>> {
     // GET /organizations/:org_id/team/:team_id/memberships/:username
     // check that "state": "active"
 
+    // Users that have not linked a GitHub account cannot be members of any team
+    if user.gh_access_token.is_empty() {
+        return Ok(None);
+    }
+
     let token = AccessToken::new(user.gh_access_token.clone());
     let membership =
-        match app
-            .github
-            .team_membership(github_org_id, github_team_id, &user.gh_login, &token)
-        {
+        match github.team_membership(github_org_id, github_team_id, &user.gh_login, &token) {
             // Officially how `false` is returned
-            Err(ref e) if e.is::<NotFound>() => return Ok(false),
+            Err(ref e) if e.is::<NotFound>() => return Ok(None),
             x => x?,
         };
 
     // There is also `state: pending` for which we could possibly give
     // some feedback, but it's not obvious how that should work.
-    Ok(membership.state == "active")
+    Ok(Some(membership).filter(GitHubTeamMembership::is_active))
 }
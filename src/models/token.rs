@@ -1,15 +1,22 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 
-use crate::models::User;
-use crate::schema::api_tokens;
-use crate::util::errors::{AppResult, InsecurelyGeneratedTokenRevoked};
+use crate::models::{OwnerKind, TrustedPublisher, User};
+use crate::schema::{api_tokens, crate_owners};
+use crate::util::errors::{bad_request, AppResult, ExpiredToken, InsecurelyGeneratedTokenRevoked};
 use crate::util::rfc3339;
 use crate::util::token::{SecureToken, SecureTokenKind};
 
+pub use self::activity::{ApiTokenActivity, NewApiTokenActivity};
+pub use self::scopes::{CrateScope, EndpointScope};
+
+mod activity;
+mod scopes;
+
 /// The model representing a row in the `api_tokens` database table.
 #[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations, Serialize)]
 #[belongs_to(User)]
+#[belongs_to(TrustedPublisher)]
 pub struct ApiToken {
     pub id: i32,
     #[serde(skip)]
@@ -23,11 +30,35 @@ pub struct ApiToken {
     pub last_used_at: Option<NaiveDateTime>,
     #[serde(skip)]
     pub revoked: bool,
+    /// `None` for tokens with full access, otherwise the actions the token may be used for
+    pub endpoint_scopes: Option<Vec<EndpointScope>>,
+    /// `None` for tokens that work for all crates, otherwise the crates the token may act on
+    pub crate_scopes: Option<Vec<CrateScope>>,
+    #[serde(with = "rfc3339::option")]
+    pub expires_at: Option<NaiveDateTime>,
+    #[serde(skip)]
+    pub expiry_notification_at: Option<NaiveDateTime>,
+    /// Set for short-lived tokens that were obtained through trusted publishing
+    #[serde(skip)]
+    pub trusted_publisher_id: Option<i32>,
 }
 
 impl ApiToken {
     /// Generates a new named API token for a user
     pub fn insert(conn: &PgConnection, user_id: i32, name: &str) -> AppResult<CreatedApiToken> {
+        Self::insert_with_scopes(conn, user_id, name, None, None, None)
+    }
+
+    /// Generates a new named API token for a user, restricted to the given endpoint and
+    /// crate scopes and optionally expiring at the given time
+    pub fn insert_with_scopes(
+        conn: &PgConnection,
+        user_id: i32,
+        name: &str,
+        endpoint_scopes: Option<Vec<EndpointScope>>,
+        crate_scopes: Option<Vec<CrateScope>>,
+        expires_at: Option<NaiveDateTime>,
+    ) -> AppResult<CreatedApiToken> {
         let token = SecureToken::generate(SecureTokenKind::Api);
 
         let model: ApiToken = diesel::insert_into(api_tokens::table)
@@ -35,6 +66,58 @@ impl ApiToken {
                 api_tokens::user_id.eq(user_id),
                 api_tokens::name.eq(name),
                 api_tokens::token.eq(&*token),
+                api_tokens::endpoint_scopes.eq(endpoint_scopes),
+                api_tokens::crate_scopes.eq(crate_scopes),
+                api_tokens::expires_at.eq(expires_at),
+            ))
+            .get_result(conn)?;
+
+        Ok(CreatedApiToken {
+            plaintext: token.plaintext().into(),
+            model,
+        })
+    }
+
+    /// Generates a short-lived token that may only publish new versions of `crate_name`,
+    /// on behalf of the owner that registered the trusted publisher
+    ///
+    /// The publisher stops working once that owner is removed from the crate or their account
+    /// is locked.
+    pub fn insert_for_trusted_publisher(
+        conn: &PgConnection,
+        trusted_publisher: &TrustedPublisher,
+        crate_name: &str,
+        expires_at: NaiveDateTime,
+    ) -> AppResult<CreatedApiToken> {
+        let still_owner = diesel::select(diesel::dsl::exists(
+            crate_owners::table
+                .filter(crate_owners::crate_id.eq(trusted_publisher.crate_id))
+                .filter(crate_owners::owner_id.eq(trusted_publisher.created_by))
+                .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+                .filter(crate_owners::deleted.eq(false)),
+        ))
+        .get_result::<bool>(conn)?;
+        let creator = User::find(conn, trusted_publisher.created_by)?;
+        if !still_owner || creator.is_locked() {
+            return Err(bad_request(&format_args!(
+                "the owner that registered this trusted publisher can no longer publish `{}`",
+                crate_name
+            )));
+        }
+
+        let token = SecureToken::generate(SecureTokenKind::TrustedPublishing);
+        let name = format!("Trusted publishing: {}", trusted_publisher.repository);
+        let crate_scope = CrateScope::try_from(crate_name).map_err(|e| bad_request(&e))?;
+
+        let model: ApiToken = diesel::insert_into(api_tokens::table)
+            .values((
+                api_tokens::user_id.eq(trusted_publisher.created_by),
+                api_tokens::name.eq(name),
+                api_tokens::token.eq(&*token),
+                api_tokens::endpoint_scopes.eq(Some(vec![EndpointScope::PublishUpdate])),
+                api_tokens::crate_scopes.eq(Some(vec![crate_scope])),
+                api_tokens::expires_at.eq(expires_at),
+                api_tokens::trusted_publisher_id.eq(trusted_publisher.id),
             ))
             .get_result(conn)?;
 
@@ -49,6 +132,7 @@ impl ApiToken {
         use diesel::{dsl::now, update};
 
         let token_ = SecureToken::parse(SecureTokenKind::Api, token_)
+            .or_else(|| SecureToken::parse(SecureTokenKind::TrustedPublishing, token_))
             .ok_or_else(InsecurelyGeneratedTokenRevoked::boxed)?;
 
         let tokens = api_tokens
@@ -56,14 +140,27 @@ impl ApiToken {
             .filter(token.eq(&token_));
 
         // If the database is in read only mode, we can't update last_used_at.
-        // Try updating in a new transaction, if that fails, fall back to reading
-        conn.transaction(|| {
-            update(tokens)
-                .set(last_used_at.eq(now.nullable()))
-                .get_result(conn)
-        })
-        .or_else(|_| tokens.first(conn))
-        .map_err(Into::into)
+        // Try updating in a new transaction, if that fails, fall back to reading.
+        // Expired tokens are never marked as used, the fallback picks them up
+        // so that we can report why they were rejected.
+        let api_token: ApiToken = conn
+            .transaction(|| {
+                update(tokens.filter(expires_at.is_null().or(expires_at.gt(now))))
+                    .set(last_used_at.eq(now.nullable()))
+                    .get_result(conn)
+            })
+            .or_else(|_| tokens.first(conn))?;
+
+        if api_token.is_expired() {
+            return Err(ExpiredToken::boxed());
+        }
+
+        Ok(api_token)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| expires_at <= Utc::now().naive_utc())
     }
 }
 
@@ -98,6 +195,11 @@ mod tests {
             name: "".to_string(),
             created_at: NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 11),
             last_used_at: Some(NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 12)),
+            endpoint_scopes: Some(vec![EndpointScope::PublishUpdate, EndpointScope::Yank]),
+            crate_scopes: Some(vec![CrateScope::try_from("ourcorp-*").unwrap()]),
+            expires_at: Some(NaiveDate::from_ymd(2017, 2, 6).and_hms(14, 23, 11)),
+            expiry_notification_at: None,
+            trusted_publisher_id: None,
         };
         let json = serde_json::to_string(&tok).unwrap();
         assert_some!(json
@@ -106,6 +208,13 @@ mod tests {
         assert_some!(json
             .as_str()
             .find(r#""last_used_at":"2017-01-06T14:23:12+00:00""#));
+        assert_some!(json
+            .as_str()
+            .find(r#""endpoint_scopes":["publish-update","yank"]"#));
+        assert_some!(json.as_str().find(r#""crate_scopes":["ourcorp-*"]"#));
+        assert_some!(json
+            .as_str()
+            .find(r#""expires_at":"2017-02-06T14:23:11+00:00""#));
     }
 
     #[test]
@@ -117,6 +226,9 @@ mod tests {
             revoked: false,
             created_at: NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 11),
             last_used_at: Some(NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 12)),
+            endpoint_scopes: None,
+            crate_scopes: None,
+            expires_at: None,
         };
         let json = serde_json::to_string(&tok).unwrap();
         assert_some!(json
@@ -0,0 +1,51 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::ApiToken;
+use crate::schema::api_token_activity;
+use crate::util::rfc3339;
+
+/// A mutating request that was made with an API token.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations, Serialize)]
+#[belongs_to(ApiToken)]
+#[table_name = "api_token_activity"]
+pub struct ApiTokenActivity {
+    pub id: i32,
+    #[serde(skip)]
+    pub api_token_id: i32,
+    /// The HTTP method and route pattern of the request, e.g. `PUT /crates/new`
+    pub endpoint: String,
+    pub crate_name: Option<String>,
+    pub version: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiTokenActivity {
+    /// Deletes all entries that were recorded before `before`, returning how many were removed.
+    pub fn prune(conn: &PgConnection, before: NaiveDateTime) -> QueryResult<usize> {
+        diesel::delete(api_token_activity::table.filter(api_token_activity::created_at.lt(before)))
+            .execute(conn)
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "api_token_activity"]
+pub struct NewApiTokenActivity<'a> {
+    pub api_token_id: i32,
+    pub endpoint: &'a str,
+    pub crate_name: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub ip_address: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+}
+
+impl NewApiTokenActivity<'_> {
+    pub fn insert(&self, conn: &PgConnection) -> QueryResult<ApiTokenActivity> {
+        diesel::insert_into(api_token_activity::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
@@ -0,0 +1,180 @@
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use std::io::Write;
+
+use crate::models::Crate;
+
+/// The actions an API token can be restricted to.
+///
+/// Tokens created without any scopes predate this restriction and keep full access to
+/// everything the user can do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "kebab-case")]
+#[sql_type = "Text"]
+pub enum EndpointScope {
+    PublishNew,
+    PublishUpdate,
+    Yank,
+    ChangeOwners,
+    ReadOnly,
+}
+
+impl From<&EndpointScope> for &'static str {
+    fn from(scope: &EndpointScope) -> Self {
+        match scope {
+            EndpointScope::PublishNew => "publish-new",
+            EndpointScope::PublishUpdate => "publish-update",
+            EndpointScope::Yank => "yank",
+            EndpointScope::ChangeOwners => "change-owners",
+            EndpointScope::ReadOnly => "read-only",
+        }
+    }
+}
+
+impl TryFrom<&str> for EndpointScope {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "publish-new" => Ok(EndpointScope::PublishNew),
+            "publish-update" => Ok(EndpointScope::PublishUpdate),
+            "yank" => Ok(EndpointScope::Yank),
+            "change-owners" => Ok(EndpointScope::ChangeOwners),
+            "read-only" => Ok(EndpointScope::ReadOnly),
+            _ => Err(format!("unknown endpoint scope: {s}")),
+        }
+    }
+}
+
+impl FromSql<Text, Pg> for EndpointScope {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        Ok(EndpointScope::try_from(s.as_str())?)
+    }
+}
+
+impl ToSql<Text, Pg> for EndpointScope {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        let s: &'static str = self.into();
+        ToSql::<Text, Pg>::to_sql(s, out)
+    }
+}
+
+/// A crate name or a name prefix ending in `*` that an API token is restricted to.
+///
+/// Names are compared the same way crate names are, so `foo-bar` also matches `foo_bar`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(try_from = "String", into = "String")]
+#[sql_type = "Text"]
+pub struct CrateScope {
+    pattern: String,
+}
+
+impl TryFrom<String> for CrateScope {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        CrateScope::try_from(pattern.as_str())
+    }
+}
+
+impl From<CrateScope> for String {
+    fn from(scope: CrateScope) -> Self {
+        scope.pattern
+    }
+}
+
+impl TryFrom<&str> for CrateScope {
+    type Error = String;
+
+    fn try_from(pattern: &str) -> Result<Self, Self::Error> {
+        let name = pattern.strip_suffix('*').unwrap_or(pattern);
+        let is_valid = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        if pattern.is_empty() || !is_valid {
+            return Err(format!("invalid crate scope: `{pattern}`"));
+        }
+
+        Ok(CrateScope {
+            pattern: pattern.to_string(),
+        })
+    }
+}
+
+impl CrateScope {
+    pub fn matches(&self, crate_name: &str) -> bool {
+        let crate_name = Crate::canonicalize_name(crate_name);
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => crate_name.starts_with(&Crate::canonicalize_name(prefix)),
+            None => crate_name == Crate::canonicalize_name(&self.pattern),
+        }
+    }
+}
+
+impl FromSql<Text, Pg> for CrateScope {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        Ok(CrateScope::try_from(s.as_str())?)
+    }
+}
+
+impl ToSql<Text, Pg> for CrateScope {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Text, Pg>::to_sql(self.pattern.as_str(), out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_scope_round_trips() {
+        let scopes = [
+            EndpointScope::PublishNew,
+            EndpointScope::PublishUpdate,
+            EndpointScope::Yank,
+            EndpointScope::ChangeOwners,
+            EndpointScope::ReadOnly,
+        ];
+        for scope in scopes {
+            let s: &'static str = (&scope).into();
+            assert_eq!(EndpointScope::try_from(s), Ok(scope));
+            assert_eq!(serde_json::to_string(&scope).unwrap(), format!("\"{s}\""));
+        }
+        assert_err!(EndpointScope::try_from("publish"));
+    }
+
+    #[test]
+    fn crate_scope_validation() {
+        assert_ok!(CrateScope::try_from("foo"));
+        assert_ok!(CrateScope::try_from("ourcorp-*"));
+        assert_ok!(CrateScope::try_from("*"));
+        assert_err!(CrateScope::try_from(""));
+        assert_err!(CrateScope::try_from("foo*bar"));
+        assert_err!(CrateScope::try_from("foo bar"));
+        assert_err!(CrateScope::try_from("**"));
+    }
+
+    #[test]
+    fn crate_scope_matching() {
+        let scope = CrateScope::try_from("foo").unwrap();
+        assert!(scope.matches("foo"));
+        assert!(scope.matches("FOO"));
+        assert!(!scope.matches("foo-bar"));
+
+        let scope = CrateScope::try_from("ourcorp-*").unwrap();
+        assert!(scope.matches("ourcorp-client"));
+        assert!(scope.matches("ourcorp_client"));
+        assert!(scope.matches("ourcorp-"));
+        assert!(!scope.matches("ourcorp"));
+        assert!(!scope.matches("other-client"));
+
+        let scope = CrateScope::try_from("*").unwrap();
+        assert!(scope.matches("anything"));
+    }
+}
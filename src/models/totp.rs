@@ -0,0 +1,198 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+use crate::models::User;
+use crate::schema::{totp_credentials, totp_recovery_codes};
+use crate::util::totp;
+
+/// The number of recovery codes generated when two-factor authentication is enabled.
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// How many invalid codes can be entered in a row before no codes are accepted for a while.
+pub const MAX_FAILED_ATTEMPTS: i32 = 5;
+
+/// How long no codes are accepted after too many invalid ones.
+const LOCKOUT_MINUTES: i64 = 15;
+
+/// The TOTP secret of a user's authenticator app.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations)]
+#[belongs_to(User)]
+#[primary_key(user_id)]
+#[table_name = "totp_credentials"]
+pub struct TotpCredential {
+    pub user_id: i32,
+    pub secret: Vec<u8>,
+    /// Unset while the user is still setting up their authenticator app
+    pub enabled_at: Option<NaiveDateTime>,
+    pub last_used_step: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub failed_attempts: i32,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+impl TotpCredential {
+    pub fn find(conn: &PgConnection, user_id: i32) -> QueryResult<Option<TotpCredential>> {
+        totp_credentials::table.find(user_id).first(conn).optional()
+    }
+
+    /// Returns the credential of the user if they have enabled two-factor authentication.
+    pub fn find_enabled(conn: &PgConnection, user_id: i32) -> QueryResult<Option<TotpCredential>> {
+        totp_credentials::table
+            .find(user_id)
+            .filter(totp_credentials::enabled_at.is_not_null())
+            .first(conn)
+            .optional()
+    }
+
+    /// Stores a new secret for the user, replacing any enrollment they did not finish.
+    pub fn start_enrollment(conn: &PgConnection, user_id: i32) -> QueryResult<TotpCredential> {
+        let secret = totp::generate_secret();
+
+        diesel::insert_into(totp_credentials::table)
+            .values((
+                totp_credentials::user_id.eq(user_id),
+                totp_credentials::secret.eq(&secret),
+            ))
+            .on_conflict(totp_credentials::user_id)
+            .do_update()
+            .set((
+                totp_credentials::secret.eq(&secret),
+                totp_credentials::enabled_at.eq(None::<NaiveDateTime>),
+                totp_credentials::last_used_step.eq(None::<i64>),
+                totp_credentials::created_at.eq(diesel::dsl::now),
+                totp_credentials::failed_attempts.eq(0),
+                totp_credentials::locked_until.eq(None::<NaiveDateTime>),
+            ))
+            .get_result(conn)
+    }
+
+    /// Checks a code of the authenticator app. Each code can only be used once, even by
+    /// concurrent requests.
+    pub fn verify(&self, conn: &PgConnection, code: &str) -> QueryResult<bool> {
+        let now = Utc::now().timestamp();
+        let step = match totp::verify(&self.secret, code, now, self.last_used_step) {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+
+        let unused = totp_credentials::last_used_step
+            .is_null()
+            .or(totp_credentials::last_used_step.lt(step));
+        let updated = diesel::update(totp_credentials::table.find(self.user_id).filter(unused))
+            .set((
+                totp_credentials::last_used_step.eq(step),
+                totp_credentials::failed_attempts.eq(0),
+            ))
+            .execute(conn)?;
+        Ok(updated > 0)
+    }
+
+    /// Returns until when no codes are accepted, if too many invalid ones were entered.
+    pub fn locked_until(&self) -> Option<NaiveDateTime> {
+        self.locked_until
+            .filter(|until| *until > Utc::now().naive_utc())
+    }
+
+    /// Resets the count of invalid codes after a valid recovery code.
+    pub fn clear_failed_attempts(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::update(totp_credentials::table.find(self.user_id))
+            .set(totp_credentials::failed_attempts.eq(0))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Records an invalid code, which may be a recovery code. After `MAX_FAILED_ATTEMPTS` in a
+    /// row no codes are accepted for a while.
+    pub fn record_failed_attempt(&self, conn: &PgConnection) -> QueryResult<()> {
+        let failed_attempts: i32 = diesel::update(totp_credentials::table.find(self.user_id))
+            .set(totp_credentials::failed_attempts.eq(totp_credentials::failed_attempts + 1))
+            .returning(totp_credentials::failed_attempts)
+            .get_result(conn)?;
+
+        if failed_attempts >= MAX_FAILED_ATTEMPTS {
+            let until = Utc::now().naive_utc() + Duration::minutes(LOCKOUT_MINUTES);
+            diesel::update(totp_credentials::table.find(self.user_id))
+                .set((
+                    totp_credentials::failed_attempts.eq(0),
+                    totp_credentials::locked_until.eq(until),
+                ))
+                .execute(conn)?;
+        }
+
+        Ok(())
+    }
+
+    /// Enables two-factor authentication and returns the recovery codes of the user.
+    pub fn enable(&self, conn: &PgConnection) -> QueryResult<Vec<String>> {
+        conn.transaction(|| {
+            diesel::update(self)
+                .set(totp_credentials::enabled_at.eq(diesel::dsl::now.nullable()))
+                .execute(conn)?;
+            Self::regenerate_recovery_codes(conn, self.user_id)
+        })
+    }
+
+    /// Disables two-factor authentication, deleting the secret and all recovery codes.
+    pub fn disable(conn: &PgConnection, user_id: i32) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::delete(
+                totp_recovery_codes::table.filter(totp_recovery_codes::user_id.eq(user_id)),
+            )
+            .execute(conn)?;
+            diesel::delete(totp_credentials::table.find(user_id)).execute(conn)?;
+            Ok(())
+        })
+    }
+
+    /// Replaces all recovery codes of the user with new ones.
+    pub fn regenerate_recovery_codes(
+        conn: &PgConnection,
+        user_id: i32,
+    ) -> QueryResult<Vec<String>> {
+        let codes = (0..RECOVERY_CODE_COUNT)
+            .map(|_| totp::generate_recovery_code())
+            .collect::<Vec<_>>();
+        let rows = codes
+            .iter()
+            .map(|code| {
+                (
+                    totp_recovery_codes::user_id.eq(user_id),
+                    totp_recovery_codes::digest.eq(totp::recovery_code_digest(code)),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        conn.transaction(|| {
+            diesel::delete(
+                totp_recovery_codes::table.filter(totp_recovery_codes::user_id.eq(user_id)),
+            )
+            .execute(conn)?;
+            diesel::insert_into(totp_recovery_codes::table)
+                .values(&rows)
+                .execute(conn)?;
+            Ok(codes)
+        })
+    }
+
+    /// Marks a recovery code of the user as used, returning whether it was valid.
+    pub fn use_recovery_code(conn: &PgConnection, user_id: i32, code: &str) -> QueryResult<bool> {
+        let used = diesel::update(
+            totp_recovery_codes::table
+                .filter(totp_recovery_codes::user_id.eq(user_id))
+                .filter(totp_recovery_codes::digest.eq(totp::recovery_code_digest(code)))
+                .filter(totp_recovery_codes::used_at.is_null()),
+        )
+        .set(totp_recovery_codes::used_at.eq(diesel::dsl::now.nullable()))
+        .execute(conn)?;
+
+        Ok(used > 0)
+    }
+
+    pub fn remaining_recovery_codes(conn: &PgConnection, user_id: i32) -> QueryResult<i64> {
+        totp_recovery_codes::table
+            .filter(totp_recovery_codes::user_id.eq(user_id))
+            .filter(totp_recovery_codes::used_at.is_null())
+            .count()
+            .get_result(conn)
+    }
+}
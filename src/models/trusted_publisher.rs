@@ -0,0 +1,54 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::Crate;
+use crate::oidc::Claims;
+use crate::schema::trusted_publishers;
+use crate::util::rfc3339;
+
+/// A CI workflow that is allowed to publish new versions of a crate without a long-lived
+/// API token.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations, Serialize)]
+#[belongs_to(Crate)]
+pub struct TrustedPublisher {
+    pub id: i32,
+    #[serde(skip)]
+    pub crate_id: i32,
+    /// The owner that registered the publisher, and on whose behalf its tokens publish
+    #[serde(skip)]
+    pub created_by: i32,
+    pub issuer: String,
+    /// The `owner/name` of the repository the workflow lives in
+    pub repository: String,
+    /// The file name of the workflow in `.github/workflows`
+    pub workflow: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+impl TrustedPublisher {
+    /// Returns whether a verified OIDC token was issued to this publisher's workflow.
+    pub fn matches(&self, claims: &Claims) -> bool {
+        claims.iss == self.issuer
+            && claims.repository.eq_ignore_ascii_case(&self.repository)
+            && claims.workflow_filename() == Some(self.workflow.as_str())
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "trusted_publishers"]
+pub struct NewTrustedPublisher<'a> {
+    pub crate_id: i32,
+    pub created_by: i32,
+    pub issuer: &'a str,
+    pub repository: &'a str,
+    pub workflow: &'a str,
+}
+
+impl NewTrustedPublisher<'_> {
+    pub fn insert(&self, conn: &PgConnection) -> QueryResult<TrustedPublisher> {
+        diesel::insert_into(trusted_publishers::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 use std::borrow::Cow;
 
@@ -6,8 +6,15 @@ use crate::app::App;
 use crate::email::Emails;
 use crate::util::errors::AppResult;
 
-use crate::models::{ApiToken, Crate, CrateOwner, Email, NewEmail, Owner, OwnerKind, Rights};
-use crate::schema::{crate_owners, emails, users};
+use crate::models::{
+    ApiToken, Crate, CrateAction, CrateOwner, Email, NewCrateOwnerAction, NewEmail, Owner,
+    OwnerKind, OwnerRole, Rights, TotpCredential, UserSession,
+};
+use crate::schema::{
+    api_tokens, crate_adoption_requests, crate_owner_invitations, crate_owner_signup_invitations,
+    crate_owner_team_invitations, crate_owners, crates, emails, follows, linked_identities, users,
+    versions, versions_published_by,
+};
 
 /// The model representing a row in the `users` database table.
 #[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable, AsChangeset, Associations)]
@@ -20,6 +27,7 @@ pub struct User {
     pub gh_id: i32,
     pub account_lock_reason: Option<String>,
     pub account_lock_until: Option<NaiveDateTime>,
+    pub is_admin: bool,
 }
 
 /// Represents a new user record insertable to the `users` table
@@ -89,18 +97,20 @@ impl<'a> NewUser<'a> {
                 let new_email = NewEmail {
                     user_id: user.id,
                     email: user_email,
+                    is_primary: true,
                 };
 
-                let token: Option<String> = insert_into(emails::table)
+                // Does nothing if the user already has a primary address
+                let email_id: Option<i32> = insert_into(emails::table)
                     .values(&new_email)
                     .on_conflict_do_nothing()
-                    .returning(emails::token)
+                    .returning(emails::id)
                     .get_result(conn)
                     .optional()?;
 
-                if let Some(token) = token {
+                if let Some(email_id) = email_id {
                     // Swallows any error. Some users might insert an invalid email address here.
-                    let _ = emails.send_user_confirm(user_email, &user.gh_login, &token);
+                    let _ = emails.send_user_confirm(conn, user_email, &user.gh_login, email_id);
                 }
             }
 
@@ -114,6 +124,48 @@ impl User {
         users::table.find(id).first(conn)
     }
 
+    /// Locks the account, which rejects all authenticated requests of the user until `until`,
+    /// or indefinitely. All sessions of the user are revoked.
+    pub fn lock(
+        &self,
+        conn: &PgConnection,
+        reason: &str,
+        until: Option<NaiveDateTime>,
+    ) -> QueryResult<User> {
+        conn.transaction(|| {
+            UserSession::revoke_all(conn, self.id)?;
+            diesel::update(self)
+                .set((
+                    users::account_lock_reason.eq(reason),
+                    users::account_lock_until.eq(until),
+                ))
+                .get_result(conn)
+        })
+    }
+
+    /// Whether the account is locked right now. Locks with an end date expire on their own.
+    pub fn is_locked(&self) -> bool {
+        match (&self.account_lock_reason, self.account_lock_until) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(_), Some(until)) => until > Utc::now().naive_utc(),
+        }
+    }
+
+    /// Whether the account was deleted through `anonymize`.
+    pub fn is_anonymized(&self) -> bool {
+        self.gh_login == anonymized_login(self.id)
+    }
+
+    pub fn unlock(&self, conn: &PgConnection) -> QueryResult<User> {
+        diesel::update(self)
+            .set((
+                users::account_lock_reason.eq(None::<String>),
+                users::account_lock_until.eq(None::<NaiveDateTime>),
+            ))
+            .get_result(conn)
+    }
+
     /// Queries the database for a user with a certain `api_token` value.
     pub fn find_by_api_token(conn: &PgConnection, token: &str) -> AppResult<User> {
         let api_token = ApiToken::find_by_api_token(conn, token)?;
@@ -121,60 +173,238 @@ impl User {
         Ok(Self::find(conn, api_token.user_id)?)
     }
 
-    pub fn owning(krate: &Crate, conn: &PgConnection) -> QueryResult<Vec<Owner>> {
+    pub fn owning(krate: &Crate, conn: &PgConnection) -> QueryResult<Vec<(Owner, OwnerRole)>> {
         let users = CrateOwner::by_owner_kind(OwnerKind::User)
             .inner_join(users::table)
-            .select(users::all_columns)
+            .select((users::all_columns, crate_owners::role))
             .filter(crate_owners::crate_id.eq(krate.id))
             .load(conn)?
             .into_iter()
-            .map(Owner::User);
+            .map(|(user, role)| (Owner::User(user), role));
 
         Ok(users.collect())
     }
 
+    /// Makes `to` an owner of all crates this user owns directly and removes this user as an
+    /// owner. Returns the names of the transferred crates.
+    pub fn transfer_crates(
+        &self,
+        conn: &PgConnection,
+        to: &User,
+        created_by: i32,
+    ) -> QueryResult<Vec<String>> {
+        conn.transaction(|| {
+            let owned = crate_owners::table
+                .filter(crate_owners::owner_id.eq(self.id))
+                .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+                .filter(crate_owners::deleted.eq(false));
+            let crates: Vec<(i32, String, OwnerRole)> = crate_owners::table
+                .inner_join(crates::table)
+                .filter(crate_owners::owner_id.eq(self.id))
+                .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+                .filter(crate_owners::deleted.eq(false))
+                .select((crates::id, crates::name, crate_owners::role))
+                .order(crates::name)
+                .load(conn)?;
+
+            for (crate_id, _, role) in &crates {
+                diesel::insert_into(crate_owners::table)
+                    .values(&CrateOwner {
+                        crate_id: *crate_id,
+                        owner_id: to.id,
+                        created_by,
+                        owner_kind: OwnerKind::User as i32,
+                        email_notifications: true,
+                        role: *role,
+                    })
+                    .on_conflict(crate_owners::table.primary_key())
+                    .do_update()
+                    .set((
+                        crate_owners::deleted.eq(false),
+                        crate_owners::role.eq(*role),
+                    ))
+                    .execute(conn)?;
+
+                NewCrateOwnerAction {
+                    crate_id: *crate_id,
+                    user_id: created_by,
+                    api_token_id: None,
+                    action: CrateAction::TransferOwnership,
+                    subject: Some(&to.gh_login),
+                    details: Some(&self.gh_login),
+                }
+                .insert(conn)?;
+            }
+
+            diesel::update(owned)
+                .set(crate_owners::deleted.eq(true))
+                .execute(conn)?;
+
+            Ok(crates.into_iter().map(|(_, name, _)| name).collect())
+        })
+    }
+
+    /// Removes this user as an owner of all crates they own directly, leaving crates without
+    /// other owners orphaned. Returns the number of crates.
+    pub fn orphan_crates(&self, conn: &PgConnection) -> QueryResult<usize> {
+        conn.transaction(|| {
+            let crate_ids: Vec<i32> = diesel::update(
+                crate_owners::table
+                    .filter(crate_owners::owner_id.eq(self.id))
+                    .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+                    .filter(crate_owners::deleted.eq(false)),
+            )
+            .set(crate_owners::deleted.eq(true))
+            .returning(crate_owners::crate_id)
+            .get_results(conn)?;
+
+            for crate_id in &crate_ids {
+                NewCrateOwnerAction {
+                    crate_id: *crate_id,
+                    user_id: self.id,
+                    api_token_id: None,
+                    action: CrateAction::RemoveOwner,
+                    subject: Some(&self.gh_login),
+                    details: None,
+                }
+                .insert(conn)?;
+            }
+
+            Ok(crate_ids.len())
+        })
+    }
+
+    /// Removes all personal data of the user, revokes their tokens and sessions, and unlinks all
+    /// login methods so nobody can log in to the account anymore.
+    ///
+    /// The row itself is kept, since versions and audit logs refer to it.
+    pub fn anonymize(&self, conn: &PgConnection) -> QueryResult<User> {
+        conn.transaction(|| {
+            diesel::update(api_tokens::table.filter(api_tokens::user_id.eq(self.id)))
+                .set(api_tokens::revoked.eq(true))
+                .execute(conn)?;
+            UserSession::revoke_all(conn, self.id)?;
+            TotpCredential::disable(conn, self.id)?;
+
+            diesel::delete(emails::table.filter(emails::user_id.eq(self.id))).execute(conn)?;
+            diesel::delete(linked_identities::table.filter(linked_identities::user_id.eq(self.id)))
+                .execute(conn)?;
+            diesel::delete(follows::table.filter(follows::user_id.eq(self.id))).execute(conn)?;
+            diesel::delete(
+                crate_owner_invitations::table.filter(
+                    crate_owner_invitations::invited_user_id
+                        .eq(self.id)
+                        .or(crate_owner_invitations::invited_by_user_id.eq(self.id)),
+                ),
+            )
+            .execute(conn)?;
+            diesel::delete(
+                crate_adoption_requests::table
+                    .filter(crate_adoption_requests::requested_by_user_id.eq(self.id)),
+            )
+            .execute(conn)?;
+            diesel::delete(
+                crate_owner_signup_invitations::table
+                    .filter(crate_owner_signup_invitations::invited_by_user_id.eq(self.id)),
+            )
+            .execute(conn)?;
+            diesel::delete(
+                crate_owner_team_invitations::table
+                    .filter(crate_owner_team_invitations::invited_by_user_id.eq(self.id)),
+            )
+            .execute(conn)?;
+
+            // The address recorded for each published version is personal data as well
+            let published = versions::table
+                .filter(versions::published_by.eq(self.id))
+                .select(versions::id);
+            diesel::delete(
+                versions_published_by::table
+                    .filter(versions_published_by::version_id.eq_any(published)),
+            )
+            .execute(conn)?;
+
+            diesel::update(self)
+                .set((
+                    users::gh_login.eq(anonymized_login(self.id)),
+                    users::name.eq(None::<String>),
+                    users::gh_avatar.eq(None::<String>),
+                    users::gh_access_token.eq(""),
+                    users::gh_id.eq(0),
+                    users::is_admin.eq(false),
+                ))
+                .get_result(conn)
+        })
+    }
+
     /// Given this set of owners, determines the strongest rights the
     /// user has.
     ///
-    /// Shortcircuits on `Full` because you can't beat it. In practice we'll always
-    /// see `[user, user, user, ..., team, team, team]`, so we could shortcircuit on
-    /// `Publish` as well, but this is a non-obvious invariant so we don't bother.
-    /// Sweet free optimization if teams are proving burdensome to check.
-    /// More than one team isn't really expected, though.
-    pub fn rights(&self, app: &App, owners: &[Owner]) -> AppResult<Rights> {
+    /// Each owner grants the rights of its role. Shortcircuits on `Full` because you
+    /// can't beat it. Teams are only asked about their members if they could improve
+    /// on the rights found so far, since that may require a request to GitHub.
+    pub fn rights(
+        &self,
+        app: &App,
+        conn: &PgConnection,
+        owners: &[(Owner, OwnerRole)],
+    ) -> AppResult<Rights> {
         let mut best = Rights::None;
-        for owner in owners {
-            match *owner {
-                Owner::User(ref other_user) => {
-                    if other_user.id == self.id {
-                        return Ok(Rights::Full);
-                    }
-                }
-                Owner::Team(ref team) => {
-                    if team.contains_user(app, self)? {
-                        best = Rights::Publish;
-                    }
+        for (owner, role) in owners {
+            let rights = role.rights();
+            if rights <= best {
+                continue;
+            }
+            let is_member = match *owner {
+                Owner::User(ref other_user) => other_user.id == self.id,
+                Owner::Team(ref team) => team.contains_user(app, conn, self)?,
+            };
+            if is_member {
+                best = rights;
+                if best == Rights::Full {
+                    break;
                 }
             }
         }
         Ok(best)
     }
 
-    /// Queries the database for the verified emails
-    /// belonging to a given user
+    /// Queries the database for the verified email address notifications are sent to,
+    /// preferring the primary address
     pub fn verified_email(&self, conn: &PgConnection) -> QueryResult<Option<String>> {
         Email::belonging_to(self)
             .select(emails::email)
             .filter(emails::verified.eq(true))
+            .order((emails::is_primary.desc(), emails::id))
+            .first(&*conn)
+            .optional()
+    }
+
+    /// Queries the database for the verified email address that is recorded when the user
+    /// publishes a crate. Without a chosen address, this is the same as `verified_email`.
+    pub fn publish_email(&self, conn: &PgConnection) -> QueryResult<Option<String>> {
+        Email::belonging_to(self)
+            .select(emails::email)
+            .filter(emails::verified.eq(true))
+            .order((
+                emails::for_publishing.desc(),
+                emails::is_primary.desc(),
+                emails::id,
+            ))
             .first(&*conn)
             .optional()
     }
 
-    /// Queries for the email belonging to a particular user
+    /// Queries for the primary email belonging to a particular user
     pub fn email(&self, conn: &PgConnection) -> AppResult<Option<String>> {
         Ok(Email::belonging_to(self)
             .select(emails::email)
+            .filter(emails::is_primary.eq(true))
             .first(&*conn)
             .optional()?)
     }
 }
+
+fn anonymized_login(user_id: i32) -> String {
+    format!("deleted-user-{user_id}")
+}
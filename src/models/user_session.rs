@@ -0,0 +1,73 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+use crate::models::User;
+use crate::schema::user_sessions;
+use crate::util::rfc3339;
+
+/// How often the `last_used_at` column of a session is updated at most
+const LAST_USED_RESOLUTION_MINUTES: i64 = 5;
+
+/// A browser session of a user. The session cookie is only valid while this row exists.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations, Serialize)]
+#[belongs_to(User)]
+pub struct UserSession {
+    pub id: i64,
+    #[serde(skip)]
+    pub user_id: i32,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub last_used_at: NaiveDateTime,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl UserSession {
+    pub fn find(conn: &PgConnection, id: i64, user_id: i32) -> QueryResult<Option<UserSession>> {
+        user_sessions::table
+            .find(id)
+            .filter(user_sessions::user_id.eq(user_id))
+            .first(conn)
+            .optional()
+    }
+
+    /// Records that the session was just used. To avoid a write on every request, this only
+    /// updates sessions that have not been used for a few minutes.
+    pub fn touch(&self, conn: &PgConnection) -> QueryResult<()> {
+        let threshold = Utc::now().naive_utc() - Duration::minutes(LAST_USED_RESOLUTION_MINUTES);
+        if self.last_used_at < threshold {
+            diesel::update(self)
+                .set(user_sessions::last_used_at.eq(diesel::dsl::now))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    pub fn revoke(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::delete(self).execute(conn)?;
+        Ok(())
+    }
+
+    /// Logs the user out everywhere.
+    pub fn revoke_all(conn: &PgConnection, user_id: i32) -> QueryResult<usize> {
+        diesel::delete(user_sessions::table.filter(user_sessions::user_id.eq(user_id)))
+            .execute(conn)
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "user_sessions"]
+pub struct NewUserSession<'a> {
+    pub user_id: i32,
+    pub ip_address: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+}
+
+impl NewUserSession<'_> {
+    pub fn insert(&self, conn: &PgConnection) -> QueryResult<UserSession> {
+        diesel::insert_into(user_sessions::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
@@ -0,0 +1,262 @@
+//! This module implements verification of the OpenID Connect tokens that CI providers issue to
+//! their jobs, which trusted publishing exchanges for short-lived API tokens.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use reqwest::blocking::Client;
+use reqwest::header;
+
+use crate::util::errors::{bad_request, internal, AppError, AppResult};
+
+/// The issuer of the OIDC tokens available to GitHub Actions workflows.
+pub const GITHUB_ACTIONS_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+/// How long the keys of an issuer are used before they are fetched again.
+const JWKS_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Tokens signed with a key we do not know yet cause the keys to be fetched again, since the
+/// issuer may have rotated them. This limits how often that can happen per issuer.
+const JWKS_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The claims of a CI-issued token that trusted publishers are matched against.
+///
+/// The expiry, issuer and audience of the token are validated while decoding it.
+#[derive(Debug, Deserialize)]
+pub struct Claims {
+    pub iss: String,
+    /// The `owner/name` of the repository the workflow is running in
+    pub repository: String,
+    /// The workflow that is running, as `owner/name/.github/workflows/file.yml@ref`
+    pub workflow_ref: String,
+}
+
+impl Claims {
+    /// Returns the file name of the workflow that requested the token.
+    pub fn workflow_filename(&self) -> Option<&str> {
+        let (path, _git_ref) = self.workflow_ref.split_once('@')?;
+        let (repository, filename) = path.split_once("/.github/workflows/")?;
+        if repository.eq_ignore_ascii_case(&self.repository) {
+            Some(filename)
+        } else {
+            None
+        }
+    }
+}
+
+/// Provides the JSON Web Key Sets that issuers sign their tokens with.
+#[derive(Debug)]
+pub struct OidcKeyStore {
+    backend: KeyStoreBackend,
+}
+
+impl OidcKeyStore {
+    /// Create a key store that fetches the keys of an issuer from its OpenID configuration.
+    pub fn new(client: Option<Client>) -> Self {
+        Self {
+            backend: KeyStoreBackend::Remote {
+                client,
+                cache: Mutex::new(HashMap::new()),
+            },
+        }
+    }
+
+    /// Create a key store that serves a fixed set of keys for each issuer, allowing tests to sign
+    /// their own tokens without reaching out to a real issuer.
+    pub fn new_static(keys: HashMap<String, JwkSet>) -> Self {
+        Self {
+            backend: KeyStoreBackend::Static { keys },
+        }
+    }
+
+    /// Returns the keys of `issuer`, from the cache if they were fetched recently enough.
+    ///
+    /// With `refresh` the keys are fetched again unless that just happened, which is used when
+    /// a token was signed with a key that is not in the cached set.
+    fn jwks(&self, issuer: &str, refresh: bool) -> AppResult<JwkSet> {
+        match &self.backend {
+            KeyStoreBackend::Remote { client, cache } => {
+                let max_age = if refresh {
+                    JWKS_MIN_REFRESH_INTERVAL
+                } else {
+                    JWKS_CACHE_TTL
+                };
+                if let Some(cached) = cache.lock().unwrap().get(issuer) {
+                    if cached.fetched_at.elapsed() < max_age {
+                        return Ok(cached.keys.clone());
+                    }
+                }
+
+                let client = client
+                    .as_ref()
+                    .ok_or_else(|| internal("no HTTP client configured to fetch OIDC keys"))?;
+                let keys = fetch_jwks(client, issuer)?;
+
+                let cached = CachedJwks {
+                    keys: keys.clone(),
+                    fetched_at: Instant::now(),
+                };
+                cache.lock().unwrap().insert(issuer.to_string(), cached);
+
+                Ok(keys)
+            }
+            KeyStoreBackend::Static { keys } => keys
+                .get(issuer)
+                .cloned()
+                .ok_or_else(|| internal(&format_args!("no OIDC keys for {issuer}"))),
+        }
+    }
+
+    /// Verifies the signature, expiry and audience of a token issued by one of the
+    /// `allowed_issuers` and returns its claims.
+    pub fn verify(
+        &self,
+        jwt: &str,
+        audience: &str,
+        allowed_issuers: &[String],
+    ) -> AppResult<Claims> {
+        let issuer = unverified_issuer(jwt)?;
+        if !allowed_issuers.contains(&issuer) {
+            return Err(bad_request(&format_args!(
+                "tokens issued by `{issuer}` are not accepted"
+            )));
+        }
+
+        let header = jsonwebtoken::decode_header(jwt).map_err(|_| invalid_token())?;
+        let kid = header.kid.ok_or_else(invalid_token)?;
+
+        let mut jwks = self.jwks(&issuer, false)?;
+        if jwks.find(&kid).is_none() {
+            jwks = self.jwks(&issuer, true)?;
+        }
+        let jwk = jwks.find(&kid).ok_or_else(invalid_token)?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|_| invalid_token())?;
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&[audience]);
+        validation.set_issuer(&[&issuer]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+
+        let data = jsonwebtoken::decode::<Claims>(jwt, &key, &validation)
+            .map_err(|e| bad_request(&format_args!("invalid OIDC token: {e}")))?;
+
+        Ok(data.claims)
+    }
+}
+
+#[derive(Debug)]
+enum KeyStoreBackend {
+    /// Backend used in production, fetching the keys over HTTP.
+    Remote {
+        client: Option<Client>,
+        cache: Mutex<HashMap<String, CachedJwks>>,
+    },
+    /// Backend used during tests, serving keys generated by the tests themselves.
+    Static { keys: HashMap<String, JwkSet> },
+}
+
+#[derive(Debug)]
+struct CachedJwks {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+/// Fetches the keys of an issuer from the location given in its OpenID configuration.
+fn fetch_jwks(client: &Client, issuer: &str) -> AppResult<JwkSet> {
+    #[derive(Deserialize)]
+    struct OpenIdConfiguration {
+        jwks_uri: String,
+    }
+
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    );
+    let configuration: OpenIdConfiguration = fetch_json(client, &url)?;
+    fetch_json(client, &configuration.jwks_uri)
+}
+
+fn fetch_json<T: serde::de::DeserializeOwned>(client: &Client, url: &str) -> AppResult<T> {
+    info!("OIDC HTTP: {url}");
+
+    client
+        .get(url)
+        .header(header::USER_AGENT, "crates.io (https://crates.io)")
+        .send()?
+        .error_for_status()?
+        .json()
+        .map_err(Into::into)
+}
+
+/// Reads the issuer of a token before its signature was verified, so that we know which keys
+/// to verify it with.
+fn unverified_issuer(jwt: &str) -> AppResult<String> {
+    #[derive(Deserialize)]
+    struct UnverifiedClaims {
+        iss: String,
+    }
+
+    let payload = jwt.split('.').nth(1).ok_or_else(invalid_token)?;
+    let payload =
+        base64::decode_config(payload, base64::URL_SAFE_NO_PAD).map_err(|_| invalid_token())?;
+    let claims: UnverifiedClaims = serde_json::from_slice(&payload).map_err(|_| invalid_token())?;
+
+    Ok(claims.iss)
+}
+
+fn invalid_token() -> Box<dyn AppError> {
+    bad_request("invalid OIDC token")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(repository: &str, workflow_ref: &str) -> Claims {
+        Claims {
+            iss: GITHUB_ACTIONS_ISSUER.into(),
+            repository: repository.into(),
+            workflow_ref: workflow_ref.into(),
+        }
+    }
+
+    #[test]
+    fn workflow_filename() {
+        let c = claims(
+            "rust-lang/foo",
+            "rust-lang/foo/.github/workflows/release.yml@refs/tags/v1.0.0",
+        );
+        assert_eq!(c.workflow_filename(), Some("release.yml"));
+
+        let c = claims(
+            "Rust-Lang/foo",
+            "rust-lang/foo/.github/workflows/release.yml@refs/heads/main",
+        );
+        assert_eq!(c.workflow_filename(), Some("release.yml"));
+
+        let c = claims(
+            "rust-lang/foo",
+            "rust-lang/bar/.github/workflows/release.yml@refs/heads/main",
+        );
+        assert_eq!(c.workflow_filename(), None);
+
+        let c = claims("rust-lang/foo", "rust-lang/foo/release.yml@refs/heads/main");
+        assert_eq!(c.workflow_filename(), None);
+    }
+
+    #[test]
+    fn unverified_issuer_requires_a_payload() {
+        assert_err!(unverified_issuer("not-a-jwt"));
+        assert_err!(unverified_issuer("a.!!!.c"));
+
+        let payload =
+            base64::encode_config(r#"{"iss":"https://example.com"}"#, base64::URL_SAFE_NO_PAD);
+        assert_eq!(
+            unverified_issuer(&format!("header.{payload}.signature")).unwrap(),
+            "https://example.com"
+        );
+    }
+}
@@ -63,6 +63,55 @@ pub fn build_router(app: &App) -> RouteBuilder {
     api_router.get("/crates/:crate_id/following", C(krate::follow::following));
     api_router.get("/crates/:crate_id/owner_team", C(krate::owners::owner_team));
     api_router.get("/crates/:crate_id/owner_user", C(krate::owners::owner_user));
+    api_router.get(
+        "/crates/:crate_id/owner_invitations",
+        C(krate::owners::invitations),
+    );
+    api_router.put(
+        "/crates/:crate_id/owner_invitations/:user",
+        C(krate::owners::accept_team_invitation),
+    );
+    api_router.delete(
+        "/crates/:crate_id/owner_invitations/:user",
+        C(krate::owners::cancel_invitation),
+    );
+    api_router.get("/crates/:crate_id/audit_log", C(krate::owners::audit_log));
+    api_router.get(
+        "/crates/:crate_id/adoption_requests",
+        C(krate::adoption::list),
+    );
+    api_router.put(
+        "/crates/:crate_id/adoption_requests",
+        C(krate::adoption::request),
+    );
+    api_router.delete(
+        "/crates/:crate_id/adoption_requests/:id",
+        C(krate::adoption::resolve),
+    );
+    api_router.put(
+        "/crates/:crate_id/deprecation",
+        C(krate::deprecation::deprecate),
+    );
+    api_router.delete(
+        "/crates/:crate_id/deprecation",
+        C(krate::deprecation::undeprecate),
+    );
+    api_router.get(
+        "/crates/:crate_id/trusted_publishers",
+        C(krate::trusted_publishing::list),
+    );
+    api_router.put(
+        "/crates/:crate_id/trusted_publishers",
+        C(krate::trusted_publishing::create),
+    );
+    api_router.delete(
+        "/crates/:crate_id/trusted_publishers/:id",
+        C(krate::trusted_publishing::delete),
+    );
+    api_router.put(
+        "/crates/:crate_id/trusted_publishing_token",
+        C(krate::trusted_publishing::exchange_token),
+    );
     api_router.get(
         "/crates/:crate_id/reverse_dependencies",
         C(krate::metadata::reverse_dependencies),
@@ -81,6 +130,37 @@ pub fn build_router(app: &App) -> RouteBuilder {
     api_router.get("/me/tokens", C(token::list));
     api_router.put("/me/tokens", C(token::new));
     api_router.delete("/me/tokens/:id", C(token::revoke));
+    api_router.get("/me/tokens/:id/activity", C(token::activity));
+    api_router.get("/me/two_factor", C(user::two_factor::status));
+    api_router.put("/me/two_factor", C(user::two_factor::begin_enrollment));
+    api_router.delete("/me/two_factor", C(user::two_factor::disable));
+    api_router.put("/me/two_factor/enable", C(user::two_factor::enable));
+    api_router.put("/me/two_factor/confirm", C(user::two_factor::confirm));
+    api_router.put(
+        "/me/two_factor/recovery_codes",
+        C(user::two_factor::regenerate_recovery_codes),
+    );
+    api_router.get("/me/export", C(user::account::export));
+    api_router.delete("/me", C(user::account::delete));
+    api_router.get("/me/emails", C(user::me::emails));
+    api_router.put("/me/emails", C(user::me::add_email));
+    api_router.delete("/me/emails/:email_id", C(user::me::remove_email));
+    api_router.put(
+        "/me/emails/:email_id/primary",
+        C(user::me::make_email_primary),
+    );
+    api_router.put(
+        "/me/emails/:email_id/publish",
+        C(user::me::use_email_for_publishing),
+    );
+    api_router.put(
+        "/me/emails/:email_id/resend",
+        C(user::me::resend_email_confirmation),
+    );
+    api_router.get("/me/sessions", C(user::me::sessions));
+    api_router.delete("/me/sessions/:id", C(user::me::revoke_session));
+    api_router.get("/me/identities", C(user::me::identities));
+    api_router.delete("/me/identities/:provider", C(user::me::unlink_identity));
     api_router.delete("/tokens/current", C(token::revoke_current));
     api_router.get(
         "/me/crate_owner_invitations",
@@ -118,6 +198,10 @@ pub fn build_router(app: &App) -> RouteBuilder {
     router.delete("/api/v1/*path", R(api_router));
 
     // Session management
+    router.get(
+        "/api/private/session/providers",
+        C(user::session::providers),
+    );
     router.get("/api/private/session/begin", C(user::session::begin));
     router.get(
         "/api/private/session/authorize",
@@ -125,9 +209,62 @@ pub fn build_router(app: &App) -> RouteBuilder {
     );
     router.delete("/api/private/session", C(user::session::logout));
 
+    // Site administration
+    router.get("/api/private/admin/actions", C(admin::actions));
+    router.put(
+        "/api/private/admin/users/:user_id/lock",
+        C(admin::lock_user),
+    );
+    router.delete(
+        "/api/private/admin/users/:user_id/lock",
+        C(admin::unlock_user),
+    );
+    router.put(
+        "/api/private/admin/users/:user_id/transfer",
+        C(admin::transfer_crates),
+    );
+    router.put(
+        "/api/private/admin/users/:user_id/publish_rate_override",
+        C(admin::set_publish_rate_override),
+    );
+    router.delete(
+        "/api/private/admin/users/:user_id/publish_rate_override",
+        C(admin::remove_publish_rate_override),
+    );
+    router.delete(
+        "/api/private/admin/crates/:crate_id/:version",
+        C(admin::delete_version),
+    );
+    router.delete(
+        "/api/private/admin/crates/:crate_id/:version/yank",
+        C(admin::yank),
+    );
+    router.put(
+        "/api/private/admin/crates/:crate_id/:version/unyank",
+        C(admin::unyank),
+    );
+    router.get(
+        "/api/private/admin/adoption_requests",
+        C(admin::adoption_requests),
+    );
+    router.put(
+        "/api/private/admin/adoption_requests/:id/approve",
+        C(admin::approve_adoption),
+    );
+    router.put(
+        "/api/private/admin/adoption_requests/:id/reject",
+        C(admin::reject_adoption),
+    );
+
     // Metrics
     router.get("/api/private/metrics/:kind", C(metrics::prometheus));
 
+    // Leaked token reports from GitHub's secret scanning
+    router.post(
+        "/api/github/secret-scanning/verify",
+        C(github::secret_scanning::verify),
+    );
+
     // Crate ownership invitations management in the frontend
     router.get(
         "/api/private/crate_owner_invitations",
@@ -1,5 +1,111 @@
 #![allow(unused_imports)]
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `admin_actions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    admin_actions (id) {
+        /// The `id` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `admin_id` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        admin_id -> Int4,
+        /// The `action` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Varchar,
+        /// The `target` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        target -> Varchar,
+        /// The `details` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        details -> Jsonb,
+        /// The `created_at` column of the `admin_actions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `api_token_activity` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    api_token_activity (id) {
+        /// The `id` column of the `api_token_activity` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `api_token_id` column of the `api_token_activity` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        api_token_id -> Int4,
+        /// The `endpoint` column of the `api_token_activity` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        endpoint -> Varchar,
+        /// The `crate_name` column of the `api_token_activity` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_name -> Nullable<Varchar>,
+        /// The `version` column of the `api_token_activity` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version -> Nullable<Varchar>,
+        /// The `ip_address` column of the `api_token_activity` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        ip_address -> Nullable<Varchar>,
+        /// The `user_agent` column of the `api_token_activity` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_agent -> Nullable<Varchar>,
+        /// The `created_at` column of the `api_token_activity` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -50,6 +156,36 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         revoked -> Bool,
+        /// The `endpoint_scopes` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Array<Text>>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        endpoint_scopes -> Nullable<Array<Text>>,
+        /// The `crate_scopes` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Array<Text>>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_scopes -> Nullable<Array<Text>>,
+        /// The `expires_at` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Nullable<Timestamp>,
+        /// The `expiry_notification_at` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expiry_notification_at -> Nullable<Timestamp>,
+        /// The `trusted_publisher_id` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        trusted_publisher_id -> Nullable<Int4>,
     }
 }
 
@@ -176,6 +312,153 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_adoption_requests` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_adoption_requests (id) {
+        /// The `id` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `requested_by_user_id` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        requested_by_user_id -> Int4,
+        /// The `reason` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Varchar,
+        /// The `status` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> Int4,
+        /// The `created_at` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `resolved_by_user_id` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        resolved_by_user_id -> Nullable<Int4>,
+        /// The `resolved_at` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_aliases` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_aliases (name) {
+        /// The `name` column of the `crate_aliases` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `crate_id` column of the `crate_aliases` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `created_at` column of the `crate_aliases` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_owner_actions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_owner_actions (id) {
+        /// The `id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `user_id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `api_token_id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        api_token_id -> Nullable<Int4>,
+        /// The `action` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Int4,
+        /// The `subject` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        subject -> Nullable<Varchar>,
+        /// The `details` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        details -> Nullable<Varchar>,
+        /// The `time` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        time -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -220,6 +503,106 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         token_generated_at -> Nullable<Timestamp>,
+        /// The `role` column of the `crate_owner_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role -> Int4,
+        /// The `reminder_sent_at` column of the `crate_owner_invitations` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reminder_sent_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_owner_signup_invitations` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_owner_signup_invitations (gh_id, crate_id) {
+        /// The `gh_id` column of the `crate_owner_signup_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        gh_id -> Int4,
+        /// The `gh_login` column of the `crate_owner_signup_invitations` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        gh_login -> Varchar,
+        /// The `crate_id` column of the `crate_owner_signup_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `invited_by_user_id` column of the `crate_owner_signup_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        invited_by_user_id -> Int4,
+        /// The `role` column of the `crate_owner_signup_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role -> Int4,
+        /// The `created_at` column of the `crate_owner_signup_invitations` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_owner_team_invitations` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_owner_team_invitations (team_id, crate_id) {
+        /// The `team_id` column of the `crate_owner_team_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        team_id -> Int4,
+        /// The `crate_id` column of the `crate_owner_team_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `invited_by_user_id` column of the `crate_owner_team_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        invited_by_user_id -> Int4,
+        /// The `role` column of the `crate_owner_team_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role -> Int4,
+        /// The `created_at` column of the `crate_owner_team_invitations` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
     }
 }
 
@@ -279,6 +662,12 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         email_notifications -> Bool,
+        /// The `role` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role -> Int4,
     }
 }
 
@@ -362,6 +751,24 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         max_upload_size -> Nullable<Int4>,
+        /// The `deprecated_at` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deprecated_at -> Nullable<Timestamp>,
+        /// The `deprecation_message` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deprecation_message -> Nullable<Varchar>,
+        /// The `deprecation_successor` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deprecation_successor -> Nullable<Varchar>,
     }
 }
 
@@ -407,7 +814,72 @@ table! {
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        keyword_id -> Int4,
+        keyword_id -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `dependencies` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    dependencies (id) {
+        /// The `id` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `version_id` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The `crate_id` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `req` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        req -> Varchar,
+        /// The `optional` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        optional -> Bool,
+        /// The `default_features` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        default_features -> Bool,
+        /// The `features` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        features -> Array<Text>,
+        /// The `target` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        target -> Nullable<Varchar>,
+        /// The `kind` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Int4,
     }
 }
 
@@ -415,64 +887,64 @@ table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
 
-    /// Representation of the `dependencies` table.
+    /// Representation of the `email_sends` table.
     ///
     /// (Automatically generated by Diesel.)
-    dependencies (id) {
-        /// The `id` column of the `dependencies` table.
+    email_sends (id) {
+        /// The `id` column of the `email_sends` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         id -> Int4,
-        /// The `version_id` column of the `dependencies` table.
+        /// The `recipient` column of the `email_sends` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        version_id -> Int4,
-        /// The `crate_id` column of the `dependencies` table.
+        recipient -> Varchar,
+        /// The `template` column of the `email_sends` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        crate_id -> Int4,
-        /// The `req` column of the `dependencies` table.
+        template -> Varchar,
+        /// The `subject` column of the `email_sends` table.
         ///
         /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        req -> Varchar,
-        /// The `optional` column of the `dependencies` table.
+        subject -> Varchar,
+        /// The `status` column of the `email_sends` table.
         ///
-        /// Its SQL type is `Bool`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        optional -> Bool,
-        /// The `default_features` column of the `dependencies` table.
+        status -> Int4,
+        /// The `attempts` column of the `email_sends` table.
         ///
-        /// Its SQL type is `Bool`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        default_features -> Bool,
-        /// The `features` column of the `dependencies` table.
+        attempts -> Int4,
+        /// The `last_error` column of the `email_sends` table.
         ///
-        /// Its SQL type is `Array<Text>`.
+        /// Its SQL type is `Nullable<Varchar>`.
         ///
         /// (Automatically generated by Diesel.)
-        features -> Array<Text>,
-        /// The `target` column of the `dependencies` table.
+        last_error -> Nullable<Varchar>,
+        /// The `created_at` column of the `email_sends` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        target -> Nullable<Varchar>,
-        /// The `kind` column of the `dependencies` table.
+        created_at -> Timestamp,
+        /// The `sent_at` column of the `email_sends` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Nullable<Timestamp>`.
         ///
         /// (Automatically generated by Diesel.)
-        kind -> Int4,
+        sent_at -> Nullable<Timestamp>,
     }
 }
 
@@ -520,6 +992,18 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         token_generated_at -> Nullable<Timestamp>,
+        /// The `is_primary` column of the `emails` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_primary -> Bool,
+        /// The `for_publishing` column of the `emails` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        for_publishing -> Bool,
     }
 }
 
@@ -546,6 +1030,41 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `github_team_memberships` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    github_team_memberships (team_id, user_id) {
+        /// The `team_id` column of the `github_team_memberships` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        team_id -> Int4,
+        /// The `user_id` column of the `github_team_memberships` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `is_member` column of the `github_team_memberships` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_member -> Bool,
+        /// The `checked_at` column of the `github_team_memberships` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        checked_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -581,6 +1100,59 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `linked_identities` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    linked_identities (id) {
+        /// The `id` column of the `linked_identities` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `linked_identities` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `provider` column of the `linked_identities` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        provider -> Varchar,
+        /// The `provider_user_id` column of the `linked_identities` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        provider_user_id -> Varchar,
+        /// The `login` column of the `linked_identities` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        login -> Varchar,
+        /// The `created_at` column of the `linked_identities` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `last_login_at` column of the `linked_identities` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_login_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -761,6 +1333,194 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `totp_credentials` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    totp_credentials (user_id) {
+        /// The `user_id` column of the `totp_credentials` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `secret` column of the `totp_credentials` table.
+        ///
+        /// Its SQL type is `Bytea`.
+        ///
+        /// (Automatically generated by Diesel.)
+        secret -> Bytea,
+        /// The `enabled_at` column of the `totp_credentials` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        enabled_at -> Nullable<Timestamp>,
+        /// The `last_used_step` column of the `totp_credentials` table.
+        ///
+        /// Its SQL type is `Nullable<Int8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_used_step -> Nullable<Int8>,
+        /// The `created_at` column of the `totp_credentials` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `failed_attempts` column of the `totp_credentials` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        failed_attempts -> Int4,
+        /// The `locked_until` column of the `totp_credentials` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        locked_until -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `totp_recovery_codes` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    totp_recovery_codes (id) {
+        /// The `id` column of the `totp_recovery_codes` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `totp_recovery_codes` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `digest` column of the `totp_recovery_codes` table.
+        ///
+        /// Its SQL type is `Bytea`.
+        ///
+        /// (Automatically generated by Diesel.)
+        digest -> Bytea,
+        /// The `used_at` column of the `totp_recovery_codes` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `trusted_publishers` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    trusted_publishers (id) {
+        /// The `id` column of the `trusted_publishers` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `trusted_publishers` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `created_by` column of the `trusted_publishers` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_by -> Int4,
+        /// The `issuer` column of the `trusted_publishers` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        issuer -> Varchar,
+        /// The `repository` column of the `trusted_publishers` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        repository -> Varchar,
+        /// The `workflow` column of the `trusted_publishers` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        workflow -> Varchar,
+        /// The `created_at` column of the `trusted_publishers` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `user_sessions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    user_sessions (id) {
+        /// The `id` column of the `user_sessions` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `user_id` column of the `user_sessions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `created_at` column of the `user_sessions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `last_used_at` column of the `user_sessions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_used_at -> Timestamp,
+        /// The `ip_address` column of the `user_sessions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        ip_address -> Nullable<Varchar>,
+        /// The `user_agent` column of the `user_sessions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_agent -> Nullable<Varchar>,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -817,6 +1577,12 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         account_lock_until -> Nullable<Timestamp>,
+        /// The `is_admin` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_admin -> Bool,
     }
 }
 
@@ -1008,9 +1774,22 @@ table! {
     }
 }
 
+joinable!(admin_actions -> users (admin_id));
+joinable!(api_token_activity -> api_tokens (api_token_id));
+joinable!(api_tokens -> trusted_publishers (trusted_publisher_id));
 joinable!(api_tokens -> users (user_id));
 joinable!(badges -> crates (crate_id));
+joinable!(crate_adoption_requests -> crates (crate_id));
+joinable!(crate_adoption_requests -> users (requested_by_user_id));
+joinable!(crate_aliases -> crates (crate_id));
+joinable!(crate_owner_actions -> api_tokens (api_token_id));
+joinable!(crate_owner_actions -> crates (crate_id));
+joinable!(crate_owner_actions -> users (user_id));
 joinable!(crate_owner_invitations -> crates (crate_id));
+joinable!(crate_owner_signup_invitations -> crates (crate_id));
+joinable!(crate_owner_signup_invitations -> users (invited_by_user_id));
+joinable!(crate_owner_team_invitations -> crates (crate_id));
+joinable!(crate_owner_team_invitations -> teams (team_id));
 joinable!(crate_owners -> crates (crate_id));
 joinable!(crate_owners -> teams (owner_id));
 joinable!(crate_owners -> users (owner_id));
@@ -1023,10 +1802,18 @@ joinable!(dependencies -> versions (version_id));
 joinable!(emails -> users (user_id));
 joinable!(follows -> crates (crate_id));
 joinable!(follows -> users (user_id));
+joinable!(github_team_memberships -> teams (team_id));
+joinable!(github_team_memberships -> users (user_id));
+joinable!(linked_identities -> users (user_id));
 joinable!(publish_limit_buckets -> users (user_id));
 joinable!(publish_rate_overrides -> users (user_id));
 joinable!(readme_renderings -> versions (version_id));
 joinable!(recent_crate_downloads -> crates (crate_id));
+joinable!(totp_credentials -> users (user_id));
+joinable!(totp_recovery_codes -> users (user_id));
+joinable!(trusted_publishers -> crates (crate_id));
+joinable!(trusted_publishers -> users (created_by));
+joinable!(user_sessions -> users (user_id));
 joinable!(version_downloads -> versions (version_id));
 joinable!(version_owner_actions -> api_tokens (api_token_id));
 joinable!(version_owner_actions -> users (user_id));
@@ -1036,19 +1823,29 @@ joinable!(versions -> users (published_by));
 joinable!(versions_published_by -> versions (version_id));
 
 allow_tables_to_appear_in_same_query!(
+    admin_actions,
+    api_token_activity,
     api_tokens,
     background_jobs,
     badges,
     categories,
+    crate_adoption_requests,
+    crate_aliases,
+    crate_owner_actions,
     crate_owner_invitations,
+    crate_owner_signup_invitations,
+    crate_owner_team_invitations,
     crate_owners,
     crates,
     crates_categories,
     crates_keywords,
     dependencies,
+    email_sends,
     emails,
     follows,
+    github_team_memberships,
     keywords,
+    linked_identities,
     metadata,
     publish_limit_buckets,
     publish_rate_overrides,
@@ -1056,6 +1853,10 @@ allow_tables_to_appear_in_same_query!(
     recent_crate_downloads,
     reserved_crate_names,
     teams,
+    totp_credentials,
+    totp_recovery_codes,
+    trusted_publishers,
+    user_sessions,
     users,
     version_downloads,
     version_owner_actions,
@@ -0,0 +1,208 @@
+use crate::builders::CrateBuilder;
+use crate::util::{RequestHelper, Response};
+use crate::{new_team, OkBool, TestApp};
+use cargo_registry::github::GithubUser;
+use cargo_registry::models::{
+    Crate, CrateOwnerSignupInvitation, CrateOwnerTeamInvitation, OwnerRole,
+};
+use cargo_registry::schema::{
+    crate_owner_signup_invitations, crate_owner_team_invitations, users, versions_published_by,
+};
+use conduit::StatusCode;
+use diesel::prelude::*;
+
+fn crate_owners(app: &TestApp, name: &str) -> Vec<String> {
+    app.db(|conn| {
+        Crate::by_name(name)
+            .first::<Crate>(conn)
+            .unwrap()
+            .owners(conn)
+            .unwrap()
+            .iter()
+            .map(|owner| owner.login().to_string())
+            .collect()
+    })
+}
+
+#[test]
+fn export_contains_the_account_data() {
+    let (app, _, user, _token) = TestApp::init().with_token();
+    app.db(|conn| CrateBuilder::new("foo_export", user.as_model().id).expect_build(conn));
+    user.put::<OkBool>("/api/v1/crates/foo_export/follow", b"")
+        .good();
+
+    let json = user.get::<serde_json::Value>("/api/v1/me/export").good();
+    assert_eq!(json["user"]["login"], "foo");
+    assert_eq!(json["emails"][0]["email"], "something@example.com");
+    assert_eq!(json["api_tokens"].as_array().unwrap().len(), 1);
+    assert_eq!(json["api_tokens"][0]["revoked"], false);
+    assert_eq!(json["sessions"].as_array().unwrap().len(), 1);
+    assert_eq!(json["owned_crates"][0]["name"], "foo_export");
+    assert_eq!(json["follows"], json!(["foo_export"]));
+    assert_eq!(
+        json["published_versions"],
+        json!([{ "crate": "foo_export", "version": "1.0.0", "email": "someone@example.com" }])
+    );
+}
+
+#[test]
+fn export_is_not_available_to_api_tokens() {
+    let (_, _, _, token) = TestApp::init().with_token();
+    let response = token.get::<()>("/api/v1/me/export");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn deleting_an_account_can_transfer_crates() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    let user_id = user.as_model().id;
+    let other = app.db_new_user("other");
+    app.db(|conn| CrateBuilder::new("foo_transfer", user_id).expect_build(conn));
+
+    let body = br#"{"crates":"transfer","to":"other"}"#;
+    user.delete_with_body::<OkBool>("/api/v1/me", body).good();
+
+    assert_eq!(crate_owners(&app, "foo_transfer"), vec!["other"]);
+    let login: String = app.db(|conn| {
+        users::table
+            .find(user_id)
+            .select(users::gh_login)
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(login, format!("deleted-user-{user_id}"));
+    let published_by_emails: i64 = app.db(|conn| {
+        versions_published_by::table
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(published_by_emails, 0);
+
+    let response = user.get::<()>("/api/v1/me");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = token.get::<()>("/api/v1/me/updates");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    other.get::<serde_json::Value>("/api/v1/me").good();
+}
+
+#[test]
+fn crates_cannot_be_transferred_to_locked_accounts() {
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+    app.db(|conn| {
+        CrateBuilder::new("foo_locked_transfer", user.as_model().id).expect_build(conn);
+        other.as_model().lock(conn, "spam", None).unwrap();
+    });
+
+    let body = br#"{"crates":"transfer","to":"other"}"#;
+    let response = user.delete_with_body::<()>("/api/v1/me", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(crate_owners(&app, "foo_locked_transfer"), vec!["foo"]);
+}
+
+#[test]
+fn deleting_an_account_cancels_the_team_invitations_it_sent() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        let krate = CrateBuilder::new("foo_team_invite", user.as_model().id).expect_build(conn);
+        let team = new_team("github:test_org:core")
+            .create_or_update(conn)
+            .unwrap();
+        let config = &app.as_inner().config;
+        let invited = CrateOwnerTeamInvitation::create(
+            team.id,
+            user.as_model().id,
+            krate.id,
+            OwnerRole::Full,
+            conn,
+            config,
+        )
+        .unwrap();
+        assert!(invited);
+    });
+
+    user.delete_with_body::<OkBool>("/api/v1/me", br#"{"crates":"orphan"}"#)
+        .good();
+
+    let invitations: i64 = app.db(|conn| {
+        crate_owner_team_invitations::table
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(invitations, 0);
+}
+
+#[test]
+fn deleting_an_account_cancels_the_signup_invitations_it_sent() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        let krate = CrateBuilder::new("foo_signup_invite", user.as_model().id).expect_build(conn);
+        let github_user = GithubUser {
+            avatar_url: None,
+            email: None,
+            id: 424242,
+            login: "not-signed-up".into(),
+            name: None,
+        };
+        let config = &app.as_inner().config;
+        let invited = CrateOwnerSignupInvitation::create(
+            &github_user,
+            user.as_model().id,
+            krate.id,
+            OwnerRole::Full,
+            conn,
+            config,
+        )
+        .unwrap();
+        assert!(invited);
+    });
+
+    user.delete_with_body::<OkBool>("/api/v1/me", br#"{"crates":"orphan"}"#)
+        .good();
+
+    let invitations: i64 = app.db(|conn| {
+        crate_owner_signup_invitations::table
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(invitations, 0);
+}
+
+#[test]
+fn deleting_an_account_can_orphan_crates() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("foo_orphan", user.as_model().id).expect_build(conn));
+
+    user.delete_with_body::<OkBool>("/api/v1/me", br#"{"crates":"orphan"}"#)
+        .good();
+
+    assert!(crate_owners(&app, "foo_orphan").is_empty());
+}
+
+#[test]
+fn deleting_an_account_requires_a_valid_request() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let assert_bad_request = |response: Response<()>| {
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    };
+    assert_bad_request(user.delete_with_body("/api/v1/me", b"{}"));
+    assert_bad_request(
+        user.delete_with_body("/api/v1/me", br#"{"crates":"transfer","to":"nobody"}"#),
+    );
+    assert_bad_request(user.delete_with_body("/api/v1/me", br#"{"crates":"transfer","to":"FOO"}"#));
+
+    // The account is still usable
+    user.get::<serde_json::Value>("/api/v1/me").good();
+    let login: String = app.db(|conn| {
+        users::table
+            .find(user.as_model().id)
+            .select(users::gh_login)
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(login, "foo");
+}
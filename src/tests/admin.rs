@@ -0,0 +1,187 @@
+use crate::builders::CrateBuilder;
+use crate::util::{MockCookieUser, RequestHelper};
+use crate::{OkBool, TestApp};
+use cargo_registry::models::{User, Version};
+use cargo_registry::schema::{publish_rate_overrides, users, versions};
+use conduit::StatusCode;
+use diesel::prelude::*;
+
+pub fn make_admin(app: &TestApp, user: &MockCookieUser) {
+    app.db(|conn| {
+        diesel::update(users::table.find(user.as_model().id))
+            .set(users::is_admin.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+fn audit_log(admin: &MockCookieUser) -> Vec<serde_json::Value> {
+    let json = admin
+        .get::<serde_json::Value>("/api/private/admin/actions")
+        .good();
+    json["actions"].as_array().unwrap().clone()
+}
+
+#[test]
+fn admin_routes_require_admins() {
+    let (app, _, user, token) = TestApp::init().with_token();
+
+    let response = user.get::<()>("/api/private/admin/actions");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let url = format!("/api/private/admin/users/{}/lock", user.as_model().id);
+    let response = user.put::<()>(&url, br#"{"reason":"x"}"#);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Admins cannot use API tokens for admin actions
+    make_admin(&app, &user);
+    let response = token.get::<()>("/api/private/admin/actions");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let json = user.get::<serde_json::Value>("/api/v1/me").good();
+    assert_eq!(json["user"]["is_admin"], true);
+    assert!(audit_log(&user).is_empty());
+}
+
+#[test]
+fn admins_can_lock_and_unlock_users() {
+    let (app, _, admin) = TestApp::init().with_user();
+    make_admin(&app, &admin);
+    let user = app.db_new_user("spammer");
+    let url = format!("/api/private/admin/users/{}/lock", user.as_model().id);
+
+    let body = br#"{"reason":"spam","until":"2100-01-01T00:00:00Z"}"#;
+    admin.put::<OkBool>(&url, body).good();
+    let response = user.get::<()>("/api/v1/me");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = admin.put::<()>(&url, br#"{"reason":""}"#);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = admin.put::<()>(
+        "/api/private/admin/users/spammer/lock",
+        br#"{"reason":"x"}"#,
+    );
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = admin.put::<()>("/api/private/admin/users/0/lock", br#"{"reason":"x"}"#);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    admin.delete::<OkBool>(&url).good();
+
+    // Locking the account revoked its sessions, so the user has to log in again
+    let response = user.get::<()>("/api/v1/me");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let user = MockCookieUser::new(&app, user.as_model().clone());
+    user.get::<serde_json::Value>("/api/v1/me").good();
+
+    let log = audit_log(&admin);
+    assert_eq!(log.len(), 2);
+    assert_eq!(log[0]["admin"], "foo");
+    assert_eq!(log[0]["action"]["action"], "unlock_user");
+    assert_eq!(log[1]["action"]["action"], "lock_user");
+    assert_eq!(log[1]["action"]["target"], "spammer");
+    assert_eq!(log[1]["action"]["details"]["reason"], "spam");
+}
+
+#[test]
+fn admins_can_yank_and_delete_versions() {
+    let (app, _, admin) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_user();
+    make_admin(&app, &admin);
+    let owner = app.db_new_user("owner");
+    app.db(|conn| {
+        CrateBuilder::new("foo_admin", owner.as_model().id)
+            .version("1.0.0")
+            .version("1.1.0")
+            .expect_build(conn);
+    });
+    app.add_versions_to_index("foo_admin", &["1.0.0", "1.1.0"]);
+
+    admin
+        .delete::<OkBool>("/api/private/admin/crates/foo_admin/1.1.0/yank")
+        .good();
+    let json = owner.show_version("foo_admin", "1.1.0");
+    assert!(json.version.yanked);
+
+    admin
+        .put::<OkBool>("/api/private/admin/crates/foo_admin/1.1.0/unyank", b"")
+        .good();
+    let json = owner.show_version("foo_admin", "1.1.0");
+    assert!(!json.version.yanked);
+
+    admin
+        .delete::<OkBool>("/api/private/admin/crates/foo_admin/1.0.0")
+        .good();
+    let remaining: Vec<Version> = app.db(|conn| versions::table.load(conn).unwrap());
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].num, "1.1.0");
+
+    let log = audit_log(&admin);
+    let actions = log
+        .iter()
+        .map(|entry| entry["action"]["action"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(actions, ["delete_version", "unyank", "yank"]);
+    assert_eq!(log[0]["action"]["target"], "foo_admin@1.0.0");
+}
+
+#[test]
+fn admins_can_transfer_crates() {
+    let (app, _, admin) = TestApp::init().with_user();
+    make_admin(&app, &admin);
+    let from = app.db_new_user("from");
+    let to = app.db_new_user("to");
+    app.db(|conn| {
+        CrateBuilder::new("foo_transfer", from.as_model().id).expect_build(conn);
+        CrateBuilder::new("bar_transfer", from.as_model().id).expect_build(conn);
+    });
+
+    let url = format!("/api/private/admin/users/{}/transfer", from.as_model().id);
+    let body = json!({ "to": to.as_model().id }).to_string();
+    let json = admin.put::<serde_json::Value>(&url, body.as_bytes()).good();
+    assert_eq!(json["crates"], json!(["bar_transfer", "foo_transfer"]));
+
+    let owners = to.show_crate_owners("foo_transfer");
+    assert_eq!(owners.users.len(), 1);
+    assert_eq!(owners.users[0].login, "to");
+
+    let url = format!("/api/private/admin/users/{}/transfer", to.as_model().id);
+    let response = admin.put::<()>(&url, body.as_bytes());
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn admins_can_set_publish_rate_overrides() {
+    let (app, _, admin) = TestApp::init().with_user();
+    make_admin(&app, &admin);
+    let user = app.db_new_user("prolific");
+    let user_id = user.as_model().id;
+
+    let url = format!("/api/private/admin/users/{user_id}/publish_rate_override");
+    admin.put::<OkBool>(&url, br#"{"burst":100}"#).good();
+    admin.put::<OkBool>(&url, br#"{"burst":200}"#).good();
+    let burst: i32 = app.db(|conn| {
+        publish_rate_overrides::table
+            .find(user_id)
+            .select(publish_rate_overrides::burst)
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(burst, 200);
+
+    let response = admin.put::<()>(&url, br#"{"burst":0}"#);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    admin.delete::<OkBool>(&url).good();
+    let count: i64 = app.db(|conn| {
+        publish_rate_overrides::table
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(count, 0);
+
+    let user: User = app.db(|conn| users::table.find(user_id).first(conn).unwrap());
+    assert!(!user.is_admin);
+    assert_eq!(audit_log(&admin).len(), 3);
+}
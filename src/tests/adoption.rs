@@ -0,0 +1,220 @@
+use crate::admin::make_admin;
+use crate::builders::CrateBuilder;
+use crate::util::{MockCookieUser, RequestHelper};
+use crate::{add_team_to_crate, new_team, OkBool, TestApp};
+use cargo_registry::models::{OwnerKind, MAX_ADOPTION_REQUESTS_PER_DAY};
+use cargo_registry::schema::{crate_adoption_requests, crate_owners};
+use chrono::{Duration, Utc};
+use conduit::StatusCode;
+use diesel::prelude::*;
+
+const URL: &str = "/api/v1/crates/abandoned/adoption_requests";
+
+fn request_adoption(user: &MockCookieUser) -> i64 {
+    let body = br#"{"reason":"the owner has not been seen in years"}"#;
+    let json = user.put::<serde_json::Value>(URL, body).good();
+    assert_eq!(json["adoption_request"]["crate"], "abandoned");
+    assert_eq!(json["adoption_request"]["status"], "pending");
+    json["adoption_request"]["id"].as_i64().unwrap()
+}
+
+fn end_waiting_period(app: &TestApp) {
+    app.db(|conn| {
+        diesel::update(crate_adoption_requests::table)
+            .set(
+                crate_adoption_requests::created_at.eq(Utc::now().naive_utc() - Duration::days(31)),
+            )
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+#[test]
+fn owners_are_emailed_and_can_object() {
+    let (app, anon, owner) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("abandoned", owner.as_model().id).expect_build(conn));
+    let adopter = app.db_new_user("adopter");
+
+    let response = adopter.put::<()>(URL, br#"{"reason":" "}"#);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = owner.put::<()>(URL, br#"{"reason":"mine"}"#);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let id = request_adoption(&adopter);
+    let response = adopter.put::<()>(URL, br#"{"reason":"again"}"#);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].subject, "Request to adopt the crate abandoned");
+    assert!(emails[0]
+        .body
+        .contains("adopter has asked to become the owner"));
+
+    // Only owners can see the pending requests
+    let json = owner.get::<serde_json::Value>(URL).good();
+    assert_eq!(json["adoption_requests"][0]["requested_by"], "adopter");
+    let response = adopter.get::<()>(URL);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = anon.get::<()>(URL);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let other = app.db_new_user("other");
+    let response = other.delete::<()>(&format!("{URL}/{id}"));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    owner.delete::<OkBool>(&format!("{URL}/{id}")).good();
+    let json = owner.get::<serde_json::Value>(URL).good();
+    assert_eq!(json["adoption_requests"].as_array().unwrap().len(), 0);
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 2);
+    assert!(emails[1].body.contains("has been objected to by an owner"));
+
+    // The objection cannot be undone by withdrawing the request
+    let response = adopter.delete::<()>(&format!("{URL}/{id}"));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Nothing is left for the admins to review
+    end_waiting_period(&app);
+    make_admin(&app, &owner);
+    let json = owner
+        .get::<serde_json::Value>("/api/private/admin/adoption_requests")
+        .good();
+    assert_eq!(json["adoption_requests"].as_array().unwrap().len(), 0);
+}
+
+#[test]
+fn admins_approve_requests_after_the_waiting_period() {
+    let (app, _, owner) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("abandoned", owner.as_model().id).expect_build(conn));
+    let adopter = app.db_new_user("adopter");
+    let admin = app.db_new_user("admin");
+    make_admin(&app, &admin);
+
+    let id = request_adoption(&adopter);
+    let approve = format!("/api/private/admin/adoption_requests/{id}/approve");
+
+    // Non-admins cannot review requests
+    let response = adopter.put::<()>(&approve, b"");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = adopter.get::<()>("/api/private/admin/adoption_requests");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // The owners still have time to object
+    let json = admin
+        .get::<serde_json::Value>("/api/private/admin/adoption_requests")
+        .good();
+    assert_eq!(json["adoption_requests"].as_array().unwrap().len(), 0);
+    let response = admin.put::<()>(&approve, b"");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    end_waiting_period(&app);
+    let json = admin
+        .get::<serde_json::Value>("/api/private/admin/adoption_requests")
+        .good();
+    assert_eq!(json["adoption_requests"][0]["id"], id);
+    admin.put::<OkBool>(&approve, b"").good();
+
+    let json = adopter
+        .get::<serde_json::Value>("/api/v1/crates/abandoned/owners")
+        .good();
+    let owners = json["users"].as_array().unwrap();
+    assert_eq!(owners.len(), 1);
+    assert_eq!(owners[0]["login"], "adopter");
+    assert_eq!(owners[0]["role"], "full");
+
+    let json = adopter
+        .get::<serde_json::Value>("/api/v1/crates/abandoned/audit_log")
+        .good();
+    let action = &json["actions"][0];
+    assert_eq!(action["action"], "adopt_crate");
+    assert_eq!(action["user"]["login"], "admin");
+    assert_eq!(action["subject"], "adopter");
+    assert_eq!(action["details"], "foo");
+
+    let json = admin
+        .get::<serde_json::Value>("/api/private/admin/actions")
+        .good();
+    let action = &json["actions"][0]["action"];
+    assert_eq!(action["action"], "approve_adoption");
+    assert_eq!(action["target"], "abandoned");
+    assert_eq!(action["details"]["previous_owners"], json!(["foo"]));
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert!(emails
+        .last()
+        .unwrap()
+        .body
+        .contains("adopt the crate abandoned has been approved"));
+
+    // The request cannot be approved twice
+    let response = admin.put::<()>(&approve, b"");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn admins_can_reject_requests() {
+    let (app, _, owner) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("abandoned", owner.as_model().id).expect_build(conn));
+    let adopter = app.db_new_user("adopter");
+    let admin = app.db_new_user("admin");
+    make_admin(&app, &admin);
+
+    let id = request_adoption(&adopter);
+    admin
+        .put::<OkBool>(
+            &format!("/api/private/admin/adoption_requests/{id}/reject"),
+            b"",
+        )
+        .good();
+
+    let json = owner
+        .get::<serde_json::Value>("/api/v1/crates/abandoned/owners")
+        .good();
+    assert_eq!(json["users"][0]["login"], "foo");
+
+    // A rejected request does not prevent asking again
+    request_adoption(&adopter);
+}
+
+#[test]
+fn crates_only_owned_by_teams_cannot_be_adopted() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let adopter = app.db_new_user("adopter");
+    app.db(|conn| {
+        let user = owner.as_model();
+        let krate = CrateBuilder::new("abandoned", user.id).expect_build(conn);
+        let team = new_team("github:test_org:core")
+            .create_or_update(conn)
+            .unwrap();
+        add_team_to_crate(&team, &krate, user, conn).unwrap();
+        diesel::update(crate_owners::table.find((krate.id, user.id, OwnerKind::User as i32)))
+            .set(crate_owners::deleted.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let body = br#"{"reason":"the team has not been seen in years"}"#;
+    let response = adopter.put::<()>(URL, body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn adoption_requests_are_rate_limited() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let adopter = app.db_new_user("adopter");
+    let body = br#"{"reason":"the owner has not been seen in years"}"#;
+
+    for i in 0..=MAX_ADOPTION_REQUESTS_PER_DAY {
+        let name = format!("abandoned_{i}");
+        app.db(|conn| CrateBuilder::new(&name, owner.as_model().id).expect_build(conn));
+        let url = format!("/api/v1/crates/{name}/adoption_requests");
+        let response = adopter.put::<serde_json::Value>(&url, body);
+        if i < MAX_ADOPTION_REQUESTS_PER_DAY {
+            response.good();
+        } else {
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        }
+    }
+}
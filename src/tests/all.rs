@@ -15,7 +15,7 @@ extern crate tracing;
 
 use crate::util::{RequestHelper, TestApp};
 use cargo_registry::{
-    models::{Crate, CrateOwner, NewCategory, NewTeam, NewUser, Team, User},
+    models::{Crate, CrateOwner, NewCategory, NewTeam, NewUser, OwnerRole, Team, User},
     schema::crate_owners,
     views::{
         EncodableCategory, EncodableCategoryWithSubcategories, EncodableCrate, EncodableKeyword,
@@ -29,7 +29,10 @@ use std::{
 
 use diesel::prelude::*;
 
+mod account;
 mod account_lock;
+mod admin;
+mod adoption;
 mod authentication;
 mod badge;
 mod blocked_routes;
@@ -37,19 +40,25 @@ mod builders;
 mod categories;
 mod category;
 mod dump_db;
+mod emails;
 mod git;
 mod keyword;
 mod krate;
+mod login_providers;
 mod metrics;
 mod not_found_error;
 mod owners;
 mod read_only_mode;
 mod record;
 mod schema_details;
+mod secret_scanning;
 mod server;
 mod server_binary;
+mod sessions;
 mod team;
 mod token;
+mod trusted_publishing;
+mod two_factor;
 mod unhealthy_database;
 mod user;
 mod util;
@@ -145,6 +154,7 @@ fn add_team_to_crate(t: &Team, krate: &Crate, u: &User, conn: &PgConnection) ->
         created_by: u.id,
         owner_kind: 1, // Team owner kind is 1 according to owner.rs
         email_notifications: true,
+        role: OwnerRole::Publish,
     };
 
     diesel::insert_into(crate_owners::table)
@@ -0,0 +1,201 @@
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::email::EmailToken;
+use cargo_registry::models::{Email, EmailSend, EmailStatus, MAX_EMAIL_ATTEMPTS};
+use cargo_registry::schema::{email_sends, emails};
+use cargo_registry::worker;
+use diesel::dsl::IntervalDsl;
+use diesel::prelude::*;
+use swirl::schema::background_jobs;
+use swirl::Job;
+
+/// Enqueues a confirmation email to `recipient`, as if it had been sent through `Emails`.
+fn enqueue_email(conn: &PgConnection, recipient: &str) -> EmailSend {
+    let email = EmailSend::create(conn, recipient, "user_confirm", "Confirm").unwrap();
+    let context = json!({ "user_name": "foo", "token": "some-token" });
+    worker::send_email(
+        email.id,
+        recipient.into(),
+        "user_confirm".into(),
+        context,
+        None,
+    )
+    .enqueue(conn)
+    .unwrap();
+    email
+}
+
+#[test]
+fn sent_emails_are_recorded() {
+    let (app, _, user) = TestApp::init().with_user();
+    user.put::<serde_json::Value>("/api/v1/me/emails", br#"{"email":"work@example.com"}"#)
+        .good();
+
+    let emails = app.db(|conn| EmailSend::recent(conn, 10, false).unwrap());
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].recipient, "work@example.com");
+    assert_eq!(emails[0].template, "user_confirm");
+    assert_eq!(emails[0].subject, "Please confirm your email address");
+    assert_eq!(emails[0].status, EmailStatus::Sent);
+    assert_eq!(emails[0].attempts, 1);
+    assert_some!(emails[0].sent_at);
+
+    let failures = app.db(|conn| EmailSend::recent(conn, 10, true).unwrap());
+    assert_eq!(failures.len(), 0);
+}
+
+#[test]
+fn background_job_delivers_emails() {
+    let (app, _) = TestApp::init().with_git_index().with_job_runner().empty();
+
+    app.db(|conn| enqueue_email(conn, "someone@example.com"));
+    app.run_pending_background_jobs();
+
+    let mails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(mails.len(), 1);
+    assert_eq!(mails[0].to, "someone@example.com");
+    assert_eq!(mails[0].subject, "Please confirm your email address");
+    assert!(mails[0].body.contains("/confirm/some-token"));
+    assert!(mails[0].html_body.contains("/confirm/some-token"));
+
+    let email: EmailSend = app.db(|conn| email_sends::table.first(conn).unwrap());
+    assert_eq!(email.status, EmailStatus::Sent);
+    assert_eq!(email.attempts, 1);
+}
+
+#[test]
+fn background_job_looks_up_the_token_when_delivering() {
+    let (app, _, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_user();
+
+    let (email, context) = app.db(|conn| {
+        let email: Email = Email::belonging_to(user.as_model()).first(conn).unwrap();
+        let send = EmailSend::create(conn, &email.email, "user_confirm", "Confirm").unwrap();
+        let token = EmailToken::Confirmation { email_id: email.id };
+        worker::send_email(
+            send.id,
+            email.email.clone(),
+            "user_confirm".into(),
+            json!({ "user_name": "foo" }),
+            Some(token),
+        )
+        .enqueue(conn)
+        .unwrap();
+
+        let data: serde_json::Value = background_jobs::table
+            .select(background_jobs::data)
+            .first(conn)
+            .unwrap();
+        (email, data)
+    });
+    // Only the id of the email address is kept in the job queue
+    assert!(!context.to_string().contains(&email.token));
+
+    app.run_pending_background_jobs();
+
+    let mails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(mails.len(), 1);
+    assert!(mails[0].body.contains(&format!("/confirm/{}", email.token)));
+}
+
+#[test]
+fn background_job_gives_up_when_the_token_no_longer_exists() {
+    let (app, _, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_user();
+
+    app.db(|conn| {
+        let email: Email = Email::belonging_to(user.as_model()).first(conn).unwrap();
+        let send = EmailSend::create(conn, &email.email, "user_confirm", "Confirm").unwrap();
+        let token = EmailToken::Confirmation { email_id: email.id };
+        worker::send_email(
+            send.id,
+            email.email.clone(),
+            "user_confirm".into(),
+            json!({ "user_name": "foo" }),
+            Some(token),
+        )
+        .enqueue(conn)
+        .unwrap();
+
+        diesel::delete(emails::table.find(email.id))
+            .execute(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 0);
+    let email: EmailSend = app.db(|conn| email_sends::table.first(conn).unwrap());
+    assert_eq!(email.status, EmailStatus::Failed);
+    assert_none!(email.sent_at);
+}
+
+#[test]
+fn background_job_gives_up_after_the_last_attempt() {
+    let (app, _) = TestApp::init().with_git_index().with_job_runner().empty();
+
+    // The address is rejected by the backend, so every attempt fails
+    app.db(|conn| {
+        let email = enqueue_email(conn, "not an address");
+        diesel::update(&email)
+            .set(email_sends::attempts.eq(MAX_EMAIL_ATTEMPTS - 1))
+            .execute(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 0);
+
+    let failures = app.db(|conn| EmailSend::recent(conn, 10, true).unwrap());
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].status, EmailStatus::Failed);
+    assert_eq!(failures[0].attempts, MAX_EMAIL_ATTEMPTS);
+    assert_some!(&failures[0].last_error);
+    assert_none!(failures[0].sent_at);
+}
+
+#[test]
+fn background_job_retries_failed_deliveries() {
+    let (app, _) = TestApp::init().with_git_index().with_job_runner().empty();
+
+    // The address is rejected by the backend, so every attempt fails
+    app.db(|conn| enqueue_email(conn, "not an address"));
+    app.run_pending_background_jobs_allowing_failures();
+
+    let email: EmailSend = app.db(|conn| email_sends::table.first(conn).unwrap());
+    assert_eq!(email.status, EmailStatus::Pending);
+    assert_eq!(email.attempts, 1);
+    assert_some!(&email.last_error);
+    let (jobs, retries): (i64, Option<i32>) = app.db(|conn| {
+        background_jobs::table
+            .select((
+                diesel::dsl::count_star(),
+                diesel::dsl::max(background_jobs::retries),
+            ))
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(jobs, 1);
+    assert_eq!(retries, Some(1));
+
+    // Skip the backoff of the remaining attempts
+    for _ in 1..MAX_EMAIL_ATTEMPTS {
+        app.db(|conn| {
+            diesel::update(background_jobs::table)
+                .set(background_jobs::last_retry.eq(diesel::dsl::now - 1.day()))
+                .execute(conn)
+                .unwrap();
+        });
+        app.run_pending_background_jobs_allowing_failures();
+    }
+
+    // The last attempt gives up, which removes the job
+    let jobs: i64 = app.db(|conn| background_jobs::table.count().get_result(conn).unwrap());
+    assert_eq!(jobs, 0);
+    let email: EmailSend = app.db(|conn| email_sends::table.first(conn).unwrap());
+    assert_eq!(email.status, EmailStatus::Failed);
+    assert_eq!(email.attempts, MAX_EMAIL_ATTEMPTS);
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 0);
+}
@@ -0,0 +1,125 @@
+use crate::builders::CrateBuilder;
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::models::EndpointScope;
+use cargo_registry::views::EncodableCrateDeprecation;
+use conduit::StatusCode;
+
+#[derive(Deserialize)]
+struct DeprecationResponse {
+    ok: bool,
+    deprecation: Option<EncodableCrateDeprecation>,
+}
+
+#[test]
+fn owner_can_deprecate_and_undeprecate_a_crate() {
+    let (app, anon, user, token) = TestApp::init().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("old_crate", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("new_crate", user.as_model().id).expect_build(conn);
+    });
+
+    let body = br#"{"deprecation":{"message":"use new_crate","successor":"new_crate"}}"#;
+    let json: DeprecationResponse = token
+        .put("/api/v1/crates/old_crate/deprecation", body)
+        .good();
+    assert!(json.ok);
+    let deprecation = json.deprecation.unwrap();
+    assert_eq!(deprecation.message, "use new_crate");
+    assert_eq!(deprecation.successor.as_deref(), Some("new_crate"));
+
+    let json = anon.show_crate("old_crate");
+    let deprecation = json.krate.deprecation.unwrap();
+    assert_eq!(deprecation.message, "use new_crate");
+    assert_eq!(deprecation.successor.as_deref(), Some("new_crate"));
+
+    assert!(anon.show_crate("new_crate").krate.deprecation.is_none());
+
+    token
+        .delete::<crate::OkBool>("/api/v1/crates/old_crate/deprecation")
+        .good();
+    assert!(anon.show_crate("old_crate").krate.deprecation.is_none());
+}
+
+#[test]
+fn deprecation_requires_existing_successor() {
+    let (app, anon, user, token) = TestApp::init().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("old_crate", user.as_model().id).expect_build(conn);
+    });
+
+    let body = br#"{"deprecation":{"message":"gone","successor":"missing"}}"#;
+    let response = token.put::<()>("/api/v1/crates/old_crate/deprecation", body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "successor crate `missing` does not exist" }] })
+    );
+
+    let body = br#"{"deprecation":{"message":"gone","successor":"old_crate"}}"#;
+    let response = token.put::<()>("/api/v1/crates/old_crate/deprecation", body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "a crate cannot be its own successor" }] })
+    );
+
+    let body = br#"{"deprecation":{"message":"   "}}"#;
+    let response = token.put::<()>("/api/v1/crates/old_crate/deprecation", body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "a deprecation message is required" }] })
+    );
+
+    assert!(anon.show_crate("old_crate").krate.deprecation.is_none());
+}
+
+#[test]
+fn non_owner_cannot_deprecate() {
+    let (app, anon, _, token) = TestApp::init().with_token();
+
+    let other = app.db_new_user("other");
+    app.db(|conn| {
+        CrateBuilder::new("old_crate", other.as_model().id).expect_build(conn);
+    });
+
+    let body = br#"{"deprecation":{"message":"abandoned"}}"#;
+    let response = token.put::<()>("/api/v1/crates/old_crate/deprecation", body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "only owners have permission to deprecate a crate" }] })
+    );
+
+    assert!(anon.show_crate("old_crate").krate.deprecation.is_none());
+}
+
+#[test]
+fn anonymous_cannot_deprecate() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("old_crate", user.as_model().id).expect_build(conn);
+    });
+
+    let body = br#"{"deprecation":{"message":"abandoned"}}"#;
+    anon.put::<()>("/api/v1/crates/old_crate/deprecation", body)
+        .assert_forbidden();
+}
+
+#[test]
+fn publish_only_tokens_cannot_deprecate() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let token = user.db_new_scoped_token("ci", &[EndpointScope::PublishUpdate]);
+
+    app.db(|conn| {
+        CrateBuilder::new("old_crate", user.as_model().id).expect_build(conn);
+    });
+
+    let body = br#"{"deprecation":{"message":"abandoned"}}"#;
+    let response = token.put::<()>("/api/v1/crates/old_crate/deprecation", body);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = token.delete::<()>("/api/v1/crates/old_crate/deprecation");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    assert!(anon.show_crate("old_crate").krate.deprecation.is_none());
+}
@@ -1,8 +1,10 @@
 mod dependencies;
+mod deprecation;
 mod downloads;
 mod following;
 mod owners;
 mod publish;
+mod rename;
 mod reverse_dependencies;
 mod search;
 mod show;
@@ -0,0 +1,127 @@
+use crate::builders::{CrateBuilder, PublishBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::models::Crate;
+use chrono::{Duration, Utc};
+use conduit::{header, Method};
+use diesel::prelude::*;
+use http::StatusCode;
+use serde_json::Value;
+
+#[test]
+fn old_name_resolves_to_renamed_crate() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("old_name", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn)
+            .rename(conn, "new_name")
+            .unwrap();
+    });
+
+    let json = anon.show_crate("old_name");
+    assert_eq!(json.krate.name, "new_name");
+    assert_eq!(json.versions.len(), 1);
+
+    let json = anon.get::<Value>("/api/v1/crates/old-name").good();
+    assert_eq!(json["renamed_from"], "old-name");
+    assert_eq!(json["crate"]["name"], "new_name");
+
+    let json = anon.get::<Value>("/api/v1/crates/new_name").good();
+    assert!(json["renamed_from"].is_null());
+}
+
+#[test]
+fn downloads_use_the_name_the_version_was_published_under() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let last_week = (Utc::now() - Duration::days(7)).naive_utc();
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("old_name", user.id)
+            .version(VersionBuilder::new("1.0.0").created_at(last_week))
+            .expect_build(conn)
+            .rename(conn, "new_name")
+            .unwrap();
+        VersionBuilder::new("2.0.0").expect_build(krate.id, user.id, conn);
+    });
+
+    anon.get::<()>("/api/v1/crates/old_name/1.0.0/download")
+        .assert_redirect_ends_with("/crates/old_name/old_name-1.0.0.crate");
+    anon.get::<()>("/api/v1/crates/new_name/1.0.0/download")
+        .assert_redirect_ends_with("/crates/old_name/old_name-1.0.0.crate");
+    anon.get::<()>("/api/v1/crates/new_name/2.0.0/download")
+        .assert_redirect_ends_with("/crates/new_name/new_name-2.0.0.crate");
+}
+
+#[test]
+fn downloads_through_a_previous_name_carry_a_rename_hint() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("old_name", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn)
+            .rename(conn, "new_name")
+            .unwrap();
+    });
+
+    // Repeated to make sure the hint is not lost once the version id is cached
+    for _ in 0..2 {
+        let response = anon.get::<()>("/api/v1/crates/old_name/1.0.0/download");
+        assert_eq!(response.headers()["x-crate-renamed-to"], "new_name");
+    }
+
+    let response = anon.get::<()>("/api/v1/crates/new_name/1.0.0/download");
+    assert_none!(response.headers().get("x-crate-renamed-to"));
+
+    let mut request = anon.request_builder(Method::GET, "/api/v1/crates/old_name/1.0.0/download");
+    request.header(header::ACCEPT, "application/json");
+    let json = anon.run::<serde_json::Value>(request).good();
+    assert_eq!(json["renamed_to"], "new_name");
+}
+
+#[test]
+fn old_name_cannot_be_published() {
+    let (app, _, user, token) = TestApp::init().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("old_name", user.as_model().id)
+            .expect_build(conn)
+            .rename(conn, "new_name")
+            .unwrap();
+    });
+
+    let response = token.enqueue_publish(PublishBuilder::new("old-name").version("1.0.0"));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "crate `old-name` has been renamed to `new_name`, please publish under the new name" }] })
+    );
+}
+
+#[test]
+fn rename_validates_new_name() {
+    let (app, _, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("first", user.id).expect_build(conn);
+        CrateBuilder::new("second", user.id).expect_build(conn);
+
+        assert_err!(krate.rename(conn, "not valid"));
+        assert_err!(krate.rename(conn, "First"));
+        assert_err!(krate.rename(conn, "second"));
+        assert_err!(krate.rename(conn, "std"));
+
+        let krate = krate.rename(conn, "third").unwrap();
+        assert_eq!(krate.aliases(conn).unwrap(), vec!["first"]);
+
+        // Renaming back to a previous name removes the alias
+        let krate = krate.rename(conn, "first").unwrap();
+        assert_eq!(krate.aliases(conn).unwrap(), vec!["third"]);
+
+        let resolved: Crate = Crate::by_name("third").first(conn).unwrap();
+        assert_eq!(resolved.name, "first");
+    });
+}
@@ -1,13 +1,14 @@
 use crate::builders::{CrateBuilder, VersionBuilder};
 use crate::util::{RequestHelper, TestApp};
 use crate::CrateMeta;
-use cargo_registry::views::{EncodableDependency, EncodableVersion};
+use cargo_registry::views::{EncodableCrateDeprecation, EncodableDependency, EncodableVersion};
 
 #[derive(Deserialize)]
 struct RevDeps {
     dependencies: Vec<EncodableDependency>,
     versions: Vec<EncodableVersion>,
     meta: CrateMeta,
+    deprecation: Option<EncodableCrateDeprecation>,
 }
 
 impl crate::util::MockAnonymousUser {
@@ -48,6 +49,30 @@ fn reverse_dependencies() {
     assert_eq!(deps.meta.total, 0);
 }
 
+#[test]
+fn reverse_dependencies_of_deprecated_crate() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let c1 = CrateBuilder::new("c1", user.id).expect_build(conn);
+        CrateBuilder::new("c2", user.id)
+            .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+            .expect_build(conn);
+        CrateBuilder::new("c3", user.id).expect_build(conn);
+        c1.deprecate(conn, "moved to c3", Some("c3")).unwrap();
+    });
+
+    let deps = anon.reverse_dependencies("c1");
+    assert_eq!(deps.meta.total, 1);
+    let deprecation = deps.deprecation.unwrap();
+    assert_eq!(deprecation.message, "moved to c3");
+    assert_eq!(deprecation.successor.as_deref(), Some("c3"));
+
+    let deps = anon.reverse_dependencies("c2");
+    assert!(deps.deprecation.is_none());
+}
+
 #[test]
 fn reverse_dependencies_when_old_version_doesnt_depend_but_new_does() {
     let (app, anon, user) = TestApp::init().with_user();
@@ -512,6 +512,31 @@ fn index_include_yanked() {
     assert_eq!(json.crates[2].name, "unyanked");
 }
 
+#[test]
+fn index_include_deprecated() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("current", user.id).expect_build(conn);
+        CrateBuilder::new("deprecated", user.id)
+            .expect_build(conn)
+            .deprecate(conn, "use current", Some("current"))
+            .unwrap();
+    });
+
+    let json = anon.search("sort=alphabetical");
+    assert_eq!(json.meta.total, 2);
+    assert_eq!(json.crates[0].name, "current");
+    assert_eq!(json.crates[1].name, "deprecated");
+    assert!(json.crates[0].deprecation.is_none());
+    assert!(json.crates[1].deprecation.is_some());
+
+    let json = anon.search("include_deprecated=no&sort=alphabetical");
+    assert_eq!(json.meta.total, 1);
+    assert_eq!(json.crates[0].name, "current");
+}
+
 #[test]
 fn yanked_versions_are_not_considered_for_max_version() {
     let (app, anon, user) = TestApp::init().with_user();
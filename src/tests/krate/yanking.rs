@@ -1,6 +1,8 @@
 use crate::builders::{CrateBuilder, PublishBuilder};
 use crate::util::{RequestHelper, TestApp};
 use crate::OkBool;
+use cargo_registry::models::Crate;
+use diesel::prelude::*;
 use http::StatusCode;
 
 impl crate::util::MockTokenUser {
@@ -246,3 +248,122 @@ fn unyank_records_an_audit_action() {
     assert_eq!(action.action, "unyank");
     assert_eq!(action.user.id, token.as_model().user_id);
 }
+
+#[test]
+fn opted_in_owners_are_notified_of_yanks() {
+    use cargo_registry::models::{CrateOwner, OwnerKind, OwnerRole};
+    use cargo_registry::schema::crate_owners;
+    use diesel::prelude::*;
+
+    let (app, _, user, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_token();
+    let other_owner = app.db_new_user("other_owner");
+    app.db(|conn| {
+        let krate = CrateBuilder::new("fyk_notified", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+
+        // The other owner opted out of notifications
+        diesel::insert_into(crate_owners::table)
+            .values(&CrateOwner {
+                crate_id: krate.id,
+                owner_id: other_owner.as_model().id,
+                created_by: user.as_model().id,
+                owner_kind: OwnerKind::User as i32,
+                email_notifications: false,
+                role: OwnerRole::Full,
+            })
+            .execute(conn)
+            .unwrap();
+    });
+    app.add_versions_to_index("fyk_notified", &["1.0.0"]);
+
+    token.yank("fyk_notified", "1.0.0").good();
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 1);
+    let email = emails.last().unwrap();
+    assert_eq!(email.to, "something@example.com");
+    assert_eq!(email.subject, "fyk_notified 1.0.0 was yanked");
+    assert!(email.body.contains("by foo with the API token \"bar\""));
+    assert!(email
+        .html_body
+        .contains("with the API token <code>bar</code>"));
+
+    user.unyank("fyk_notified", "1.0.0").good();
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 2);
+    let email = emails.last().unwrap();
+    assert_eq!(email.subject, "fyk_notified 1.0.0 was unyanked");
+    assert!(email.body.contains("by foo through the crates.io website"));
+}
+
+#[test]
+fn yanking_a_version_published_before_a_rename_updates_the_old_index_file() {
+    let (app, _, user, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("fyk_old_name", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+    app.add_versions_to_index("fyk_old_name", &["1.0.0"]);
+    app.db(|conn| {
+        let krate = Crate::by_name("fyk_old_name").first::<Crate>(conn).unwrap();
+        krate.rename(conn, "fyk_new_name").unwrap();
+    });
+
+    token.yank("fyk_new_name", "1.0.0").good();
+
+    let crates = app.crates_from_index_head("fyk_old_name");
+    assert_eq!(crates.len(), 1);
+    assert_some_eq!(crates[0].yanked, true);
+
+    token.unyank("fyk_old_name", "1.0.0").good();
+
+    let crates = app.crates_from_index_head("fyk_old_name");
+    assert_some_eq!(crates[0].yanked, false);
+}
+
+#[test]
+fn yank_notifications_continue_after_a_failed_recipient() {
+    use cargo_registry::models::{CrateOwner, OwnerKind, OwnerRole};
+    use cargo_registry::schema::{crate_owners, emails};
+
+    let (app, _, user, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_token();
+    let broken = app.db_new_user("broken");
+    app.db(|conn| {
+        let krate = CrateBuilder::new("fyk_broken", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+        diesel::insert_into(crate_owners::table)
+            .values(&CrateOwner {
+                crate_id: krate.id,
+                owner_id: broken.as_model().id,
+                created_by: user.as_model().id,
+                owner_kind: OwnerKind::User as i32,
+                email_notifications: true,
+                role: OwnerRole::Full,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::update(emails::table.filter(emails::user_id.eq(broken.as_model().id)))
+            .set(emails::email.eq("not an email address"))
+            .execute(conn)
+            .unwrap();
+    });
+    app.add_versions_to_index("fyk_broken", &["1.0.0"]);
+
+    // The job does not fail, which would notify the other owner again when retried
+    token.yank("fyk_broken", "1.0.0").good();
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, "something@example.com");
+}
@@ -0,0 +1,343 @@
+use crate::builders::CrateBuilder;
+use crate::util::{encode_session, MockAnonymousUser, MockCookieUser, RequestHelper, Response};
+use crate::TestApp;
+use cargo_registry::login::{GitHubIdentity, LoginProvider, ProviderUser};
+use cargo_registry::models::{
+    CrateOwnerInvitation, NewLinkedIdentity, Owner, TotpCredential, User,
+};
+use cargo_registry::schema::{crate_owner_invitations, crate_owner_signup_invitations, users};
+use cargo_registry::util::errors::AppResult;
+use conduit::{header, Method, StatusCode};
+use diesel::prelude::*;
+use oauth2::{AuthorizationCode, CsrfToken};
+use url::Url;
+
+/// A login provider that logs in whichever account is encoded in the code it receives.
+struct MockProvider;
+
+impl LoginProvider for MockProvider {
+    fn name(&self) -> &str {
+        "gitlab"
+    }
+
+    fn display_name(&self) -> &str {
+        "GitLab"
+    }
+
+    fn authorize_url(&self) -> (Url, CsrfToken) {
+        let state = CsrfToken::new_random();
+        let url = format!(
+            "https://gitlab.example.com/authorize?state={}",
+            state.secret()
+        );
+        (Url::parse(&url).unwrap(), state)
+    }
+
+    fn exchange_code(&self, code: AuthorizationCode) -> AppResult<ProviderUser> {
+        let (id, login) = code.secret().split_once(':').unwrap();
+        Ok(ProviderUser {
+            id: id.into(),
+            login: login.into(),
+            name: None,
+            avatar: None,
+            email: Some(format!("{login}@example.com")),
+            github: None,
+        })
+    }
+}
+
+/// Like `MockProvider`, but the accounts are GitHub accounts with the GitHub id encoded in the
+/// code.
+struct MockGitHubProvider;
+
+impl LoginProvider for MockGitHubProvider {
+    fn name(&self) -> &str {
+        "mock-github"
+    }
+
+    fn display_name(&self) -> &str {
+        "Mock GitHub"
+    }
+
+    fn authorize_url(&self) -> (Url, CsrfToken) {
+        MockProvider.authorize_url()
+    }
+
+    fn exchange_code(&self, code: AuthorizationCode) -> AppResult<ProviderUser> {
+        let mut user = MockProvider.exchange_code(code)?;
+        user.github = Some(GitHubIdentity {
+            id: user.id.parse().unwrap(),
+            access_token: "some random token".into(),
+        });
+        Ok(user)
+    }
+}
+
+fn app() -> (TestApp, MockAnonymousUser) {
+    TestApp::init()
+        .with_login_provider(Box::new(MockProvider))
+        .empty()
+}
+
+/// Completes the OAuth flow of the mock provider for the account `id:login`, as the given user
+/// if they are logged in.
+fn authorize(
+    anon: &MockAnonymousUser,
+    account: &str,
+    user: Option<&MockCookieUser>,
+) -> Response<serde_json::Value> {
+    authorize_with(anon, "gitlab", account, user)
+}
+
+fn authorize_with(
+    anon: &MockAnonymousUser,
+    provider: &str,
+    account: &str,
+    user: Option<&MockCookieUser>,
+) -> Response<serde_json::Value> {
+    let mut session = user.map(MockCookieUser::session_data).unwrap_or_default();
+    session.insert("oauth_state".to_string(), "state".to_string());
+    session.insert("oauth_provider".to_string(), provider.to_string());
+    let cookie = encode_session(anon.app().as_inner().session_key(), &session);
+
+    let mut request = anon.request_builder(Method::GET, "/api/private/session/authorize");
+    request.header(header::COOKIE, &cookie);
+    request.with_query(&format!("code={account}&state=state"));
+    anon.run(request)
+}
+
+#[test]
+fn providers_are_listed() {
+    let (_, anon) = app();
+
+    let json = anon
+        .get::<serde_json::Value>("/api/private/session/providers")
+        .good();
+    assert_eq!(
+        json,
+        json!({ "providers": [
+            { "name": "github", "display_name": "GitHub" },
+            { "name": "gitlab", "display_name": "GitLab" },
+        ] })
+    );
+
+    let json = anon
+        .get_with_query::<serde_json::Value>("/api/private/session/begin", "provider=gitlab")
+        .good();
+    let url = json["url"].as_str().unwrap();
+    assert!(url.starts_with("https://gitlab.example.com/"));
+    assert!(url.contains(json["state"].as_str().unwrap()));
+
+    let response = anon.get_with_query::<()>("/api/private/session/begin", "provider=bitbucket");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn new_users_can_sign_up_with_other_providers() {
+    let (app, anon) = app();
+
+    let json = authorize(&anon, "42:alice", None).good();
+    assert_eq!(json["user"]["login"], "gitlab:alice");
+    assert_eq!(json["user"]["email"], "alice@example.com");
+
+    let user: User = app.db(|conn| {
+        users::table
+            .filter(users::gh_login.eq("gitlab:alice"))
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(user.gh_id, 0);
+    assert_eq!(user.gh_access_token, "");
+
+    // Logging in again finds the same user
+    let json = authorize(&anon, "42:alice-renamed", None).good();
+    assert_eq!(json["user"]["id"], user.id);
+
+    let user = MockCookieUser::new(&app, user);
+    let json = user
+        .get::<serde_json::Value>("/api/v1/me/identities")
+        .good();
+    let identities = json["identities"].as_array().unwrap();
+    assert_eq!(identities.len(), 1);
+    assert_eq!(identities[0]["provider"], "gitlab");
+    assert_eq!(identities[0]["login"], "alice-renamed");
+}
+
+#[test]
+fn new_users_cannot_take_existing_logins() {
+    let (app, anon) = app();
+    app.db_new_user("gitlab:foo");
+
+    let response = authorize(&anon, "42:Foo", None);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let json = response.into_json();
+    let detail = json["errors"][0]["detail"].as_str().unwrap();
+    assert!(detail.starts_with("the username `gitlab:Foo` is already taken."));
+}
+
+#[test]
+fn github_logins_only_resolve_to_github_users() {
+    let (app, anon) = app();
+    let github_user = app.db_new_user("foo");
+    let json = authorize(&anon, "42:foo", None).good();
+    let gitlab_user_id = json["user"]["id"].as_i64().unwrap() as i32;
+
+    app.db(|conn| {
+        let user = Owner::find_user_by_login(conn, "Foo").unwrap();
+        assert_eq!(user.id, github_user.as_model().id);
+        let user = Owner::find_user_by_login(conn, "gitlab:foo").unwrap();
+        assert_eq!(user.id, gitlab_user_id);
+        assert_err!(Owner::find_user_by_login(conn, "gitlab:bar"));
+    });
+
+    // Unlinking GitHub gives up the GitHub login
+    app.db(|conn| {
+        NewLinkedIdentity {
+            user_id: github_user.as_model().id,
+            provider: "gitlab",
+            provider_user_id: "43",
+            login: "foo-on-gitlab",
+        }
+        .insert(conn)
+        .unwrap();
+        NewLinkedIdentity {
+            user_id: github_user.as_model().id,
+            provider: "github",
+            provider_user_id: &github_user.as_model().gh_id.to_string(),
+            login: "foo",
+        }
+        .insert(conn)
+        .unwrap();
+    });
+    github_user
+        .delete::<serde_json::Value>("/api/v1/me/identities/github")
+        .good();
+    let json = github_user.get::<serde_json::Value>("/api/v1/me").good();
+    assert_eq!(json["user"]["login"], "gitlab:foo-on-gitlab");
+    app.db(|conn| assert_err!(Owner::find_user_by_login(conn, "foo")));
+}
+
+#[test]
+fn logged_in_users_can_link_accounts() {
+    let (app, anon) = app();
+    let foo = app.db_new_user("foo");
+
+    let json = authorize(&anon, "42:foo-on-gitlab", Some(&foo)).good();
+    assert_eq!(json["user"]["login"], "foo");
+
+    // The linked account now logs in as `foo`
+    let json = authorize(&anon, "42:foo-on-gitlab", None).good();
+    assert_eq!(json["user"]["id"], foo.as_model().id);
+
+    // An account can only be linked to one user, and a user can only link one account per
+    // provider
+    let bar = app.db_new_user("bar");
+    let response = authorize(&anon, "42:foo-on-gitlab", Some(&bar));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = authorize(&anon, "43:other", Some(&foo));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Once there is another way to log in, the account can be unlinked again
+    app.db(|conn| {
+        NewLinkedIdentity {
+            user_id: foo.as_model().id,
+            provider: "github",
+            provider_user_id: &foo.as_model().gh_id.to_string(),
+            login: "foo",
+        }
+        .insert(conn)
+        .unwrap();
+    });
+    let json = foo.get::<serde_json::Value>("/api/v1/me/identities").good();
+    assert_eq!(json["identities"].as_array().unwrap().len(), 2);
+
+    foo.delete::<serde_json::Value>("/api/v1/me/identities/gitlab")
+        .good();
+    let json = foo.get::<serde_json::Value>("/api/v1/me/identities").good();
+    let identities = json["identities"].as_array().unwrap();
+    assert_eq!(identities.len(), 1);
+    assert_eq!(identities[0]["provider"], "github");
+}
+
+#[test]
+fn linking_requires_a_recent_second_factor() {
+    let (app, anon) = app();
+    let foo = app.db_new_user("foo");
+    let user_id = foo.as_model().id;
+    app.db(|conn| {
+        let credential = TotpCredential::start_enrollment(conn, user_id).unwrap();
+        credential.enable(conn).unwrap();
+    });
+
+    let response = authorize(&anon, "42:foo-on-gitlab", Some(&foo));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // The account was not linked, so it signs up as a new user
+    let json = authorize(&anon, "42:foo-on-gitlab", None).good();
+    assert_ne!(json["user"]["id"], user_id);
+}
+
+#[test]
+fn only_login_method_cannot_be_unlinked() {
+    let (app, anon) = app();
+    authorize(&anon, "42:alice", None).good();
+    let user: User = app.db(|conn| {
+        users::table
+            .filter(users::gh_login.eq("gitlab:alice"))
+            .first(conn)
+            .unwrap()
+    });
+    let user = MockCookieUser::new(&app, user);
+
+    let response = user.delete::<()>("/api/v1/me/identities/gitlab");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = user.delete::<()>("/api/v1/me/identities/github");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let token = user.db_new_token("bar");
+    let response = token.get::<()>("/api/v1/me/identities");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn github_users_are_invited_before_they_sign_up() {
+    let (app, anon, owner) = TestApp::with_proxy()
+        .with_login_provider(Box::new(MockGitHubProvider))
+        .with_user();
+    let krate = app.db(|conn| CrateBuilder::new("welcome", owner.as_model().id).expect_build(conn));
+
+    let body = br#"{"owners":["newcomer"]}"#;
+    let json = owner
+        .put::<serde_json::Value>("/api/v1/crates/welcome/owners", body)
+        .good();
+    assert_eq!(
+        json["msg"],
+        "user Newcomer has been invited to be an owner of crate welcome, \
+         they will see the invitation once they log in to crates.io"
+    );
+    let json = owner
+        .get::<serde_json::Value>("/api/v1/crates/welcome/owner_invitations")
+        .good();
+    assert_eq!(json["invitations"][0]["login"], "Newcomer");
+    assert_eq!(json["invitations"][0]["kind"], "user");
+
+    let json = authorize_with(&anon, "mock-github", "424242:Newcomer", None).good();
+    let user_id = json["user"]["id"].as_i64().unwrap() as i32;
+
+    let invitations: Vec<CrateOwnerInvitation> = app.db(|conn| {
+        crate_owner_invitations::table
+            .filter(crate_owner_invitations::invited_user_id.eq(user_id))
+            .load(conn)
+            .unwrap()
+    });
+    assert_eq!(invitations.len(), 1);
+    assert_eq!(invitations[0].crate_id, krate.id);
+    assert_eq!(invitations[0].invited_by_user_id, owner.as_model().id);
+    let remaining: i64 = app.db(|conn| {
+        crate_owner_signup_invitations::table
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(remaining, 0);
+}
@@ -3,13 +3,13 @@ use crate::{
     builders::{CrateBuilder, PublishBuilder},
     new_team,
     util::{MockAnonymousUser, MockCookieUser, MockTokenUser, RequestHelper, Response},
-    TestApp,
+    OkBool, TestApp,
 };
 use cargo_registry::{
-    models::Crate,
+    models::{Crate, CrateOwnerTeamInvitation, OwnerRole},
     views::{
         EncodableCrateOwnerInvitation, EncodableCrateOwnerInvitationV1, EncodableOwner,
-        EncodablePublicUser, InvitationResponse,
+        EncodablePendingOwnerInvitation, EncodablePublicUser, InvitationResponse,
     },
     Emails,
 };
@@ -416,7 +416,7 @@ fn deleted_ownership_isnt_in_owner_user() {
     app.db(|conn| {
         let krate = CrateBuilder::new("foo_my_packages", user.id).expect_build(conn);
         krate
-            .owner_remove(app.as_inner(), conn, user, &user.gh_login)
+            .owner_remove(app.as_inner(), conn, user, None, &user.gh_login)
             .unwrap();
     });
 
@@ -696,6 +696,123 @@ fn test_accept_expired_invitation_by_mail() {
     assert_eq!(json.users.len(), 1);
 }
 
+#[test]
+fn background_job_reminds_invited_users_and_purges_expired_invitations() {
+    use cargo_registry::schema::crate_owner_invitations;
+    use cargo_registry::worker;
+    use swirl::Job;
+
+    let (app, _) = TestApp::init().with_git_index().with_job_runner().empty();
+    let owner = app.db_new_user("owner");
+    let owner_token = owner.db_new_token("owner-token");
+    let reminded_user = app.db_new_user("reminded_user");
+    let expired_user = app.db_new_user("expired_user");
+    app.db_new_user("fresh_user");
+    app.db(|conn| CrateBuilder::new("demo_crate", owner.as_model().id).expect_build(conn));
+
+    owner_token.add_user_owner("demo_crate", "reminded_user");
+    owner_token.add_user_owner("demo_crate", "expired_user");
+    owner_token.add_user_owner("demo_crate", "fresh_user");
+
+    let expiration_days = app.as_inner().config.ownership_invitations_expiration_days;
+    let move_invitation_back = |user: &MockCookieUser, days: i64| {
+        app.db(|conn| {
+            diesel::update(crate_owner_invitations::table)
+                .filter(crate_owner_invitations::invited_user_id.eq(user.as_model().id))
+                .set(
+                    crate_owner_invitations::created_at
+                        .eq((Utc::now() - Duration::days(days)).naive_utc()),
+                )
+                .execute(conn)
+                .unwrap();
+        });
+    };
+    move_invitation_back(&reminded_user, expiration_days as i64 / 2 + 1);
+    move_invitation_back(&expired_user, expiration_days as i64 + 1);
+
+    let run_job = || {
+        app.db(|conn| {
+            worker::process_owner_invitations(expiration_days)
+                .enqueue(conn)
+                .unwrap()
+        });
+        app.run_pending_background_jobs();
+    };
+
+    // The first three emails are the invitations themselves
+    run_job();
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    let subjects = emails[3..]
+        .iter()
+        .map(|email| email.subject.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        subjects,
+        vec![
+            "Invitation of expired_user to demo_crate expired",
+            "Reminder: crate ownership invitation",
+        ]
+    );
+    assert!(emails[4].body.contains("owner has invited you"));
+
+    let json = reminded_user.list_invitations();
+    assert_eq!(json.crate_owner_invitations.len(), 1);
+    let remaining: i64 = app.db(|conn| {
+        crate_owner_invitations::table
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(remaining, 2);
+
+    // Every invitation is only reminded of once
+    run_job();
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 5);
+}
+
+#[test]
+fn background_job_purges_invitations_of_inviters_that_cannot_be_emailed() {
+    use cargo_registry::schema::{crate_owner_invitations, emails};
+    use cargo_registry::worker;
+    use swirl::Job;
+
+    let (app, _) = TestApp::init().with_git_index().with_job_runner().empty();
+    let owner = app.db_new_user("owner");
+    let owner_token = owner.db_new_token("owner-token");
+    app.db_new_user("invited_user");
+    app.db(|conn| CrateBuilder::new("demo_crate", owner.as_model().id).expect_build(conn));
+    owner_token.add_user_owner("demo_crate", "invited_user");
+
+    let expiration_days = app.as_inner().config.ownership_invitations_expiration_days;
+    app.db(|conn| {
+        diesel::update(crate_owner_invitations::table)
+            .set(
+                crate_owner_invitations::created_at
+                    .eq((Utc::now() - Duration::days(expiration_days as i64 + 1)).naive_utc()),
+            )
+            .execute(conn)
+            .unwrap();
+        diesel::update(emails::table.filter(emails::user_id.eq(owner.as_model().id)))
+            .set(emails::email.eq("not an email address"))
+            .execute(conn)
+            .unwrap();
+        worker::process_owner_invitations(expiration_days)
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    // Only the invitation itself was sent
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 1);
+    let remaining: i64 = app.db(|conn| {
+        crate_owner_invitations::table
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(remaining, 0);
+}
+
 #[test]
 fn inactive_users_dont_get_invitations() {
     use cargo_registry::models::NewUser;
@@ -1085,3 +1202,215 @@ fn invitation_list_other_crates() {
         owner.get_with_query::<()>("/api/private/crate_owner_invitations", "crate_name=crate_2");
     assert_eq!(resp.status(), StatusCode::FORBIDDEN);
 }
+
+fn add_owners_with_role(
+    token: &MockTokenUser,
+    krate_name: &str,
+    owners: &[&str],
+    role: &str,
+) -> Response<serde_json::Value> {
+    let url = format!("/api/v1/crates/{krate_name}/owners");
+    let body = json!({ "owners": owners, "role": role }).to_string();
+    token.put(&url, body.as_bytes())
+}
+
+#[test]
+fn publish_only_owners_cannot_modify_owners() {
+    let (app, anon, user, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_token();
+    let krate = app.db(|conn| {
+        CrateBuilder::new("owners_publish_only", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn)
+    });
+    app.add_versions_to_index(&krate.name, &["1.0.0"]);
+
+    let publisher = app.db_new_user("publisher");
+    add_owners_with_role(&token, &krate.name, &["publisher"], "publish").good();
+    publisher.accept_ownership_invitation(&krate.name, krate.id);
+
+    let owners = anon.show_crate_owners(&krate.name).users;
+    let mut roles = owners
+        .iter()
+        .map(|owner| (owner.login.as_str(), owner.role.as_str()))
+        .collect::<Vec<_>>();
+    roles.sort_unstable();
+    assert_eq!(roles, vec![("foo", "full"), ("publisher", "publish")]);
+
+    let publisher_token = publisher.db_new_token("publish");
+    let response = publisher_token.add_named_owner(&krate.name, "foo");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "publish-only owners don't have permission to modify owners" }] })
+    );
+
+    // Yanking only requires publish rights
+    publisher_token
+        .delete::<OkBool>("/api/v1/crates/owners_publish_only/1.0.0/yank")
+        .good();
+}
+
+#[test]
+fn roles_of_existing_owners_can_be_changed() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    let krate = app
+        .db(|conn| CrateBuilder::new("owners_change_role", user.as_model().id).expect_build(conn));
+    let other = create_and_add_owner(&app, &token, "other", &krate);
+
+    let json = add_owners_with_role(&token, &krate.name, &["foo"], "full").good();
+    assert_eq!(json["errors"][0]["detail"], "`foo` is already an owner");
+
+    let json = add_owners_with_role(&token, &krate.name, &["foo"], "publish").good();
+    assert_eq!(json["msg"], "role of foo has been changed to publish");
+
+    let response = token.add_named_owner(&krate.name, "new");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "publish-only owners don't have permission to modify owners" }] })
+    );
+
+    // The last owner with full rights cannot give them up
+    let other_token = other.db_new_token("other");
+    let json = add_owners_with_role(&other_token, &krate.name, &["other"], "publish").good();
+    assert_eq!(
+        json["errors"][0]["detail"],
+        "at least one owner of a crate must have full rights to manage its owners"
+    );
+}
+
+#[test]
+fn invalid_owner_roles_are_rejected() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    app.db(|conn| CrateBuilder::new("owners_bad_role", user.as_model().id).expect_build(conn));
+    app.db_new_user("other");
+
+    let json = add_owners_with_role(&token, "owners_bad_role", &["other"], "admin").good();
+    assert_eq!(
+        json["errors"][0]["detail"],
+        "invalid role `admin`, expected `full` or `publish`"
+    );
+}
+
+#[test]
+fn owners_can_list_and_cancel_invitations() {
+    let (app, anon, owner, token) = TestApp::init().with_token();
+    let krate = app
+        .db(|conn| CrateBuilder::new("owners_invitations", owner.as_model().id).expect_build(conn));
+    let invitee = app.db_new_user("invitee");
+    add_owners_with_role(&token, &krate.name, &["invitee"], "publish").good();
+
+    let team = app.db(|conn| {
+        let team = new_team("github:test_org:pending")
+            .create_or_update(conn)
+            .unwrap();
+        let config = &app.as_inner().config;
+        let created = CrateOwnerTeamInvitation::create(
+            team.id,
+            owner.as_model().id,
+            krate.id,
+            OwnerRole::Publish,
+            conn,
+            config,
+        )
+        .unwrap();
+        assert!(created);
+        team
+    });
+
+    let url = "/api/v1/crates/owners_invitations/owner_invitations";
+    let json = owner.get::<serde_json::Value>(url).good();
+    let invitations: Vec<EncodablePendingOwnerInvitation> =
+        serde_json::from_value(json["invitations"].clone()).unwrap();
+    let summary = invitations
+        .iter()
+        .map(|i| {
+            let inviter = i.invited_by.as_deref();
+            (i.login.as_str(), i.kind.as_str(), i.role.as_str(), inviter)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            ("invitee", "user", "publish", Some("foo")),
+            (team.login.as_str(), "team", "publish", Some("foo")),
+        ]
+    );
+
+    // Only owners with full rights can see and cancel invitations
+    let response = invitee.get::<()>(url);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = anon.get::<()>(url);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let json = invitee
+        .delete::<serde_json::Value>(&format!("{url}/invitee"))
+        .good();
+    assert_eq!(
+        json["errors"][0]["detail"],
+        "only owners with full rights have permission to remove invitations"
+    );
+
+    token.delete::<OkBool>(&format!("{url}/invitee")).good();
+    token
+        .delete::<OkBool>(&format!("{url}/{}", team.login))
+        .good();
+    token
+        .delete::<()>(&format!("{url}/invitee"))
+        .assert_not_found();
+
+    let json = owner.get::<serde_json::Value>(url).good();
+    assert_eq!(json["invitations"], json!([]));
+    let response = invitee.try_accept_ownership_invitation::<()>(&krate.name, krate.id);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn owner_changes_are_recorded_in_the_audit_log() {
+    let (app, anon, owner, token) = TestApp::init().with_token();
+    let krate = app
+        .db(|conn| CrateBuilder::new("owners_audit_log", owner.as_model().id).expect_build(conn));
+    let invitee = app.db_new_user("invitee");
+
+    add_owners_with_role(&token, &krate.name, &["invitee"], "publish").good();
+    invitee.accept_ownership_invitation(&krate.name, krate.id);
+    add_owners_with_role(&token, &krate.name, &["invitee"], "full").good();
+    token.remove_named_owner(&krate.name, "invitee").good();
+
+    let url = "/api/v1/crates/owners_audit_log/audit_log";
+    let json = owner.get::<serde_json::Value>(url).good();
+    let summary = json["actions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|action| {
+            (
+                action["action"].as_str().unwrap(),
+                action["user"]["login"].as_str().unwrap(),
+                action["subject"].as_str(),
+                action["details"].as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            ("remove_owner", "foo", Some("invitee"), None),
+            ("change_owner_role", "foo", Some("invitee"), Some("full")),
+            (
+                "accept_invitation",
+                "invitee",
+                Some("invitee"),
+                Some("publish")
+            ),
+            ("invite_owner", "foo", Some("invitee"), Some("publish")),
+        ]
+    );
+    assert_eq!(json["meta"]["total"], 4);
+
+    // Only owners can see the audit log
+    let response = invitee.get::<()>(url);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = anon.get::<()>(url);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,192 @@
+use crate::builders::CrateBuilder;
+use crate::util::{MockAnonymousUser, Response};
+use crate::{RequestHelper, TestApp};
+use cargo_registry::{
+    github::GitHubPublicKey,
+    models::{ApiToken, NewTrustedPublisher},
+    schema::api_tokens,
+};
+use chrono::{Duration, Utc};
+use conduit::{Method, StatusCode};
+use diesel::prelude::*;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+
+static URL: &str = "/api/github/secret-scanning/verify";
+static KEY_IDENTIFIER: &str = "test-key";
+
+fn generate_key() -> PKey<Private> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+fn app_with_key(key: &PKey<Private>) -> (TestApp, MockAnonymousUser) {
+    let public_key = GitHubPublicKey {
+        key_identifier: KEY_IDENTIFIER.into(),
+        key: String::from_utf8(key.public_key_to_pem().unwrap()).unwrap(),
+    };
+
+    TestApp::init()
+        .with_config(|config| config.secret_scanning_public_keys = vec![public_key])
+        .empty()
+}
+
+fn sign(key: &PKey<Private>, body: &[u8]) -> String {
+    let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+    signer.update(body).unwrap();
+    base64::encode(signer.sign_to_vec().unwrap())
+}
+
+fn report(
+    anon: &MockAnonymousUser,
+    key_identifier: &str,
+    signature: &str,
+    body: &[u8],
+) -> Response<()> {
+    let mut request = anon.request_builder(Method::POST, URL);
+    request.header("GITHUB-PUBLIC-KEY-IDENTIFIER", key_identifier);
+    request.header("GITHUB-PUBLIC-KEY-SIGNATURE", signature);
+    request.with_body(body);
+    anon.run(request)
+}
+
+fn alert_body(token: &str) -> Vec<u8> {
+    serde_json::to_vec(&json!([{
+        "token": token,
+        "type": "crates_io_token",
+        "url": "https://github.com/foo/bar/blob/main/.cargo/credentials",
+        "source": "content",
+    }]))
+    .unwrap()
+}
+
+#[test]
+fn reported_token_is_revoked() {
+    let key = generate_key();
+    let (app, anon) = app_with_key(&key);
+    let user = app.db_new_user("foo");
+    let token = user.db_new_token("leaked");
+
+    let body = alert_body(token.plaintext());
+    let response = report(&anon, KEY_IDENTIFIER, &sign(&key, &body), &body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!([{
+            "token_raw": token.plaintext(),
+            "token_type": "crates_io_token",
+            "label": "true_positive",
+        }])
+    );
+
+    let revoked: ApiToken =
+        app.db(|conn| assert_ok!(api_tokens::table.find(token.as_model().id).first(conn)));
+    assert!(revoked.revoked);
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, "something@example.com");
+    assert_eq!(emails[0].subject, "Exposed API token found");
+    assert!(emails[0].body.contains("\"leaked\""));
+    assert!(emails[0]
+        .body
+        .contains("https://github.com/foo/bar/blob/main"));
+
+    // Reporting the same token again does not send another email
+    let response = report(&anon, KEY_IDENTIFIER, &sign(&key, &body), &body);
+    assert_eq!(response.into_json()[0]["label"], "false_positive");
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 1);
+}
+
+#[test]
+fn reported_trusted_publishing_token_is_revoked() {
+    let key = generate_key();
+    let (app, anon) = app_with_key(&key);
+    let user = app.db_new_user("foo");
+    let user_id = user.as_model().id;
+    let in_an_hour = (Utc::now() + Duration::hours(1)).naive_utc();
+
+    let token = app.db(|conn| {
+        let krate = CrateBuilder::new("foo_leaked", user_id).expect_build(conn);
+        let publisher = NewTrustedPublisher {
+            crate_id: krate.id,
+            created_by: user_id,
+            issuer: "https://token.actions.githubusercontent.com",
+            repository: "foo/bar",
+            workflow: "release.yml",
+        }
+        .insert(conn)
+        .unwrap();
+        ApiToken::insert_for_trusted_publisher(conn, &publisher, &krate.name, in_an_hour).unwrap()
+    });
+    assert!(token.plaintext.starts_with("ctp"));
+
+    let body = alert_body(&token.plaintext);
+    let response = report(&anon, KEY_IDENTIFIER, &sign(&key, &body), &body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json()[0]["label"], "true_positive");
+
+    let revoked: ApiToken =
+        app.db(|conn| assert_ok!(api_tokens::table.find(token.model.id).first(conn)));
+    assert!(revoked.revoked);
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 1);
+}
+
+#[test]
+fn unknown_tokens_are_false_positives() {
+    let key = generate_key();
+    let (app, anon) = app_with_key(&key);
+    let user = app.db_new_user("foo");
+    let token = user.db_new_token("safe");
+
+    for leaked in ["cioAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA", "not-a-token"] {
+        let body = alert_body(leaked);
+        let response = report(&anon, KEY_IDENTIFIER, &sign(&key, &body), &body);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_json()[0]["label"], "false_positive");
+    }
+
+    let token: ApiToken =
+        app.db(|conn| assert_ok!(api_tokens::table.find(token.as_model().id).first(conn)));
+    assert!(!token.revoked);
+    assert!(app.as_inner().emails.mails_in_memory().unwrap().is_empty());
+}
+
+#[test]
+fn reports_must_be_signed() {
+    let key = generate_key();
+    let (app, anon) = app_with_key(&key);
+    let user = app.db_new_user("foo");
+    let token = user.db_new_token("leaked");
+    let body = alert_body(token.plaintext());
+
+    let other_key = generate_key();
+    let response = report(&anon, KEY_IDENTIFIER, &sign(&other_key, &body), &body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "invalid signature" }] })
+    );
+
+    let response = report(&anon, "other-key", &sign(&key, &body), &body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "unknown public key identifier" }] })
+    );
+
+    let response = report(&anon, KEY_IDENTIFIER, "not base64!", &body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let mut request = anon.request_builder(Method::POST, URL);
+    request.with_body(&body);
+    let response = anon.run::<()>(request);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let token: ApiToken =
+        app.db(|conn| assert_ok!(api_tokens::table.find(token.as_model().id).first(conn)));
+    assert!(!token.revoked);
+}
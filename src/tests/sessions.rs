@@ -0,0 +1,62 @@
+use crate::util::{encode_session, MockCookieUser, RequestHelper};
+use crate::{OkBool, TestApp};
+use conduit::{header, Method, StatusCode};
+use std::collections::HashMap;
+
+#[test]
+fn sessions_can_be_listed_and_revoked() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    let other_device = MockCookieUser::new(&app, user.as_model().clone());
+
+    let json = user.get::<serde_json::Value>("/api/v1/me/sessions").good();
+    let sessions = json["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(json["current_session_id"], user.session_id());
+    assert!(sessions[0]["created_at"].is_string());
+    assert!(sessions[0]["last_used_at"].is_string());
+
+    let url = format!("/api/v1/me/sessions/{}", other_device.session_id());
+    user.delete::<OkBool>(&url).good();
+
+    let response = other_device.get::<()>("/api/v1/me");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    user.get::<serde_json::Value>("/api/v1/me").good();
+
+    // Revoked sessions and sessions of other users cannot be revoked
+    user.delete::<()>(&url).assert_not_found();
+    let stranger = app.db_new_user("stranger");
+    let url = format!("/api/v1/me/sessions/{}", user.session_id());
+    stranger.delete::<()>(&url).assert_not_found();
+
+    let response = token.get::<()>("/api/v1/me/sessions");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn logging_out_revokes_the_session() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    user.delete::<bool>("/api/private/session").good();
+
+    // A copy of the cookie is no longer valid
+    let response = user.get::<()>("/api/v1/me");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn legacy_cookies_are_rejected() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    // Cookies from before sessions were stored in the database cannot be revoked, so users
+    // have to log in again
+    let mut session = HashMap::new();
+    session.insert("user_id".to_string(), user.as_model().id.to_string());
+    let cookie = encode_session(app.as_inner().session_key(), &session);
+    let mut request = anon.request_builder(Method::GET, "/api/v1/me");
+    request.header(header::COOKIE, &cookie);
+    let response = anon.run::<()>(request);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let json = user.get::<serde_json::Value>("/api/v1/me/sessions").good();
+    assert_eq!(json["sessions"].as_array().unwrap().len(), 1);
+}
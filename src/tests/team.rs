@@ -3,10 +3,14 @@ use crate::{
     builders::{CrateBuilder, PublishBuilder},
     new_team,
     record::GhUser,
-    OwnerTeamsResponse, RequestHelper, TestApp,
+    OkBool, OwnerTeamsResponse, RequestHelper, TestApp,
 };
-use cargo_registry::models::{Crate, NewTeam, NewUser};
+use cargo_registry::models::{Crate, NewTeam, NewUser, TeamMembership};
+use cargo_registry::schema::github_team_memberships;
+use cargo_registry::worker;
+use chrono::{Duration, Utc};
 use std::sync::Once;
+use swirl::Job;
 
 use conduit::StatusCode;
 use diesel::*;
@@ -366,7 +370,7 @@ fn crates_by_team_id_not_including_deleted_owners() {
         let krate = CrateBuilder::new("foo", user.id).expect_build(conn);
         add_team_to_crate(&t, &krate, user, conn).unwrap();
         krate
-            .owner_remove(app.as_inner(), conn, user, &t.login)
+            .owner_remove(app.as_inner(), conn, user, None, &t.login)
             .unwrap();
         t
     });
@@ -374,3 +378,126 @@ fn crates_by_team_id_not_including_deleted_owners() {
     let json = anon.search(&format!("team_id={}", team.id));
     assert_eq!(json.crates.len(), 0);
 }
+
+#[test]
+fn cached_memberships_are_used_without_asking_github() {
+    // Without a proxy, any request to GitHub would fail the test
+    let (app, _, user) = TestApp::init()
+        .with_config(|config| {
+            config.team_membership_cache_ttl = std::time::Duration::from_secs(60 * 60)
+        })
+        .with_git_index()
+        .with_job_runner()
+        .with_user();
+    let other = app.db_new_user("other");
+    let owner = app.db_new_user("owner");
+
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let team = new_team("github:test_org:cached")
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("foo_cached_team", owner.id)
+            .version("1.0.0")
+            .expect_build(conn);
+        add_team_to_crate(&team, &krate, owner, conn).unwrap();
+
+        TeamMembership::record(conn, team.id, user.as_model().id, true).unwrap();
+        TeamMembership::record(conn, team.id, other.as_model().id, false).unwrap();
+    });
+    app.add_versions_to_index("foo_cached_team", &["1.0.0"]);
+
+    let url = "/api/v1/crates/foo_cached_team/1.0.0/yank";
+    user.delete::<OkBool>(url).good();
+    let json = other.delete::<serde_json::Value>(url).good();
+    assert_eq!(
+        json["errors"][0]["detail"],
+        "must already be an owner to yank or unyank"
+    );
+}
+
+#[test]
+fn recently_confirmed_memberships_are_used_while_github_is_unavailable() {
+    let (app, _, user) = TestApp::with_proxy()
+        .with_config(|config| {
+            config.team_membership_fallback_ttl = std::time::Duration::from_secs(24 * 60 * 60)
+        })
+        .with_git_index()
+        .with_job_runner()
+        .with_user();
+    let owner = app.db_new_user("owner");
+
+    let set_checked_at = |team_id: i32, age: Duration| {
+        app.db(|conn| {
+            diesel::update(github_team_memberships::table.find((team_id, user.as_model().id)))
+                .set(github_team_memberships::checked_at.eq(Utc::now().naive_utc() - age))
+                .execute(conn)
+                .unwrap();
+        });
+    };
+
+    let team = app.db(|conn| {
+        let owner = owner.as_model();
+        let team = NewTeam::new("github:test_org:unavailable", 1000, 2000, None, None)
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("foo_unavailable_team", owner.id)
+            .version("1.0.0")
+            .expect_build(conn);
+        add_team_to_crate(&team, &krate, owner, conn).unwrap();
+
+        TeamMembership::record(conn, team.id, user.as_model().id, true).unwrap();
+        team
+    });
+    app.add_versions_to_index("foo_unavailable_team", &["1.0.0"]);
+
+    // GitHub answers with server errors to both requests
+    set_checked_at(team.id, Duration::hours(1));
+    user.delete::<OkBool>("/api/v1/crates/foo_unavailable_team/1.0.0/yank")
+        .good();
+
+    set_checked_at(team.id, Duration::days(2));
+    let response = user.put::<()>("/api/v1/crates/foo_unavailable_team/1.0.0/unyank", b"");
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn refreshing_memberships_asks_github_again() {
+    let (app, _) = TestApp::full().empty();
+    let member = app.db_new_user("member");
+    let former_member = app.db_new_user("former-member");
+
+    let team = app.db(|conn| {
+        let team = NewTeam::new("github:test_org:refreshed", 1000, 2000, None, None)
+            .create_or_update(conn)
+            .unwrap();
+        for user in [&member, &former_member] {
+            TeamMembership::record(conn, team.id, user.as_model().id, true).unwrap();
+        }
+        diesel::update(github_team_memberships::table)
+            .set(
+                github_team_memberships::checked_at.eq(Utc::now().naive_utc() - Duration::hours(1)),
+            )
+            .execute(conn)
+            .unwrap();
+        team
+    });
+
+    app.db(|conn| {
+        worker::refresh_team_memberships(60).enqueue(conn).unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    app.db(|conn| {
+        let member = TeamMembership::find(conn, team.id, member.as_model().id)
+            .unwrap()
+            .unwrap();
+        assert!(member.is_member);
+        assert!(member.checked_within(std::time::Duration::from_secs(60)));
+
+        let former_member = TeamMembership::find(conn, team.id, former_member.as_model().id)
+            .unwrap()
+            .unwrap();
+        assert!(!former_member.is_member);
+    });
+}
@@ -1,11 +1,15 @@
-use crate::{RequestHelper, TestApp};
+use crate::builders::{CrateBuilder, PublishBuilder};
+use crate::{OkBool, RequestHelper, TestApp};
 use cargo_registry::{
-    models::ApiToken,
-    schema::api_tokens,
+    models::{ApiToken, ApiTokenActivity, CrateScope, EndpointScope},
+    schema::{api_token_activity, api_tokens, emails},
     util::errors::TOKEN_FORMAT_ERROR,
     views::{EncodableApiTokenWithToken, EncodableMe},
+    worker,
 };
+use chrono::{Duration, Utc};
 use std::collections::HashSet;
+use swirl::Job;
 
 use conduit::{header, StatusCode};
 use diesel::prelude::*;
@@ -374,3 +378,451 @@ fn old_tokens_give_specific_error_message() {
         json!({ "errors": [{ "detail": TOKEN_FORMAT_ERROR }] })
     );
 }
+
+#[test]
+fn create_token_with_endpoint_scopes() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let body =
+        br#"{ "api_token": { "name": "ci", "endpoint_scopes": ["publish-update", "yank"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    let expected = Some(vec![EndpointScope::PublishUpdate, EndpointScope::Yank]);
+    assert_eq!(json.api_token.endpoint_scopes, expected);
+
+    let tokens: Vec<ApiToken> =
+        app.db(|conn| assert_ok!(ApiToken::belonging_to(user.as_model()).load(conn)));
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].endpoint_scopes, expected);
+
+    let json = user.get::<serde_json::Value>(URL).good();
+    assert_eq!(
+        json["api_tokens"][0]["endpoint_scopes"],
+        json!(["publish-update", "yank"])
+    );
+}
+
+#[test]
+fn create_token_with_invalid_endpoint_scopes() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let body = br#"{ "api_token": { "name": "ci", "endpoint_scopes": [] } }"#;
+    let response = user.put::<()>(URL, body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = br#"{ "api_token": { "name": "ci", "endpoint_scopes": ["everything"] } }"#;
+    let response = user.put::<()>(URL, body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn scoped_token_cannot_change_owners() {
+    let (app, _, user) = TestApp::init().with_user();
+    let token =
+        user.db_new_scoped_token("ci", &[EndpointScope::PublishUpdate, EndpointScope::Yank]);
+    app.db_new_user("other");
+    app.db(|conn| {
+        CrateBuilder::new("foo_scoped", user.as_model().id).expect_build(conn);
+    });
+
+    let response = token.add_named_owner("foo_scoped", "other");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "this API token does not have the `change-owners` scope required for this action" }] })
+    );
+
+    let token = user.db_new_scoped_token("owners", &[EndpointScope::ChangeOwners]);
+    token.add_named_owner("foo_scoped", "other").good();
+}
+
+#[test]
+fn scoped_token_publish_new_and_update() {
+    let (app, _, user) = TestApp::full().with_user();
+    let update_only = user.db_new_scoped_token("update", &[EndpointScope::PublishUpdate]);
+    let new_only = user.db_new_scoped_token("new", &[EndpointScope::PublishNew]);
+
+    let response = update_only.enqueue_publish(PublishBuilder::new("foo_scoped"));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "this API token does not have the `publish-new` scope required for this action" }] })
+    );
+
+    new_only
+        .enqueue_publish(PublishBuilder::new("foo_scoped"))
+        .good();
+    app.run_pending_background_jobs();
+
+    let response = new_only.enqueue_publish(PublishBuilder::new("foo_scoped").version("2.0.0"));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    update_only
+        .enqueue_publish(PublishBuilder::new("foo_scoped").version("2.0.0"))
+        .good();
+}
+
+#[test]
+fn scoped_token_yank() {
+    let (app, _, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_scoped", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+    app.add_versions_to_index("foo_scoped", &["1.0.0"]);
+
+    let read_only = user.db_new_scoped_token("read", &[EndpointScope::ReadOnly]);
+    read_only
+        .delete::<()>("/api/v1/crates/foo_scoped/1.0.0/yank")
+        .assert_forbidden();
+    read_only.search("following=1");
+
+    let yank = user.db_new_scoped_token("yank", &[EndpointScope::Yank]);
+    yank.delete::<OkBool>("/api/v1/crates/foo_scoped/1.0.0/yank")
+        .good();
+    yank.get_with_query::<()>("/api/v1/crates", "following=1")
+        .assert_forbidden();
+}
+
+#[test]
+fn scoped_token_cannot_be_used_for_unscoped_actions() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_scoped", user.as_model().id).expect_build(conn);
+    });
+
+    let token = user.db_new_scoped_token("all", &[EndpointScope::ReadOnly]);
+    let response = token.put::<()>("/api/v1/crates/foo_scoped/follow", b"");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "scoped API tokens cannot be used for this action" }] })
+    );
+
+    // Legacy tokens without scopes keep working
+    let token = user.db_new_token("legacy");
+    token
+        .put::<OkBool>("/api/v1/crates/foo_scoped/follow", b"")
+        .good();
+}
+
+#[test]
+fn create_token_with_crate_scopes() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let body = br#"{ "api_token": { "name": "ci", "crate_scopes": ["ourcorp-*", "serde"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(
+        json.api_token.crate_scopes,
+        Some(vec![
+            CrateScope::try_from("ourcorp-*").unwrap(),
+            CrateScope::try_from("serde").unwrap(),
+        ])
+    );
+
+    let json = user.get::<serde_json::Value>(URL).good();
+    assert_eq!(
+        json["api_tokens"][0]["crate_scopes"],
+        json!(["ourcorp-*", "serde"])
+    );
+
+    let body = br#"{ "api_token": { "name": "ci", "crate_scopes": ["not a crate"] } }"#;
+    let response = user.put::<()>(URL, body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = br#"{ "api_token": { "name": "ci", "crate_scopes": [] } }"#;
+    let response = user.put::<()>(URL, body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn crate_scoped_token_only_works_for_matching_crates() {
+    let (app, _, user) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_user();
+    app.db_new_user("other");
+    app.db(|conn| {
+        CrateBuilder::new("ourcorp-client", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+        CrateBuilder::new("personal", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+    app.add_versions_to_index("ourcorp-client", &["1.0.0"]);
+
+    let token = user.db_new_crate_scoped_token("ci", &["ourcorp-*"]);
+
+    token
+        .delete::<OkBool>("/api/v1/crates/ourcorp-client/1.0.0/yank")
+        .good();
+    token.add_named_owner("ourcorp-client", "other").good();
+
+    let response = token.delete::<()>("/api/v1/crates/personal/1.0.0/yank");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "this API token cannot be used for the crate `personal`" }] })
+    );
+
+    let response = token.add_named_owner("personal", "other");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = token.enqueue_publish(PublishBuilder::new("personal").version("2.0.0"));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = token.put::<()>("/api/v1/crates/personal/follow", b"");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn crate_scopes_are_matched_against_the_current_name_of_renamed_crates() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db_new_user("other");
+    app.db(|conn| {
+        CrateBuilder::new("ourcorp-legacy", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn)
+            .rename(conn, "personal")
+            .unwrap();
+    });
+
+    // The scope matches the old name, which still resolves to the crate
+    let token = user.db_new_crate_scoped_token("ci", &["ourcorp-*"]);
+
+    let response = token.delete::<()>("/api/v1/crates/ourcorp-legacy/1.0.0/yank");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "this API token cannot be used for the crate `personal`" }] })
+    );
+
+    let response = token.add_named_owner("ourcorp-legacy", "other");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let body = br#"{"deprecation":{"message":"unmaintained"}}"#;
+    let response = token.put::<()>("/api/v1/crates/ourcorp-legacy/deprecation", body);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = token.delete::<()>("/api/v1/crates/ourcorp-legacy/deprecation");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn create_token_with_expiration() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let body = br#"{ "api_token": { "name": "ci", "expires_at": "2100-01-01T00:00:00+00:00" } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    let expires_at = json.api_token.expires_at.unwrap();
+    assert_eq!(expires_at.to_string(), "2100-01-01 00:00:00");
+
+    let token: ApiToken =
+        app.db(|conn| assert_ok!(ApiToken::belonging_to(user.as_model()).first(conn)));
+    assert_eq!(token.expires_at, Some(expires_at));
+
+    let body = br#"{ "api_token": { "name": "ci", "expires_at": "2000-01-01T00:00:00+00:00" } }"#;
+    let response = user.put::<()>(URL, body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "expires_at must be in the future" }] })
+    );
+}
+
+#[test]
+fn create_token_respects_max_lifetime() {
+    let (_, _, user) = TestApp::init()
+        .with_config(|config| config.max_token_lifetime_days = Some(90))
+        .with_user();
+
+    let body = br#"{ "api_token": { "name": "ci", "expires_at": "2100-01-01T00:00:00+00:00" } }"#;
+    let response = user.put::<()>(URL, body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "API tokens cannot be valid for more than 90 days" }] })
+    );
+
+    let body = br#"{ "api_token": { "name": "ci" } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    let expires_at = json.api_token.expires_at.unwrap();
+    let max_expires_at = (Utc::now() + Duration::days(90)).naive_utc();
+    assert!(expires_at <= max_expires_at);
+    assert!(expires_at > max_expires_at - Duration::minutes(5));
+}
+
+#[test]
+fn expired_tokens_give_specific_error_message() {
+    let (app, _, user) = TestApp::init().with_user();
+    let yesterday = (Utc::now() - Duration::days(1)).naive_utc();
+    let token = user.db_new_expiring_token("old", yesterday);
+
+    let response = token.get_with_query::<()>("/api/v1/crates", "following=1");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "The given API token has expired. You can generate a new token at https://crates.io/me." }] })
+    );
+
+    let expired: ApiToken =
+        app.db(|conn| assert_ok!(api_tokens::table.find(token.as_model().id).first(conn)));
+    assert_none!(expired.last_used_at);
+
+    let tomorrow = (Utc::now() + Duration::days(1)).naive_utc();
+    let token = user.db_new_expiring_token("new", tomorrow);
+    token.search("following=1");
+}
+
+#[test]
+fn owners_are_notified_about_expiring_tokens() {
+    let (app, _) = TestApp::init().with_git_index().with_job_runner().empty();
+    let user = app.db_new_user("foo");
+
+    let in_three_days = (Utc::now() + Duration::days(3)).naive_utc();
+    let in_a_month = (Utc::now() + Duration::days(30)).naive_utc();
+    user.db_new_expiring_token("expiring", in_three_days);
+    user.db_new_expiring_token("long-lived", in_a_month);
+    user.db_new_token("forever");
+
+    app.db(|conn| worker::notify_expiring_tokens().enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, "something@example.com");
+    assert_eq!(emails[0].subject, "Your API token is about to expire");
+    assert!(emails[0].body.contains("\"expiring\""));
+
+    // Every token is only announced once
+    app.db(|conn| worker::notify_expiring_tokens().enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 1);
+}
+
+#[test]
+fn expiry_notifications_continue_after_a_failed_recipient() {
+    let (app, _) = TestApp::init().with_git_index().with_job_runner().empty();
+    let broken = app.db_new_user("broken");
+    let user = app.db_new_user("foo");
+    app.db(|conn| {
+        diesel::update(emails::table.filter(emails::user_id.eq(broken.as_model().id)))
+            .set(emails::email.eq("not an email address"))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let in_three_days = (Utc::now() + Duration::days(3)).naive_utc();
+    let broken_token = broken.db_new_expiring_token("broken", in_three_days);
+    user.db_new_expiring_token("expiring", in_three_days);
+
+    app.db(|conn| worker::notify_expiring_tokens().enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert!(emails[0].body.contains("\"expiring\""));
+
+    let token: ApiToken = app.db(|conn| {
+        assert_ok!(api_tokens::table
+            .find(broken_token.as_model().id)
+            .first(conn))
+    });
+    assert_none!(token.expiry_notification_at);
+}
+
+#[test]
+fn mutating_token_requests_are_recorded() {
+    let (app, _, user, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_token();
+    app.db(|conn| {
+        CrateBuilder::new("foo_activity", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+    app.add_versions_to_index("foo_activity", &["1.0.0"]);
+
+    token
+        .delete::<OkBool>("/api/v1/crates/foo_activity/1.0.0/yank")
+        .good();
+    token
+        .put::<OkBool>("/api/v1/crates/foo_activity/1.0.0/unyank", b"")
+        .good();
+    token.search("following=1");
+
+    // Cookie sessions have no token to record activity for
+    user.delete::<OkBool>("/api/v1/crates/foo_activity/1.0.0/yank")
+        .good();
+
+    let url = format!("/api/v1/me/tokens/{}/activity", token.as_model().id);
+    let json = user.get::<serde_json::Value>(&url).good();
+    let activity = json["activity"].as_array().unwrap();
+    assert_eq!(activity.len(), 2);
+    assert_eq!(
+        activity[0]["endpoint"],
+        "PUT /crates/:crate_id/:version/unyank"
+    );
+    assert_eq!(
+        activity[1]["endpoint"],
+        "DELETE /crates/:crate_id/:version/yank"
+    );
+    for entry in activity {
+        assert_eq!(entry["crate_name"], "foo_activity");
+        assert_eq!(entry["version"], "1.0.0");
+        assert_eq!(entry["user_agent"], "conduit-test");
+        assert!(entry["ip_address"].is_string());
+    }
+    assert_eq!(json["meta"]["more"], false);
+
+    // Only the owner of the token can see its activity, and not with an API token
+    let other = app.db_new_user("other");
+    let response = other.get::<()>(&url);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = token.get::<()>(&url);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn old_token_activity_is_pruned() {
+    let (app, _, _, token) = TestApp::init()
+        .with_git_index()
+        .with_job_runner()
+        .with_token();
+    let token_id = token.as_model().id;
+    let long_ago = (Utc::now() - Duration::days(100)).naive_utc();
+
+    app.db(|conn| {
+        diesel::insert_into(api_token_activity::table)
+            .values(&vec![
+                (
+                    api_token_activity::api_token_id.eq(token_id),
+                    api_token_activity::endpoint.eq("PUT /crates/new"),
+                    api_token_activity::created_at.eq(long_ago),
+                ),
+                (
+                    api_token_activity::api_token_id.eq(token_id),
+                    api_token_activity::endpoint.eq("DELETE /crates/:crate_id/:version/yank"),
+                    api_token_activity::created_at.eq(Utc::now().naive_utc()),
+                ),
+            ])
+            .execute(conn)
+            .unwrap();
+
+        worker::prune_token_activity(90).enqueue(conn).unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    let remaining: Vec<ApiTokenActivity> =
+        app.db(|conn| api_token_activity::table.load(conn).unwrap());
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(
+        remaining[0].endpoint,
+        "DELETE /crates/:crate_id/:version/yank"
+    );
+}
@@ -0,0 +1,331 @@
+use crate::builders::{CrateBuilder, PublishBuilder};
+use crate::util::{MockAnonymousUser, MockCookieUser, Response};
+use crate::{RequestHelper, TestApp};
+use cargo_registry::oidc::{OidcKeyStore, GITHUB_ACTIONS_ISSUER};
+use cargo_registry::schema::crate_owners;
+use chrono::{Duration, Utc};
+use conduit::{header, Method, StatusCode};
+use diesel::prelude::*;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use openssl::rsa::Rsa;
+use std::collections::HashMap;
+
+static KEY_ID: &str = "test-key";
+static REPOSITORY: &str = "rust-lang/foo";
+static WORKFLOW_REF: &str = "rust-lang/foo/.github/workflows/release.yml@refs/tags/v2.0.0";
+
+/// Stands in for the signing keys of GitHub Actions.
+struct Issuer {
+    private_key_pem: Vec<u8>,
+    jwks: JwkSet,
+}
+
+impl Issuer {
+    fn new() -> Self {
+        let rsa = Rsa::generate(2048).unwrap();
+        let encode = |bytes: Vec<u8>| base64::encode_config(bytes, base64::URL_SAFE_NO_PAD);
+        let jwks = serde_json::from_value(json!({
+            "keys": [{
+                "kty": "RSA",
+                "use": "sig",
+                "alg": "RS256",
+                "kid": KEY_ID,
+                "n": encode(rsa.n().to_vec()),
+                "e": encode(rsa.e().to_vec()),
+            }]
+        }))
+        .unwrap();
+
+        Self {
+            private_key_pem: rsa.private_key_to_pem().unwrap(),
+            jwks,
+        }
+    }
+
+    fn key_store(&self) -> OidcKeyStore {
+        let mut keys = HashMap::new();
+        keys.insert(GITHUB_ACTIONS_ISSUER.to_string(), self.jwks.clone());
+        OidcKeyStore::new_static(keys)
+    }
+
+    fn sign(&self, claims: serde_json::Value) -> String {
+        let header = Header {
+            kid: Some(KEY_ID.into()),
+            ..Header::new(Algorithm::RS256)
+        };
+        let key = EncodingKey::from_rsa_pem(&self.private_key_pem).unwrap();
+        jsonwebtoken::encode(&header, &claims, &key).unwrap()
+    }
+}
+
+fn claims() -> serde_json::Value {
+    json!({
+        "iss": GITHUB_ACTIONS_ISSUER,
+        "aud": "crates.io",
+        "exp": (Utc::now() + Duration::minutes(5)).timestamp(),
+        "repository": REPOSITORY,
+        "workflow_ref": WORKFLOW_REF,
+    })
+}
+
+fn setup(issuer: &Issuer) -> (TestApp, MockAnonymousUser, MockCookieUser) {
+    let (app, anon, user) = TestApp::init()
+        .with_oidc_keys(issuer.key_store())
+        .with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+    (app, anon, user)
+}
+
+fn register(
+    user: &MockCookieUser,
+    repository: &str,
+    workflow: &str,
+) -> Response<serde_json::Value> {
+    let body = json!({
+        "trusted_publisher": { "repository": repository, "workflow": workflow }
+    });
+    user.put(
+        "/api/v1/crates/foo/trusted_publishers",
+        body.to_string().as_bytes(),
+    )
+}
+
+fn exchange(anon: &MockAnonymousUser, jwt: &str) -> Response<serde_json::Value> {
+    let body = json!({ "jwt": jwt });
+    anon.put(
+        "/api/v1/crates/foo/trusted_publishing_token",
+        body.to_string().as_bytes(),
+    )
+}
+
+#[test]
+fn owners_can_manage_trusted_publishers() {
+    let issuer = Issuer::new();
+    let (app, anon, user) = setup(&issuer);
+
+    let json = register(&user, REPOSITORY, "release.yml").good();
+    let publisher = &json["trusted_publisher"];
+    assert_eq!(publisher["issuer"], GITHUB_ACTIONS_ISSUER);
+    assert_eq!(publisher["repository"], REPOSITORY);
+    assert_eq!(publisher["workflow"], "release.yml");
+    let id = publisher["id"].as_i64().unwrap();
+
+    let response = register(&user, "Rust-Lang/foo", "release.yml");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "this trusted publisher is already registered" }] })
+    );
+
+    let response = register(&user, "foo", "release.yml");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = register(&user, REPOSITORY, ".github/workflows/release.yml");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let json = user
+        .get::<serde_json::Value>("/api/v1/crates/foo/trusted_publishers")
+        .good();
+    assert_eq!(json["trusted_publishers"].as_array().unwrap().len(), 1);
+
+    // Other users and API tokens cannot see or change the configuration
+    let other = app.db_new_user("bar");
+    let response = other.get::<()>("/api/v1/crates/foo/trusted_publishers");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = register(&other, "bar/foo", "release.yml");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let token = user.db_new_token("bar");
+    let response = token.get::<()>("/api/v1/crates/foo/trusted_publishers");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Deleting a publisher revokes the tokens that were obtained through it
+    let api_token = exchange(&anon, &issuer.sign(claims())).good()["api_token"]["token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let url = format!("/api/v1/crates/foo/trusted_publishers/{id}");
+    user.delete::<serde_json::Value>(&url).good();
+
+    let json = user
+        .get::<serde_json::Value>("/api/v1/crates/foo/trusted_publishers")
+        .good();
+    assert!(json["trusted_publishers"].as_array().unwrap().is_empty());
+
+    let mut request = anon.request_builder(Method::PUT, "/api/v1/crates/new");
+    request.header(header::AUTHORIZATION, &api_token);
+    request.with_body(&PublishBuilder::new("foo").version("2.0.0").body());
+    let response = anon.run::<()>(request);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn trusted_publisher_changes_are_recorded_in_the_audit_log() {
+    let issuer = Issuer::new();
+    let (_app, _anon, user) = setup(&issuer);
+
+    let json = register(&user, REPOSITORY, "release.yml").good();
+    let id = json["trusted_publisher"]["id"].as_i64().unwrap();
+    let url = format!("/api/v1/crates/foo/trusted_publishers/{id}");
+    user.delete::<serde_json::Value>(&url).good();
+
+    let json = user
+        .get::<serde_json::Value>("/api/v1/crates/foo/audit_log")
+        .good();
+    let summary = json["actions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|action| {
+            (
+                action["action"].as_str().unwrap(),
+                action["user"]["login"].as_str().unwrap(),
+                action["subject"].as_str(),
+                action["details"].as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                "remove_trusted_publisher",
+                "foo",
+                Some(REPOSITORY),
+                Some("release.yml")
+            ),
+            (
+                "add_trusted_publisher",
+                "foo",
+                Some(REPOSITORY),
+                Some("release.yml")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn exchanged_token_is_scoped_to_the_crate() {
+    let issuer = Issuer::new();
+    let (app, anon, user) = setup(&issuer);
+    register(&user, REPOSITORY, "release.yml").good();
+
+    let json = exchange(&anon, &issuer.sign(claims())).good();
+    let api_token = &json["api_token"];
+    assert_eq!(api_token["endpoint_scopes"], json!(["publish-update"]));
+    assert_eq!(api_token["crate_scopes"], json!(["foo"]));
+    assert!(api_token["expires_at"].is_string());
+    let api_token = api_token["token"].as_str().unwrap();
+    assert!(api_token.starts_with("ctp"));
+
+    let publish = |name: &str| {
+        let mut request = anon.request_builder(Method::PUT, "/api/v1/crates/new");
+        request.header(header::AUTHORIZATION, api_token);
+        request.with_body(&PublishBuilder::new(name).version("2.0.0").body());
+        anon.run::<()>(request)
+    };
+
+    // The token only publishes, other changes to the crate need a personal token
+    let mut request = anon.request_builder(Method::PUT, "/api/v1/crates/foo/deprecation");
+    request.header(header::AUTHORIZATION, api_token);
+    request.with_body(br#"{"deprecation":{"message":"moved to CI"}}"#);
+    assert_eq!(anon.run::<()>(request).status(), StatusCode::FORBIDDEN);
+
+    // Other crates cannot be touched, and new crates cannot be published
+    app.db(|conn| {
+        CrateBuilder::new("bar", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+    assert_eq!(publish("bar").status(), StatusCode::FORBIDDEN);
+
+    assert_eq!(publish("baz").status(), StatusCode::FORBIDDEN);
+
+    // Nor is managing the owner's account with it
+    let mut request = anon.request_builder(Method::PUT, "/api/v1/me/tokens");
+    request.header(header::AUTHORIZATION, api_token);
+    request.with_body(br#"{"api_token":{"name":"persistent"}}"#);
+    assert_eq!(anon.run::<()>(request).status(), StatusCode::BAD_REQUEST);
+
+    // The short-lived tokens do not clutter the owner's token list
+    let json = user.get::<serde_json::Value>("/api/v1/me/tokens").good();
+    assert!(json["api_tokens"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn oidc_tokens_must_match_a_trusted_publisher() {
+    let issuer = Issuer::new();
+    let (_, anon, user) = setup(&issuer);
+
+    // Nothing is registered yet
+    let response = exchange(&anon, &issuer.sign(claims()));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    register(&user, REPOSITORY, "release.yml").good();
+
+    let mut wrong_workflow = claims();
+    wrong_workflow["workflow_ref"] =
+        json!("rust-lang/foo/.github/workflows/ci.yml@refs/heads/main");
+    let mut wrong_repository = claims();
+    wrong_repository["repository"] = json!("rust-lang/bar");
+    wrong_repository["workflow_ref"] =
+        json!("rust-lang/bar/.github/workflows/release.yml@refs/heads/main");
+    let mut wrong_audience = claims();
+    wrong_audience["aud"] = json!("sigstore");
+    let mut expired = claims();
+    expired["exp"] = json!((Utc::now() - Duration::minutes(5)).timestamp());
+    let mut unknown_issuer = claims();
+    unknown_issuer["iss"] = json!("https://gitlab.com");
+
+    for claims in [
+        wrong_workflow,
+        wrong_repository,
+        wrong_audience,
+        expired,
+        unknown_issuer,
+    ] {
+        let response = exchange(&anon, &issuer.sign(claims));
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // Tokens signed by anyone but the issuer are rejected
+    let impostor = Issuer::new();
+    let response = exchange(&anon, &impostor.sign(claims()));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = exchange(&anon, "not-a-jwt");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    exchange(&anon, &issuer.sign(claims())).good();
+}
+
+#[test]
+fn publishers_stop_working_when_their_creator_cannot_publish() {
+    let issuer = Issuer::new();
+    let (app, anon, user) = setup(&issuer);
+    register(&user, REPOSITORY, "release.yml").good();
+    let user = user.as_model();
+
+    app.db(|conn| user.lock(conn, "spam", None).unwrap());
+    let response = exchange(&anon, &issuer.sign(claims()));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the owner that registered this trusted publisher can no longer publish `foo`" }] })
+    );
+
+    app.db(|conn| user.unlock(conn).unwrap());
+    exchange(&anon, &issuer.sign(claims())).good();
+
+    app.db(|conn| {
+        diesel::update(crate_owners::table.filter(crate_owners::owner_id.eq(user.id)))
+            .set(crate_owners::deleted.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+    let response = exchange(&anon, &issuer.sign(claims()));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
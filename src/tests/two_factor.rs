@@ -0,0 +1,243 @@
+use crate::builders::CrateBuilder;
+use crate::util::{encode_session, MockAnonymousUser, MockCookieUser, RequestHelper, Response};
+use crate::{OkBool, TestApp};
+use cargo_registry::models::MAX_FAILED_ATTEMPTS;
+use cargo_registry::schema::totp_credentials;
+use cargo_registry::util::totp;
+use chrono::{Duration, Utc};
+use conduit::{header, Method, StatusCode};
+use diesel::prelude::*;
+
+const NEW_TOKEN: &[u8] = br#"{"api_token":{"name":"bar"}}"#;
+
+fn current_code(app: &TestApp, user: &MockCookieUser) -> String {
+    let secret: Vec<u8> = app.db(|conn| {
+        totp_credentials::table
+            .find(user.as_model().id)
+            .select(totp_credentials::secret)
+            .first(conn)
+            .unwrap()
+    });
+    totp::code(&secret, totp::time_step(Utc::now().timestamp()))
+}
+
+/// Enables two-factor authentication for the user and returns their recovery codes.
+fn enable_two_factor(app: &TestApp, user: &MockCookieUser) -> Vec<String> {
+    user.put::<serde_json::Value>("/api/v1/me/two_factor", b"")
+        .good();
+    let body = json!({ "code": current_code(app, user) }).to_string();
+    let json = user
+        .put::<serde_json::Value>("/api/v1/me/two_factor/enable", body.as_bytes())
+        .good();
+
+    serde_json::from_value(json["recovery_codes"].clone()).unwrap()
+}
+
+/// Sends a request from a session in which a code was confirmed `age` seconds ago.
+fn confirmed_request<T>(
+    anon: &MockAnonymousUser,
+    user: &MockCookieUser,
+    age: i64,
+    method: Method,
+    path: &str,
+    body: &[u8],
+) -> Response<T> {
+    let user_id = user.as_model().id;
+    let confirmed_at = Utc::now().timestamp() - age;
+
+    let mut session = user.session_data();
+    session.insert(
+        "second_factor_at".to_string(),
+        format!("{user_id}:{confirmed_at}"),
+    );
+    let cookie = encode_session(anon.app().as_inner().session_key(), &session);
+
+    let mut request = anon.request_builder(method, path);
+    request.header(header::COOKIE, &cookie);
+    request.with_body(body);
+    anon.run(request)
+}
+
+#[test]
+fn users_can_enroll() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let json = user
+        .get::<serde_json::Value>("/api/v1/me/two_factor")
+        .good();
+    assert_eq!(json["enabled"], false);
+
+    let json = user
+        .put::<serde_json::Value>("/api/v1/me/two_factor", b"")
+        .good();
+    let secret = json["secret"].as_str().unwrap();
+    assert_eq!(secret.len(), 32);
+    let url = json["otpauth_url"].as_str().unwrap();
+    assert!(url.starts_with("otpauth://totp/"), "{url}");
+    assert!(url.contains(secret), "{url}");
+
+    let response = user.put::<()>("/api/v1/me/two_factor/enable", br#"{"code":"000000"}"#);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = json!({ "code": current_code(&app, &user) }).to_string();
+    let json = user
+        .put::<serde_json::Value>("/api/v1/me/two_factor/enable", body.as_bytes())
+        .good();
+    assert_eq!(json["recovery_codes"].as_array().unwrap().len(), 10);
+
+    let json = user
+        .get::<serde_json::Value>("/api/v1/me/two_factor")
+        .good();
+    assert_eq!(json["enabled"], true);
+    assert_eq!(json["recovery_codes_remaining"], 10);
+
+    // Codes cannot be used twice
+    let response = user.put::<()>("/api/v1/me/two_factor/confirm", body.as_bytes());
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = user.put::<()>("/api/v1/me/two_factor", b"");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn sensitive_actions_require_a_recent_second_factor() {
+    let (app, anon, user) = TestApp::init().with_user();
+    enable_two_factor(&app, &user);
+
+    let response = user.put::<()>("/api/v1/me/tokens", NEW_TOKEN);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(response.into_json()["errors"][0]["detail"]
+        .as_str()
+        .unwrap()
+        .contains("two-factor authentication code"));
+
+    let response = confirmed_request::<()>(
+        &anon,
+        &user,
+        20 * 60,
+        Method::PUT,
+        "/api/v1/me/tokens",
+        NEW_TOKEN,
+    );
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let json = confirmed_request::<serde_json::Value>(
+        &anon,
+        &user,
+        60,
+        Method::PUT,
+        "/api/v1/me/tokens",
+        NEW_TOKEN,
+    )
+    .good();
+    assert_eq!(json["api_token"]["name"], "bar");
+}
+
+#[test]
+fn managing_trusted_publishers_requires_a_recent_second_factor() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("foo_tp", user.as_model().id).expect_build(conn));
+    enable_two_factor(&app, &user);
+
+    let url = "/api/v1/crates/foo_tp/trusted_publishers";
+    let body = br#"{"trusted_publisher":{"repository":"rust-lang/foo","workflow":"release.yml"}}"#;
+    let response = user.put::<()>(url, body);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let json =
+        confirmed_request::<serde_json::Value>(&anon, &user, 60, Method::PUT, url, body).good();
+    let id = json["trusted_publisher"]["id"].as_i64().unwrap();
+
+    let url = format!("{url}/{id}");
+    let response = user.delete::<()>(&url);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    confirmed_request::<serde_json::Value>(&anon, &user, 60, Method::DELETE, &url, b"").good();
+}
+
+#[test]
+fn accepting_team_invitations_requires_a_recent_second_factor() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("foo_team", user.as_model().id).expect_build(conn));
+    enable_two_factor(&app, &user);
+
+    let url = "/api/v1/crates/foo_team/owner_invitations/github:test_org:core";
+    let response = user.put::<()>(url, b"");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(response.into_json()["errors"][0]["detail"]
+        .as_str()
+        .unwrap()
+        .contains("two-factor authentication code"));
+}
+
+#[test]
+fn users_without_two_factor_are_not_affected() {
+    let (_, _, user) = TestApp::init().with_user();
+    user.put::<serde_json::Value>("/api/v1/me/tokens", NEW_TOKEN)
+        .good();
+}
+
+#[test]
+fn recovery_codes_can_only_be_used_once() {
+    let (app, _, user) = TestApp::init().with_user();
+    let codes = enable_two_factor(&app, &user);
+
+    let body = json!({ "code": codes[0].to_uppercase() }).to_string();
+    user.put::<OkBool>("/api/v1/me/two_factor/confirm", body.as_bytes())
+        .good();
+    let response = user.put::<()>("/api/v1/me/two_factor/confirm", body.as_bytes());
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let json = user
+        .get::<serde_json::Value>("/api/v1/me/two_factor")
+        .good();
+    assert_eq!(json["recovery_codes_remaining"], 9);
+}
+
+#[test]
+fn too_many_invalid_codes_lock_out_the_user() {
+    let (app, _, user) = TestApp::init().with_user();
+    let codes = enable_two_factor(&app, &user);
+
+    for _ in 0..MAX_FAILED_ATTEMPTS {
+        let response = user.put::<()>("/api/v1/me/two_factor/confirm", br#"{"code":"000000"}"#);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // Not even valid codes are accepted during the lockout
+    let body = json!({ "code": codes[0] }).to_string();
+    let response = user.put::<()>("/api/v1/me/two_factor/confirm", body.as_bytes());
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    app.db(|conn| {
+        diesel::update(totp_credentials::table.find(user.as_model().id))
+            .set(totp_credentials::locked_until.eq(Utc::now().naive_utc() - Duration::minutes(1)))
+            .execute(conn)
+            .unwrap();
+    });
+    user.put::<OkBool>("/api/v1/me/two_factor/confirm", body.as_bytes())
+        .good();
+}
+
+#[test]
+fn disabling_requires_a_recent_second_factor() {
+    let (app, anon, user) = TestApp::init().with_user();
+    enable_two_factor(&app, &user);
+
+    let response = user.delete::<()>("/api/v1/me/two_factor");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    confirmed_request::<OkBool>(
+        &anon,
+        &user,
+        0,
+        Method::DELETE,
+        "/api/v1/me/two_factor",
+        b"",
+    )
+    .good();
+    let json = user
+        .get::<serde_json::Value>("/api/v1/me/two_factor")
+        .good();
+    assert_eq!(json["enabled"], false);
+    user.put::<serde_json::Value>("/api/v1/me/tokens", NEW_TOKEN)
+        .good();
+}
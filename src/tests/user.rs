@@ -198,7 +198,7 @@ fn crates_by_user_id_not_including_deleted_owners() {
     app.db(|conn| {
         let krate = CrateBuilder::new("foo_my_packages", user.id).expect_build(conn);
         krate
-            .owner_remove(app.as_inner(), conn, user, "foo")
+            .owner_remove(app.as_inner(), conn, user, None, "foo")
             .unwrap();
     });
 
@@ -330,7 +330,7 @@ fn user_total_downloads() {
             .execute(conn)
             .unwrap();
         no_longer_my_krate
-            .owner_remove(app.as_inner(), conn, user, &user.gh_login)
+            .owner_remove(app.as_inner(), conn, user, None, &user.gh_login)
             .unwrap();
     });
 
@@ -630,7 +630,7 @@ fn test_user_owned_crates_doesnt_include_deleted_ownership() {
     app.db(|conn| {
         let krate = CrateBuilder::new("foo_my_packages", user_model.id).expect_build(conn);
         krate
-            .owner_remove(app.as_inner(), conn, user_model, &user_model.gh_login)
+            .owner_remove(app.as_inner(), conn, user_model, None, &user_model.gh_login)
             .unwrap();
     });
 
@@ -754,3 +754,100 @@ fn test_update_email_notifications_not_owned() {
     // There should be no change to the `email_notifications` value for a crate not belonging to me
     assert!(email_notifications);
 }
+
+fn email_token(app: &TestApp, address: &str) -> String {
+    use cargo_registry::schema::emails;
+
+    app.db(|conn| {
+        emails::table
+            .filter(emails::email.eq(address))
+            .select(emails::token)
+            .first(conn)
+            .unwrap()
+    })
+}
+
+#[test]
+fn users_can_register_multiple_emails() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let json = user
+        .put::<serde_json::Value>("/api/v1/me/emails", br#"{"email":"work@example.com"}"#)
+        .good();
+    let work_id = json["email"]["id"].as_i64().unwrap();
+    assert_eq!(json["email"]["primary"], false);
+    assert_eq!(json["email"]["verified"], false);
+    assert_eq!(json["email"]["verification_sent"], true);
+
+    let mails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(mails.last().unwrap().to, "work@example.com");
+
+    let response = user.put::<()>("/api/v1/me/emails", br#"{"email":"Work@example.com"}"#);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let json = user.get::<serde_json::Value>("/api/v1/me/emails").good();
+    let emails = json["emails"].as_array().unwrap();
+    assert_eq!(emails.len(), 2);
+    assert_eq!(emails[0]["email"], "something@example.com");
+    assert_eq!(emails[0]["primary"], true);
+    let personal_id = emails[0]["id"].as_i64().unwrap();
+
+    // Only verified addresses can be made primary
+    let url = format!("/api/v1/me/emails/{work_id}/primary");
+    let response = user.put::<()>(&url, b"");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    user.confirm_email(&email_token(&app, "work@example.com"));
+    user.put::<OkBool>(&url, b"").good();
+
+    let json = user.show_me();
+    assert_eq!(json.user.email.unwrap(), "work@example.com");
+    assert!(json.user.email_verified);
+
+    // The primary address cannot be removed
+    let response = user.delete::<()>(&format!("/api/v1/me/emails/{work_id}"));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    user.delete::<OkBool>(&format!("/api/v1/me/emails/{personal_id}"))
+        .good();
+
+    let json = user.get::<serde_json::Value>("/api/v1/me/emails").good();
+    assert_eq!(json["emails"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn users_can_choose_the_email_recorded_on_publish() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let json = user
+        .put::<serde_json::Value>("/api/v1/me/emails", br#"{"email":"work@example.com"}"#)
+        .good();
+    let url = format!("/api/v1/me/emails/{}/publish", json["email"]["id"]);
+
+    let response = user.put::<()>(&url, b"");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    user.confirm_email(&email_token(&app, "work@example.com"));
+    user.put::<OkBool>(&url, b"").good();
+
+    let (publish_email, verified_email) = app.db(|conn| {
+        let user = user.as_model();
+        (
+            user.publish_email(conn).unwrap(),
+            user.verified_email(conn).unwrap(),
+        )
+    });
+    assert_eq!(publish_email.unwrap(), "work@example.com");
+    // Notifications still go to the primary address
+    assert_eq!(verified_email.unwrap(), "something@example.com");
+
+    let json = user.get::<serde_json::Value>("/api/v1/me/emails").good();
+    assert_eq!(json["emails"][1]["for_publishing"], true);
+}
+
+#[test]
+fn adding_emails_requires_a_cookie_session() {
+    let (_, _, _, token) = TestApp::init().with_token();
+
+    let response = token.put::<()>("/api/v1/me/emails", br#"{"email":"work@example.com"}"#);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
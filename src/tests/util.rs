@@ -23,7 +23,10 @@ use crate::{
     builders::PublishBuilder, CategoryListResponse, CategoryResponse, CrateList, CrateResponse,
     GoodCrate, OkBool, OwnersResponse, VersionResponse,
 };
-use cargo_registry::models::{ApiToken, CreatedApiToken, User};
+use cargo_registry::models::{
+    ApiToken, CrateScope, CreatedApiToken, EndpointScope, NewUserSession, User,
+};
+use chrono::NaiveDateTime;
 
 use conduit::{BoxError, Handler, Method};
 use conduit_cookie::SessionMiddleware;
@@ -54,15 +57,20 @@ pub use test_app::TestApp;
 /// The implementation matches roughly what is happening inside of the
 /// `SessionMiddleware` from `conduit_cookie`.
 pub fn encode_session_header(session_key: &str, user_id: i32) -> String {
-    let cookie_name = "cargo_session";
-    let cookie_key = cookie::Key::derive_from(session_key.as_bytes());
-
     // build session data map
     let mut map = HashMap::new();
     map.insert("user_id".into(), user_id.to_string());
 
+    encode_session(session_key, &map)
+}
+
+/// Like `encode_session_header`, but for arbitrary session data.
+pub fn encode_session(session_key: &str, map: &HashMap<String, String>) -> String {
+    let cookie_name = "cargo_session";
+    let cookie_key = cookie::Key::derive_from(session_key.as_bytes());
+
     // encode the map into a cookie value string
-    let encoded = SessionMiddleware::encode(&map);
+    let encoded = SessionMiddleware::encode(map);
 
     // put the cookie into a signed cookie jar
     let cookie = Cookie::build(cookie_name, encoded).finish();
@@ -213,12 +221,13 @@ impl RequestHelper for MockAnonymousUser {
 pub struct MockCookieUser {
     app: TestApp,
     user: User,
+    session_id: i64,
 }
 
 impl RequestHelper for MockCookieUser {
     fn request_builder(&self, method: Method, path: &str) -> MockRequest {
         let session_key = &self.app.as_inner().session_key();
-        let cookie = encode_session_header(session_key, self.user.id);
+        let cookie = encode_session(session_key, &self.session_data());
 
         let mut request = req(method, path);
         request.header(header::COOKIE, &cookie);
@@ -232,10 +241,23 @@ impl RequestHelper for MockCookieUser {
 
 impl MockCookieUser {
     /// Creates an instance from a database `User` instance
+    ///
+    /// This method logs the user in by creating a session in the database
     pub fn new(app: &TestApp, user: User) -> Self {
+        let session = app.db(|conn| {
+            NewUserSession {
+                user_id: user.id,
+                ip_address: None,
+                user_agent: None,
+            }
+            .insert(conn)
+            .unwrap()
+        });
+
         Self {
             app: app.clone(),
             user,
+            session_id: session.id,
         }
     }
 
@@ -244,6 +266,19 @@ impl MockCookieUser {
         &self.user
     }
 
+    /// The id of the session the requests of this user are made with
+    pub fn session_id(&self) -> i64 {
+        self.session_id
+    }
+
+    /// The data of the session cookie, for tests that need to add to it
+    pub fn session_data(&self) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        map.insert("user_id".into(), self.user.id.to_string());
+        map.insert("session_id".into(), self.session_id.to_string());
+        map
+    }
+
     /// Creates a token and wraps it in a helper struct
     ///
     /// This method updates the database directly
@@ -256,6 +291,60 @@ impl MockCookieUser {
             token,
         }
     }
+
+    /// Creates a token restricted to the given endpoint scopes and wraps it in a helper struct
+    ///
+    /// This method updates the database directly
+    pub fn db_new_scoped_token(&self, name: &str, scopes: &[EndpointScope]) -> MockTokenUser {
+        let token = self.app.db(|conn| {
+            ApiToken::insert_with_scopes(
+                conn,
+                self.user.id,
+                name,
+                Some(scopes.to_vec()),
+                None,
+                None,
+            )
+            .unwrap()
+        });
+        MockTokenUser {
+            app: self.app.clone(),
+            token,
+        }
+    }
+
+    /// Creates a token that only works for the given crate names or patterns and wraps it in
+    /// a helper struct
+    ///
+    /// This method updates the database directly
+    pub fn db_new_crate_scoped_token(&self, name: &str, patterns: &[&str]) -> MockTokenUser {
+        let crate_scopes = patterns
+            .iter()
+            .map(|pattern| CrateScope::try_from(*pattern).unwrap())
+            .collect();
+        let token = self.app.db(|conn| {
+            ApiToken::insert_with_scopes(conn, self.user.id, name, None, Some(crate_scopes), None)
+                .unwrap()
+        });
+        MockTokenUser {
+            app: self.app.clone(),
+            token,
+        }
+    }
+
+    /// Creates a token that expires at the given time and wraps it in a helper struct
+    ///
+    /// This method updates the database directly
+    pub fn db_new_expiring_token(&self, name: &str, expires_at: NaiveDateTime) -> MockTokenUser {
+        let token = self.app.db(|conn| {
+            ApiToken::insert_with_scopes(conn, self.user.id, name, None, None, Some(expires_at))
+                .unwrap()
+        });
+        MockTokenUser {
+            app: self.app.clone(),
+            token,
+        }
+    }
 }
 
 /// A type that can generate token authenticated requests
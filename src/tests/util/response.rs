@@ -46,6 +46,10 @@ impl<T> Response<T> {
         self.response.status()
     }
 
+    pub fn headers(&self) -> &header::HeaderMap {
+        self.response.headers()
+    }
+
     #[track_caller]
     pub fn assert_redirect_ends_with(&self, target: &str) -> &Self {
         assert!(self
@@ -3,6 +3,8 @@ use crate::git::UpstreamIndex;
 use crate::record;
 use crate::util::{chaosproxy::ChaosProxy, fresh_schema::FreshSchema};
 use cargo_registry::config;
+use cargo_registry::login::LoginProvider;
+use cargo_registry::oidc::{OidcKeyStore, GITHUB_ACTIONS_ISSUER};
 use cargo_registry::{
     background_jobs::Environment,
     db::DieselPool,
@@ -74,6 +76,8 @@ impl TestApp {
             bomb: None,
             index: None,
             build_job_runner: false,
+            oidc_keys: None,
+            login_providers: vec![],
         }
     }
 
@@ -116,15 +120,13 @@ impl TestApp {
                     emails::user_id.eq(user.id),
                     emails::email.eq(email),
                     emails::verified.eq(true),
+                    emails::is_primary.eq(true),
                 ))
                 .execute(conn)
                 .unwrap();
             user
         });
-        MockCookieUser {
-            app: self.clone(),
-            user,
-        }
+        MockCookieUser::new(self, user)
     }
 
     /// Obtain a reference to the upstream repository ("the index")
@@ -149,6 +151,39 @@ impl TestApp {
             .expect("Could not determine if jobs failed");
     }
 
+    /// Like `run_pending_background_jobs`, but failed jobs are left in the queue to be retried
+    /// instead of failing the test.
+    pub fn run_pending_background_jobs_allowing_failures(&self) {
+        let runner = &self.0.runner;
+        let runner = runner.as_ref().expect("Index has not been initialized");
+
+        runner.run_all_pending_jobs().expect("Could not run jobs");
+    }
+
+    /// Add index entries for versions of a crate that was created with `CrateBuilder`, which
+    /// only writes to the database. Jobs updating existing entries, like `sync_yanked`, fail
+    /// without them.
+    pub fn add_versions_to_index(&self, krate: &str, versions: &[&str]) {
+        use cargo_registry::{git, worker};
+        use swirl::Job;
+
+        for version in versions {
+            let entry = git::Crate {
+                name: krate.into(),
+                vers: version.to_string(),
+                deps: vec![],
+                cksum: "0".repeat(64),
+                features: Default::default(),
+                features2: None,
+                yanked: Some(false),
+                links: None,
+                v: None,
+            };
+            self.db(|conn| worker::add_crate(entry).enqueue(conn).unwrap());
+        }
+        self.run_pending_background_jobs();
+    }
+
     /// Obtain a reference to the inner `App` value
     pub fn as_inner(&self) -> &App {
         &self.0.app
@@ -172,6 +207,8 @@ pub struct TestAppBuilder {
     bomb: Option<record::Bomb>,
     index: Option<UpstreamIndex>,
     build_job_runner: bool,
+    oidc_keys: Option<OidcKeyStore>,
+    login_providers: Vec<Box<dyn LoginProvider>>,
 }
 
 impl TestAppBuilder {
@@ -189,7 +226,12 @@ impl TestAppBuilder {
             (None, None)
         };
 
-        let (app, middle) = build_app(self.config, self.proxy);
+        let (app, middle) = build_app(
+            self.config,
+            self.proxy,
+            self.oidc_keys,
+            self.login_providers,
+        );
 
         let runner = if self.build_job_runner {
             let repository_config = RepositoryConfig {
@@ -201,6 +243,8 @@ impl TestAppBuilder {
                 index,
                 app.config.uploader().clone(),
                 app.http_client().clone(),
+                app.github.clone(),
+                app.emails.clone(),
             );
 
             Some(
@@ -277,6 +321,19 @@ impl TestAppBuilder {
         self
     }
 
+    /// Verify trusted publishing tokens with the given keys instead of fetching them from the
+    /// issuer
+    pub fn with_oidc_keys(mut self, keys: OidcKeyStore) -> Self {
+        self.oidc_keys = Some(keys);
+        self
+    }
+
+    /// Add a login provider, replacing the configured provider with the same name
+    pub fn with_login_provider(mut self, provider: Box<dyn LoginProvider>) -> Self {
+        self.login_providers.push(provider);
+        self
+    }
+
     pub fn with_slow_real_db_pool(mut self) -> Self {
         self.config.use_test_database_pool = false;
         self
@@ -309,6 +366,7 @@ fn simple_config() -> config::Server {
         allowed_origins: Vec::new(),
         downloads_persist_interval_ms: 1000,
         ownership_invitations_expiration_days: 30,
+        crate_adoption_waiting_period_days: 30,
         metrics_authorization_token: None,
         use_test_database_pool: true,
         instance_metrics_log_every_seconds: None,
@@ -316,12 +374,22 @@ fn simple_config() -> config::Server {
         blocked_routes: HashSet::new(),
         version_id_cache_size: 10000,
         version_id_cache_ttl: Duration::from_secs(5 * 60),
+        // Always ask GitHub, so that tests replay the recorded requests
+        team_membership_cache_ttl: Duration::ZERO,
+        team_membership_fallback_ttl: Duration::ZERO,
+        max_token_lifetime_days: None,
+        secret_scanning_public_keys: vec![],
+        trusted_publishing_issuers: vec![GITHUB_ACTIONS_ISSUER.to_string()],
+        gitlab_login: None,
+        openid_login: None,
     }
 }
 
 fn build_app(
     config: config::Server,
     proxy: Option<String>,
+    oidc_keys: Option<OidcKeyStore>,
+    login_providers: Vec<Box<dyn LoginProvider>>,
 ) -> (Arc<App>, conduit_middleware::MiddlewareBuilder) {
     let client = if let Some(proxy) = proxy {
         let mut builder = Client::builder();
@@ -336,7 +404,15 @@ fn build_app(
 
     // Use the in-memory email backend for all tests, allowing tests to analyze the emails sent by
     // the application. This will also prevent cluttering the filesystem.
-    app.emails = Emails::new_in_memory();
+    app.emails = Arc::new(Emails::new_in_memory());
+
+    if let Some(oidc_keys) = oidc_keys {
+        app.oidc_keys = oidc_keys;
+    }
+
+    for provider in login_providers {
+        app.login_providers.add(provider);
+    }
 
     let app = Arc::new(app);
     let handler = cargo_registry::build_handler(Arc::clone(&app));
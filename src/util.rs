@@ -13,6 +13,7 @@ mod request_helpers;
 mod request_proxy;
 pub mod rfc3339;
 pub(crate) mod token;
+pub mod totp;
 
 pub type AppResponse = Response<conduit::Body>;
 pub type EndpointResult = Result<AppResponse, Box<dyn errors::AppError>>;
@@ -27,8 +27,8 @@ mod json;
 
 pub use json::TOKEN_FORMAT_ERROR;
 pub(crate) use json::{
-    InsecurelyGeneratedTokenRevoked, MetricsDisabled, NotFound, OwnershipInvitationExpired,
-    ReadOnlyMode, RouteBlocked, TooManyRequests,
+    ExpiredToken, InsecurelyGeneratedTokenRevoked, MetricsDisabled, NotFound,
+    OwnershipInvitationExpired, ReadOnlyMode, RouteBlocked, TooManyRequests,
 };
 
 /// Returns an error with status 200 and the provided description as JSON
@@ -57,6 +57,31 @@ pub fn account_locked(reason: &str, until: Option<NaiveDateTime>) -> Box<dyn App
     })
 }
 
+/// Returns a 403 error for a scoped API token that was not granted `scope`
+pub fn missing_token_scope(scope: Option<&'static str>) -> Box<dyn AppError> {
+    Box::new(json::MissingTokenScope(scope))
+}
+
+/// Returns a 403 error for an API token that is not allowed to act on `crate_name`
+pub fn missing_crate_scope(crate_name: &str) -> Box<dyn AppError> {
+    Box::new(json::MissingCrateScope(crate_name.to_string()))
+}
+
+/// Returns a 403 error for a sensitive action that needs a recent two-factor authentication code
+pub fn second_factor_required() -> Box<dyn AppError> {
+    Box::new(json::SecondFactorRequired)
+}
+
+/// Returns a 429 error after too many invalid two-factor authentication codes
+pub fn second_factor_locked(until: NaiveDateTime) -> Box<dyn AppError> {
+    Box::new(json::SecondFactorLocked(until))
+}
+
+/// Returns a 429 error when a user asked to adopt too many crates in a short time
+pub fn too_many_adoption_requests(max_per_day: i64) -> Box<dyn AppError> {
+    Box::new(json::TooManyAdoptionRequests(max_per_day))
+}
+
 pub fn forbidden() -> Box<dyn AppError> {
     Box::new(json::Forbidden)
 }
@@ -201,6 +201,29 @@ impl fmt::Display for InsecurelyGeneratedTokenRevoked {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct ExpiredToken;
+
+impl ExpiredToken {
+    pub fn boxed() -> Box<dyn AppError> {
+        Box::new(Self)
+    }
+}
+
+impl AppError for ExpiredToken {
+    fn response(&self) -> Option<AppResponse> {
+        Some(json_error(&self.to_string(), StatusCode::UNAUTHORIZED))
+    }
+}
+
+impl fmt::Display for ExpiredToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            "The given API token has expired. You can generate a new token at https://crates.io/me.",
+        )
+    }
+}
+
 #[derive(Debug)]
 pub(super) struct AccountLocked {
     pub(super) reason: String,
@@ -232,6 +255,108 @@ impl fmt::Display for AccountLocked {
     }
 }
 
+/// A scoped API token was used for an action it was not granted. `None` means that no scope
+/// covers the action at all, so only cookie sessions and unscoped tokens may perform it.
+#[derive(Debug)]
+pub(super) struct MissingTokenScope(pub(super) Option<&'static str>);
+
+impl AppError for MissingTokenScope {
+    fn response(&self) -> Option<AppResponse> {
+        Some(json_error(&self.to_string(), StatusCode::FORBIDDEN))
+    }
+}
+
+impl fmt::Display for MissingTokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(scope) => write!(
+                f,
+                "this API token does not have the `{scope}` scope required for this action"
+            ),
+            None => "scoped API tokens cannot be used for this action".fmt(f),
+        }
+    }
+}
+
+/// A user with two-factor authentication enabled has not confirmed a code recently enough for a
+/// sensitive action.
+#[derive(Debug)]
+pub(super) struct SecondFactorRequired;
+
+impl AppError for SecondFactorRequired {
+    fn response(&self) -> Option<AppResponse> {
+        Some(json_error(&self.to_string(), StatusCode::FORBIDDEN))
+    }
+}
+
+impl fmt::Display for SecondFactorRequired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            "this action requires confirming your identity with a two-factor authentication code",
+        )
+    }
+}
+
+/// Too many invalid two-factor authentication codes were entered in a row.
+#[derive(Debug)]
+pub(super) struct SecondFactorLocked(pub(super) NaiveDateTime);
+
+impl AppError for SecondFactorLocked {
+    fn response(&self) -> Option<AppResponse> {
+        Some(json_error(&self.to_string(), StatusCode::TOO_MANY_REQUESTS))
+    }
+}
+
+impl fmt::Display for SecondFactorLocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let until = self.0.format("%Y-%m-%d at %H:%M:%S UTC");
+        write!(
+            f,
+            "too many invalid two-factor authentication codes, please try again after {until}"
+        )
+    }
+}
+
+/// A user asked to adopt more crates than allowed per day.
+#[derive(Debug)]
+pub(super) struct TooManyAdoptionRequests(pub(super) i64);
+
+impl AppError for TooManyAdoptionRequests {
+    fn response(&self) -> Option<AppResponse> {
+        Some(json_error(&self.to_string(), StatusCode::TOO_MANY_REQUESTS))
+    }
+}
+
+impl fmt::Display for TooManyAdoptionRequests {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "you can ask to adopt at most {} crates per day, please try again tomorrow",
+            self.0
+        )
+    }
+}
+
+/// An API token restricted to certain crates was used for another crate.
+#[derive(Debug)]
+pub(super) struct MissingCrateScope(pub(super) String);
+
+impl AppError for MissingCrateScope {
+    fn response(&self) -> Option<AppResponse> {
+        Some(json_error(&self.to_string(), StatusCode::FORBIDDEN))
+    }
+}
+
+impl fmt::Display for MissingCrateScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "this API token cannot be used for the crate `{}`",
+            self.0
+        )
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct OwnershipInvitationExpired {
     pub(crate) crate_name: String,
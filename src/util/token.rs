@@ -122,6 +122,7 @@ secure_token_kind! {
     #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
     pub(crate) enum SecureTokenKind {
         Api => "cio", // Crates.IO
+        TrustedPublishing => "ctp", // Crates Trusted Publishing
     }
 }
 
@@ -172,6 +173,7 @@ mod tests {
         };
 
         ensure(SecureTokenKind::Api, "cio");
+        ensure(SecureTokenKind::TrustedPublishing, "ctp");
 
         assert!(
             remaining.is_empty(),
@@ -0,0 +1,171 @@
+//! Time-based one-time passwords (RFC 6238) as generated by authenticator apps
+//!
+//! Codes have 6 digits, are derived with HMAC-SHA1 and change every 30 seconds, which are the
+//! defaults all common authenticator apps support.
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use rand::{distributions::Uniform, rngs::OsRng, Rng, RngCore};
+use sha2::{Digest, Sha256};
+
+const SECRET_LENGTH: usize = 20;
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Codes of the previous and next time step are accepted to allow for clock drift.
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0; SECRET_LENGTH];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// Encodes the secret in unpadded base32, the format authenticator apps expect.
+pub fn encode_secret(secret: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut encoded = String::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in secret {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+/// The URL that authenticator apps can scan as a QR code.
+pub fn provisioning_url(secret: &[u8], issuer: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}",
+        secret = encode_secret(secret)
+    )
+}
+
+/// Returns the time step a Unix timestamp falls into.
+pub fn time_step(timestamp: i64) -> i64 {
+    timestamp.div_euclid(STEP_SECONDS)
+}
+
+/// Returns the code for a time step.
+pub fn code(secret: &[u8], step: i64) -> String {
+    let key = PKey::hmac(secret).expect("failed to create HMAC key");
+    let mut signer = Signer::new(MessageDigest::sha1(), &key).expect("failed to create signer");
+    let hmac = signer
+        .sign_oneshot_to_vec(&step.to_be_bytes())
+        .expect("failed to compute HMAC");
+
+    // Dynamic truncation, see RFC 4226 section 5.3
+    let offset = (hmac[hmac.len() - 1] & 0xf) as usize;
+    let value = u32::from_be_bytes([
+        hmac[offset] & 0x7f,
+        hmac[offset + 1],
+        hmac[offset + 2],
+        hmac[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        value % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+/// Checks `code` against the time steps around `timestamp`, returning the matching step.
+///
+/// Steps up to and including `last_used_step` are rejected, so that a code cannot be used twice.
+pub fn verify(
+    secret: &[u8],
+    code: &str,
+    timestamp: i64,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    let code = code.trim();
+    let current = time_step(timestamp);
+
+    (current - ALLOWED_DRIFT_STEPS..=current + ALLOWED_DRIFT_STEPS)
+        .filter(|step| last_used_step.map_or(true, |last| *step > last))
+        .find(|step| self::code(secret, *step) == code)
+}
+
+/// Generates a recovery code that can be used once instead of a TOTP code.
+pub fn generate_recovery_code() -> String {
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+    let code: String = OsRng
+        .sample_iter(Uniform::from(0..CHARS.len()))
+        .map(|idx| CHARS[idx] as char)
+        .take(RECOVERY_CODE_LENGTH)
+        .collect();
+
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+/// Recovery codes are only stored as digests. Users may type them in any case and without the
+/// dash.
+pub fn recovery_code_digest(code: &str) -> Vec<u8> {
+    let normalized: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    Sha256::digest(normalized.as_bytes()).as_slice().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA1 secret of the test vectors in RFC 6238
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn codes_match_the_rfc_test_vectors() {
+        // The RFC lists 8 digit codes, of which we use the last 6
+        assert_eq!(code(SECRET, time_step(59)), "287082");
+        assert_eq!(code(SECRET, time_step(1111111109)), "081804");
+        assert_eq!(code(SECRET, time_step(1234567890)), "005924");
+        assert_eq!(code(SECRET, time_step(2000000000)), "279037");
+    }
+
+    #[test]
+    fn verify_allows_clock_drift_and_rejects_reuse() {
+        let now = 1111111109;
+        let step = time_step(now);
+
+        assert_eq!(verify(SECRET, "081804", now, None), Some(step));
+        assert_eq!(verify(SECRET, " 081804 ", now + 30, None), Some(step));
+        assert_eq!(verify(SECRET, "081804", now + 60, None), None);
+        assert_eq!(verify(SECRET, "081804", now, Some(step)), None);
+        assert_eq!(verify(SECRET, "000000", now, None), None);
+    }
+
+    #[test]
+    fn secrets_are_encoded_in_base32() {
+        assert_eq!(encode_secret(b""), "");
+        assert_eq!(encode_secret(b"f"), "MY");
+        assert_eq!(encode_secret(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(encode_secret(&generate_secret()).len(), 32);
+    }
+
+    #[test]
+    fn recovery_codes_are_normalized() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(
+            recovery_code_digest(&code),
+            recovery_code_digest(&code.to_uppercase().replace('-', ""))
+        );
+    }
+}
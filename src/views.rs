@@ -4,8 +4,9 @@ use url::Url;
 
 use crate::github;
 use crate::models::{
-    Badge, Category, Crate, CrateOwnerInvitation, CreatedApiToken, Dependency, DependencyKind,
-    Keyword, Owner, ReverseDependency, Team, TopVersions, User, Version, VersionDownload,
+    Badge, Category, Crate, CrateAdoptionRequest, CrateOwnerAction, CrateOwnerInvitation,
+    CrateScope, CreatedApiToken, Dependency, DependencyKind, Email, EndpointScope, Keyword, Owner,
+    OwnerRole, ReverseDependency, Team, TopVersions, User, Version, VersionDownload,
     VersionOwnerAction,
 };
 use crate::util::rfc3339;
@@ -117,6 +118,78 @@ pub struct EncodableCrateOwnerInvitation {
     pub expires_at: NaiveDateTime,
 }
 
+/// A pending invitation of a user or team, as shown to the owners of the crate.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct EncodablePendingOwnerInvitation {
+    pub login: String,
+    /// Either `user` or `team`
+    pub kind: String,
+    pub role: String,
+    pub invited_by: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub expires_at: NaiveDateTime,
+}
+
+/// An entry of the audit log of a crate.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodableCrateOwnerAction {
+    pub action: String,
+    pub user: EncodablePublicUser,
+    pub subject: Option<String>,
+    pub details: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub time: NaiveDateTime,
+}
+
+impl From<(CrateOwnerAction, User)> for EncodableCrateOwnerAction {
+    fn from((action, user): (CrateOwnerAction, User)) -> Self {
+        Self {
+            action: action.action.into(),
+            user: user.into(),
+            subject: action.subject,
+            details: action.details,
+            time: action.time,
+        }
+    }
+}
+
+/// A request to adopt a crate whose owners have become unreachable.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodableCrateAdoptionRequest {
+    pub id: i32,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub requested_by: String,
+    pub reason: String,
+    pub status: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    /// Unless an owner objects, the request is reviewed by the crates.io team after this time.
+    #[serde(with = "rfc3339")]
+    pub review_after: NaiveDateTime,
+}
+
+impl EncodableCrateAdoptionRequest {
+    pub fn from(
+        request: CrateAdoptionRequest,
+        crate_name: String,
+        requested_by: String,
+        review_after: NaiveDateTime,
+    ) -> Self {
+        Self {
+            id: request.id,
+            krate: crate_name,
+            requested_by,
+            reason: request.reason,
+            status: <&str>::from(request.status).into(),
+            created_at: request.created_at,
+            review_after,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Copy, Clone)]
 pub struct InvitationResponse {
     pub crate_id: i32,
@@ -232,6 +305,7 @@ pub struct EncodableCrate {
     pub repository: Option<String>,
     pub links: EncodableCrateLinks,
     pub exact_match: bool,
+    pub deprecation: Option<EncodableCrateDeprecation>,
 }
 
 impl EncodableCrate {
@@ -246,6 +320,7 @@ impl EncodableCrate {
         exact_match: bool,
         recent_downloads: Option<i64>,
     ) -> Self {
+        let deprecation = EncodableCrateDeprecation::from_crate(&krate);
         let Crate {
             name,
             created_at,
@@ -307,6 +382,7 @@ impl EncodableCrate {
                 owner_user: Some(format!("/api/v1/crates/{name}/owner_user")),
                 reverse_dependencies: format!("/api/v1/crates/{name}/reverse_dependencies"),
             },
+            deprecation,
         }
     }
 
@@ -358,6 +434,25 @@ impl EncodableCrate {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct EncodableCrateDeprecation {
+    pub message: String,
+    pub successor: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub deprecated_at: NaiveDateTime,
+}
+
+impl EncodableCrateDeprecation {
+    /// Returns `None` if the crate is not deprecated.
+    pub fn from_crate(krate: &Crate) -> Option<Self> {
+        krate.deprecated_at.map(|deprecated_at| Self {
+            message: krate.deprecation_message.clone().unwrap_or_default(),
+            successor: krate.deprecation_successor.clone(),
+            deprecated_at,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncodableCrateLinks {
     pub version_downloads: String,
@@ -376,10 +471,13 @@ pub struct EncodableOwner {
     pub url: Option<String>,
     pub name: Option<String>,
     pub avatar: Option<String>,
+    /// Either `full` or `publish`
+    pub role: String,
 }
 
-impl From<Owner> for EncodableOwner {
-    fn from(owner: Owner) -> Self {
+impl From<(Owner, OwnerRole)> for EncodableOwner {
+    fn from((owner, role): (Owner, OwnerRole)) -> Self {
+        let role = <&'static str>::from(role).to_string();
         match owner {
             Owner::User(User {
                 id,
@@ -396,6 +494,7 @@ impl From<Owner> for EncodableOwner {
                     url: Some(url),
                     name,
                     kind: String::from("user"),
+                    role,
                 }
             }
             Owner::Team(Team {
@@ -413,6 +512,7 @@ impl From<Owner> for EncodableOwner {
                     avatar,
                     name,
                     kind: String::from("team"),
+                    role,
                 }
             }
         }
@@ -462,6 +562,10 @@ pub struct EncodableApiTokenWithToken {
     pub created_at: NaiveDateTime,
     #[serde(with = "rfc3339::option")]
     pub last_used_at: Option<NaiveDateTime>,
+    pub endpoint_scopes: Option<Vec<EndpointScope>>,
+    pub crate_scopes: Option<Vec<CrateScope>>,
+    #[serde(with = "rfc3339::option")]
+    pub expires_at: Option<NaiveDateTime>,
 }
 
 impl From<CreatedApiToken> for EncodableApiTokenWithToken {
@@ -473,6 +577,9 @@ impl From<CreatedApiToken> for EncodableApiTokenWithToken {
             revoked: token.model.revoked,
             created_at: token.model.created_at,
             last_used_at: token.model.last_used_at,
+            endpoint_scopes: token.model.endpoint_scopes,
+            crate_scopes: token.model.crate_scopes,
+            expires_at: token.model.expires_at,
         }
     }
 }
@@ -503,6 +610,7 @@ pub struct EncodablePrivateUser {
     pub email: Option<String>,
     pub avatar: Option<String>,
     pub url: Option<String>,
+    pub is_admin: bool,
 }
 
 impl EncodablePrivateUser {
@@ -518,6 +626,7 @@ impl EncodablePrivateUser {
             name,
             gh_login,
             gh_avatar,
+            is_admin,
             ..
         } = user;
         let url = format!("https://github.com/{gh_login}");
@@ -531,6 +640,31 @@ impl EncodablePrivateUser {
             login: gh_login,
             name,
             url: Some(url),
+            is_admin,
+        }
+    }
+}
+
+/// The serialization format for the `Email` model.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodableEmail {
+    pub id: i32,
+    pub email: String,
+    pub verified: bool,
+    pub verification_sent: bool,
+    pub primary: bool,
+    pub for_publishing: bool,
+}
+
+impl From<Email> for EncodableEmail {
+    fn from(email: Email) -> Self {
+        EncodableEmail {
+            id: email.id,
+            email: email.email,
+            verified: email.verified,
+            verification_sent: email.verified || email.token_generated_at.is_some(),
+            primary: email.is_primary,
+            for_publishing: email.for_publishing,
         }
     }
 }
@@ -801,6 +935,11 @@ mod tests {
                 reverse_dependencies: "".to_string(),
             },
             exact_match: false,
+            deprecation: Some(EncodableCrateDeprecation {
+                message: "".to_string(),
+                successor: None,
+                deprecated_at: NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 13),
+            }),
         };
         let json = serde_json::to_string(&crt).unwrap();
         assert_some!(json
@@ -809,6 +948,9 @@ mod tests {
         assert_some!(json
             .as_str()
             .find(r#""created_at":"2017-01-06T14:23:12+00:00""#));
+        assert_some!(json
+            .as_str()
+            .find(r#""deprecated_at":"2017-01-06T14:23:13+00:00""#));
     }
 
     #[test]
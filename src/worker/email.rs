@@ -0,0 +1,66 @@
+//! Deliver the emails enqueued by `Emails`.
+
+use diesel::prelude::*;
+use swirl::PerformError;
+
+use crate::background_jobs::Environment;
+use crate::email::EmailToken;
+use crate::models::{EmailSend, MAX_EMAIL_ATTEMPTS};
+
+/// Renders the email called `template` with the given context, delivers it and records the
+/// outcome in the `email_sends` row with the id `email_id`.
+///
+/// The `token` is looked up now and added to the context. If it no longer exists, for example
+/// because the invitation was accepted in the meantime, the email is marked as failed without
+/// being sent.
+///
+/// A failed delivery fails the job, which makes swirl retry it with an exponential backoff.
+/// After `MAX_EMAIL_ATTEMPTS` failed attempts the email is marked as failed and the job succeeds,
+/// so that it is not retried forever.
+#[swirl::background_job]
+pub fn send_email(
+    env: &Environment,
+    conn: &PgConnection,
+    email_id: i32,
+    recipient: String,
+    template: String,
+    mut context: serde_json::Value,
+    token: Option<EmailToken>,
+) -> Result<(), PerformError> {
+    if let Some(token) = token {
+        match token.load(conn).optional()? {
+            Some(token) => context["token"] = token.into(),
+            None => {
+                EmailSend::give_up(conn, email_id, "the token of the email no longer exists")?;
+                return Ok(());
+            }
+        }
+    }
+
+    let emails = env.emails();
+    let result = emails
+        .render(&template, &context)
+        .map_err(|error| error.to_string())
+        .and_then(|email| {
+            emails
+                .deliver(&recipient, email)
+                .map_err(|error| error.to_string())
+        });
+    let error = match result {
+        Ok(()) => {
+            EmailSend::mark_sent(conn, email_id)?;
+            return Ok(());
+        }
+        Err(error) => error,
+    };
+
+    if EmailSend::record_failure(conn, email_id, &error)? {
+        return Err(error.into());
+    }
+
+    warn!(
+        "Giving up on email {} after {} failed attempts: {}",
+        email_id, MAX_EMAIL_ATTEMPTS, error
+    );
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use crate::background_jobs::Environment;
 use crate::git::Crate;
-use crate::schema;
+use crate::{models, schema};
 use anyhow::Context;
 use chrono::Utc;
 use diesel::prelude::*;
@@ -41,9 +41,11 @@ pub fn sync_yanked(
 ) -> Result<(), PerformError> {
     trace!(?krate, ?version_num, "Load yanked status from database");
 
+    // `krate` is the name the version was published under, which is an alias if the crate has
+    // been renamed since then
     let yanked: bool = schema::versions::table
         .inner_join(schema::crates::table)
-        .filter(schema::crates::name.eq(&krate))
+        .filter(models::Crate::with_name_or_alias(&krate))
         .filter(schema::versions::num.eq(&version_num))
         .select(schema::versions::yanked)
         .get_result(conn)
@@ -5,12 +5,24 @@
 
 mod daily_db_maintenance;
 pub mod dump_db;
+mod email;
 mod git;
+mod owner_invitations;
+mod owner_notifications;
+pub mod prune_token_activity;
 mod readmes;
+pub mod team_memberships;
+mod token_expiry;
 mod update_downloads;
 
 pub use daily_db_maintenance::daily_db_maintenance;
 pub use dump_db::dump_db;
+pub use email::send_email;
 pub use git::{add_crate, squash_index, sync_yanked};
+pub use owner_invitations::process_owner_invitations;
+pub use owner_notifications::notify_owners_of_version_action;
+pub use prune_token_activity::prune_token_activity;
 pub use readmes::render_and_upload_readme;
+pub use team_memberships::refresh_team_memberships;
+pub use token_expiry::notify_expiring_tokens;
 pub use update_downloads::update_downloads;
@@ -0,0 +1,129 @@
+//! Remind users of pending crate ownership invitations and purge the expired ones.
+
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use swirl::PerformError;
+
+use crate::background_jobs::Environment;
+use crate::models::{CrateOwnerInvitation, CrateOwnerSignupInvitation, User};
+use crate::schema::{crate_owner_invitations, crate_owner_signup_invitations, crates};
+
+/// Deletes every invitation older than `expiration_days`, telling the inviter that it was not
+/// accepted, and sends a single reminder for invitations that are halfway to expiring.
+///
+/// This is meant to be enqueued once per day. Expired invitations are deleted even if the email
+/// to the inviter could not be enqueued. Invitations are only marked as reminded once the email
+/// has been enqueued, so a reminder that could not be enqueued is retried the next time the job
+/// runs.
+#[swirl::background_job]
+pub fn process_owner_invitations(
+    env: &Environment,
+    conn: &PgConnection,
+    expiration_days: u64,
+) -> Result<(), PerformError> {
+    let now = Utc::now().naive_utc();
+    let expiration = Duration::days(expiration_days as i64);
+
+    let expired: Vec<(CrateOwnerInvitation, String)> = crate_owner_invitations::table
+        .inner_join(crates::table)
+        .select((crate_owner_invitations::all_columns, crates::name))
+        .filter(crate_owner_invitations::created_at.le(now - expiration))
+        .load(conn)?;
+
+    info!("Purging {} expired crate owner invitations", expired.len());
+
+    for (invitation, crate_name) in expired {
+        let inviter = User::find(conn, invitation.invited_by_user_id)?;
+        if let Some(email) = inviter.verified_email(conn)? {
+            let invitee = User::find(conn, invitation.invited_user_id)?;
+            let result = env.emails().send_owner_invite_expired(
+                conn,
+                &email,
+                &inviter.gh_login,
+                &invitee.gh_login,
+                &crate_name,
+            );
+            if let Err(error) = result {
+                warn!(
+                    "Could not tell {} that the invitation of {} to {} expired: {error}",
+                    inviter.gh_login, invitee.gh_login, crate_name
+                );
+            }
+        }
+
+        diesel::delete(&invitation).execute(conn)?;
+    }
+
+    // Invitations of users who never signed up expire the same way
+    let expired: Vec<(CrateOwnerSignupInvitation, String)> = crate_owner_signup_invitations::table
+        .inner_join(crates::table)
+        .select((crate_owner_signup_invitations::all_columns, crates::name))
+        .filter(crate_owner_signup_invitations::created_at.le(now - expiration))
+        .load(conn)?;
+
+    for (invitation, crate_name) in expired {
+        let inviter = User::find(conn, invitation.invited_by_user_id)?;
+        if let Some(email) = inviter.verified_email(conn)? {
+            let result = env.emails().send_owner_invite_expired(
+                conn,
+                &email,
+                &inviter.gh_login,
+                &invitation.gh_login,
+                &crate_name,
+            );
+            if let Err(error) = result {
+                warn!(
+                    "Could not tell {} that the invitation of {} to {} expired: {error}",
+                    inviter.gh_login, invitation.gh_login, crate_name
+                );
+            }
+        }
+
+        diesel::delete(&invitation).execute(conn)?;
+    }
+
+    let reminders: Vec<(CrateOwnerInvitation, String)> = crate_owner_invitations::table
+        .inner_join(crates::table)
+        .select((crate_owner_invitations::all_columns, crates::name))
+        .filter(crate_owner_invitations::reminder_sent_at.is_null())
+        .filter(crate_owner_invitations::created_at.le(now - expiration / 2))
+        .load(conn)?;
+
+    info!(
+        "Reminding users of {} crate owner invitations",
+        reminders.len()
+    );
+
+    for (invitation, crate_name) in reminders {
+        let invitee = User::find(conn, invitation.invited_user_id)?;
+        let email = match invitee.verified_email(conn)? {
+            Some(email) => email,
+            None => continue,
+        };
+        let inviter = User::find(conn, invitation.invited_by_user_id)?;
+
+        let result = env.emails().send_owner_invite_reminder(
+            conn,
+            &email,
+            &invitee.gh_login,
+            &inviter.gh_login,
+            &crate_name,
+            invitation.invited_user_id,
+            invitation.crate_id,
+            invitation.created_at + expiration,
+        );
+        if let Err(error) = result {
+            warn!(
+                "Could not remind {} of the invitation to {}: {error}",
+                invitee.gh_login, crate_name
+            );
+            continue;
+        }
+
+        diesel::update(&invitation)
+            .set(crate_owner_invitations::reminder_sent_at.eq(now))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
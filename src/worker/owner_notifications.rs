@@ -0,0 +1,81 @@
+//! Tell the owners of a crate about new, yanked and unyanked versions, so that unexpected
+//! releases made with a compromised API token get noticed.
+
+use diesel::prelude::*;
+use swirl::PerformError;
+
+use crate::background_jobs::Environment;
+use crate::models::{CrateOwner, OwnerKind, User, Version, VersionAction, VersionOwnerAction};
+use crate::schema::{api_tokens, crate_owners, crates, users, version_owner_actions, versions};
+
+/// Emails every owner of the crate who opted into notifications about the version owner action
+/// with the id `action_id`.
+///
+/// Owners with a verified email address are notified even if they performed the action
+/// themselves, since that is exactly what happens when their token leaks. The emails are
+/// delivered by separate `send_email` jobs.
+#[swirl::background_job]
+pub fn notify_owners_of_version_action(
+    env: &Environment,
+    conn: &PgConnection,
+    action_id: i32,
+) -> Result<(), PerformError> {
+    let action: VersionOwnerAction = version_owner_actions::table.find(action_id).first(conn)?;
+    let version: Version = versions::table.find(action.version_id).first(conn)?;
+    let crate_name: String = crates::table
+        .find(version.crate_id)
+        .select(crates::name)
+        .first(conn)?;
+    let actor = User::find(conn, action.user_id)?;
+    let token_name: Option<String> = match action.api_token_id {
+        Some(api_token_id) => Some(
+            api_tokens::table
+                .find(api_token_id)
+                .select(api_tokens::name)
+                .first(conn)?,
+        ),
+        None => None,
+    };
+
+    let verb = match action.action {
+        VersionAction::Publish => "published",
+        VersionAction::Yank => "yanked",
+        VersionAction::Unyank => "unyanked",
+    };
+
+    let owners: Vec<User> = CrateOwner::by_owner_kind(OwnerKind::User)
+        .filter(crate_owners::crate_id.eq(version.crate_id))
+        .filter(crate_owners::email_notifications.eq(true))
+        .inner_join(users::table)
+        .select(users::all_columns)
+        .load(conn)?;
+
+    for owner in owners {
+        let email = match owner.verified_email(conn)? {
+            Some(email) => email,
+            None => continue,
+        };
+
+        let result = env.emails().send_version_action_notification(
+            conn,
+            &email,
+            &owner.gh_login,
+            &crate_name,
+            &version.num,
+            verb,
+            &actor.gh_login,
+            token_name.as_deref(),
+            action.time,
+        );
+        if let Err(error) = result {
+            // Failing the job would notify the other owners again when it is retried
+            warn!(
+                "Could not notify {} about version action {}: {error}",
+                owner.gh_login, action_id
+            );
+            continue;
+        }
+    }
+
+    Ok(())
+}
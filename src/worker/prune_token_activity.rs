@@ -0,0 +1,18 @@
+//! Delete API token activity older than the configured retention period.
+
+use chrono::{Duration, Utc};
+use swirl::PerformError;
+
+use crate::models::ApiTokenActivity;
+
+/// The number of days API token activity is kept for if `API_TOKEN_ACTIVITY_RETENTION_DAYS`
+/// is not set when enqueueing the job.
+pub const DEFAULT_RETENTION_DAYS: i64 = 90;
+
+#[swirl::background_job]
+pub fn prune_token_activity(conn: &PgConnection, retention_days: i64) -> Result<(), PerformError> {
+    let cutoff = Utc::now().naive_utc() - Duration::days(retention_days);
+    let deleted = ApiTokenActivity::prune(conn, cutoff)?;
+    info!("Deleted {deleted} API token activity entries older than {retention_days} days");
+    Ok(())
+}
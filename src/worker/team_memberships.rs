@@ -0,0 +1,71 @@
+//! Keep the cached GitHub team memberships up to date, so that team members can publish without
+//! waiting for GitHub.
+
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use swirl::PerformError;
+
+use crate::background_jobs::Environment;
+use crate::github::GitHubUnavailable;
+use crate::models::{Team, TeamMembership, User};
+use crate::schema::{github_team_memberships, teams, users};
+
+/// The number of seconds after which memberships are checked again if
+/// `TEAM_MEMBERSHIP_REFRESH_AFTER` is not set when enqueueing the job.
+pub const DEFAULT_REFRESH_AFTER_SECONDS: i64 = 5 * 60;
+
+/// Asks GitHub again about every membership confirmed more than `refresh_after_seconds` ago.
+///
+/// Cached answers saying that a user is not a member are only kept until they are that old.
+/// Memberships GitHub cannot answer for right now are kept as they are, so they can still be
+/// used while GitHub is unavailable.
+#[swirl::background_job]
+pub fn refresh_team_memberships(
+    env: &Environment,
+    conn: &PgConnection,
+    refresh_after_seconds: i64,
+) -> Result<(), PerformError> {
+    let cutoff = Utc::now().naive_utc() - Duration::seconds(refresh_after_seconds);
+
+    diesel::delete(
+        github_team_memberships::table
+            .filter(github_team_memberships::is_member.eq(false))
+            .filter(github_team_memberships::checked_at.lt(cutoff)),
+    )
+    .execute(conn)?;
+
+    let memberships: Vec<(TeamMembership, Team, User)> = github_team_memberships::table
+        .inner_join(teams::table)
+        .inner_join(users::table)
+        .filter(github_team_memberships::checked_at.lt(cutoff))
+        .order((
+            github_team_memberships::team_id,
+            github_team_memberships::user_id,
+        ))
+        .load(conn)?;
+
+    info!("Refreshing {} team memberships", memberships.len());
+
+    for (membership, team, user) in memberships {
+        match team.github_contains_user(env.github(), &user) {
+            Ok(is_member) => TeamMembership::record(conn, team.id, user.id, is_member)?,
+            Err(error) if error.is::<GitHubUnavailable>() => {
+                warn!(
+                    "Could not refresh the membership of {} in {}: {error}",
+                    user.gh_login, team.login
+                );
+            }
+            // GitHub refused to answer, for example because the user revoked access to their
+            // account, so the membership cannot be vouched for anymore
+            Err(error) => {
+                info!(
+                    "Removing the membership of {} in {}: {error}",
+                    user.gh_login, team.login
+                );
+                diesel::delete(&membership).execute(conn)?;
+            }
+        }
+    }
+
+    Ok(())
+}
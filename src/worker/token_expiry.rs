@@ -0,0 +1,69 @@
+//! Warn users about API tokens that are about to expire.
+
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use swirl::PerformError;
+
+use crate::background_jobs::Environment;
+use crate::models::{ApiToken, User};
+use crate::schema::api_tokens;
+
+/// How many days before a token expires its owner gets notified.
+const EXPIRY_NOTIFICATION_DAYS: i64 = 7;
+
+/// Sends a single email for every token expiring within the next week.
+///
+/// This is meant to be enqueued once per day. Tokens are only marked as
+/// notified once the email has been sent, so users without a verified email
+/// address will be picked up again if they verify one before the token expires.
+#[swirl::background_job]
+pub fn notify_expiring_tokens(env: &Environment, conn: &PgConnection) -> Result<(), PerformError> {
+    let now = Utc::now().naive_utc();
+    let notify_before = now + Duration::days(EXPIRY_NOTIFICATION_DAYS);
+
+    let tokens: Vec<ApiToken> = api_tokens::table
+        .filter(api_tokens::revoked.eq(false))
+        .filter(api_tokens::expiry_notification_at.is_null())
+        // Trusted publishing tokens are short-lived by design
+        .filter(api_tokens::trusted_publisher_id.is_null())
+        .filter(api_tokens::expires_at.gt(now))
+        .filter(api_tokens::expires_at.le(notify_before))
+        .load(conn)?;
+
+    info!("Found {} API tokens expiring soon", tokens.len());
+
+    for token in tokens {
+        let expires_at = match token.expires_at {
+            Some(expires_at) => expires_at,
+            None => continue,
+        };
+
+        let user = User::find(conn, token.user_id)?;
+        let email = match user.verified_email(conn)? {
+            Some(email) => email,
+            None => continue,
+        };
+
+        // The token is not marked as notified, so the next run tries again
+        let result = env.emails().send_token_expiry_notification(
+            conn,
+            &email,
+            &user.gh_login,
+            &token.name,
+            expires_at,
+        );
+        if let Err(error) = result {
+            warn!(
+                "Could not notify {} about the expiry of token {}: {error}",
+                user.gh_login, token.id
+            );
+            continue;
+        }
+
+        diesel::update(&token)
+            .set(api_tokens::expiry_notification_at.eq(now))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}